@@ -20,6 +20,7 @@ fn main() {
     let cfg = Cfg {
         minify_css: !html_only,
         minify_js: !html_only,
+        ..Cfg::new()
     };
 
     for t in fs::read_dir(input_dir).unwrap().map(|d| d.unwrap()) {
@@ -1,36 +1,67 @@
+use std::env::args_os;
 use std::fs::File;
 use std::io::{stdin, stdout, Read, Write};
+use std::path::PathBuf;
 use std::process::exit;
 use std::sync::Arc;
+use std::time::Duration;
 
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
 use structopt::StructOpt;
 
-use minify_html::{minify, Cfg};
+use minify_html::{
+    canonicalise_with_cfg, diff, format, lint, minify_with_diagnostics, AttributeQuotes,
+    CanonicaliseCfg, Cfg, Encoding, EntityPolicy, FormatCfg, Severity, DEFAULT_MAX_DEPTH,
+};
 
 #[derive(StructOpt)]
 #[structopt(
     name = "minify-html",
-    about = "Extremely fast and smart HTML + JS + CSS minifier"
+    about = "Extremely fast and smart HTML + JS + CSS minifier",
+    after_help = "To check HTML for authoring errors without minifying it, use `minify-html lint`. To compare the structure of two HTML files, use `minify-html diff`. To make minified HTML readable, use `minify-html format`."
 )]
 // WARNING: Keep descriptions in sync with Cfg.
 struct Cli {
     /// Files to minify; omit for stdin. If more than one is provided, they will be parallel minified in place, and --output must be omitted.
     #[structopt(parse(from_os_str))]
-    inputs: Vec<std::path::PathBuf>,
+    inputs: Vec<PathBuf>,
 
     /// Output destination; omit for stdout.
     #[structopt(short, long, parse(from_os_str))]
-    output: Option<std::path::PathBuf>,
+    output: Option<PathBuf>,
+
+    /// Use settings for HTML emails: keep conditional comments, never omit tags, always quote attribute values, leave legacy attributes untouched, and only use widely understood character references. Other options can be enabled on top.
+    #[structopt(long)]
+    email: bool,
 
     /// Minify JS in `<script>` tags that have a valid or no `type` attribute value.
     #[structopt(long)]
     minify_js: bool,
 
+    /// Parse the contents of `<noscript>` tags as raw text and output them as is, like browsers with scripting enabled.
+    #[structopt(long)]
+    parse_noscript_as_raw_text: bool,
+
+    /// Replace whitespace sequences containing a line break with a single line break instead of a space or nothing.
+    #[structopt(long)]
+    preserve_line_breaks: bool,
+
     /// Minify CSS in `<style>` tags and `style` attributes.
     #[structopt(long)]
     minify_css: bool,
 
+    /// Always quote attribute values, and write empty non-boolean attribute values as `=""` instead of omitting them.
+    #[structopt(long)]
+    always_quote_attribute_values: bool,
+
+    /// Only output ASCII characters, escaping all others as character references, or as escapes in JS and CSS.
+    #[structopt(long)]
+    ascii_only: bool,
+
+    /// Only output character references that are widely understood: always terminated with a semicolon, in lowercase, and not used just to shorten other characters.
+    #[structopt(long)]
+    conservative_entities: bool,
+
     #[structopt(long)]
     /// Do not minify DOCTYPEs. Minified DOCTYPEs may not be spec compliant.
     do_not_minify_doctype: bool,
@@ -39,6 +70,22 @@ struct Cli {
     #[structopt(long)]
     ensure_spec_compliant_unquoted_attribute_values: bool,
 
+    /// Ensure the output doesn't contain any parse errors, even if the source does. Output may be larger.
+    #[structopt(long)]
+    ensure_no_parse_errors: bool,
+
+    /// Use the `display` and `white-space` properties declared in `style` attributes and simple rules in `<style>` elements to choose how whitespace is minified in each element.
+    #[structopt(long)]
+    infer_whitespace_from_css: bool,
+
+    /// Encoding of the input if it doesn't have a BOM, such as `windows-1252`. Defaults to the encoding declared in `<meta charset>`, or UTF-8.
+    #[structopt(long, parse(try_from_str = parse_encoding))]
+    input_encoding: Option<Encoding>,
+
+    /// Keep the byte order mark (BOM) at the start of the input, if present.
+    #[structopt(long)]
+    keep_bom: bool,
+
     /// Do not omit closing tags when possible.
     #[structopt(long)]
     keep_closing_tags: bool,
@@ -47,6 +94,10 @@ struct Cli {
     #[structopt(long)]
     keep_html_and_head_opening_tags: bool,
 
+    /// Output in the same encoding as the input, instead of UTF-8.
+    #[structopt(long)]
+    keep_input_encoding: bool,
+
     /// Keep spaces between attributes when possible to conform to HTML standards.
     #[structopt(long)]
     keep_spaces_between_attributes: bool,
@@ -55,6 +106,26 @@ struct Cli {
     #[structopt(long)]
     keep_comments: bool,
 
+    /// Keep conditional comments (e.g. `<!--[if mso]>...<![endif]-->`), even if other comments are removed.
+    #[structopt(long)]
+    keep_conditional_comments: bool,
+
+    /// Output the input as is if elements are nested deeper than this. Defaults to 512.
+    #[structopt(long)]
+    max_depth: Option<usize>,
+
+    /// Output the contents of a `<script>` or `<style>` as is if minifying them takes longer than this many milliseconds.
+    #[structopt(long)]
+    max_embedded_minification_time: Option<u64>,
+
+    /// Output the input as is if it's larger than this many bytes.
+    #[structopt(long)]
+    max_input_size: Option<usize>,
+
+    /// Break output lines longer than this many bytes where it's safe to do so, and warn about lines that can't be broken.
+    #[structopt(long)]
+    max_line_length: Option<usize>,
+
     /// Remove all bangs.
     #[structopt(long)]
     remove_bangs: bool,
@@ -62,49 +133,369 @@ struct Cli {
     /// Remove all processing_instructions.
     #[structopt(long)]
     remove_processing_instructions: bool,
+
+    /// Output the canonical form of the input instead of minifying it, for comparing or snapshotting documents. Only the options below and `--max-depth` apply.
+    #[structopt(long)]
+    canonicalise: bool,
+
+    /// When canonicalising, write attributes in source order instead of sorted by name.
+    #[structopt(long)]
+    keep_attribute_order: bool,
+
+    /// When canonicalising, how to quote attribute values: `source`, `double`, `single`, or `minimal`.
+    #[structopt(long, default_value = "source")]
+    attribute_quotes: AttributeQuotes,
+
+    /// When canonicalising, which characters to write as character references: `minimal`, `conservative`, `ascii`, or `source`.
+    #[structopt(long, default_value = "minimal")]
+    entities: EntityPolicy,
+
+    /// When canonicalising, minify whitespace in text using the same rules as minification.
+    #[structopt(long)]
+    normalise_whitespace: bool,
+
+    /// When canonicalising, put children on their own lines indented by this many spaces where whitespace isn't significant.
+    #[structopt(long)]
+    indent: Option<usize>,
+}
+
+#[derive(StructOpt)]
+#[structopt(
+    name = "minify-html lint",
+    bin_name = "minify-html lint",
+    about = "Report authoring errors that may make minification change the structure of HTML, without minifying it. Exits with a non-zero status if any errors (not just warnings) are found."
+)]
+struct LintCli {
+    /// Files to check; omit for stdin.
+    #[structopt(parse(from_os_str))]
+    inputs: Vec<PathBuf>,
+
+    /// Parse the contents of `<noscript>` tags as raw text, like browsers with scripting enabled.
+    #[structopt(long)]
+    parse_noscript_as_raw_text: bool,
+
+    /// Encoding of the input if it doesn't have a BOM, such as `windows-1252`. Defaults to the encoding declared in `<meta charset>`, or UTF-8.
+    #[structopt(long, parse(try_from_str = parse_encoding))]
+    input_encoding: Option<Encoding>,
+
+    /// Stop checking if elements are nested deeper than this. Defaults to 512.
+    #[structopt(long)]
+    max_depth: Option<usize>,
+}
+
+#[derive(StructOpt)]
+#[structopt(
+    name = "minify-html diff",
+    bin_name = "minify-html diff",
+    about = "Compare the elements, attributes, and text of two HTML files, ignoring differences in formatting like whitespace that would be minified, attribute order and quotes, character references, and omitted tags. Exits with status 1 if they differ, and 2 if a file can't be read or is nested too deeply."
+)]
+struct DiffCli {
+    /// First file, whose nodes are marked with `-` if they're not in the second.
+    #[structopt(parse(from_os_str))]
+    a: PathBuf,
+
+    /// Second file, whose nodes are marked with `+` if they're not in the first.
+    #[structopt(parse(from_os_str))]
+    b: PathBuf,
+
+    /// Parse the contents of `<noscript>` tags as raw text, like browsers with scripting enabled.
+    #[structopt(long)]
+    parse_noscript_as_raw_text: bool,
+
+    /// Encoding of the inputs if they don't have a BOM, such as `windows-1252`. Defaults to the encoding declared in `<meta charset>`, or UTF-8.
+    #[structopt(long, parse(try_from_str = parse_encoding))]
+    input_encoding: Option<Encoding>,
+
+    /// Fail with status 2 if elements are nested deeper than this. Defaults to 512.
+    #[structopt(long)]
+    max_depth: Option<usize>,
+}
+
+#[derive(StructOpt)]
+#[structopt(
+    name = "minify-html format",
+    bin_name = "minify-html format",
+    about = "Add line breaks and indentation to HTML, such as minified output, to make it easier to read. Line breaks are only added where whitespace is insignificant, so minifying the output gives the same result as minifying the input."
+)]
+struct FormatCli {
+    /// File to format; omit for stdin.
+    #[structopt(parse(from_os_str))]
+    input: Option<PathBuf>,
+
+    /// Output destination; omit for stdout.
+    #[structopt(short, long, parse(from_os_str))]
+    output: Option<PathBuf>,
+
+    /// Number of spaces to indent each level of nesting by.
+    #[structopt(long, default_value = "2")]
+    indent: usize,
+
+    /// Write closing tags that were omitted in the source, such as `</li>` and `</p>`.
+    #[structopt(long)]
+    restore_closing_tags: bool,
+
+    /// Fail if elements are nested deeper than this. Defaults to 512.
+    #[structopt(long)]
+    max_depth: Option<usize>,
+
+    /// Encoding of the input if it doesn't have a BOM, such as `windows-1252`. Defaults to the encoding declared in `<meta charset>`, or UTF-8. The output is always UTF-8.
+    #[structopt(long, parse(try_from_str = parse_encoding))]
+    input_encoding: Option<Encoding>,
+}
+
+fn parse_encoding(label: &str) -> Result<Encoding, String> {
+    Encoding::for_label(label.as_bytes()).ok_or_else(|| format!("Unsupported encoding: {}", label))
 }
 
 macro_rules! io_expect {
     ($name:expr, $expr:expr, $msg:literal) => {
+        io_expect!($name, $expr, $msg, ())
+    };
+    ($name:expr, $expr:expr, $msg:literal, $ret:expr) => {
         match $expr {
             Ok(r) => r,
             Err(e) => {
                 eprintln!("[{}] {}: {}", $name, $msg, e);
-                return;
+                return $ret;
             }
         }
     };
 }
 
+// Returns the canonical form of the input if `canonicalise_cfg` is provided, or otherwise the minified input after printing any diagnostics.
+fn process(
+    input_name: &str,
+    src_code: &[u8],
+    cfg: &Cfg,
+    canonicalise_cfg: Option<&CanonicaliseCfg>,
+) -> Vec<u8> {
+    if let Some(canonicalise_cfg) = canonicalise_cfg {
+        let mut out_code = Vec::new();
+        if let Err(e) = canonicalise_with_cfg(&mut out_code, src_code, canonicalise_cfg) {
+            eprintln!(
+                "[{}] Could not canonicalise, so it was left as is: {}",
+                input_name, e
+            );
+            return src_code.to_vec();
+        };
+        return out_code;
+    };
+    let (out_code, diagnostics) = minify_with_diagnostics(src_code, cfg);
+    for d in diagnostics {
+        eprintln!("[{}] {}", input_name, d);
+    }
+    out_code
+}
+
+// Prints the problems in the input, and returns whether it has any errors or couldn't be read.
+fn lint_input(input: Option<&PathBuf>, cfg: &Cfg) -> bool {
+    let input_name = input
+        .map(|p| p.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "stdin".to_string());
+    let mut src_file: Box<dyn Read> = match input {
+        Some(p) => Box::new(io_expect!(
+            input_name,
+            File::open(p),
+            "Could not open source file",
+            true
+        )),
+        None => Box::new(stdin()),
+    };
+    let mut src_code = Vec::<u8>::new();
+    io_expect!(
+        input_name,
+        src_file.read_to_end(&mut src_code),
+        "Could not load source code",
+        true
+    );
+    let problems = lint(&src_code, cfg);
+    // Print all problems at once, so that those from files checked in parallel aren't interleaved.
+    let report = problems
+        .iter()
+        .map(|p| format!("{}:{}\n", input_name, p))
+        .collect::<String>();
+    print!("{}", report);
+    problems.iter().any(|p| p.severity() == Severity::Error)
+}
+
+fn main_lint() {
+    let args = LintCli::from_iter(args_os().skip(1));
+    let cfg = Cfg {
+        input_encoding: args.input_encoding,
+        max_depth: args.max_depth.or(Some(DEFAULT_MAX_DEPTH)),
+        parse_noscript_as_raw_text: args.parse_noscript_as_raw_text,
+        ..Cfg::default()
+    };
+    let failed = if args.inputs.is_empty() {
+        lint_input(None, &cfg)
+    } else {
+        args.inputs
+            .par_iter()
+            .map(|input| lint_input(Some(input), &cfg))
+            .collect::<Vec<_>>()
+            .contains(&true)
+    };
+    if failed {
+        exit(1);
+    };
+}
+
+fn main_diff() {
+    let args = DiffCli::from_iter(args_os().skip(1));
+    let cfg = Cfg {
+        input_encoding: args.input_encoding,
+        max_depth: args.max_depth.or(Some(DEFAULT_MAX_DEPTH)),
+        parse_noscript_as_raw_text: args.parse_noscript_as_raw_text,
+        ..Cfg::default()
+    };
+    let read = |input: &PathBuf| match std::fs::read(input) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!(
+                "[{}] Could not load source code: {}",
+                input.to_string_lossy(),
+                e
+            );
+            exit(2);
+        }
+    };
+    let differences = match diff(&read(&args.a), &read(&args.b), &cfg) {
+        Ok(differences) => differences,
+        Err(e) => {
+            eprintln!("Could not compare: {}", e);
+            exit(2);
+        }
+    };
+    if differences.is_empty() {
+        return;
+    };
+    println!("--- {}", args.a.to_string_lossy());
+    println!("+++ {}", args.b.to_string_lossy());
+    for d in differences {
+        println!("{}", d);
+    }
+    exit(1);
+}
+
+fn main_format() {
+    let args = FormatCli::from_iter(args_os().skip(1));
+    let cfg = FormatCfg {
+        indent: args.indent,
+        restore_closing_tags: args.restore_closing_tags,
+        max_depth: args.max_depth.or(Some(DEFAULT_MAX_DEPTH)),
+        input_encoding: args.input_encoding,
+        ..FormatCfg::default()
+    };
+    let input_name = args
+        .input
+        .as_ref()
+        .map(|p| p.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "stdin".to_string());
+    let mut src_file: Box<dyn Read> = match &args.input {
+        Some(p) => Box::new(io_expect!(
+            input_name,
+            File::open(p),
+            "Could not open source file"
+        )),
+        None => Box::new(stdin()),
+    };
+    let mut src_code = Vec::<u8>::new();
+    io_expect!(
+        input_name,
+        src_file.read_to_end(&mut src_code),
+        "Could not load source code"
+    );
+    let out_code = match format(&src_code, &cfg) {
+        Ok(out_code) => out_code,
+        Err(e) => {
+            eprintln!("[{}] Could not format: {}", input_name, e);
+            exit(1);
+        }
+    };
+    let mut out_file: Box<dyn Write> = match &args.output {
+        Some(p) => Box::new(io_expect!(
+            input_name,
+            File::create(p),
+            "Could not open output file"
+        )),
+        None => Box::new(stdout()),
+    };
+    io_expect!(
+        input_name,
+        out_file.write_all(&out_code),
+        "Could not save output code"
+    );
+}
+
 fn main() {
+    match args_os().nth(1).as_ref().and_then(|a| a.to_str()) {
+        Some("lint") => return main_lint(),
+        Some("diff") => return main_diff(),
+        Some("format") => return main_format(),
+        _ => {}
+    };
     let args = Cli::from_args();
     if args.output.is_some() && args.inputs.len() > 1 {
         eprintln!("Cannot provide --output when multiple inputs are provided.");
         exit(1);
     };
 
-    let cfg = Arc::new(Cfg {
+    let mut cfg = Cfg {
+        always_quote_attribute_values: args.always_quote_attribute_values,
+        ascii_only: args.ascii_only,
+        conservative_entities: args.conservative_entities,
         do_not_minify_doctype: args.do_not_minify_doctype,
         ensure_spec_compliant_unquoted_attribute_values: args
             .ensure_spec_compliant_unquoted_attribute_values,
+        ensure_no_parse_errors: args.ensure_no_parse_errors,
+        infer_whitespace_from_css: args.infer_whitespace_from_css,
+        input_encoding: args.input_encoding,
+        keep_bom: args.keep_bom,
         keep_closing_tags: args.keep_closing_tags,
         keep_comments: args.keep_comments,
+        keep_conditional_comments: args.keep_conditional_comments,
         keep_html_and_head_opening_tags: args.keep_html_and_head_opening_tags,
+        keep_input_encoding: args.keep_input_encoding,
         keep_spaces_between_attributes: args.keep_spaces_between_attributes,
+        max_depth: args.max_depth.or(Some(DEFAULT_MAX_DEPTH)),
+        max_embedded_minification_time: args
+            .max_embedded_minification_time
+            .map(Duration::from_millis),
+        max_input_size: args.max_input_size,
+        max_line_length: args.max_line_length,
         minify_css: args.minify_css,
         minify_js: args.minify_js,
+        parse_noscript_as_raw_text: args.parse_noscript_as_raw_text,
+        preserve_line_breaks: args.preserve_line_breaks,
         remove_bangs: args.remove_bangs,
         remove_processing_instructions: args.remove_processing_instructions,
-    });
+        ..Cfg::default()
+    };
+    if args.email {
+        cfg.enable_email_safe_mode();
+    };
+    let cfg = Arc::new(cfg);
+    let canonicalise_cfg = if args.canonicalise {
+        Some(CanonicaliseCfg {
+            sort_attributes: !args.keep_attribute_order,
+            attribute_quotes: args.attribute_quotes,
+            entities: args.entities,
+            normalise_whitespace: args.normalise_whitespace,
+            indent: args.indent,
+            max_depth: args.max_depth.or(Some(DEFAULT_MAX_DEPTH)),
+        })
+    } else {
+        None
+    };
 
     if args.inputs.len() <= 1 {
         // Single file mode or stdin mode.
         let input_name = args
             .inputs
-            .get(0)
+            .first()
             .map(|p| p.to_string_lossy().into_owned())
             .unwrap_or_else(|| "stdin".to_string());
-        let mut src_file: Box<dyn Read> = match args.inputs.get(0) {
+        let mut src_file: Box<dyn Read> = match args.inputs.first() {
             Some(p) => Box::new(io_expect!(
                 input_name,
                 File::open(p),
@@ -118,7 +509,7 @@ fn main() {
             src_file.read_to_end(&mut src_code),
             "Could not load source code"
         );
-        let out_code = minify(&src_code, &cfg);
+        let out_code = process(&input_name, &src_code, &cfg, canonicalise_cfg.as_ref());
         let mut out_file: Box<dyn Write> = match args.output {
             Some(p) => Box::new(io_expect!(
                 input_name,
@@ -130,30 +521,30 @@ fn main() {
         io_expect!(
             input_name,
             out_file.write_all(&out_code),
-            "Could not save minified code"
+            "Could not save output code"
         );
     } else {
         args.inputs.par_iter().for_each(|input| {
             let input_name = input.to_string_lossy().into_owned();
 
             let mut src_file =
-                io_expect!(input_name, File::open(&input), "Could not open source file");
+                io_expect!(input_name, File::open(input), "Could not open source file");
             let mut src_code = Vec::<u8>::new();
             io_expect!(
                 input_name,
                 src_file.read_to_end(&mut src_code),
                 "Could not load source code"
             );
-            let out_code = minify(&src_code, &cfg);
+            let out_code = process(&input_name, &src_code, &cfg, canonicalise_cfg.as_ref());
             let mut out_file = io_expect!(
                 input_name,
-                File::create(&input),
+                File::create(input),
                 "Could not open output file"
             );
             io_expect!(
                 input_name,
                 out_file.write_all(&out_code),
-                "Could not save minified code"
+                "Could not save output code"
             );
             // Just print the name, since this is the default output and any prefix becomes redundant. It'd also allow piping into another command (quite nice for something like `minify-html *.html | xargs gzip`), copying as list of files, etc.
             println!("{}", input_name);
@@ -24,15 +24,28 @@ AttributeMinification {
     case_insensitive: ${caseInsensitive}, 
     collapse: ${collapse}, 
     default_value: ${
-      defaultValue == undefined ? "None" : `Some(b"${defaultValue}")`
+      defaultValue == undefined
+        ? "None"
+        : `Some(Cow::Borrowed(b"${defaultValue}"))`
     },
     redundant_if_empty: ${redundantIfEmpty}, 
     trim: ${trim}, 
 }
 `;
 
+// Whitespace is significant in browsing context names, so a name like " _self" isn't the same as "_self".
+// https://html.spec.whatwg.org/multipage/document-sequences.html#valid-navigable-target-name-or-keyword
+for (const attr of ["formtarget", "target"]) {
+  for (const tagsMap of Object.values(htmlData.attributes[attr] ?? {})) {
+    for (const tagAttr of Object.values(tagsMap ?? {})) {
+      tagAttr.trim = false;
+    }
+  }
+}
+
 let code = `
 use lazy_static::lazy_static;
+use std::borrow::Cow;
 use std::collections::HashMap;
 use crate::common::spec::tag::ns::Namespace;
 
@@ -40,7 +53,8 @@ pub struct AttributeMinification {
     pub boolean: bool,
     pub case_insensitive: bool,
     pub collapse: bool,
-    pub default_value: Option<&'static [u8]>,
+    // Owned values allow runtime rules to be built from non-static data.
+    pub default_value: Option<Cow<'static, [u8]>>,
     pub redundant_if_empty: bool,
     pub trim: bool,
 }
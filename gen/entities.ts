@@ -11,6 +11,22 @@ const trieBuilder = new TrieBuilder("ENTITY", "EntityType");
 trieBuilder.addPattern(parsePattern("&#[0-9]"), "EntityType::Dec");
 trieBuilder.addPattern(parsePattern("&#x[0-9a-fA-F]"), "EntityType::Hex");
 const shorterEncodedEntities = [];
+// Shortest name of each entity that is a single non-ASCII scalar, keyed by the scalar. Lowercase names are preferred if there are multiple.
+const shortestNamedEntities = new Map<number, string>();
+for (const [encoded, entity] of Object.entries(entities)) {
+  const [cp, ...rest] = entity.codepoints;
+  if (cp >= 0x80 && !rest.length && encoded.endsWith(";")) {
+    const name = encoded.slice(1, -1);
+    const cur = shortestNamedEntities.get(cp);
+    if (
+      cur == undefined ||
+      name.length < cur.length ||
+      (name.length == cur.length && name > cur)
+    ) {
+      shortestNamedEntities.set(cp, name);
+    }
+  }
+}
 for (const [encoded, entity] of Object.entries(entities)) {
   const encodedBytes = Buffer.from(encoded, "utf8");
   const decodedBytes = Buffer.from(entity.characters, "utf8");
@@ -33,6 +49,17 @@ pub static SHORTER_ENCODED_ENTITIES_DECODED: &[&[u8]] = &[
   ${shorterEncodedEntities.map(([_, decoded]) => decoded).join(",\n  ")}
 ];
 
+// The shortest named reference for each non-ASCII scalar that has one, sorted by scalar, and whether it can be written without a semicolon.
+pub static SHORTEST_NAMED_ENTITIES: &[(char, &[u8], bool)] = &[
+  ${[...shortestNamedEntities]
+    .sort(([a], [b]) => a - b)
+    .map(
+      ([cp, name]) =>
+        `('\\u{${cp.toString(16)}}', b"${name}", ${`&${name}` in entities})`
+    )
+    .join(",\n  ")}
+];
+
 #[derive(Clone, Copy)]
 pub enum EntityType {
   Named(&'static [u8]),
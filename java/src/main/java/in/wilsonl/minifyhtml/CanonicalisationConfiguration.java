@@ -0,0 +1,81 @@
+package in.wilsonl.minifyhtml;
+
+/**
+ * Class representing canonicalisation configuration.
+ */
+public class CanonicalisationConfiguration {
+  public final boolean sort_attributes;
+  public final String attribute_quotes;
+  public final String entities;
+  public final boolean normalise_whitespace;
+  public final int indent;
+
+  public CanonicalisationConfiguration(
+    boolean sort_attributes,
+    String attribute_quotes,
+    String entities,
+    boolean normalise_whitespace,
+    int indent
+  ) {
+    this.sort_attributes = sort_attributes;
+    this.attribute_quotes = attribute_quotes;
+    this.entities = entities;
+    this.normalise_whitespace = normalise_whitespace;
+    this.indent = indent;
+  }
+
+  /**
+   * Builder to help create configuration.
+   */
+  public static class Builder {
+    private boolean sort_attributes = true;
+    private String attribute_quotes = "source";
+    private String entities = "minimal";
+    private boolean normalise_whitespace = false;
+    private int indent = -1;
+
+    public Builder setSortAttributes(boolean val) {
+      this.sort_attributes = val;
+      return this;
+    }
+
+    /**
+     * @param val one of "source", "double", "single", or "minimal"
+     */
+    public Builder setAttributeQuotes(String val) {
+      this.attribute_quotes = val;
+      return this;
+    }
+
+    /**
+     * @param val one of "minimal", "conservative", "ascii", or "source"
+     */
+    public Builder setEntities(String val) {
+      this.entities = val;
+      return this;
+    }
+
+    public Builder setNormaliseWhitespace(boolean val) {
+      this.normalise_whitespace = val;
+      return this;
+    }
+
+    /**
+     * @param val number of spaces per level, or -1 to not indent
+     */
+    public Builder setIndent(int val) {
+      this.indent = val;
+      return this;
+    }
+
+    public CanonicalisationConfiguration build() {
+      return new CanonicalisationConfiguration(
+        this.sort_attributes,
+        this.attribute_quotes,
+        this.entities,
+        this.normalise_whitespace,
+        this.indent
+      );
+    }
+  }
+}
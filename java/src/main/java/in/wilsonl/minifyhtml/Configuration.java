@@ -4,37 +4,61 @@ package in.wilsonl.minifyhtml;
  * Class representing minification configuration.
  */
 public class Configuration {
+  public final boolean always_quote_attribute_values;
+  public final boolean ascii_only;
+  public final boolean conservative_entities;
   public final boolean do_not_minify_doctype;
   public final boolean ensure_spec_compliant_unquoted_attribute_values;
+  public final boolean ensure_no_parse_errors;
+  public final boolean infer_whitespace_from_css;
   public final boolean keep_closing_tags;
   public final boolean keep_comments;
+  public final boolean keep_conditional_comments;
   public final boolean keep_html_and_head_opening_tags;
   public final boolean keep_spaces_between_attributes;
   public final boolean minify_css;
   public final boolean minify_js;
+  public final boolean parse_noscript_as_raw_text;
+  public final boolean preserve_line_breaks;
   public final boolean remove_bangs;
   public final boolean remove_processing_instructions;
 
   public Configuration(
+    boolean always_quote_attribute_values,
+    boolean ascii_only,
+    boolean conservative_entities,
     boolean do_not_minify_doctype,
     boolean ensure_spec_compliant_unquoted_attribute_values,
+    boolean ensure_no_parse_errors,
+    boolean infer_whitespace_from_css,
     boolean keep_closing_tags,
     boolean keep_comments,
+    boolean keep_conditional_comments,
     boolean keep_html_and_head_opening_tags,
     boolean keep_spaces_between_attributes,
     boolean minify_css,
     boolean minify_js,
+    boolean parse_noscript_as_raw_text,
+    boolean preserve_line_breaks,
     boolean remove_bangs,
     boolean remove_processing_instructions
   ) {
+    this.always_quote_attribute_values = always_quote_attribute_values;
+    this.ascii_only = ascii_only;
+    this.conservative_entities = conservative_entities;
     this.do_not_minify_doctype = do_not_minify_doctype;
     this.ensure_spec_compliant_unquoted_attribute_values = ensure_spec_compliant_unquoted_attribute_values;
+    this.ensure_no_parse_errors = ensure_no_parse_errors;
+    this.infer_whitespace_from_css = infer_whitespace_from_css;
     this.keep_closing_tags = keep_closing_tags;
     this.keep_comments = keep_comments;
+    this.keep_conditional_comments = keep_conditional_comments;
     this.keep_html_and_head_opening_tags = keep_html_and_head_opening_tags;
     this.keep_spaces_between_attributes = keep_spaces_between_attributes;
     this.minify_css = minify_css;
     this.minify_js = minify_js;
+    this.parse_noscript_as_raw_text = parse_noscript_as_raw_text;
+    this.preserve_line_breaks = preserve_line_breaks;
     this.remove_bangs = remove_bangs;
     this.remove_processing_instructions = remove_processing_instructions;
   }
@@ -43,17 +67,40 @@ public class Configuration {
    * Builder to help create configuration.
    */
   public static class Builder {
+    private boolean always_quote_attribute_values = false;
+    private boolean ascii_only = false;
+    private boolean conservative_entities = false;
     private boolean do_not_minify_doctype = false;
     private boolean ensure_spec_compliant_unquoted_attribute_values = false;
+    private boolean ensure_no_parse_errors = false;
+    private boolean infer_whitespace_from_css = false;
     private boolean keep_closing_tags = false;
     private boolean keep_comments = false;
+    private boolean keep_conditional_comments = false;
     private boolean keep_html_and_head_opening_tags = false;
     private boolean keep_spaces_between_attributes = false;
     private boolean minify_css = false;
     private boolean minify_js = false;
+    private boolean parse_noscript_as_raw_text = false;
+    private boolean preserve_line_breaks = false;
     private boolean remove_bangs = false;
     private boolean remove_processing_instructions = false;
 
+    public Builder setAlwaysQuoteAttributeValues(boolean val) {
+      this.always_quote_attribute_values = val;
+      return this;
+    }
+
+    public Builder setAsciiOnly(boolean val) {
+      this.ascii_only = val;
+      return this;
+    }
+
+    public Builder setConservativeEntities(boolean val) {
+      this.conservative_entities = val;
+      return this;
+    }
+
     public Builder setDoNotMinifyDoctype(boolean val) {
       this.do_not_minify_doctype = val;
       return this;
@@ -64,6 +111,16 @@ public class Configuration {
       return this;
     }
 
+    public Builder setEnsureNoParseErrors(boolean val) {
+      this.ensure_no_parse_errors = val;
+      return this;
+    }
+
+    public Builder setInferWhitespaceFromCss(boolean val) {
+      this.infer_whitespace_from_css = val;
+      return this;
+    }
+
     public Builder setKeepClosingTags(boolean val) {
       this.keep_closing_tags = val;
       return this;
@@ -74,6 +131,11 @@ public class Configuration {
       return this;
     }
 
+    public Builder setKeepConditionalComments(boolean val) {
+      this.keep_conditional_comments = val;
+      return this;
+    }
+
     public Builder setKeepHtmlAndHeadOpeningTags(boolean val) {
       this.keep_html_and_head_opening_tags = val;
       return this;
@@ -94,6 +156,16 @@ public class Configuration {
       return this;
     }
 
+    public Builder setParseNoscriptAsRawText(boolean val) {
+      this.parse_noscript_as_raw_text = val;
+      return this;
+    }
+
+    public Builder setPreserveLineBreaks(boolean val) {
+      this.preserve_line_breaks = val;
+      return this;
+    }
+
     public Builder setRemoveBangs(boolean val) {
       this.remove_bangs = val;
       return this;
@@ -107,14 +179,22 @@ public class Configuration {
 
     public Configuration build() {
       return new Configuration(
+        this.always_quote_attribute_values,
+        this.ascii_only,
+        this.conservative_entities,
         this.do_not_minify_doctype,
         this.ensure_spec_compliant_unquoted_attribute_values,
+        this.ensure_no_parse_errors,
+        this.infer_whitespace_from_css,
         this.keep_closing_tags,
         this.keep_comments,
+        this.keep_conditional_comments,
         this.keep_html_and_head_opening_tags,
         this.keep_spaces_between_attributes,
         this.minify_css,
         this.minify_js,
+        this.parse_noscript_as_raw_text,
+        this.preserve_line_breaks,
         this.remove_bangs,
         this.remove_processing_instructions
       );
@@ -55,4 +55,15 @@ public class MinifyHtml {
    * @return minified HTML code
    */
   public static native String minify(String code, Configuration cfg);
+
+  /**
+   * Write the canonical form of HTML code represented as a {@link String}, for comparing or snapshotting documents.
+   * The {@link String} will be copied to a UTF-8 byte array in native code, and then copied back into a Java {@link String}.
+   *
+   * @param code HTML code to canonicalise
+   * @param cfg  {@link CanonicalisationConfiguration} canonicalisation settings to use
+   * @return canonical HTML code
+   * @throws IllegalArgumentException if {@code cfg} has an unknown attribute quotes or entities value
+   */
+  public static native String canonicalise(String code, CanonicalisationConfiguration cfg);
 }
@@ -1,11 +1,29 @@
 use jni::objects::{JClass, JObject, JString};
 use jni::sys::jstring;
 use jni::JNIEnv;
-use minify_html::{minify as minify_html_native, Cfg};
+use minify_html::{
+    canonicalise_with_cfg, minify as minify_html_native, AttributeQuotes, CanonicaliseCfg, Cfg,
+    Encoding, EntityPolicy,
+};
 use std::str::from_utf8;
 
 fn build_cfg(env: &JNIEnv, obj: &JObject) -> Cfg {
     Cfg {
+        always_quote_attribute_values: env
+            .get_field(*obj, "always_quote_attribute_values", "Z")
+            .unwrap()
+            .z()
+            .unwrap(),
+        ascii_only: env
+            .get_field(*obj, "ascii_only", "Z")
+            .unwrap()
+            .z()
+            .unwrap(),
+        conservative_entities: env
+            .get_field(*obj, "conservative_entities", "Z")
+            .unwrap()
+            .z()
+            .unwrap(),
         do_not_minify_doctype: env
             .get_field(*obj, "do_not_minify_doctype", "Z")
             .unwrap()
@@ -16,6 +34,16 @@ fn build_cfg(env: &JNIEnv, obj: &JObject) -> Cfg {
             .unwrap()
             .z()
             .unwrap(),
+        ensure_no_parse_errors: env
+            .get_field(*obj, "ensure_no_parse_errors", "Z")
+            .unwrap()
+            .z()
+            .unwrap(),
+        infer_whitespace_from_css: env
+            .get_field(*obj, "infer_whitespace_from_css", "Z")
+            .unwrap()
+            .z()
+            .unwrap(),
         keep_closing_tags: env
             .get_field(*obj, "keep_closing_tags", "Z")
             .unwrap()
@@ -26,6 +54,11 @@ fn build_cfg(env: &JNIEnv, obj: &JObject) -> Cfg {
             .unwrap()
             .z()
             .unwrap(),
+        keep_conditional_comments: env
+            .get_field(*obj, "keep_conditional_comments", "Z")
+            .unwrap()
+            .z()
+            .unwrap(),
         keep_html_and_head_opening_tags: env
             .get_field(*obj, "keep_html_and_head_opening_tags", "Z")
             .unwrap()
@@ -38,6 +71,16 @@ fn build_cfg(env: &JNIEnv, obj: &JObject) -> Cfg {
             .unwrap(),
         minify_css: env.get_field(*obj, "minify_css", "Z").unwrap().z().unwrap(),
         minify_js: env.get_field(*obj, "minify_js", "Z").unwrap().z().unwrap(),
+        parse_noscript_as_raw_text: env
+            .get_field(*obj, "parse_noscript_as_raw_text", "Z")
+            .unwrap()
+            .z()
+            .unwrap(),
+        preserve_line_breaks: env
+            .get_field(*obj, "preserve_line_breaks", "Z")
+            .unwrap()
+            .z()
+            .unwrap(),
         remove_bangs: env
             .get_field(*obj, "remove_bangs", "Z")
             .unwrap()
@@ -48,9 +91,46 @@ fn build_cfg(env: &JNIEnv, obj: &JObject) -> Cfg {
             .unwrap()
             .z()
             .unwrap(),
+        // Java strings have already been converted to UTF-8, regardless of any `<meta charset>`.
+        input_encoding: Some(Encoding::Utf8),
+        ..Cfg::default()
     }
 }
 
+fn get_string_field(env: &JNIEnv, obj: &JObject, name: &str) -> String {
+    let value = env
+        .get_field(*obj, name, "Ljava/lang/String;")
+        .unwrap()
+        .l()
+        .unwrap();
+    env.get_string(JString::from(value)).unwrap().into()
+}
+
+fn build_canonicalise_cfg(env: &JNIEnv, obj: &JObject) -> Result<CanonicaliseCfg, String> {
+    let indent = env.get_field(*obj, "indent", "I").unwrap().i().unwrap();
+    Ok(CanonicaliseCfg {
+        sort_attributes: env
+            .get_field(*obj, "sort_attributes", "Z")
+            .unwrap()
+            .z()
+            .unwrap(),
+        attribute_quotes: get_string_field(env, obj, "attribute_quotes")
+            .parse::<AttributeQuotes>()?,
+        entities: get_string_field(env, obj, "entities").parse::<EntityPolicy>()?,
+        normalise_whitespace: env
+            .get_field(*obj, "normalise_whitespace", "Z")
+            .unwrap()
+            .z()
+            .unwrap(),
+        indent: if indent < 0 {
+            None
+        } else {
+            Some(indent as usize)
+        },
+        ..CanonicaliseCfg::default()
+    })
+}
+
 #[no_mangle]
 pub extern "system" fn Java_in_wilsonl_minifyhtml_MinifyHtml_minify(
     env: JNIEnv,
@@ -65,3 +145,31 @@ pub extern "system" fn Java_in_wilsonl_minifyhtml_MinifyHtml_minify(
     let out_code_str = from_utf8(&out_code).unwrap();
     env.new_string(out_code_str).unwrap().into_inner()
 }
+
+#[no_mangle]
+pub extern "system" fn Java_in_wilsonl_minifyhtml_MinifyHtml_canonicalise(
+    env: JNIEnv,
+    _class: JClass,
+    input: JString,
+    cfg: JObject,
+) -> jstring {
+    let cfg = match build_canonicalise_cfg(&env, &cfg) {
+        Ok(cfg) => cfg,
+        Err(msg) => {
+            env.throw_new("java/lang/IllegalArgumentException", msg)
+                .unwrap();
+            return JObject::null().into_inner();
+        }
+    };
+    let source: String = env.get_string(input).unwrap().into();
+    let code = source.into_bytes();
+
+    let mut out_code = Vec::new();
+    if let Err(e) = canonicalise_with_cfg(&mut out_code, &code, &cfg) {
+        env.throw_new("java/lang/IllegalArgumentException", e.to_string())
+            .unwrap();
+        return JObject::null().into_inner();
+    };
+    let out_code_str = from_utf8(&out_code).unwrap();
+    env.new_string(out_code_str).unwrap().into_inner()
+}
@@ -34,3 +34,26 @@ export function minify(
     remove_processing_instructions?: boolean;
   }
 ): Buffer;
+
+/**
+ * Writes the canonical form of a Buffer containing UTF-8 HTML code, for comparing or snapshotting documents.
+ *
+ * @param src - Source HTML code
+ * @param cfg - Configurable canonicalisation settings to use
+ * @returns Canonical HTML code
+ */
+export function canonicalise(
+  src: Buffer,
+  cfg: {
+    /** Write attributes sorted by name, instead of in source order. Defaults to true. */
+    sort_attributes?: boolean;
+    /** How to quote attribute values. Defaults to `"source"`. */
+    attribute_quotes?: "source" | "double" | "single" | "minimal";
+    /** Which characters to write as character references. Defaults to `"minimal"`. */
+    entities?: "minimal" | "conservative" | "ascii" | "source";
+    /** Minify whitespace in text using the same rules as minification. */
+    normalise_whitespace?: boolean;
+    /** Put children on their own lines, indented by this many spaces per level, where whitespace between them isn't significant. */
+    indent?: number;
+  }
+): Buffer;
@@ -45,13 +45,60 @@ fn minify(mut cx: FunctionContext) -> JsResult<JsBuffer> {
             .get_opt::<JsBoolean, _, _>(&mut cx, "remove_processing_instructions")?
             .map(|v| v.value(&mut cx))
             .unwrap_or(false),
+        ..minify_html::Cfg::default()
     };
     let out = minify_html::minify(src.as_slice(&mut cx), &cfg);
     Ok(JsBuffer::external(&mut cx, out))
 }
 
+fn canonicalise(mut cx: FunctionContext) -> JsResult<JsBuffer> {
+    let src = cx.argument::<JsBuffer>(0)?;
+    let opt = cx.argument::<JsObject>(1)?;
+    let attribute_quotes = match opt
+        .get_opt::<JsString, _, _>(&mut cx, "attribute_quotes")?
+        .map(|v| v.value(&mut cx).parse::<minify_html::AttributeQuotes>())
+    {
+        Some(Ok(v)) => v,
+        Some(Err(e)) => return cx.throw_type_error(e),
+        None => minify_html::AttributeQuotes::Source,
+    };
+    let entities = match opt
+        .get_opt::<JsString, _, _>(&mut cx, "entities")?
+        .map(|v| v.value(&mut cx).parse::<minify_html::EntityPolicy>())
+    {
+        Some(Ok(v)) => v,
+        Some(Err(e)) => return cx.throw_type_error(e),
+        None => minify_html::EntityPolicy::Minimal,
+    };
+    let cfg = minify_html::CanonicaliseCfg {
+        sort_attributes: opt
+            .get_opt::<JsBoolean, _, _>(&mut cx, "sort_attributes")?
+            .map(|v| v.value(&mut cx))
+            .unwrap_or(true),
+        attribute_quotes,
+        entities,
+        normalise_whitespace: opt
+            .get_opt::<JsBoolean, _, _>(&mut cx, "normalise_whitespace")?
+            .map(|v| v.value(&mut cx))
+            .unwrap_or(false),
+        indent: opt
+            .get_opt::<JsNumber, _, _>(&mut cx, "indent")?
+            .map(|v| v.value(&mut cx))
+            .filter(|&v| v >= 0.0)
+            .map(|v| v as usize),
+        ..minify_html::CanonicaliseCfg::default()
+    };
+    let mut out = Vec::new();
+    let res = minify_html::canonicalise_with_cfg(&mut out, src.as_slice(&mut cx), &cfg);
+    if let Err(e) = res {
+        return cx.throw_error(e.to_string());
+    };
+    Ok(JsBuffer::external(&mut cx, out))
+}
+
 #[neon::main]
 fn main(mut cx: ModuleContext) -> NeonResult<()> {
     cx.export_function("minify", minify)?;
+    cx.export_function("canonicalise", canonicalise)?;
     Ok(())
 }
@@ -1,13 +1,32 @@
+from typing import Optional
+
 def minify(
     code: str,
+    always_quote_attribute_values: bool = False,
+    ascii_only: bool = False,
+    conservative_entities: bool = False,
     do_not_minify_doctype: bool = False,
     ensure_spec_compliant_unquoted_attribute_values: bool = False,
+    ensure_no_parse_errors: bool = False,
+    infer_whitespace_from_css: bool = False,
     keep_closing_tags: bool = False,
     keep_comments: bool = False,
+    keep_conditional_comments: bool = False,
     keep_html_and_head_opening_tags: bool = False,
     keep_spaces_between_attributes: bool = False,
     minify_css: bool = False,
     minify_js: bool = False,
+    parse_noscript_as_raw_text: bool = False,
+    preserve_line_breaks: bool = False,
     remove_bangs: bool = False,
     remove_processing_instructions: bool = False,
 ) -> str: ...
+
+def canonicalise(
+    code: str,
+    sort_attributes: bool = True,
+    attribute_quotes: str = "source",
+    entities: str = "minimal",
+    normalise_whitespace: bool = False,
+    indent: Optional[int] = None,
+) -> str: ...
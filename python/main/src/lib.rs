@@ -1,31 +1,51 @@
-use minify_html::{minify as minify_html_native, Cfg};
+use minify_html::{
+    canonicalise_with_cfg, minify as minify_html_native, AttributeQuotes, CanonicaliseCfg, Cfg,
+    Encoding, EntityPolicy,
+};
+use pyo3::exceptions::PyValueError;
 use pyo3::prelude::*;
 use pyo3::wrap_pyfunction;
 use std::string::String;
 
 #[pyfunction(
     py_args = "*",
+    always_quote_attribute_values = "false",
+    ascii_only = "false",
+    conservative_entities = "false",
     do_not_minify_doctype = "false",
     ensure_spec_compliant_unquoted_attribute_values = "false",
+    ensure_no_parse_errors = "false",
+    infer_whitespace_from_css = "false",
     keep_closing_tags = "false",
     keep_comments = "false",
+    keep_conditional_comments = "false",
     keep_html_and_head_opening_tags = "false",
     keep_spaces_between_attributes = "false",
     minify_css = "false",
     minify_js = "false",
+    parse_noscript_as_raw_text = "false",
+    preserve_line_breaks = "false",
     remove_bangs = "false",
     remove_processing_instructions = "false"
 )]
 fn minify(
     code: String,
+    always_quote_attribute_values: bool,
+    ascii_only: bool,
+    conservative_entities: bool,
     do_not_minify_doctype: bool,
     ensure_spec_compliant_unquoted_attribute_values: bool,
+    ensure_no_parse_errors: bool,
+    infer_whitespace_from_css: bool,
     keep_closing_tags: bool,
     keep_comments: bool,
+    keep_conditional_comments: bool,
     keep_html_and_head_opening_tags: bool,
     keep_spaces_between_attributes: bool,
     minify_css: bool,
     minify_js: bool,
+    parse_noscript_as_raw_text: bool,
+    preserve_line_breaks: bool,
     remove_bangs: bool,
     remove_processing_instructions: bool,
 ) -> PyResult<String> {
@@ -33,24 +53,71 @@ fn minify(
     let out_code = minify_html_native(
         &code,
         &Cfg {
+            always_quote_attribute_values,
+            ascii_only,
+            conservative_entities,
             do_not_minify_doctype,
             ensure_spec_compliant_unquoted_attribute_values,
+            ensure_no_parse_errors,
+            infer_whitespace_from_css,
             keep_closing_tags,
             keep_comments,
+            keep_conditional_comments,
             keep_html_and_head_opening_tags,
             keep_spaces_between_attributes,
             minify_css,
             minify_js,
+            parse_noscript_as_raw_text,
+            preserve_line_breaks,
             remove_bangs,
             remove_processing_instructions,
+            // Python strings are always UTF-8 once converted, regardless of any `<meta charset>`.
+            input_encoding: Some(Encoding::Utf8),
+            ..Cfg::default()
         },
     );
     Ok(String::from_utf8(out_code).unwrap())
 }
 
+#[pyfunction(
+    py_args = "*",
+    sort_attributes = "true",
+    attribute_quotes = "\"source\"",
+    entities = "\"minimal\"",
+    normalise_whitespace = "false",
+    indent = "None"
+)]
+fn canonicalise(
+    code: String,
+    sort_attributes: bool,
+    attribute_quotes: &str,
+    entities: &str,
+    normalise_whitespace: bool,
+    indent: Option<usize>,
+) -> PyResult<String> {
+    let code = code.into_bytes();
+    let cfg = CanonicaliseCfg {
+        sort_attributes,
+        attribute_quotes: attribute_quotes
+            .parse::<AttributeQuotes>()
+            .map_err(PyValueError::new_err)?,
+        entities: entities
+            .parse::<EntityPolicy>()
+            .map_err(PyValueError::new_err)?,
+        normalise_whitespace,
+        indent,
+        ..CanonicaliseCfg::default()
+    };
+    let mut out_code = Vec::new();
+    canonicalise_with_cfg(&mut out_code, &code, &cfg)
+        .map_err(|e| PyValueError::new_err(e.to_string()))?;
+    Ok(String::from_utf8(out_code).unwrap())
+}
+
 #[pymodule]
 fn minify_html(_py: Python, m: &PyModule) -> PyResult<()> {
     m.add_wrapped(wrap_pyfunction!(minify))?;
+    m.add_wrapped(wrap_pyfunction!(canonicalise))?;
 
     Ok(())
 }
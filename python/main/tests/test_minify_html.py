@@ -0,0 +1,20 @@
+import unittest
+
+import minify_html
+
+
+class TestMinifyHtml(unittest.TestCase):
+    def test_minify(self):
+        self.assertEqual(minify_html.minify("<p>  Hello, world!  </p>"), "<p>Hello, world!")
+
+    def test_declared_encoding_is_ignored(self):
+        # The string has already been decoded, so a `<meta charset>` must not cause it to be decoded again.
+        code = "<meta charset=windows-1252><p>café €</p>"
+        self.assertEqual(minify_html.minify(code), "<meta charset=utf-8><p>café €")
+        self.assertEqual(
+            minify_html.canonicalise(code), "<meta charset=utf-8><p>café €</p>"
+        )
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -4,14 +4,52 @@ use pyo3::prelude::*;
 use pyo3::wrap_pyfunction;
 use std::str::from_utf8_unchecked;
 
-#[pyfunction(py_args = "*", minify_js = "false", minify_css = "false")]
-fn minify(code: String, minify_js: bool, minify_css: bool) -> PyResult<String> {
+#[pyfunction(
+    py_args = "*",
+    do_not_minify_doctype = "false",
+    ensure_spec_compliant_unquoted_attribute_values = "false",
+    keep_closing_tags = "false",
+    keep_comments = "false",
+    keep_html_and_head_opening_tags = "false",
+    keep_spaces_between_attributes = "false",
+    minify_css = "false",
+    minify_js = "false",
+    parse_noscript_as_raw_text = "false",
+    recover_from_errors = "false",
+    remove_bangs = "false",
+    remove_processing_instructions = "false"
+)]
+fn minify(
+    code: String,
+    do_not_minify_doctype: bool,
+    ensure_spec_compliant_unquoted_attribute_values: bool,
+    keep_closing_tags: bool,
+    keep_comments: bool,
+    keep_html_and_head_opening_tags: bool,
+    keep_spaces_between_attributes: bool,
+    minify_css: bool,
+    minify_js: bool,
+    parse_noscript_as_raw_text: bool,
+    recover_from_errors: bool,
+    remove_bangs: bool,
+    remove_processing_instructions: bool,
+) -> PyResult<String> {
     let mut code = code.into_bytes();
     match minify_html_native(
         &mut code,
         &Cfg {
-            minify_js,
+            do_not_minify_doctype,
+            ensure_spec_compliant_unquoted_attribute_values,
+            keep_closing_tags,
+            keep_comments,
+            keep_html_and_head_opening_tags,
+            keep_spaces_between_attributes,
             minify_css,
+            minify_js,
+            parse_noscript_as_raw_text,
+            recover_from_errors,
+            remove_bangs,
+            remove_processing_instructions,
         },
     ) {
         Ok(out_len) => Ok(unsafe { from_utf8_unchecked(&code[0..out_len]).to_string() }),
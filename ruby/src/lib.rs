@@ -1,14 +1,37 @@
-use minify_html::{minify as minify_html_native, Cfg};
-use rutie::{class, methods, Boolean, Class, Hash, Object, RString, Symbol, VM};
+use minify_html::{
+    canonicalise_with_cfg, minify as minify_html_native, AttributeQuotes, CanonicaliseCfg, Cfg,
+    Encoding, EntityPolicy,
+};
+use rutie::{
+    class, methods, AnyObject, Boolean, Class, Hash, Integer, Object, RString, Symbol, VM,
+};
 use std::str::from_utf8;
 
 macro_rules! get_cfg_hash_prop {
     ($cfg_hash:ident, $prop:literal) => {
+        get_cfg_hash_prop!($cfg_hash, $prop, false)
+    };
+    ($cfg_hash:ident, $prop:literal, $default:expr) => {
         $cfg_hash
             .at(&Symbol::new($prop))
             .try_convert_to::<Boolean>()
-            .map_or(false, |v| v.to_bool())
+            .map_or($default, |v| v.to_bool())
+    };
+}
+
+// Accepts a string or symbol, and raises an ArgumentError if it isn't a valid value.
+fn parse_cfg_hash_str<T: std::str::FromStr<Err = String>>(value: AnyObject, default: T) -> T {
+    let value = if let Ok(v) = value.try_convert_to::<RString>() {
+        v.to_string()
+    } else if let Ok(v) = value.try_convert_to::<Symbol>() {
+        v.to_str().to_string()
+    } else {
+        return default;
     };
+    value
+        .parse::<T>()
+        .map_err(|e| VM::raise(Class::from_existing("ArgumentError"), &e))
+        .unwrap()
 }
 
 class!(MinifyHtml);
@@ -29,22 +52,67 @@ methods! {
             .unwrap();
 
         let cfg = &Cfg {
+            always_quote_attribute_values: get_cfg_hash_prop!(cfg_hash, "always_quote_attribute_values"),
+            ascii_only: get_cfg_hash_prop!(cfg_hash, "ascii_only"),
+            conservative_entities: get_cfg_hash_prop!(cfg_hash, "conservative_entities"),
             do_not_minify_doctype: get_cfg_hash_prop!(cfg_hash, "do_not_minify_doctype"),
             ensure_spec_compliant_unquoted_attribute_values: get_cfg_hash_prop!(cfg_hash, "ensure_spec_compliant_unquoted_attribute_values"),
+            ensure_no_parse_errors: get_cfg_hash_prop!(cfg_hash, "ensure_no_parse_errors"),
+            infer_whitespace_from_css: get_cfg_hash_prop!(cfg_hash, "infer_whitespace_from_css"),
             keep_closing_tags: get_cfg_hash_prop!(cfg_hash, "keep_closing_tags"),
             keep_comments: get_cfg_hash_prop!(cfg_hash, "keep_comments"),
+            keep_conditional_comments: get_cfg_hash_prop!(cfg_hash, "keep_conditional_comments"),
             keep_html_and_head_opening_tags: get_cfg_hash_prop!(cfg_hash, "keep_html_and_head_opening_tags"),
             keep_spaces_between_attributes: get_cfg_hash_prop!(cfg_hash, "keep_spaces_between_attributes"),
             minify_css: get_cfg_hash_prop!(cfg_hash, "minify_css"),
             minify_js: get_cfg_hash_prop!(cfg_hash, "minify_js"),
+            parse_noscript_as_raw_text: get_cfg_hash_prop!(cfg_hash, "parse_noscript_as_raw_text"),
+            preserve_line_breaks: get_cfg_hash_prop!(cfg_hash, "preserve_line_breaks"),
             remove_bangs: get_cfg_hash_prop!(cfg_hash, "remove_bangs"),
             remove_processing_instructions: get_cfg_hash_prop!(cfg_hash, "remove_processing_instructions"),
+            // The source has already been converted to a UTF-8 string, regardless of any `<meta charset>`.
+            input_encoding: Some(Encoding::Utf8),
+            ..Cfg::default()
         };
 
         let out_code = minify_html_native(&code, cfg);
         let out_str = from_utf8(&out_code).unwrap();
         RString::new_utf8(out_str)
     }
+
+    fn canonicalise(source: RString, cfg_hash: Hash) -> RString {
+        let code = source
+            .map_err(|e| VM::raise_ex(e) )
+            .unwrap()
+            .to_string()
+            .into_bytes();
+
+        let cfg_hash = cfg_hash
+            .map_err(|e| VM::raise_ex(e) )
+            .unwrap();
+
+        let cfg = &CanonicaliseCfg {
+            sort_attributes: get_cfg_hash_prop!(cfg_hash, "sort_attributes", true),
+            attribute_quotes: parse_cfg_hash_str(cfg_hash.at(&Symbol::new("attribute_quotes")), AttributeQuotes::Source),
+            entities: parse_cfg_hash_str(cfg_hash.at(&Symbol::new("entities")), EntityPolicy::Minimal),
+            normalise_whitespace: get_cfg_hash_prop!(cfg_hash, "normalise_whitespace"),
+            indent: cfg_hash
+                .at(&Symbol::new("indent"))
+                .try_convert_to::<Integer>()
+                .ok()
+                .map(|v| v.to_i64())
+                .filter(|&v| v >= 0)
+                .map(|v| v as usize),
+            ..CanonicaliseCfg::default()
+        };
+
+        let mut out_code = Vec::new();
+        canonicalise_with_cfg(&mut out_code, &code, cfg)
+            .map_err(|e| VM::raise(Class::from_existing("ArgumentError"), &e.to_string()))
+            .unwrap();
+        let out_str = from_utf8(&out_code).unwrap();
+        RString::new_utf8(out_str)
+    }
 }
 
 #[allow(non_snake_case)]
@@ -52,5 +120,6 @@ methods! {
 pub extern "C" fn Init_minify_html() {
     Class::new("MinifyHtml", None).define(|itself| {
         itself.def_self("minify", minify);
+        itself.def_self("canonicalise", canonicalise);
     });
 }
@@ -0,0 +1,53 @@
+// Line and column of a position in code, both starting from 1. Columns are counted in Unicode characters, and in UTF-16 code
+// units for editors and languages that use them.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Location {
+    pub line: usize,
+    // Position of the first byte of the line.
+    pub line_start: usize,
+    pub column: usize,
+    pub column_utf16: usize,
+}
+
+// Finds the locations of positions in one pass over the code, so positions must be located in ascending order. Locating each
+// position from the start of its line instead would take quadratic time when there are many of them (e.g. on one long line).
+pub struct Locator<'c> {
+    code: &'c [u8],
+    scanned: usize,
+    // Location of `scanned`.
+    location: Location,
+}
+
+impl<'c> Locator<'c> {
+    pub fn new(code: &'c [u8]) -> Locator<'c> {
+        Locator {
+            code,
+            scanned: 0,
+            location: Location {
+                line: 1,
+                line_start: 0,
+                column: 1,
+                column_utf16: 1,
+            },
+        }
+    }
+
+    pub fn locate(&mut self, position: usize) -> Location {
+        debug_assert!(position >= self.scanned);
+        let loc = &mut self.location;
+        for (i, &c) in self.code[self.scanned..position].iter().enumerate() {
+            if c == b'\n' {
+                loc.line += 1;
+                loc.line_start = self.scanned + i + 1;
+                loc.column = 1;
+                loc.column_utf16 = 1;
+            } else if c & 0b1100_0000 != 0b1000_0000 {
+                // Count the first byte of each UTF-8 sequence. Characters with four bytes are surrogate pairs in UTF-16.
+                loc.column += 1;
+                loc.column_utf16 += if c >= 0b1111_0000 { 2 } else { 1 };
+            };
+        }
+        self.scanned = position;
+        *loc
+    }
+}
@@ -1,6 +1,8 @@
 pub mod gen;
+pub mod location;
+pub mod panic;
 pub mod pattern;
 pub mod spec;
 #[cfg(test)]
-mod tests;
+pub mod tests;
 pub mod whitespace;
@@ -0,0 +1,12 @@
+use std::any::Any;
+
+// Extracts the message from the payload of a caught panic, which is a `String` or `&'static str` unless `panic_any` was used.
+pub fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => match payload.downcast::<&'static str>() {
+            Ok(message) => message.to_string(),
+            Err(_) => "unknown panic".to_string(),
+        },
+    }
+}
@@ -24,3 +24,254 @@ lazy_static! {
         s
     };
 }
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ScriptDataEnd {
+    // Position of the `</script` that ends the script data.
+    Found(usize),
+    // There is no end tag, and the script data ends in the given state. If double escaped, a `</script` appended to the code would not end it.
+    NotFound { double_escaped: bool },
+}
+
+fn is_script_tag_name_end(c: Option<&u8>) -> bool {
+    matches!(
+        c,
+        Some(b'\t')
+            | Some(b'\n')
+            | Some(b'\x0c')
+            | Some(b'\r')
+            | Some(b' ')
+            | Some(b'/')
+            | Some(b'>')
+    )
+}
+
+fn is_script_end_tag_at(code: &[u8], i: usize) -> bool {
+    code.len() >= i + 8
+        && code[i..i + 2] == *b"</"
+        && code[i + 2..i + 8].eq_ignore_ascii_case(b"script")
+}
+
+// Finds the end of script data, following the [escaped and double escaped states](https://html.spec.whatwg.org/multipage/parsing.html#script-data-escaped-state) for `<!--` and `<script` sequences. See notes/Script data.md.
+pub fn find_script_data_end(code: &[u8]) -> ScriptDataEnd {
+    #[derive(PartialEq)]
+    enum State {
+        Normal,
+        Escaped,
+        DoubleEscaped,
+    }
+    let mut state = State::Normal;
+    // Number of consecutive dashes immediately before the current position while escaped. `<!--` counts as two.
+    let mut dashes = 0;
+    let mut i = 0;
+    while i < code.len() {
+        let rest = &code[i..];
+        match rest[0] {
+            b'-' if state != State::Normal => {
+                dashes += 1;
+                i += 1;
+                continue;
+            }
+            b'>' if state != State::Normal && dashes >= 2 => {
+                state = State::Normal;
+            }
+            b'<' if state == State::Normal && rest.starts_with(b"<!--") => {
+                state = State::Escaped;
+                dashes = 2;
+                i += 4;
+                continue;
+            }
+            b'<' if is_script_end_tag_at(code, i) => {
+                if state != State::DoubleEscaped {
+                    return ScriptDataEnd::Found(i);
+                };
+                if is_script_tag_name_end(rest.get(8)) {
+                    state = State::Escaped;
+                };
+            }
+            b'<' if state == State::Escaped
+                && rest.len() >= 7
+                && rest[1..7].eq_ignore_ascii_case(b"script")
+                && is_script_tag_name_end(rest.get(7)) =>
+            {
+                state = State::DoubleEscaped;
+            }
+            _ => {}
+        };
+        dashes = 0;
+        i += 1;
+    }
+    ScriptDataEnd::NotFound {
+        double_escaped: state == State::DoubleEscaped,
+    }
+}
+
+fn is_identifier_char(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || c == b'_' || c == b'$' || c >= 0x80
+}
+
+// Keywords that can be followed by an expression, so a `/` after them starts a regular expression instead of being a division.
+static KEYWORDS_BEFORE_EXPRESSION: &[&[u8]] = &[
+    b"await",
+    b"case",
+    b"delete",
+    b"do",
+    b"else",
+    b"in",
+    b"instanceof",
+    b"new",
+    b"of",
+    b"return",
+    b"throw",
+    b"typeof",
+    b"void",
+    b"yield",
+];
+
+enum Literal {
+    String(u8),
+    Template,
+    Regex { in_class: bool },
+    LineComment,
+    BlockComment,
+}
+
+// Finds the positions of each `</script` in JS code, or returns None if one isn't in a string, template literal, regular expression, or comment, where escaping it as `<\/script` would change the code (e.g. `x</script/.source`, which is `x < /script/.source`). Whether a `/` starts a regular expression is decided by the token before it, which is enough for minified code.
+fn find_escapable_script_end_tags(code: &[u8]) -> Option<Vec<usize>> {
+    let mut positions = Vec::new();
+    let mut literal: Option<Literal> = None;
+    // Number of unclosed `{` in each open template literal substitution, innermost last.
+    let mut substitutions = Vec::<usize>::new();
+    let mut regex_allowed = true;
+    let mut i = 0;
+    while i < code.len() {
+        let c = code[i];
+        if c == b'<' && is_script_end_tag_at(code, i) {
+            match literal {
+                Some(_) => positions.push(i),
+                None => return None,
+            };
+        };
+        match &mut literal {
+            Some(Literal::String(quote)) => match c {
+                b'\\' => i += 1,
+                c if c == *quote => literal = None,
+                _ => {}
+            },
+            Some(Literal::Template) => match c {
+                b'\\' => i += 1,
+                b'`' => {
+                    literal = None;
+                    regex_allowed = false;
+                }
+                b'$' if code.get(i + 1) == Some(&b'{') => {
+                    substitutions.push(0);
+                    literal = None;
+                    regex_allowed = true;
+                    i += 1;
+                }
+                _ => {}
+            },
+            Some(Literal::Regex { in_class }) => match c {
+                b'\\' => i += 1,
+                b'[' => *in_class = true,
+                b']' => *in_class = false,
+                b'/' if !*in_class => literal = None,
+                _ => {}
+            },
+            Some(Literal::LineComment) => {
+                if c == b'\n' || c == b'\r' {
+                    literal = None;
+                };
+            }
+            Some(Literal::BlockComment) => {
+                if code[i..].starts_with(b"*/") {
+                    literal = None;
+                    i += 1;
+                };
+            }
+            None => match c {
+                b'"' | b'\'' => {
+                    literal = Some(Literal::String(c));
+                    regex_allowed = false;
+                }
+                b'`' => {
+                    literal = Some(Literal::Template);
+                    regex_allowed = false;
+                }
+                b'/' if code.get(i + 1) == Some(&b'/') => {
+                    literal = Some(Literal::LineComment);
+                    i += 1;
+                }
+                b'/' if code.get(i + 1) == Some(&b'*') => {
+                    literal = Some(Literal::BlockComment);
+                    i += 1;
+                }
+                b'/' if regex_allowed => {
+                    literal = Some(Literal::Regex { in_class: false });
+                    regex_allowed = false;
+                }
+                b'{' => {
+                    if let Some(depth) = substitutions.last_mut() {
+                        *depth += 1;
+                    };
+                    regex_allowed = true;
+                }
+                b'}' => match substitutions.last_mut() {
+                    Some(0) => {
+                        substitutions.pop();
+                        literal = Some(Literal::Template);
+                    }
+                    Some(depth) => {
+                        *depth -= 1;
+                        regex_allowed = false;
+                    }
+                    None => regex_allowed = false,
+                },
+                b')' | b']' => regex_allowed = false,
+                // Postfix increments and decrements end an operand.
+                b'+' | b'-' if code.get(i + 1) == Some(&c) => {
+                    regex_allowed = false;
+                    i += 1;
+                }
+                c if is_identifier_char(c) => {
+                    let start = i;
+                    while code
+                        .get(i + 1)
+                        .filter(|&&c| {
+                            is_identifier_char(c) || c == b'.' && code[start].is_ascii_digit()
+                        })
+                        .is_some()
+                    {
+                        i += 1;
+                    }
+                    regex_allowed = KEYWORDS_BEFORE_EXPRESSION.contains(&&code[start..=i]);
+                }
+                c if c.is_ascii_whitespace() => {}
+                _ => regex_allowed = true,
+            },
+        };
+        i += 1;
+    }
+    Some(positions)
+}
+
+// Escapes any `</script` in minified code as `<\/script`, which is equivalent in strings, template literals, regular expressions, and comments, where they'd appear (e.g. after the minifier joins `"</" + "script>"`).
+// Returns None if there's a `</script` anywhere else, or if the code still can't be safely placed before the closing tag because it ends in the double escaped state.
+pub fn escape_script_end_tags(code: Vec<u8>) -> Option<Vec<u8>> {
+    let positions = find_escapable_script_end_tags(&code)?;
+    let mut res = Vec::with_capacity(code.len() + positions.len());
+    let mut last = 0;
+    for position in positions {
+        res.extend_from_slice(&code[last..position + 1]);
+        res.push(b'\\');
+        last = position + 1;
+    }
+    res.extend_from_slice(&code[last..]);
+    match find_script_data_end(&res) {
+        ScriptDataEnd::NotFound {
+            double_escaped: false,
+        } => Some(res),
+        _ => None,
+    }
+}
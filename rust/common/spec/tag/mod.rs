@@ -1,5 +1,6 @@
 pub mod ns;
 pub mod omission;
+pub mod raw_text;
 pub mod void;
 pub mod whitespace;
 
@@ -0,0 +1,37 @@
+use lazy_static::lazy_static;
+use memchr::memchr;
+use std::collections::HashSet;
+
+lazy_static! {
+    // HTML elements other than `script` and `style` whose contents are always [raw text](https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-inbody) in browsers. `noscript` is also raw text, but only when scripting is enabled, and `plaintext` has no end tag at all.
+    pub static ref RAW_TEXT_TAGS: HashSet<&'static [u8]> = {
+        let mut s = HashSet::<&'static [u8]>::new();
+        s.insert(b"iframe");
+        s.insert(b"noembed");
+        s.insert(b"noframes");
+        s.insert(b"xmp");
+        s
+    };
+}
+
+// Finds the start of the first end tag for the raw text element `name`. Unlike `</style`, the tag name must be followed by whitespace, `/`, or `>`, as otherwise browsers treat it as text (e.g. `</xmpl>`).
+pub fn find_raw_text_end(code: &[u8], name: &[u8]) -> Option<usize> {
+    let mut i = 0;
+    while let Some(pos) = memchr(b'<', &code[i..]) {
+        let start = i + pos;
+        let rest = &code[start..];
+        let end_of_name = 2 + name.len();
+        if rest.len() > end_of_name
+            && rest[1] == b'/'
+            && rest[2..end_of_name].eq_ignore_ascii_case(name)
+            && matches!(
+                rest[end_of_name],
+                b'\t' | b'\n' | b'\x0c' | b'\r' | b' ' | b'/' | b'>'
+            )
+        {
+            return Some(start);
+        };
+        i = start + 1;
+    }
+    None
+}
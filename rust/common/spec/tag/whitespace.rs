@@ -9,31 +9,31 @@ pub struct WhitespaceMinification {
     pub trim: bool,
 }
 
-static CONTENT: &WhitespaceMinification = &WhitespaceMinification {
+pub static CONTENT: &WhitespaceMinification = &WhitespaceMinification {
     collapse: true,
     destroy_whole: false,
     trim: true,
 };
 
-static CONTENT_FIRST: &WhitespaceMinification = &WhitespaceMinification {
+pub static CONTENT_FIRST: &WhitespaceMinification = &WhitespaceMinification {
     collapse: true,
     destroy_whole: false,
     trim: true,
 };
 
-static FORMATTING: &WhitespaceMinification = &WhitespaceMinification {
+pub static FORMATTING: &WhitespaceMinification = &WhitespaceMinification {
     collapse: true,
     destroy_whole: false,
     trim: false,
 };
 
-static LAYOUT: &WhitespaceMinification = &WhitespaceMinification {
+pub static LAYOUT: &WhitespaceMinification = &WhitespaceMinification {
     collapse: true,
     destroy_whole: true,
     trim: true,
 };
 
-static WHITESPACE_SENSITIVE: &WhitespaceMinification = &WhitespaceMinification {
+pub static WHITESPACE_SENSITIVE: &WhitespaceMinification = &WhitespaceMinification {
     collapse: false,
     destroy_whole: false,
     trim: false,
@@ -45,7 +45,7 @@ static ROOT: &WhitespaceMinification = &WhitespaceMinification {
     trim: true,
 };
 
-static DEFAULT_HTML: &WhitespaceMinification = &WhitespaceMinification {
+pub static DEFAULT_HTML: &WhitespaceMinification = &WhitespaceMinification {
     collapse: true,
     destroy_whole: false,
     trim: false,
@@ -291,8 +291,16 @@ pub fn get_whitespace_minification_for_tag(
                     .unwrap_or(&DEFAULT_HTML)
             }
         }
-        Namespace::Svg => SVG_TAG_WHITESPACE_MINIFICATION
-            .get(tag_name)
-            .unwrap_or(&DEFAULT_SVG),
+        Namespace::Svg => {
+            let min = SVG_TAG_WHITESPACE_MINIFICATION
+                .get(tag_name)
+                .unwrap_or(&DEFAULT_SVG);
+            // Text inherits `white-space` from a `<pre>` ancestor, but whitespace in other SVG elements isn't rendered.
+            if descendant_of_pre && !min.destroy_whole {
+                WHITESPACE_SENSITIVE
+            } else {
+                min
+            }
+        }
     }
 }
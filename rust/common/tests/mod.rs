@@ -1,5 +1,9 @@
+use crate::cfg::Cfg;
+use crate::common::spec::script::escape_script_end_tags;
 use crate::tests::eval;
-use crate::tests::{eval_with_css_min, eval_with_js_min};
+use crate::tests::{eval_with_cfg, eval_with_css_min, eval_with_js_min};
+
+pub mod options;
 
 #[test]
 fn test_collapse_whitespace() {
@@ -56,6 +60,11 @@ fn test_no_whitespace_minification() {
         b"<div>  <pre>  <span>  1 <pre>\n</pre>    2   </span>  </pre>  </div>",
         b"<div><pre>  <span>  1 <pre>\n</pre>    2   </span>  </pre></div>",
     );
+    // Only SVG text is rendered, and it inherits `white-space`.
+    eval(
+        b"<pre><svg> <circle/> <text> a  b </text> </svg></pre>",
+        b"<pre><svg><circle/><text> a  b </text></svg></pre>",
+    );
     eval(
         br#"<pre><code>fn main() {
   println!("Hello, world!");
@@ -85,6 +94,13 @@ fn test_parsing_omitted_closing_tag() {
     );
 }
 
+#[test]
+fn test_svg_tag_attr_namespace() {
+    // An `<svg>` is itself in the svg namespace, where an empty `class` isn't redundant.
+    eval(b"<svg class=\"\"></svg>", b"<svg class></svg>");
+    eval(b"<div class=\"\"></div>", b"<div></div>");
+}
+
 #[test]
 fn test_self_closing_svg_tag_whitespace_removal() {
     eval(b"<svg><path d=a /></svg>", b"<svg><path d=a /></svg>");
@@ -393,6 +409,19 @@ fn test_unintentional_entity_prevention() {
     eval(b"&am&nLt;", b"&am&nLt;");
     eval(b"&am&nLt;a", b"&am&nLt;a");
     eval(b"&am&nLt", b"&am&nLt");
+
+    // `&amp` without a semicolon followed by an alphanumeric isn't decoded in attribute values.
+    eval(br#"<a title="&amp;amp"></a>"#, br#"<a title=&#38amp></a>"#);
+    eval(br#"<a title="&&#108t"></a>"#, br#"<a title=&#38lt></a>"#);
+    eval(
+        br#"<a href="?a=1&amp;section=1"></a>"#,
+        br#"<a href=?a=1&section=1></a>"#,
+    );
+
+    // Decoded entities after the first aren't processed again as code.
+    eval(b"<p>&amp;&amp;lt;", b"<p>&&amplt;");
+    eval(b"<p>&amp;&lt;p>", b"<p>&&LTp>");
+    eval(br#"<p title="&amp;&quot;x">"#, br#"<p title=&"x>"#);
 }
 
 #[test]
@@ -445,7 +474,6 @@ fn test_comments_removal() {
         b"<pre>a <!-- akd--sj\n <!-- \t\0f--ajk--df->lafj -->  b</pre>",
         b"<pre>a   b</pre>",
     );
-    eval(b"&a<!-- akd--sj\n <!-- \t\0f--ajk--df->lafj -->mp", b"&amp");
     eval(
         b"<script><!-- akd--sj\n <!-- \t\0f--ajk--df->lafj --></script>",
         b"<script><!-- akd--sj\n <!-- \t\0f--ajk--df->lafj --></script>",
@@ -456,6 +484,9 @@ fn test_comments_removal() {
 fn test_processing_instructions() {
     eval(b"<?php hello??? >>  ?>", b"<?php hello??? >>  ?>");
     eval(b"av<?xml 1.0 ?>g", b"av<?xml 1.0 ?>g");
+    // Leading whitespace is trimmed even if it's after an instruction.
+    eval(b"<p><?x?> a</p>", b"<p><?x?>a");
+    eval(b"<!DOCTYPE html> a", b"<!doctypehtml>a");
 }
 
 #[test]
@@ -487,27 +518,134 @@ fn test_js_minification() {
     );
 }
 
-/* TODO Reenable once unintentional script closing tag escaping is implemented in minify-js.
 #[test]
 fn test_js_minification_unintentional_closing_tag() {
     eval_with_js_min(
         br#"<script>let a = "</" + "script>";</script>"#,
-        br#"<script>let a="<\/script>";</script>"#,
+        br#"<script>let a="<\/script>"</script>"#,
     );
     eval_with_js_min(
         br#"<script>let a = "</S" + "cRiPT>";</script>"#,
-        br#"<script>let a="<\/ScRiPT>";</script>"#,
+        br#"<script>let a="<\/ScRiPT>"</script>"#,
     );
     eval_with_js_min(
         br#"<script>let a = "\u003c/script>";</script>"#,
-        br#"<script>let a="<\/script>";</script>"#,
+        br#"<script>let a="<\/script>"</script>"#,
     );
     eval_with_js_min(
         br#"<script>let a = "\u003c/scrIPt>";</script>"#,
-        br#"<script>let a="<\/scrIPt>";</script>"#,
+        br#"<script>let a="<\/scrIPt>"</script>"#,
+    );
+    // The minified code must not end in the double escaped state, even if the `</script` sequences are escaped.
+    eval_with_js_min(
+        br#"<script>let a = "<!--<script>"; let b = "</script>"; let c = "-->";</script>"#,
+        br#"<script>let a="<!--<script>";let b="<\/script>";let c="-->"</script>"#,
+    );
+    // A `</script` outside a literal can't be escaped, so the code is kept.
+    eval_with_js_min(
+        br#"<script> let a = b\u003c/script/.source; </script>"#,
+        br#"<script>let a = b\u003c/script/.source;</script>"#,
+    );
+}
+
+#[test]
+fn test_escape_script_end_tags() {
+    let escape = |code: &[u8]| escape_script_end_tags(code.to_vec());
+    assert_eq!(
+        escape(br#"a="</script>",b='</SCRIPT',c=`</script${"}"}</script`,d=/[</script]/,e=1/2/*</script>*/"#),
+        Some(
+            br#"a="<\/script>",b='<\/SCRIPT',c=`<\/script${"}"}<\/script`,d=/[<\/script]/,e=1/2/*<\/script>*/"#
+                .to_vec()
+        ),
+    );
+    // A `/` after an operand is a division, and after an operator or keyword starts a regular expression.
+    assert_eq!(
+        escape(br#"a=b/2,c=(d)/"</script"#),
+        Some(br#"a=b/2,c=(d)/"<\/script"#.to_vec()),
+    );
+    assert_eq!(
+        escape(br#"return/"/.test(a)?"</script":b"#),
+        Some(br#"return/"/.test(a)?"<\/script":b"#.to_vec()),
+    );
+    assert_eq!(escape(br#"a=/"/,b</script/.source"#), None);
+    assert_eq!(escape(b"x</script/.source"), None);
+    assert_eq!(escape(b"a++/b</script/"), None);
+}
+
+#[test]
+fn test_script_data_escaped_states() {
+    // A `<script` inside `<!--` means the next `</script>` doesn't end the element.
+    eval(
+        b"<script type=text/plain><!--\n  <script>alert();</script>\n</script>",
+        b"<script type=text/plain><!--\n  <script>alert();</script>\n</script>",
+    );
+    eval(
+        b"<script type=text/plain><!--<script></script>--></script>a",
+        b"<script type=text/plain><!--<script></script>--></script>a",
+    );
+    // Without a `<script` inside `<!--`, or after the `-->`, the first `</script>` ends the element.
+    eval(
+        b"<script type=text/plain><!--\n</script>a",
+        b"<script type=text/plain><!--\n</script>a",
+    );
+    eval(
+        b"<script type=text/plain><!--<script>--></script>a",
+        b"<script type=text/plain><!--<script>--></script>a",
+    );
+    eval(
+        b"<script type=text/plain><!--<scripts></script>a",
+        b"<script type=text/plain><!--<scripts></script>a",
+    );
+    eval(
+        b"<script type=text/plain><!--><script></script>a",
+        b"<script type=text/plain><!--><script></script>a",
+    );
+    // Each `</script>` in a double escaped state returns to the escaped state.
+    eval(
+        b"<script type=text/plain><!--<script><script></script>\n<script></script>--></script>a",
+        b"<script type=text/plain><!--<script><script></script>\n<script></script>--></script>a",
+    );
+}
+
+#[test]
+fn test_raw_text_elements() {
+    // Entities aren't decoded and tags aren't parsed in raw text, so it's kept as is.
+    eval(
+        b"<xmp>  a &amp; <b>b</b>  </xmp>",
+        b"<xmp>  a &amp; <b>b</b>  </xmp>",
+    );
+    eval(b"<iframe><p>a</iframe>", b"<iframe><p>a</iframe>");
+    eval(
+        b"<noframes><!-- a --></NOFRAMES >",
+        b"<noframes><!-- a --></noframes>",
+    );
+    // Only an end tag with the exact name closes the element.
+    eval(
+        b"<noembed></noembedded></noembed>",
+        b"<noembed></noembedded></noembed>",
+    );
+    // `<plaintext>` can't be closed.
+    eval(
+        b"<plaintext></plaintext> <p>  a",
+        b"<plaintext></plaintext> <p>  a",
+    );
+}
+
+#[test]
+fn test_noscript_as_raw_text() {
+    let mut cfg = Cfg::new();
+    eval_with_cfg(
+        b"<noscript><p>a &amp; b</p></noscript>",
+        b"<noscript><p>a & b</p></noscript>",
+        &cfg,
+    );
+    cfg.parse_noscript_as_raw_text = true;
+    eval_with_cfg(
+        b"<noscript><p>a &amp; b</p></noscript>",
+        b"<noscript><p>a &amp; b</p></noscript>",
+        &cfg,
     );
 }
-*/
 
 #[test]
 fn test_style_element_minification() {
@@ -529,3 +667,77 @@ fn test_self_closing_svg() {
         b"<a><svg viewbox=\"0 0 700 100\"></svg></a><footer></footer>",
     );
 }
+
+#[test]
+fn test_attr_value_trim_and_case_insensitivity() {
+    eval(br#"<input type="  TEXT  ">"#, br#"<input>"#);
+    eval(br#"<input type=" a  B ">"#, br#"<input type="a  b">"#);
+    eval(
+        br#"<meta http-equiv="  Content-Type  ">"#,
+        br#"<meta http-equiv=content-type>"#,
+    );
+}
+
+// Enables an option, followed by the source, the output by default, and the output with the option enabled.
+type CfgCase = (fn(&mut Cfg), &'static [u8], &'static [u8], &'static [u8]);
+
+// Each option is enabled on its own, and outputs must be the same in both variants so either can be used.
+#[test]
+fn test_cfg_matrix() {
+    let cases: &[CfgCase] = &[
+        (
+            |cfg| cfg.do_not_minify_doctype = true,
+            b"<!DOCTYPE html><p>a",
+            b"<!doctypehtml><p>a",
+            b"<!doctype html><p>a",
+        ),
+        (
+            |cfg| cfg.ensure_spec_compliant_unquoted_attribute_values = true,
+            br#"<a b="=" c="x"></a>"#,
+            br#"<a b== c=x></a>"#,
+            br#"<a b="="c=x></a>"#,
+        ),
+        (
+            |cfg| cfg.keep_closing_tags = true,
+            b"<div><p>a</p><p>b</p></div>",
+            b"<div><p>a<p>b</div>",
+            b"<div><p>a</p><p>b</p></div>",
+        ),
+        (
+            |cfg| cfg.keep_comments = true,
+            b"<div> <!-- a --> <p>b</p> c<!--d-->e </div>",
+            b"<div><p>b</p> ce</div>",
+            b"<div><!-- a --><p>b</p> c<!--d-->e</div>",
+        ),
+        (
+            |cfg| cfg.keep_html_and_head_opening_tags = true,
+            b"<html><head><title>a</title></head><body></body></html>",
+            b"<title>a</title><body>",
+            b"<html><head><title>a</title><body>",
+        ),
+        (
+            |cfg| cfg.keep_spaces_between_attributes = true,
+            br#"<div a="x y" b="z"></div>"#,
+            br#"<div a="x y"b=z></div>"#,
+            br#"<div a="x y" b=z></div>"#,
+        ),
+        (
+            |cfg| cfg.remove_bangs = true,
+            b"<p>a<!x>b",
+            b"<p>a<!x>b",
+            b"<p>ab",
+        ),
+        (
+            |cfg| cfg.remove_processing_instructions = true,
+            b"<p>a<?x?>b",
+            b"<p>a<?x?>b",
+            b"<p>ab",
+        ),
+    ];
+    for &(enable, src, expected_default, expected_enabled) in cases {
+        eval_with_cfg(src, expected_default, &Cfg::new());
+        let mut cfg = Cfg::new();
+        enable(&mut cfg);
+        eval_with_cfg(src, expected_enabled, &cfg);
+    }
+}
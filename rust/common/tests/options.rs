@@ -0,0 +1,53 @@
+// Boolean options of the main variant's `Cfg` that tests combine by name. Options that only apply to other encodings or
+// limit the input or output are left out, as are `infer_whitespace_from_css` and the custom rules, which would need
+// generated CSS and rules.
+pub const OPTIONS: &[&str] = &[
+    "always_quote_attribute_values",
+    "ascii_only",
+    "conservative_entities",
+    "do_not_minify_doctype",
+    "ensure_no_parse_errors",
+    "ensure_spec_compliant_unquoted_attribute_values",
+    "keep_closing_tags",
+    "keep_comments",
+    "keep_conditional_comments",
+    "keep_html_and_head_opening_tags",
+    "keep_spaces_between_attributes",
+    "minify_css",
+    "minify_js",
+    "parse_noscript_as_raw_text",
+    "preserve_line_breaks",
+    "remove_bangs",
+    "remove_processing_instructions",
+];
+
+// Enables the option named `$name` in `OPTIONS` on `$cfg`, which is a main variant `Cfg` (i.e. `minify_html::Cfg` in
+// onepass). It's a macro so that it works with the type from either crate.
+macro_rules! enable_option {
+    ($cfg:expr, $name:expr) => {
+        match $name {
+            "always_quote_attribute_values" => $cfg.always_quote_attribute_values = true,
+            "ascii_only" => $cfg.ascii_only = true,
+            "conservative_entities" => $cfg.conservative_entities = true,
+            "do_not_minify_doctype" => $cfg.do_not_minify_doctype = true,
+            "ensure_no_parse_errors" => $cfg.ensure_no_parse_errors = true,
+            "ensure_spec_compliant_unquoted_attribute_values" => {
+                $cfg.ensure_spec_compliant_unquoted_attribute_values = true
+            }
+            "keep_closing_tags" => $cfg.keep_closing_tags = true,
+            "keep_comments" => $cfg.keep_comments = true,
+            "keep_conditional_comments" => $cfg.keep_conditional_comments = true,
+            "keep_html_and_head_opening_tags" => $cfg.keep_html_and_head_opening_tags = true,
+            "keep_spaces_between_attributes" => $cfg.keep_spaces_between_attributes = true,
+            "minify_css" => $cfg.minify_css = true,
+            "minify_js" => $cfg.minify_js = true,
+            "parse_noscript_as_raw_text" => $cfg.parse_noscript_as_raw_text = true,
+            "preserve_line_breaks" => $cfg.preserve_line_breaks = true,
+            "remove_bangs" => $cfg.remove_bangs = true,
+            "remove_processing_instructions" => $cfg.remove_processing_instructions = true,
+            name => panic!("unknown option {}", name),
+        }
+    };
+}
+
+pub(crate) use enable_option;
@@ -1,3 +1,5 @@
+use std::borrow::Cow;
+
 use crate::common::gen::codepoints::WHITESPACE;
 
 pub fn trimmed(val: &[u8]) -> &[u8] {
@@ -14,34 +16,112 @@ pub fn trimmed(val: &[u8]) -> &[u8] {
     &val[start..end]
 }
 
-pub fn left_trim(val: &mut Vec<u8>) {
+// Values may be borrowed from the source, so these functions only copy them if they need to be changed in a way that can't be done by slicing.
+fn retain(val: &mut Cow<[u8]>, start: usize, end: usize) {
+    match val {
+        Cow::Borrowed(b) => *b = &b[start..end],
+        Cow::Owned(o) => {
+            o.truncate(end);
+            o.drain(0..start);
+        }
+    };
+}
+
+pub fn left_trim(val: &mut Cow<[u8]>) {
     let mut len = 0;
     while val.get(len).filter(|&&c| WHITESPACE[c]).is_some() {
         len += 1;
     }
-    val.drain(0..len);
+    retain(val, len, val.len());
 }
 
-pub fn right_trim(val: &mut Vec<u8>) {
-    let mut retain = val.len();
-    while retain > 0 && val.get(retain - 1).filter(|&&c| WHITESPACE[c]).is_some() {
-        retain -= 1;
+pub fn right_trim(val: &mut Cow<[u8]>) {
+    let mut retain_len = val.len();
+    while retain_len > 0
+        && val
+            .get(retain_len - 1)
+            .filter(|&&c| WHITESPACE[c])
+            .is_some()
+    {
+        retain_len -= 1;
     }
-    val.truncate(retain);
+    retain(val, 0, retain_len);
+}
+
+fn is_line_break(c: u8) -> bool {
+    c == b'\n' || c == b'\r'
 }
 
-pub fn collapse_whitespace(val: &mut Vec<u8>) {
+// Like `left_trim`, but leaves a single `\n` if the removed whitespace contained a line break.
+pub fn left_trim_to_line_break(val: &mut Cow<[u8]>) {
+    let mut len = 0;
+    while val.get(len).filter(|&&c| WHITESPACE[c]).is_some() {
+        len += 1;
+    }
+    if contains_line_break(&val[..len]) {
+        retain(val, len - 1, val.len());
+        if val[0] != b'\n' {
+            val.to_mut()[0] = b'\n';
+        };
+    } else {
+        retain(val, len, val.len());
+    };
+}
+
+// Like `right_trim`, but leaves a single `\n` if the removed whitespace contained a line break.
+pub fn right_trim_to_line_break(val: &mut Cow<[u8]>) {
+    let mut retain_len = val.len();
+    while retain_len > 0
+        && val
+            .get(retain_len - 1)
+            .filter(|&&c| WHITESPACE[c])
+            .is_some()
+    {
+        retain_len -= 1;
+    }
+    if contains_line_break(&val[retain_len..]) {
+        retain(val, 0, retain_len + 1);
+        if val[retain_len] != b'\n' {
+            val.to_mut()[retain_len] = b'\n';
+        };
+    } else {
+        retain(val, 0, retain_len);
+    };
+}
+
+// If `preserve_line_breaks`, a whitespace sequence containing a line break is collapsed to a single `\n` instead of a space.
+pub fn collapse_whitespace(val: &mut Cow<[u8]>, preserve_line_breaks: bool) {
+    // Avoid copying if there's nothing to collapse, which is common.
+    let mut prev_is_whitespace = false;
+    let unchanged = val.iter().all(|&c| {
+        let is_whitespace = WHITESPACE[c];
+        let ok = !is_whitespace
+            || (!prev_is_whitespace && (c == b' ' || (preserve_line_breaks && c == b'\n')));
+        prev_is_whitespace = is_whitespace;
+        ok
+    });
+    if unchanged {
+        return;
+    };
+    let val = val.to_mut();
     let mut write = 0;
     let mut in_whitespace = false;
     for i in 0..val.len() {
         let mut c = val[i];
         if WHITESPACE[c] {
             if in_whitespace {
+                if preserve_line_breaks && is_line_break(c) {
+                    val[write - 1] = b'\n';
+                };
                 // Skip this character.
                 continue;
             };
             in_whitespace = true;
-            c = b' ';
+            c = if preserve_line_breaks && is_line_break(c) {
+                b'\n'
+            } else {
+                b' '
+            };
         } else {
             in_whitespace = false;
         };
@@ -51,7 +131,11 @@ pub fn collapse_whitespace(val: &mut Vec<u8>) {
     val.truncate(write);
 }
 
-pub fn remove_all_whitespace(val: &mut Vec<u8>) {
+pub fn remove_all_whitespace(val: &mut Cow<[u8]>) {
+    if !val.iter().any(|&c| WHITESPACE[c]) {
+        return;
+    };
+    let val = val.to_mut();
     let mut write = 0;
     for i in 0..val.len() {
         let c = val[i];
@@ -65,6 +149,10 @@ pub fn remove_all_whitespace(val: &mut Vec<u8>) {
     val.truncate(write);
 }
 
+pub fn contains_line_break(val: &[u8]) -> bool {
+    val.iter().any(|&c| is_line_break(c))
+}
+
 pub fn is_all_whitespace(val: &[u8]) -> bool {
     for &c in val {
         if !WHITESPACE[c] {
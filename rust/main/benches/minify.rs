@@ -0,0 +1,41 @@
+// Measures the time to minify each of the benchmark inputs in `bench/inputs`, without minifying JS and CSS so that only parsing
+// and minifying the HTML is measured. Run with `cargo bench`, optionally setting `MHB_ITERATIONS` (default 200).
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::time::Instant;
+
+use minify_html::{minify, Cfg};
+
+fn main() {
+    let iterations = env::var("MHB_ITERATIONS")
+        .map(|v| v.parse::<usize>().unwrap())
+        .unwrap_or(200);
+    let input_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../bench/inputs");
+    let cfg = Cfg::new();
+
+    let mut inputs = fs::read_dir(input_dir)
+        .unwrap()
+        .map(|d| d.unwrap().path())
+        .collect::<Vec<_>>();
+    inputs.sort();
+    let mut total = 0.0;
+    for path in inputs {
+        let source = fs::read(&path).unwrap();
+        // Warm up.
+        minify(&source, &cfg);
+        let start = Instant::now();
+        for _ in 0..iterations {
+            minify(&source, &cfg);
+        }
+        let ms = start.elapsed().as_secs_f64() * 1000.0 / iterations as f64;
+        total += ms;
+        println!(
+            "{:<16}{:>8.3} ms",
+            path.file_name().unwrap().to_str().unwrap(),
+            ms
+        );
+    }
+    println!("{:<16}{:>8.3} ms", "Total", total);
+}
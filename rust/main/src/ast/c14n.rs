@@ -4,60 +4,176 @@ use aho_corasick::{AhoCorasickBuilder, MatchKind};
 use lazy_static::lazy_static;
 
 use crate::ast::{ElementClosingTag, NodeData};
+use crate::cfg::canonicalise::{AttributeQuotes, CanonicaliseCfg, EntityPolicy};
+use crate::cfg::whitespace::WhitespaceRules;
 use crate::common::pattern::Replacer;
+use crate::common::spec::tag::ns::Namespace;
+use crate::common::spec::tag::whitespace::get_whitespace_minification_for_tag;
+use crate::common::whitespace::is_all_whitespace;
+use crate::entity::decode::decode_entities;
+use crate::minify::ascii::encode_non_ascii_as_refs;
 
-lazy_static! {
-    static ref TEXT_REPLACER: Replacer = Replacer::new(
-        AhoCorasickBuilder::new()
-            .dfa(true)
-            .match_kind(MatchKind::LeftmostLongest)
-            .build(vec![b"&".to_vec(), b"<".to_vec(),]),
-        vec![b"&amp;".to_vec(), b"&lt;".to_vec(),],
-    );
-    static ref DOUBLE_QUOTED_REPLACER: Replacer = Replacer::new(
+fn build_replacer(patterns: &[&[u8]], replacements: &[&[u8]]) -> Replacer {
+    Replacer::new(
         AhoCorasickBuilder::new()
             .dfa(true)
             .match_kind(MatchKind::LeftmostLongest)
-            .build(vec![b"&".to_vec(), b"\"".to_vec(),]),
-        vec![b"&amp;".to_vec(), b"&#34;".to_vec(),],
-    );
-    static ref SINGLE_QUOTED_REPLACER: Replacer = Replacer::new(
-        AhoCorasickBuilder::new()
-            .dfa(true)
-            .match_kind(MatchKind::LeftmostLongest)
-            .build(vec![b"&".to_vec(), b"'".to_vec(),]),
-        vec![b"&amp;".to_vec(), b"&#39;".to_vec(),],
+            .build(patterns),
+        replacements.iter().map(|r| r.to_vec()).collect(),
+    )
+}
+
+static UNQUOTED_PATTERNS: &[&[u8]] = &[
+    b"&", b">", b"\"", b"'", b"\x09", b"\x0a", b"\x0c", b"\x0d", b"\x20",
+];
+static UNQUOTED_REPLACEMENTS: &[&[u8]] = &[
+    b"&amp;", b"&gt;", b"&#34;", b"&#39;", b"&#9;", b"&#10;", b"&#12;", b"&#13;", b"&#32;",
+];
+
+lazy_static! {
+    static ref TEXT_REPLACER: Replacer = build_replacer(&[b"&", b"<"], &[b"&amp;", b"&lt;"]);
+    static ref DOUBLE_QUOTED_REPLACER: Replacer =
+        build_replacer(&[b"&", b"\""], &[b"&amp;", b"&#34;"]);
+    static ref SINGLE_QUOTED_REPLACER: Replacer =
+        build_replacer(&[b"&", b"'"], &[b"&amp;", b"&#39;"]);
+    static ref UNQUOTED_REPLACER: Replacer =
+        build_replacer(UNQUOTED_PATTERNS, UNQUOTED_REPLACEMENTS);
+    static ref CONSERVATIVE_REPLACER: Replacer = build_replacer(
+        &[b"&", b"<", b">", b"\"", b"'"],
+        &[b"&amp;", b"&lt;", b"&gt;", b"&#34;", b"&#39;"],
     );
-    static ref UNQUOTED_REPLACER: Replacer = Replacer::new(
-        AhoCorasickBuilder::new()
-            .dfa(true)
-            .match_kind(MatchKind::LeftmostLongest)
-            .build(vec![
-                b"&".to_vec(),
-                b">".to_vec(),
-                b"\"".to_vec(),
-                b"'".to_vec(),
-                b"\x09".to_vec(),
-                b"\x0a".to_vec(),
-                b"\x0c".to_vec(),
-                b"\x0d".to_vec(),
-                b"\x20".to_vec(),
-            ]),
-        vec![
-            b"&amp;".to_vec(),
-            b"&gt;".to_vec(),
-            b"&#34;".to_vec(),
-            b"&#39;".to_vec(),
-            b"&#9;".to_vec(),
-            b"&#10;".to_vec(),
-            b"&#12;".to_vec(),
-            b"&#13;".to_vec(),
-            b"&#32;".to_vec(),
-        ],
+    static ref CONSERVATIVE_UNQUOTED_REPLACER: Replacer = build_replacer(
+        &[UNQUOTED_PATTERNS, &[b"<"]].concat(),
+        &[UNQUOTED_REPLACEMENTS, &[b"&lt;"]].concat(),
     );
 }
 
+// Characters that require an attribute value to be quoted, including those that are only parse errors when unquoted.
+fn can_be_unquoted(value: &[u8]) -> bool {
+    !value.is_empty()
+        && value.iter().all(|c| {
+            !matches!(
+                c,
+                b'\x09'
+                    | b'\x0a'
+                    | b'\x0c'
+                    | b'\x0d'
+                    | b' '
+                    | b'"'
+                    | b'\''
+                    | b'='
+                    | b'<'
+                    | b'>'
+                    | b'`'
+            )
+        })
+}
+
+fn write_escaped<T: Write>(
+    out: &mut T,
+    cfg: &CanonicaliseCfg,
+    minimal: &Replacer,
+    conservative: &Replacer,
+    value: &[u8],
+) -> std::io::Result<()> {
+    let escaped = match cfg.entities {
+        EntityPolicy::Minimal | EntityPolicy::Source => minimal.replace_all(value),
+        EntityPolicy::Conservative | EntityPolicy::Ascii => conservative.replace_all(value),
+    };
+    if cfg.entities == EntityPolicy::Ascii {
+        out.write_all(&encode_non_ascii_as_refs(&escaped, true))
+    } else {
+        out.write_all(&escaped)
+    }
+}
+
+// Whether `source` can be written as is for `value` with `EntityPolicy::Source`, which isn't the case if the value was changed after parsing or joined from several parts of the source.
+fn can_write_source(cfg: &CanonicaliseCfg, source: &[u8], value: &[u8], in_attr_val: bool) -> bool {
+    cfg.entities == EntityPolicy::Source && decode_entities(source, in_attr_val) == value
+}
+
+fn write_indent<T: Write>(out: &mut T, spaces: usize, depth: usize) -> std::io::Result<()> {
+    out.write_all(b"\n")?;
+    for _ in 0..spaces * depth {
+        out.write_all(b" ")?;
+    }
+    Ok(())
+}
+
 pub fn c14n_serialise_ast<T: Write>(out: &mut T, node: &NodeData) -> std::io::Result<()> {
+    c14n_serialise_node(
+        out,
+        &CanonicaliseCfg::default(),
+        &WhitespaceRules::default(),
+        false,
+        0,
+        node,
+    )
+}
+
+// Returns whether the nodes were indented, in which case the parent's closing tag should be on its own line. `depth` is the number of ancestors of `nodes`.
+#[allow(clippy::too_many_arguments)]
+pub fn c14n_serialise_nodes<T: Write>(
+    out: &mut T,
+    cfg: &CanonicaliseCfg,
+    rules: &WhitespaceRules,
+    ns: Namespace,
+    descendant_of_pre: bool,
+    // Use empty slice if root.
+    parent: &[u8],
+    depth: usize,
+    nodes: &[NodeData],
+) -> std::io::Result<bool> {
+    // Line breaks can only be added where whitespace is removed entirely when minifying, and there is no text they could be adjacent to.
+    let indent = cfg.indent.filter(|_| {
+        let whitespace = match rules.get_minification(parent) {
+            Some(min) if ns == Namespace::Html && !descendant_of_pre => min,
+            _ => get_whitespace_minification_for_tag(ns, parent, descendant_of_pre),
+        };
+        whitespace.destroy_whole
+            && whitespace.trim
+            && nodes.iter().all(|n| match n {
+                NodeData::Text { value, .. } => is_all_whitespace(value),
+                NodeData::ScriptOrStyleContent { .. } => false,
+                _ => true,
+            })
+    });
+    let spaces = match indent {
+        Some(spaces) => spaces,
+        None => {
+            for n in nodes {
+                c14n_serialise_node(out, cfg, rules, descendant_of_pre, depth, n)?;
+            }
+            return Ok(false);
+        }
+    };
+    let mut wrote_any = false;
+    for n in nodes {
+        if let NodeData::Text { .. } = n {
+            continue;
+        };
+        if !parent.is_empty() {
+            write_indent(out, spaces, depth)?;
+        } else if wrote_any {
+            out.write_all(b"\n")?;
+        };
+        c14n_serialise_node(out, cfg, rules, descendant_of_pre, depth, n)?;
+        wrote_any = true;
+    }
+    if parent.is_empty() && wrote_any {
+        out.write_all(b"\n")?;
+    };
+    Ok(wrote_any)
+}
+
+fn c14n_serialise_node<T: Write>(
+    out: &mut T,
+    cfg: &CanonicaliseCfg,
+    rules: &WhitespaceRules,
+    descendant_of_pre: bool,
+    depth: usize,
+    node: &NodeData,
+) -> std::io::Result<()> {
     match node {
         NodeData::Bang { code, .. } => {
             out.write_all(b"<!")?;
@@ -82,30 +198,71 @@ pub fn c14n_serialise_ast<T: Write>(out: &mut T, node: &NodeData) -> std::io::Re
             closing_tag,
             children,
             name,
+            namespace,
             ..
         } => {
             out.write_all(b"<")?;
             out.write_all(name)?;
             let mut attrs_sorted = attributes.iter().collect::<Vec<_>>();
-            attrs_sorted.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+            if cfg.sort_attributes {
+                attrs_sorted.sort_unstable_by(|a, b| a.0.cmp(b.0));
+            } else {
+                attrs_sorted.sort_unstable_by_key(|a| a.1.index);
+            };
             for (name, value) in attrs_sorted.iter() {
                 out.write_all(b" ")?;
                 out.write_all(name)?;
                 if !value.value.is_empty() {
                     out.write_all(b"=")?;
-                    match value.quote {
+                    let quote = match cfg.attribute_quotes {
+                        AttributeQuotes::Source => value.quote,
+                        AttributeQuotes::Double => Some(b'"'),
+                        AttributeQuotes::Single => Some(b'\''),
+                        AttributeQuotes::Minimal if can_be_unquoted(&value.value) => None,
+                        AttributeQuotes::Minimal => Some(b'"'),
+                    };
+                    match quote {
+                        _ if quote == value.quote
+                            && can_write_source(cfg, value.source.0, &value.value, true) =>
+                        {
+                            if let Some(c) = quote {
+                                out.write_all(&[c])?;
+                            };
+                            out.write_all(value.source.0)?;
+                            if let Some(c) = quote {
+                                out.write_all(&[c])?;
+                            };
+                        }
                         Some(b'"') => {
                             out.write_all(b"\"")?;
-                            out.write_all(&DOUBLE_QUOTED_REPLACER.replace_all(&value.value))?;
+                            write_escaped(
+                                out,
+                                cfg,
+                                &DOUBLE_QUOTED_REPLACER,
+                                &CONSERVATIVE_REPLACER,
+                                &value.value,
+                            )?;
                             out.write_all(b"\"")?;
                         }
                         Some(b'\'') => {
                             out.write_all(b"'")?;
-                            out.write_all(&SINGLE_QUOTED_REPLACER.replace_all(&value.value))?;
+                            write_escaped(
+                                out,
+                                cfg,
+                                &SINGLE_QUOTED_REPLACER,
+                                &CONSERVATIVE_REPLACER,
+                                &value.value,
+                            )?;
                             out.write_all(b"'")?;
                         }
                         None => {
-                            out.write_all(&UNQUOTED_REPLACER.replace_all(&value.value))?;
+                            write_escaped(
+                                out,
+                                cfg,
+                                &UNQUOTED_REPLACER,
+                                &CONSERVATIVE_UNQUOTED_REPLACER,
+                                &value.value,
+                            )?;
                         }
                         _ => unreachable!(),
                     };
@@ -115,10 +272,22 @@ pub fn c14n_serialise_ast<T: Write>(out: &mut T, node: &NodeData) -> std::io::Re
                 out.write_all(b" /")?;
             };
             out.write_all(b">")?;
-            for c in children {
-                c14n_serialise_ast(out, c)?;
-            }
+            let indented = c14n_serialise_nodes(
+                out,
+                cfg,
+                rules,
+                *namespace,
+                descendant_of_pre
+                    || (*namespace == Namespace::Html
+                        && (name.as_ref() == b"pre" || rules.is_pre_like(name))),
+                name,
+                depth + 1,
+                children,
+            )?;
             if closing_tag == &ElementClosingTag::Present {
+                if indented {
+                    write_indent(out, cfg.indent.unwrap(), depth)?;
+                };
                 out.write_all(b"</")?;
                 out.write_all(name)?;
                 out.write_all(b">")?;
@@ -132,8 +301,11 @@ pub fn c14n_serialise_ast<T: Write>(out: &mut T, node: &NodeData) -> std::io::Re
         NodeData::ScriptOrStyleContent { code, .. } => {
             out.write_all(code)?;
         }
-        NodeData::Text { value } => {
-            out.write_all(&TEXT_REPLACER.replace_all(value))?;
+        NodeData::Text { value, source } if can_write_source(cfg, source.0, value, false) => {
+            out.write_all(source.0)?;
+        }
+        NodeData::Text { value, .. } => {
+            write_escaped(out, cfg, &TEXT_REPLACER, &CONSERVATIVE_REPLACER, value)?;
         }
     };
     Ok(())
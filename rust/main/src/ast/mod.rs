@@ -1,10 +1,14 @@
-use std::collections::HashMap;
+use std::borrow::Cow;
 use std::fmt::{Debug, Formatter};
 use std::str::from_utf8;
 
+use bumpalo::collections::Vec as BumpVec;
+use bumpalo::Bump;
+
 use crate::common::spec::tag::ns::Namespace;
 
 pub mod c14n;
+pub mod normalise;
 
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
 pub enum ElementClosingTag {
@@ -14,6 +18,7 @@ pub enum ElementClosingTag {
     Void,
 }
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
 pub enum ScriptOrStyleLang {
     CSS,
@@ -21,78 +26,163 @@ pub enum ScriptOrStyleLang {
     JS,
 }
 
-pub struct AttrVal {
+// Source code of a value before its entities were decoded, for serialisation only, so it's not used for equality.
+#[derive(Clone, Copy)]
+pub struct SourceCode<'a>(pub &'a [u8]);
+
+impl<'a> PartialEq for SourceCode<'a> {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl<'a> Eq for SourceCode<'a> {}
+
+pub struct AttrVal<'a> {
     // For serialisation only, not used for equality or value.
     pub quote: Option<u8>,
-    pub value: Vec<u8>,
+    // Position of the attribute in its tag, for serialisation in source order only, not used for equality or value.
+    pub index: usize,
+    pub value: Cow<'a, [u8]>,
+    // Without the quotes, if any.
+    pub source: SourceCode<'a>,
 }
 
-impl AttrVal {
+impl<'a> AttrVal<'a> {
     pub fn as_slice(&self) -> &[u8] {
-        self.value.as_slice()
+        &self.value
     }
 }
 
-impl Debug for AttrVal {
+impl<'a> Debug for AttrVal<'a> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         f.write_str(from_utf8(&self.value).unwrap())
     }
 }
 
-impl PartialEq for AttrVal {
+impl<'a> PartialEq for AttrVal<'a> {
     fn eq(&self, other: &Self) -> bool {
         self.value == other.value
     }
 }
 
-impl Eq for AttrVal {}
+impl<'a> Eq for AttrVal<'a> {}
+
+// Attributes of an element, allocated in the arena like nodes. Elements rarely have more than a few attributes, so they're looked up by name with a linear search instead of a hash map. Like a map, each name only appears once, and equality doesn't depend on order.
+pub struct Attributes<'a>(BumpVec<'a, (Cow<'a, [u8]>, AttrVal<'a>)>);
+
+impl<'a> Attributes<'a> {
+    pub fn new_in(arena: &'a Bump) -> Attributes<'a> {
+        Attributes(BumpVec::new_in(arena))
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn get(&self, name: &[u8]) -> Option<&AttrVal<'a>> {
+        self.0
+            .iter()
+            .find(|(n, _)| n.as_ref() == name)
+            .map(|(_, v)| v)
+    }
+
+    pub fn contains_key(&self, name: &[u8]) -> bool {
+        self.get(name).is_some()
+    }
+
+    // Replaces the value of an existing attribute with the same name.
+    pub fn insert(&mut self, name: Cow<'a, [u8]>, value: AttrVal<'a>) {
+        match self.0.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, v)) => *v = value,
+            None => self.0.push((name, value)),
+        };
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Cow<'a, [u8]>, &AttrVal<'a>)> {
+        self.0.iter().map(|(n, v)| (n, v))
+    }
+
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut AttrVal<'a>> {
+        self.0.iter_mut().map(|(_, v)| v)
+    }
+
+    #[cfg(test)]
+    pub fn retain(&mut self, mut f: impl FnMut(&[u8], &mut AttrVal<'a>) -> bool) {
+        self.0.retain_mut(|(n, v)| f(n, v));
+    }
+}
+
+impl<'a> IntoIterator for Attributes<'a> {
+    type Item = (Cow<'a, [u8]>, AttrVal<'a>);
+    type IntoIter = bumpalo::collections::vec::IntoIter<'a, Self::Item>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<'a> PartialEq for Attributes<'a> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().all(|(n, v)| other.get(n) == Some(v))
+    }
+}
+
+impl<'a> Eq for Attributes<'a> {}
 
+// Nodes borrow from the source code wherever possible, and are allocated in an arena, as allocation otherwise dominates the time spent parsing. Only values that are changed when parsing, such as text with decoded entities, or tag and attribute names with uppercase characters, are copied.
 // Derive Eq for testing.
 #[derive(Eq, PartialEq)]
-pub enum NodeData {
+pub enum NodeData<'a> {
     Bang {
-        code: Vec<u8>,
+        code: &'a [u8],
         // If the source unexpectedly ended before `>`, we can't add it, as otherwise output could be longer than source.
         ended: bool,
     },
     Comment {
-        code: Vec<u8>,
+        code: &'a [u8],
         // If the source unexpectedly ended before `-->`, we can't add it, as otherwise output could be longer than source.
         ended: bool,
     },
     Doctype {
-        legacy: Vec<u8>,
+        legacy: &'a [u8],
         // If the source unexpectedly ended before `>`, we can't add it, as otherwise output could be longer than source.
         ended: bool,
     },
     Element {
-        attributes: HashMap<Vec<u8>, AttrVal>,
-        children: Vec<NodeData>,
+        attributes: Attributes<'a>,
+        children: BumpVec<'a, NodeData<'a>>,
         // If the source doesn't have a closing tag, then we can't add one, as otherwise output could be longer than source.
         closing_tag: ElementClosingTag,
-        name: Vec<u8>,
+        name: Cow<'a, [u8]>,
         namespace: Namespace,
         // WARNING: This should only be set during minification, as minification can alter tree (e.g. remove text nodes completely).
-        // If the next text or element sibling is an element, this will be set to its tag name.
+        // If the next sibling that's written (not counting empty text) is an element, this will be set to its tag name.
         // Otherwise, this will be empty. It should be empty on creation.
-        next_sibling_element_name: Vec<u8>,
+        next_sibling_element_name: Cow<'a, [u8]>,
     },
     Instruction {
-        code: Vec<u8>,
+        code: &'a [u8],
         // If the source unexpectedly ended before `?>`, we can't add it, as otherwise output could be longer than source.
         ended: bool,
     },
     // Entities should not be decoded in ScriptOrStyleContent.
     ScriptOrStyleContent {
-        code: Vec<u8>,
+        code: &'a [u8],
         lang: ScriptOrStyleLang,
     },
     Text {
-        value: Vec<u8>,
+        value: Cow<'a, [u8]>,
+        // Only of the first part if the text was joined from several parts of the source (e.g. around dropped malformed code), in which case it doesn't decode to `value`.
+        source: SourceCode<'a>,
     },
 }
 
-impl Debug for NodeData {
+impl<'a> Debug for NodeData<'a> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
             NodeData::Bang { code, ended } => f
@@ -121,7 +211,7 @@ impl Debug for NodeData {
                 .debug_struct("Element")
                 .field("tag", &{
                     let mut out = format!("{:?}:{}", namespace, from_utf8(name).unwrap());
-                    for (n, v) in attributes {
+                    for (n, v) in attributes.iter() {
                         out.push_str(format!(" {}={:?}", from_utf8(n).unwrap(), v).as_str());
                     }
                     out
@@ -143,7 +233,7 @@ impl Debug for NodeData {
                 .field("code", &from_utf8(code).unwrap().to_string())
                 .field("lang", lang)
                 .finish(),
-            NodeData::Text { value } => f.write_str(from_utf8(value).unwrap()),
+            NodeData::Text { value, .. } => f.write_str(from_utf8(value).unwrap()),
         }
     }
 }
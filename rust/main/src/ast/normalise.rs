@@ -0,0 +1,173 @@
+use std::borrow::Cow;
+
+use bumpalo::collections::Vec as BumpVec;
+
+use crate::ast::{ElementClosingTag, NodeData};
+use crate::cfg::whitespace::WhitespaceRules;
+use crate::common::spec::tag::ns::Namespace;
+use crate::common::spec::tag::whitespace::{
+    get_whitespace_minification_for_tag, WhitespaceMinification,
+};
+use crate::common::spec::tag::EMPTY_SLICE;
+use crate::common::whitespace::{
+    collapse_whitespace, is_all_whitespace, left_trim, right_trim, trimmed,
+};
+
+fn is_optional_wrapper(node: &NodeData) -> bool {
+    match node {
+        NodeData::Element {
+            attributes,
+            name,
+            namespace: Namespace::Html,
+            ..
+        } => attributes.is_empty() && matches!(name.as_ref(), b"html" | b"head" | b"body"),
+        _ => false,
+    }
+}
+
+// Removes differences in tags that don't change the document, other than omitted closing tags: attribute values are double quoted, and `<html>`, `<head>`, and `<body>` elements without attributes are replaced with their children, as their tags can be omitted.
+pub fn normalise_tags(nodes: &mut BumpVec<NodeData>) {
+    while nodes.iter().any(is_optional_wrapper) {
+        let mut unwrapped = BumpVec::new_in(nodes.bump());
+        for n in nodes.drain(..) {
+            match n {
+                NodeData::Element { children, .. } if is_optional_wrapper(&n) => {
+                    unwrapped.extend(children)
+                }
+                n => unwrapped.push(n),
+            };
+        }
+        *nodes = unwrapped;
+    }
+    for n in nodes.iter_mut() {
+        if let NodeData::Element {
+            attributes,
+            children,
+            ..
+        } = n
+        {
+            for value in attributes.values_mut() {
+                value.quote = Some(b'"');
+            }
+            normalise_tags(children);
+        };
+    }
+}
+
+pub fn restore_closing_tags(nodes: &mut [NodeData]) {
+    for n in nodes.iter_mut() {
+        if let NodeData::Element {
+            children,
+            closing_tag,
+            ..
+        } = n
+        {
+            if *closing_tag == ElementClosingTag::Omitted {
+                *closing_tag = ElementClosingTag::Present;
+            };
+            restore_closing_tags(children);
+        };
+    }
+}
+
+// Minifies whitespace in text using the same rules as minification (but without removing any nodes other than text that becomes empty), merges adjacent text, and trims the contents of `<script>` and `<style>`, so that documents that only differ in insignificant whitespace have equal ASTs.
+pub fn normalise_whitespace(
+    rules: &WhitespaceRules,
+    ns: Namespace,
+    descendant_of_pre: bool,
+    // Use empty slice if none.
+    parent: &[u8],
+    nodes: &mut BumpVec<NodeData>,
+) {
+    let &WhitespaceMinification {
+        collapse,
+        destroy_whole,
+        trim,
+    } = match rules.get_minification(parent) {
+        Some(min) if ns == Namespace::Html && !descendant_of_pre => min,
+        _ => get_whitespace_minification_for_tag(ns, parent, descendant_of_pre),
+    };
+
+    let mut i = 1;
+    while i < nodes.len() {
+        match (&nodes[i - 1], &nodes[i]) {
+            (NodeData::Text { .. }, NodeData::Text { .. }) => {
+                if let NodeData::Text { value: next, .. } = nodes.remove(i) {
+                    if let NodeData::Text { value, .. } = &mut nodes[i - 1] {
+                        value.to_mut().extend_from_slice(&next);
+                    };
+                };
+            }
+            _ => i += 1,
+        };
+    }
+
+    let mut found_first_text_or_elem = false;
+    for n in nodes.iter_mut() {
+        match n {
+            NodeData::Element {
+                children,
+                name,
+                namespace,
+                ..
+            } => {
+                found_first_text_or_elem = true;
+                normalise_whitespace(
+                    rules,
+                    *namespace,
+                    descendant_of_pre
+                        || (*namespace == Namespace::Html
+                            && (name.as_ref() == b"pre" || rules.is_pre_like(name))),
+                    name,
+                    children,
+                );
+            }
+            NodeData::ScriptOrStyleContent { code, .. } => *code = trimmed(code),
+            NodeData::Text { value, .. } => {
+                if !found_first_text_or_elem {
+                    if trim {
+                        left_trim(value);
+                    };
+                    found_first_text_or_elem = !value.is_empty();
+                };
+                if destroy_whole && is_all_whitespace(value) {
+                    *value = Cow::Borrowed(b"");
+                } else if collapse {
+                    collapse_whitespace(value, false);
+                };
+            }
+            _ => {}
+        };
+    }
+    if trim {
+        for n in nodes.iter_mut().rev() {
+            match n {
+                NodeData::Element { .. } => break,
+                NodeData::Text { value, .. } => {
+                    right_trim(value);
+                    if !value.is_empty() {
+                        break;
+                    };
+                }
+                _ => {}
+            };
+        }
+    };
+    nodes.retain(|n| !matches!(n, NodeData::Text { value, .. } if value.is_empty()));
+}
+
+// Normalises parsed nodes so that documents that only differ in ignored formatting have equal ASTs: whitespace is normalised using `whitespace_rules` if provided, and tags are normalised and omitted closing tags restored if `tags`.
+// Whitespace must be normalised first, as unwrapping `<html>`, `<head>`, and `<body>` changes the parent that determines how it's normalised.
+pub fn normalise(
+    nodes: &mut BumpVec<NodeData>,
+    whitespace_rules: Option<&WhitespaceRules>,
+    tags: bool,
+) {
+    if let Some(rules) = whitespace_rules {
+        normalise_whitespace(rules, Namespace::Html, false, EMPTY_SLICE, nodes);
+    };
+    if tags {
+        normalise_tags(nodes);
+        restore_closing_tags(nodes);
+    };
+}
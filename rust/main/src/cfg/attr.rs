@@ -0,0 +1,59 @@
+use std::collections::HashMap;
+
+use crate::common::gen::attrs::{AttributeMinification, ATTRS};
+use crate::common::spec::tag::ns::Namespace;
+
+/// User-defined attribute minification rules, consulted before the built-in rules.
+///
+/// Tag and attribute names must be lowercase, as they are compared against parsed names, which are always lowercased.
+#[derive(Default)]
+pub struct AttrRules {
+    // Keyed by tag name then attribute name. An empty tag name matches all elements in the namespace.
+    html: HashMap<Vec<u8>, HashMap<Vec<u8>, AttributeMinification>>,
+    svg: HashMap<Vec<u8>, HashMap<Vec<u8>, AttributeMinification>>,
+}
+
+impl AttrRules {
+    fn by_ns(&self, ns: Namespace) -> &HashMap<Vec<u8>, HashMap<Vec<u8>, AttributeMinification>> {
+        match ns {
+            Namespace::Html => &self.html,
+            Namespace::Svg => &self.svg,
+        }
+    }
+
+    /// Adds a rule for the attribute `attr` on elements named `tag` in the namespace `ns`, replacing any existing rule for the same key.
+    /// Use an empty slice for `tag` to apply the rule to all elements in the namespace.
+    ///
+    /// To prevent an attribute from being minified at all, use a rule with all flags disabled and no default value.
+    pub fn insert(&mut self, ns: Namespace, tag: &[u8], attr: &[u8], min: AttributeMinification) {
+        let map = match ns {
+            Namespace::Html => &mut self.html,
+            Namespace::Svg => &mut self.svg,
+        };
+        map.entry(tag.to_vec())
+            .or_default()
+            .insert(attr.to_vec(), min);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.html.is_empty() && self.svg.is_empty()
+    }
+
+    // Rules specific to `tag` take precedence over rules for all elements.
+    pub fn get(&self, ns: Namespace, tag: &[u8], attr: &[u8]) -> Option<&AttributeMinification> {
+        let map = self.by_ns(ns);
+        map.get(tag)
+            .and_then(|attrs| attrs.get(attr))
+            .or_else(|| map.get(b"".as_ref()).and_then(|attrs| attrs.get(attr)))
+    }
+
+    // Falls back to the built-in rules if there is no user-defined rule.
+    pub fn get_or_builtin(
+        &self,
+        ns: Namespace,
+        tag: &[u8],
+        attr: &[u8],
+    ) -> Option<&AttributeMinification> {
+        self.get(ns, tag, attr).or_else(|| ATTRS.get(ns, tag, attr))
+    }
+}
@@ -0,0 +1,97 @@
+use std::str::FromStr;
+
+use crate::cfg::DEFAULT_MAX_DEPTH;
+
+/// How attribute values are quoted in canonical output.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AttributeQuotes {
+    /// Use the same quotes as the source, or none if the value was unquoted.
+    Source,
+    /// Always use double quotes.
+    Double,
+    /// Always use single quotes.
+    Single,
+    /// Only quote values that can't be written unquoted, using double quotes.
+    Minimal,
+}
+
+impl FromStr for AttributeQuotes {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "source" => Ok(AttributeQuotes::Source),
+            "double" => Ok(AttributeQuotes::Double),
+            "single" => Ok(AttributeQuotes::Single),
+            "minimal" => Ok(AttributeQuotes::Minimal),
+            _ => Err(format!(
+                "Unknown attribute quotes {:?}, expected source, double, single, or minimal",
+                s
+            )),
+        }
+    }
+}
+
+/// Which characters are written as character references in canonical output.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum EntityPolicy {
+    /// Only characters that would otherwise be misinterpreted: `&` and `<` in text, `&` and the quote in quoted attribute values, and `&`, `>`, quotes, and whitespace in unquoted attribute values.
+    Minimal,
+    /// `&`, `<`, `>`, `"`, and `'` everywhere, as well as whitespace in unquoted attribute values.
+    Conservative,
+    /// Like `Conservative`, but non-ASCII characters in text and attribute values are also written as numeric character references.
+    Ascii,
+    /// Write text and attribute values with the same character references as the source, as long as they're in the same quotes and haven't been changed (e.g. by `normalise_whitespace`). Other values are written like `Minimal`.
+    Source,
+}
+
+impl FromStr for EntityPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "minimal" => Ok(EntityPolicy::Minimal),
+            "conservative" => Ok(EntityPolicy::Conservative),
+            "ascii" => Ok(EntityPolicy::Ascii),
+            "source" => Ok(EntityPolicy::Source),
+            _ => Err(format!(
+                "Unknown entity policy {:?}, expected minimal, conservative, ascii, or source",
+                s
+            )),
+        }
+    }
+}
+
+/// Configuration settings for [`canonicalise_with_cfg`](crate::canonicalise_with_cfg). The defaults produce the same output as [`canonicalise`](crate::canonicalise).
+#[derive(Clone, Debug)]
+pub struct CanonicaliseCfg {
+    /// Write attributes sorted by name, instead of in source order.
+    pub sort_attributes: bool,
+    pub attribute_quotes: AttributeQuotes,
+    pub entities: EntityPolicy,
+    /// Minify whitespace in text using the same rules as minification, so that documents that only differ in insignificant whitespace have the same canonical form.
+    pub normalise_whitespace: bool,
+    /// Put children on their own lines, indented by this many spaces per level, where whitespace between them isn't significant (e.g. in `<ul>` or `<table>`, but not `<p>` or `<pre>`). Whitespace-only text in those places is dropped.
+    pub indent: Option<usize>,
+    /// The maximum depth that elements can be nested to. Deeper nesting stops canonicalisation with an [`InvalidData`](std::io::ErrorKind::InvalidData) error wrapping `DepthLimitExceeded`. Defaults to `DEFAULT_MAX_DEPTH`; see `Cfg::max_depth`.
+    pub max_depth: Option<usize>,
+}
+
+impl CanonicaliseCfg {
+    pub fn new() -> CanonicaliseCfg {
+        CanonicaliseCfg::default()
+    }
+}
+
+impl Default for CanonicaliseCfg {
+    fn default() -> Self {
+        CanonicaliseCfg {
+            sort_attributes: true,
+            attribute_quotes: AttributeQuotes::Source,
+            entities: EntityPolicy::Minimal,
+            normalise_whitespace: false,
+            indent: None,
+            max_depth: Some(DEFAULT_MAX_DEPTH),
+        }
+    }
+}
@@ -0,0 +1,36 @@
+use crate::cfg::whitespace::WhitespaceRules;
+use crate::cfg::DEFAULT_MAX_DEPTH;
+use crate::encoding::Encoding;
+
+/// Configuration settings for [`format`](fn@crate::format).
+#[derive(Clone, Debug)]
+pub struct FormatCfg {
+    /// Number of spaces to indent each level of nesting by.
+    pub indent: usize,
+    /// Write closing tags that were omitted in the source, such as `</li>` and `</p>`.
+    pub restore_closing_tags: bool,
+    /// The maximum depth that elements can be nested to. Deeper nesting stops formatting with a `DepthLimitExceeded` error. Defaults to `DEFAULT_MAX_DEPTH`; see `Cfg::max_depth`.
+    pub max_depth: Option<usize>,
+    /// The encoding of the input, used if it doesn't start with a byte order mark (BOM). See `Cfg::input_encoding`. The output is always UTF-8.
+    pub input_encoding: Option<Encoding>,
+    /// User-defined whitespace categories for HTML elements, which decide where line breaks can be added. Use the same rules as when minifying, so that minifying the output still gives the same result. See `Cfg::whitespace_rules`.
+    pub whitespace_rules: WhitespaceRules,
+}
+
+impl FormatCfg {
+    pub fn new() -> FormatCfg {
+        FormatCfg::default()
+    }
+}
+
+impl Default for FormatCfg {
+    fn default() -> Self {
+        FormatCfg {
+            indent: 2,
+            restore_closing_tags: false,
+            max_depth: Some(DEFAULT_MAX_DEPTH),
+            input_encoding: None,
+            whitespace_rules: WhitespaceRules::default(),
+        }
+    }
+}
@@ -1,19 +1,109 @@
+use crate::cfg::attr::AttrRules;
+use crate::cfg::whitespace::WhitespaceRules;
+use crate::common::gen::attrs::AttributeMinification;
+use crate::common::spec::tag::ns::Namespace;
+use crate::encoding::Encoding;
+use std::time::Duration;
+
+pub mod attr;
+pub mod canonicalise;
+pub mod format;
+pub mod whitespace;
+
+// Attributes from HTML 4 and earlier that are obsolete in browsers but still commonly relied on by email clients.
+static LEGACY_PRESENTATIONAL_ATTRIBUTES: &[&[u8]] = &[
+    b"align",
+    b"alink",
+    b"background",
+    b"bgcolor",
+    b"border",
+    b"cellpadding",
+    b"cellspacing",
+    b"clear",
+    b"color",
+    b"face",
+    b"frame",
+    b"height",
+    b"hspace",
+    b"link",
+    b"nowrap",
+    b"rules",
+    b"size",
+    b"text",
+    b"valign",
+    b"vlink",
+    b"vspace",
+    b"width",
+];
+
+/// The default maximum depth that elements can be nested to, which is where Chromium's HTML parser also stops nesting.
+/// Each level of nesting uses stack space when parsing and minifying, and this is low enough for threads with the 2 MiB
+/// stack that Rust gives new threads by default.
+pub const DEFAULT_MAX_DEPTH: usize = 512;
+
 /// Configuration settings that can be adjusted and passed to a minification function to change the
 /// minification approach.
-#[derive(Default)]
 pub struct Cfg {
+    /// Always quote attribute values, and write empty non-boolean attribute values as `=""` instead of omitting them.
+    pub always_quote_attribute_values: bool,
+    /// Only output ASCII characters, for consumers that mangle other bytes. Non-ASCII characters in text and attribute values are written as the shortest named or numeric character reference (with a semicolon if `conservative_entities`); in `<script>` and `<style>` they're written as `\u` escapes in JavaScript and `\` escapes in CSS.
+    ///
+    /// Tag and attribute names, comments, and the contents of `<script>` elements that aren't JavaScript can't contain escapes, so non-ASCII characters in them are output as is. Escaping JavaScript changes the raw value of template literals, as seen by `String.raw` and other tag functions.
+    pub ascii_only: bool,
+    /// Additional attribute minification rules (e.g. boolean attributes or default values of custom elements), consulted before the built-in rules.
+    pub attribute_rules: AttrRules,
+    /// Only output character references that are widely understood: always terminated with a semicolon, lowercase `&lt;` and `&amp;` instead of `&LT` and `&amp`, and no named references used only to shorten other characters. This produces slightly larger output.
+    pub conservative_entities: bool,
     /// Do not minify DOCTYPEs. Minified DOCTYPEs may not be spec compliant.
     pub do_not_minify_doctype: bool,
     /// Ensure all unquoted attribute values in the output do not contain any characters prohibited by the [WHATWG specification](https://html.spec.whatwg.org/multipage/syntax.html#attributes-2).
     pub ensure_spec_compliant_unquoted_attribute_values: bool,
+    /// Ensure the output doesn't contain any [parse errors](https://html.spec.whatwg.org/multipage/parsing.html#parse-errors), even if the source does. This implies `conservative_entities`, `do_not_minify_doctype`, `ensure_spec_compliant_unquoted_attribute_values`, and `keep_spaces_between_attributes`, and also:
+    ///
+    /// - encodes every `<` in text;
+    /// - removes bangs (e.g. `<![CDATA[...]]>`), processing instructions, and comments that aren't well formed, and terminates unterminated comments and DOCTYPEs;
+    /// - removes attributes with names that can't be written without a parse error, and the tags (but not contents) of elements with names that don't start with an ASCII letter.
+    ///
+    /// The contents of `<script>` and `<style>` and characters that are always errors (e.g. NUL and other control characters) are output as is.
+    pub ensure_no_parse_errors: bool,
+    /// Use the `display` and `white-space` properties declared in `style` attributes and simple rules in `<style>` elements to choose how whitespace is minified in each element. When the styles are too complex to determine, whitespace is preserved if `white-space` may apply, and otherwise the tag's default is used.
+    ///
+    /// Stylesheets outside the document (e.g. `<link rel=stylesheet>`) are not taken into account.
+    pub infer_whitespace_from_css: bool,
+    /// The encoding of the input, used if it doesn't start with a byte order mark (BOM). If not provided, the encoding declared by a `<meta charset>` or `<meta http-equiv=content-type>` in the first 1024 bytes is used, or UTF-8 if there is none.
+    ///
+    /// The input is transcoded to UTF-8 for processing; invalid sequences are replaced with U+FFFD and reported as `Diagnostic::MalformedInput`. If the declared encoding isn't supported, the input is processed as is and `Diagnostic::UnsupportedEncoding` is reported.
+    pub input_encoding: Option<Encoding>,
+    /// Keep the byte order mark (BOM) at the start of the input, if present. It's otherwise removed, as it's redundant for UTF-8 output.
+    pub keep_bom: bool,
     /// Do not omit closing tags when possible.
     pub keep_closing_tags: bool,
     /// Do not omit `<html>` and `<head>` opening tags when they don't have attributes.
     pub keep_html_and_head_opening_tags: bool,
+    /// Output in the same encoding as the input, instead of UTF-8. Characters that can't be represented in the encoding are written as numeric character references, except in CSS and JavaScript, where they're escaped instead. They're still written as references in other raw text (e.g. `<script type=text/plain>`), where they won't be decoded. UTF-16 output always starts with a BOM.
+    ///
+    /// If not enabled and the input isn't UTF-8, the label in its `<meta charset>` is replaced with `utf-8`.
+    pub keep_input_encoding: bool,
     /// Keep spaces between attributes when possible to conform to HTML standards.
     pub keep_spaces_between_attributes: bool,
     /// Keep all comments.
     pub keep_comments: bool,
+    /// Keep conditional comments (e.g. `<!--[if mso]>...<![endif]-->`), even if other comments are removed.
+    pub keep_conditional_comments: bool,
+    /// The maximum depth that elements can be nested to. Deeper nesting stops parsing and the input is output as is, reported as `Diagnostic::TooDeeplyNested`. Defaults to `DEFAULT_MAX_DEPTH`.
+    ///
+    /// Parsing and minification recurse once per level of nesting, so extremely deep nesting (e.g. 100,000 `<div>` tags) can overflow the stack. Only raise or remove the limit for trusted input, or when running on a thread with a larger stack.
+    pub max_depth: Option<usize>,
+    /// The maximum time to spend minifying each `<script>` and `<style>` element and `style` attribute. If exceeded, the contents are output as is (but trimmed) and `Diagnostic::EmbeddedMinificationTimedOut` is reported.
+    ///
+    /// When set, each minification runs on a new thread. A minification that takes too long can't be stopped, so it continues in the background until it finishes, and its result is discarded. At most 64 of these threads run at once across all calls; while they're all busy, embedded code is output as is and `Diagnostic::EmbeddedMinifierBusy` is reported.
+    pub max_embedded_minification_time: Option<Duration>,
+    /// The maximum size of the input in bytes. Larger inputs are output as is, reported as `Diagnostic::InputTooLarge`.
+    pub max_input_size: Option<usize>,
+    /// Break output lines that are longer than this many bytes (excluding the line break), e.g. to stay under the 998 octet line limit of SMTP.
+    ///
+    /// Line breaks are only inserted where they don't change the semantics of the document: between attributes in a tag, or in place of a space in collapsible text. If a line can't be broken safely, it's left as is and a `Diagnostic::LineTooLong` is returned from `minify_with_diagnostics`.
+    pub max_line_length: Option<usize>,
     /// If enabled, CSS in `<style>` tags and `style` attributes are minified.
     pub minify_css: bool,
     /// If enabled, JavaScript in `<script>` tags are minified using
@@ -23,10 +113,52 @@ pub struct Cfg {
     /// [MIME type](https://mimesniff.spec.whatwg.org/#javascript-mime-type) is considered to
     /// contain JavaScript, as per the specification.
     pub minify_js: bool,
+    /// Parse the contents of `<noscript>` as raw text and output them as is, like browsers with scripting enabled do. Otherwise, they're parsed and minified as HTML, like browsers with scripting disabled (and most other HTML consumers) do.
+    pub parse_noscript_as_raw_text: bool,
+    /// When collapsing, trimming, or removing whitespace in content and attribute values, replace any whitespace sequence containing a line break with a single line break instead of a space or nothing.
+    ///
+    /// Useful for keeping the output diffable and readable, at the cost of a slightly larger output.
+    pub preserve_line_breaks: bool,
     /// Remove all bangs.
     pub remove_bangs: bool,
     /// Remove all processing_instructions.
     pub remove_processing_instructions: bool,
+    /// Additional whitespace minification categories for HTML elements by tag name or prefix (e.g. custom elements), consulted before the built-in categories.
+    pub whitespace_rules: WhitespaceRules,
+}
+
+impl Default for Cfg {
+    fn default() -> Self {
+        Cfg {
+            always_quote_attribute_values: false,
+            ascii_only: false,
+            attribute_rules: AttrRules::default(),
+            conservative_entities: false,
+            do_not_minify_doctype: false,
+            ensure_spec_compliant_unquoted_attribute_values: false,
+            ensure_no_parse_errors: false,
+            infer_whitespace_from_css: false,
+            input_encoding: None,
+            keep_bom: false,
+            keep_closing_tags: false,
+            keep_html_and_head_opening_tags: false,
+            keep_input_encoding: false,
+            keep_spaces_between_attributes: false,
+            keep_comments: false,
+            keep_conditional_comments: false,
+            max_depth: Some(DEFAULT_MAX_DEPTH),
+            max_embedded_minification_time: None,
+            max_input_size: None,
+            max_line_length: None,
+            minify_css: false,
+            minify_js: false,
+            parse_noscript_as_raw_text: false,
+            preserve_line_breaks: false,
+            remove_bangs: false,
+            remove_processing_instructions: false,
+            whitespace_rules: WhitespaceRules::default(),
+        }
+    }
 }
 
 impl Cfg {
@@ -42,4 +174,52 @@ impl Cfg {
             ..Cfg::default()
         }
     }
+
+    /// Configuration for output that has no parse errors according to the WHATWG specification. See `ensure_no_parse_errors` for details.
+    pub fn strict() -> Cfg {
+        Cfg {
+            ensure_no_parse_errors: true,
+            ..Cfg::spec_compliant()
+        }
+    }
+
+    /// Configuration for HTML emails, which are rendered by clients such as Outlook (Word), Gmail, and older webmail that have their own rules and quirks. See `enable_email_safe_mode` for details.
+    pub fn email() -> Cfg {
+        let mut cfg = Cfg::default();
+        cfg.enable_email_safe_mode();
+        cfg
+    }
+
+    /// Enables options for HTML emails on top of the existing configuration:
+    ///
+    /// - Keep conditional comments, which Outlook uses.
+    /// - Never omit closing tags or `<html>` and `<head>` opening tags, and keep spaces between attributes.
+    /// - Always quote attribute values.
+    /// - Don't minify the DOCTYPE, which can change the rendering mode.
+    /// - Leave the values of legacy presentational attributes such as `bgcolor` and `align` untouched.
+    /// - Only use widely understood character references.
+    pub fn enable_email_safe_mode(&mut self) {
+        self.always_quote_attribute_values = true;
+        self.conservative_entities = true;
+        self.do_not_minify_doctype = true;
+        self.keep_closing_tags = true;
+        self.keep_conditional_comments = true;
+        self.keep_html_and_head_opening_tags = true;
+        self.keep_spaces_between_attributes = true;
+        for attr in LEGACY_PRESENTATIONAL_ATTRIBUTES {
+            self.attribute_rules.insert(
+                Namespace::Html,
+                b"",
+                attr,
+                AttributeMinification {
+                    boolean: false,
+                    case_insensitive: false,
+                    collapse: false,
+                    default_value: None,
+                    redundant_if_empty: false,
+                    trim: false,
+                },
+            );
+        }
+    }
 }
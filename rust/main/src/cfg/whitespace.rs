@@ -0,0 +1,75 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+use crate::common::spec::tag::whitespace::{
+    WhitespaceMinification, CONTENT, CONTENT_FIRST, FORMATTING, LAYOUT, WHITESPACE_SENSITIVE,
+};
+
+/// How whitespace in an element's content is minified. See the README for details on each category.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum WhitespaceCategory {
+    Content,
+    ContentFirst,
+    Formatting,
+    Layout,
+    WhitespaceSensitive,
+}
+
+impl WhitespaceCategory {
+    fn minification(self) -> &'static WhitespaceMinification {
+        match self {
+            WhitespaceCategory::Content => CONTENT,
+            WhitespaceCategory::ContentFirst => CONTENT_FIRST,
+            WhitespaceCategory::Formatting => FORMATTING,
+            WhitespaceCategory::Layout => LAYOUT,
+            WhitespaceCategory::WhitespaceSensitive => WHITESPACE_SENSITIVE,
+        }
+    }
+}
+
+/// User-defined whitespace categories for HTML elements, consulted before the built-in categories.
+///
+/// Tag names and prefixes must be lowercase, as they are compared against parsed names, which are always lowercased.
+#[derive(Clone, Debug, Default)]
+pub struct WhitespaceRules {
+    tags: HashMap<Vec<u8>, WhitespaceCategory>,
+    // Kept sorted by descending length so that the longest matching prefix is found first.
+    prefixes: Vec<(Vec<u8>, WhitespaceCategory)>,
+}
+
+impl WhitespaceRules {
+    /// Uses `category` for elements named exactly `tag`. Takes precedence over any prefix rule.
+    pub fn insert_tag(&mut self, tag: &[u8], category: WhitespaceCategory) {
+        self.tags.insert(tag.to_vec(), category);
+    }
+
+    /// Uses `category` for elements with names starting with `prefix` (e.g. `ui-`). If multiple prefixes match, the longest wins.
+    pub fn insert_prefix(&mut self, prefix: &[u8], category: WhitespaceCategory) {
+        self.prefixes.retain(|(p, _)| p != prefix);
+        self.prefixes.push((prefix.to_vec(), category));
+        self.prefixes.sort_by_key(|p| Reverse(p.0.len()));
+    }
+
+    pub fn get(&self, tag: &[u8]) -> Option<WhitespaceCategory> {
+        self.tags.get(tag).copied().or_else(|| {
+            self.prefixes
+                .iter()
+                .find(|(p, _)| tag.starts_with(p))
+                .map(|(_, c)| *c)
+        })
+    }
+
+    // Whether `tag` is mapped to `WhitespaceSensitive`, in which case whitespace in its descendants is preserved too, like `<pre>`.
+    pub(crate) fn is_pre_like(&self, tag: &[u8]) -> bool {
+        self.get(tag) == Some(WhitespaceCategory::WhitespaceSensitive)
+    }
+
+    // Use empty slice for `tag` if root, which is never matched.
+    pub fn get_minification(&self, tag: &[u8]) -> Option<&'static WhitespaceMinification> {
+        if tag.is_empty() {
+            None
+        } else {
+            self.get(tag).map(|c| c.minification())
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use std::fmt;
+
+use crate::encoding::Encoding;
+
+/// A language of code embedded in HTML.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum EmbeddedLanguage {
+    Css,
+    Js,
+}
+
+/// A problem found during minification that didn't prevent output from being produced, but means the output may not meet all requirements of the configuration.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Diagnostic {
+    /// The JS or CSS minifier returned an error for the contents of a `<script>` or `<style>`, or a `style` attribute, so they were output as is (but trimmed). This usually means the code is malformed.
+    EmbeddedMinificationFailed {
+        language: EmbeddedLanguage,
+        error: String,
+    },
+    /// The JS or CSS minifier panicked on the contents of a `<script>` or `<style>`, or a `style` attribute, so they were output as is (but trimmed). This is a bug in the minifier.
+    EmbeddedMinifierPanicked {
+        language: EmbeddedLanguage,
+        message: String,
+    },
+    /// Minifying the contents of a `<script>` or `<style>` or a `style` attribute took longer than `Cfg::max_embedded_minification_time`, so they were output as is (but trimmed).
+    EmbeddedMinificationTimedOut { language: EmbeddedLanguage },
+    /// Too many earlier minifications of embedded code that exceeded `Cfg::max_embedded_minification_time` are still running in the background, so the contents of a `<script>` or `<style>` or a `style` attribute were output as is (but trimmed) without being minified.
+    EmbeddedMinifierBusy { language: EmbeddedLanguage },
+    /// The input is larger than `Cfg::max_input_size`, so it was output as is.
+    InputTooLarge {
+        /// Size of the input in bytes.
+        size: usize,
+        max: usize,
+    },
+    /// A line in the output is longer than `Cfg::max_line_length`, as there was nowhere to safely break it (e.g. a long word, attribute value, or script).
+    LineTooLong {
+        /// One-based line number in the output.
+        line: usize,
+        /// Length of the line in bytes, excluding the line break.
+        length: usize,
+    },
+    /// The input contained byte sequences that were invalid in its encoding, which were replaced with U+FFFD.
+    MalformedInput {
+        encoding: Encoding,
+        /// Number of invalid sequences.
+        count: usize,
+    },
+    /// The minified contents of a `<script>` had a `</script` that couldn't be escaped, as it wasn't in a string, template literal, regular expression, or comment (e.g. `x</script/.source`), or would otherwise not end with the script, so they were output as is (but trimmed).
+    MinifiedJsNotEscapable,
+    /// Elements in the input are nested deeper than `Cfg::max_depth`, so it was output as is.
+    TooDeeplyNested { max: usize },
+    /// The input declared an encoding that isn't supported in its `<meta charset>`, so it was processed as is. Non-ASCII characters may be output incorrectly.
+    UnsupportedEncoding { label: String },
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Diagnostic::EmbeddedMinificationFailed { language, error } => write!(
+                f,
+                "Could not minify embedded {:?}, so it was left as is: {}",
+                language, error
+            ),
+            Diagnostic::EmbeddedMinifierPanicked { language, message } => write!(
+                f,
+                "Minifier panicked on embedded {:?}, so it was left as is: {}",
+                language, message
+            ),
+            Diagnostic::EmbeddedMinificationTimedOut { language } => write!(
+                f,
+                "Minification of embedded {:?} took too long, so it was left as is",
+                language
+            ),
+            Diagnostic::EmbeddedMinifierBusy { language } => write!(
+                f,
+                "Too many slow minifications are still running, so embedded {:?} was left as is",
+                language
+            ),
+            Diagnostic::InputTooLarge { size, max } => write!(
+                f,
+                "Input is {} bytes, which is larger than the maximum of {}, so it was left as is",
+                size, max
+            ),
+            Diagnostic::LineTooLong { line, length } => write!(
+                f,
+                "Line {} of the output is {} bytes long and has no safe break point",
+                line, length
+            ),
+            Diagnostic::MalformedInput { encoding, count } => write!(
+                f,
+                "Input has {} invalid {:?} sequence(s), which were replaced with U+FFFD",
+                count, encoding
+            ),
+            Diagnostic::MinifiedJsNotEscapable => write!(
+                f,
+                "Minified JS has a `</script` that can't be escaped, so it was left as is"
+            ),
+            Diagnostic::TooDeeplyNested { max } => write!(
+                f,
+                "Input has elements nested deeper than the maximum of {}, so it was left as is",
+                max
+            ),
+            Diagnostic::UnsupportedEncoding { label } => {
+                write!(f, "Input declares unsupported encoding {:?}", label)
+            }
+        }
+    }
+}
+
+/// Elements in a document are nested deeper than the configured maximum depth, so it couldn't be processed.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct DepthLimitExceeded {
+    pub max: usize,
+}
+
+impl fmt::Display for DepthLimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Elements are nested deeper than the maximum of {}",
+            self.max
+        )
+    }
+}
+
+impl std::error::Error for DepthLimitExceeded {}
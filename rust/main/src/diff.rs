@@ -0,0 +1,354 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+use crate::ast::c14n::c14n_serialise_ast;
+use crate::ast::{Attributes, NodeData};
+
+/// A difference found by [`diff`](fn@crate::diff), and which node it's in.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Difference {
+    /// Names of the node and its ancestors, outermost first and separated by ` > `, with a one-based index if the node has siblings with the same name (e.g. `html > body > p[2] > #text`). Removed nodes are located in the first document, and all others in the second.
+    pub path: String,
+    pub kind: DifferenceKind,
+}
+
+/// What changed between two documents. Nodes and values are in canonical form.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum DifferenceKind {
+    /// A node is only in the second document.
+    Added {
+        node: String,
+    },
+    /// A node is only in the first document.
+    Removed {
+        node: String,
+    },
+    /// A text, comment, or other non-element node has different content.
+    Changed {
+        old: String,
+        new: String,
+    },
+    AttributeAdded {
+        name: String,
+        value: String,
+    },
+    AttributeRemoved {
+        name: String,
+        value: String,
+    },
+    AttributeChanged {
+        name: String,
+        old: String,
+        new: String,
+    },
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            DifferenceKind::Added { node } => write!(f, "+ {}: {}", self.path, node),
+            DifferenceKind::Removed { node } => write!(f, "- {}: {}", self.path, node),
+            DifferenceKind::Changed { old, new } => {
+                write!(f, "~ {}: {:?} -> {:?}", self.path, old, new)
+            }
+            DifferenceKind::AttributeAdded { name, value } => {
+                write!(f, "+ {} [{}={:?}]", self.path, name, value)
+            }
+            DifferenceKind::AttributeRemoved { name, value } => {
+                write!(f, "- {} [{}={:?}]", self.path, name, value)
+            }
+            DifferenceKind::AttributeChanged { name, old, new } => {
+                write!(f, "~ {} [{}]: {:?} -> {:?}", self.path, name, old, new)
+            }
+        }
+    }
+}
+
+fn to_string(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).into_owned()
+}
+
+fn serialise(node: &NodeData) -> Vec<u8> {
+    let mut out = Vec::new();
+    c14n_serialise_ast(&mut out, node).unwrap();
+    out
+}
+
+// Hashes of a node and its descendants, computed once for the whole tree, so that comparing siblings doesn't require serialising them again at each level.
+pub struct NodeHash {
+    hash: u64,
+    children: Vec<NodeHash>,
+}
+
+impl NodeHash {
+    pub fn of_nodes(nodes: &[NodeData]) -> Vec<NodeHash> {
+        nodes.iter().map(NodeHash::of).collect()
+    }
+
+    fn of(node: &NodeData) -> NodeHash {
+        let mut hasher = DefaultHasher::new();
+        std::mem::discriminant(node).hash(&mut hasher);
+        let children = match node {
+            NodeData::Bang { code, .. }
+            | NodeData::Comment { code, .. }
+            | NodeData::Instruction { code, .. } => {
+                code.hash(&mut hasher);
+                Vec::new()
+            }
+            NodeData::Doctype { legacy, .. } => {
+                legacy.hash(&mut hasher);
+                Vec::new()
+            }
+            NodeData::Element {
+                attributes,
+                children,
+                closing_tag,
+                name,
+                namespace,
+                ..
+            } => {
+                name.hash(&mut hasher);
+                std::mem::discriminant(namespace).hash(&mut hasher);
+                std::mem::discriminant(closing_tag).hash(&mut hasher);
+                let mut attributes = attributes
+                    .iter()
+                    .map(|(name, value)| (name, &value.value))
+                    .collect::<Vec<_>>();
+                attributes.sort_unstable();
+                attributes.hash(&mut hasher);
+                let children = NodeHash::of_nodes(children);
+                for c in children.iter() {
+                    c.hash.hash(&mut hasher);
+                }
+                children
+            }
+            NodeData::ScriptOrStyleContent { code, .. } => {
+                code.hash(&mut hasher);
+                Vec::new()
+            }
+            NodeData::Text { value, .. } => {
+                value.hash(&mut hasher);
+                Vec::new()
+            }
+        };
+        NodeHash {
+            hash: hasher.finish(),
+            children,
+        }
+    }
+}
+
+fn label(node: &NodeData) -> String {
+    match node {
+        NodeData::Bang { .. } => "#bang".to_string(),
+        NodeData::Comment { .. } => "#comment".to_string(),
+        NodeData::Doctype { .. } => "#doctype".to_string(),
+        NodeData::Element { name, .. } => to_string(name),
+        NodeData::Instruction { .. } => "#instruction".to_string(),
+        NodeData::ScriptOrStyleContent { .. } | NodeData::Text { .. } => "#text".to_string(),
+    }
+}
+
+// Returns the path of each node, which is its label, indexed if any sibling has the same label.
+fn paths(parent: &str, labels: &[String]) -> Vec<String> {
+    let mut totals = HashMap::<&str, usize>::new();
+    for l in labels {
+        *totals.entry(l).or_default() += 1;
+    }
+    let mut seen = HashMap::<&str, usize>::new();
+    labels
+        .iter()
+        .map(|l| {
+            let label = if totals[l.as_str()] > 1 {
+                let i = seen.entry(l).or_default();
+                *i += 1;
+                format!("{}[{}]", l, i)
+            } else {
+                l.clone()
+            };
+            if parent.is_empty() {
+                label
+            } else {
+                format!("{} > {}", parent, label)
+            }
+        })
+        .collect()
+}
+
+// Larger lists that remain after removing their common prefix and suffix are compared position by position, as finding their longest common subsequence would take too much time and memory.
+const MAX_LCS_CELLS: usize = 1 << 22;
+
+// Returns the indices of the pairs in the longest common subsequence of `a` and `b`, in order.
+fn lcs<T>(a: &[T], b: &[T], eq: impl Fn(&T, &T) -> bool) -> Vec<(usize, usize)> {
+    let prefix = a.iter().zip(b).take_while(|(x, y)| eq(x, y)).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| eq(x, y))
+        .count();
+    let (n, m) = (a.len() - prefix - suffix, b.len() - prefix - suffix);
+    let mut pairs: Vec<(usize, usize)> = (0..prefix).map(|i| (i, i)).collect();
+    if (n + 1) * (m + 1) > MAX_LCS_CELLS {
+        pairs.extend(
+            (0..n.min(m))
+                .filter(|&i| eq(&a[prefix + i], &b[prefix + i]))
+                .map(|i| (prefix + i, prefix + i)),
+        );
+    } else {
+        // lengths[i][j] is the length of the LCS of a[i..n] and b[j..m], relative to the prefix.
+        let mut lengths = vec![0u32; (n + 1) * (m + 1)];
+        for i in (0..n).rev() {
+            for j in (0..m).rev() {
+                lengths[i * (m + 1) + j] = if eq(&a[prefix + i], &b[prefix + j]) {
+                    lengths[(i + 1) * (m + 1) + j + 1] + 1
+                } else {
+                    lengths[(i + 1) * (m + 1) + j].max(lengths[i * (m + 1) + j + 1])
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < n && j < m {
+            if eq(&a[prefix + i], &b[prefix + j]) {
+                pairs.push((prefix + i, prefix + j));
+                i += 1;
+                j += 1;
+            } else if lengths[(i + 1) * (m + 1) + j] >= lengths[i * (m + 1) + j + 1] {
+                i += 1;
+            } else {
+                j += 1;
+            };
+        }
+    };
+    pairs.extend((0..suffix).map(|k| (prefix + n + k, prefix + m + k)));
+    pairs
+}
+
+fn diff_attributes(path: &str, a: &Attributes, b: &Attributes, out: &mut Vec<Difference>) {
+    let mut names = a.iter().chain(b.iter()).map(|(n, _)| n).collect::<Vec<_>>();
+    names.sort_unstable();
+    names.dedup();
+    for name in names {
+        let kind = match (a.get(name), b.get(name)) {
+            (Some(old), Some(new)) if old != new => DifferenceKind::AttributeChanged {
+                name: to_string(name),
+                old: to_string(&old.value),
+                new: to_string(&new.value),
+            },
+            (Some(old), None) => DifferenceKind::AttributeRemoved {
+                name: to_string(name),
+                value: to_string(&old.value),
+            },
+            (None, Some(new)) => DifferenceKind::AttributeAdded {
+                name: to_string(name),
+                value: to_string(&new.value),
+            },
+            _ => continue,
+        };
+        out.push(Difference {
+            path: path.to_string(),
+            kind,
+        });
+    }
+}
+
+// Compares a pair of nodes with the same label.
+fn diff_node(
+    path: &str,
+    (a, a_hash): (&NodeData, &NodeHash),
+    (b, b_hash): (&NodeData, &NodeHash),
+    out: &mut Vec<Difference>,
+) {
+    match (a, b) {
+        (
+            NodeData::Element {
+                attributes: a_attrs,
+                children: a_children,
+                ..
+            },
+            NodeData::Element {
+                attributes: b_attrs,
+                children: b_children,
+                ..
+            },
+        ) => {
+            diff_attributes(path, a_attrs, b_attrs, out);
+            diff_nodes(
+                path,
+                (a_children, &a_hash.children),
+                (b_children, &b_hash.children),
+                out,
+            );
+        }
+        _ => out.push(Difference {
+            path: path.to_string(),
+            kind: DifferenceKind::Changed {
+                old: to_string(&serialise(a)),
+                new: to_string(&serialise(b)),
+            },
+        }),
+    };
+}
+
+// Siblings are first matched by whether they're equal, so that unchanged nodes are never reported, and then the remaining nodes between those are matched by name, so that changes within a node are reported instead of replacing the whole node. Nodes are only serialised when they're reported.
+pub fn diff_nodes(
+    path: &str,
+    (a, a_hashes): (&[NodeData], &[NodeHash]),
+    (b, b_hashes): (&[NodeData], &[NodeHash]),
+    out: &mut Vec<Difference>,
+) {
+    let a_labels = a.iter().map(label).collect::<Vec<_>>();
+    let b_labels = b.iter().map(label).collect::<Vec<_>>();
+    let a_paths = paths(path, &a_labels);
+    let b_paths = paths(path, &b_labels);
+
+    // Hashes rule out most unequal nodes without comparing them, and equal hashes are confirmed in case of a collision.
+    let a_nodes = a.iter().zip(a_hashes).collect::<Vec<_>>();
+    let b_nodes = b.iter().zip(b_hashes).collect::<Vec<_>>();
+    let unchanged = lcs(&a_nodes, &b_nodes, |(x, x_hash), (y, y_hash)| {
+        x_hash.hash == y_hash.hash && x == y
+    });
+    let (mut a_start, mut b_start) = (0, 0);
+    for (a_end, b_end) in unchanged
+        .into_iter()
+        .chain(std::iter::once((a.len(), b.len())))
+    {
+        let matched = lcs(
+            &a_labels[a_start..a_end],
+            &b_labels[b_start..b_end],
+            |x, y| x == y,
+        );
+        let (mut i, mut j) = (a_start, b_start);
+        for (mi, mj) in matched
+            .into_iter()
+            .map(|(mi, mj)| (a_start + mi, b_start + mj))
+            .chain(std::iter::once((a_end, b_end)))
+        {
+            for i in i..mi {
+                out.push(Difference {
+                    path: a_paths[i].clone(),
+                    kind: DifferenceKind::Removed {
+                        node: to_string(&serialise(&a[i])),
+                    },
+                });
+            }
+            for j in j..mj {
+                out.push(Difference {
+                    path: b_paths[j].clone(),
+                    kind: DifferenceKind::Added {
+                        node: to_string(&serialise(&b[j])),
+                    },
+                });
+            }
+            if mi < a_end {
+                diff_node(&b_paths[mj], a_nodes[mi], b_nodes[mj], out);
+            };
+            i = mi + 1;
+            j = mj + 1;
+        }
+        a_start = a_end + 1;
+        b_start = b_end + 1;
+    }
+}
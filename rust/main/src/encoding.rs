@@ -0,0 +1,386 @@
+use std::borrow::Cow;
+use std::char::{decode_utf16, REPLACEMENT_CHARACTER};
+use std::str::from_utf8;
+
+use crate::common::gen::codepoints::WHITESPACE;
+
+/// A character encoding of input or output code.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Encoding {
+    Utf8,
+    Utf16Be,
+    Utf16Le,
+    /// Also used for ISO-8859-1 and ASCII, as [browsers do](https://encoding.spec.whatwg.org/#names-and-labels).
+    Windows1252,
+}
+
+// https://encoding.spec.whatwg.org/#names-and-labels
+static LABELS: &[(&[u8], Encoding)] = &[
+    (b"unicode-1-1-utf-8", Encoding::Utf8),
+    (b"unicode11utf8", Encoding::Utf8),
+    (b"unicode20utf8", Encoding::Utf8),
+    (b"utf-8", Encoding::Utf8),
+    (b"utf8", Encoding::Utf8),
+    (b"x-unicode20utf8", Encoding::Utf8),
+    (b"unicodefffe", Encoding::Utf16Be),
+    (b"utf-16be", Encoding::Utf16Be),
+    (b"csunicode", Encoding::Utf16Le),
+    (b"iso-10646-ucs-2", Encoding::Utf16Le),
+    (b"ucs-2", Encoding::Utf16Le),
+    (b"unicode", Encoding::Utf16Le),
+    (b"unicodefeff", Encoding::Utf16Le),
+    (b"utf-16", Encoding::Utf16Le),
+    (b"utf-16le", Encoding::Utf16Le),
+    (b"ansi_x3.4-1968", Encoding::Windows1252),
+    (b"ascii", Encoding::Windows1252),
+    (b"cp1252", Encoding::Windows1252),
+    (b"cp819", Encoding::Windows1252),
+    (b"csisolatin1", Encoding::Windows1252),
+    (b"ibm819", Encoding::Windows1252),
+    (b"iso-8859-1", Encoding::Windows1252),
+    (b"iso-ir-100", Encoding::Windows1252),
+    (b"iso8859-1", Encoding::Windows1252),
+    (b"iso88591", Encoding::Windows1252),
+    (b"iso_8859-1", Encoding::Windows1252),
+    (b"iso_8859-1:1987", Encoding::Windows1252),
+    (b"l1", Encoding::Windows1252),
+    (b"latin1", Encoding::Windows1252),
+    (b"us-ascii", Encoding::Windows1252),
+    (b"windows-1252", Encoding::Windows1252),
+    (b"x-cp1252", Encoding::Windows1252),
+];
+
+// Code points of bytes 0x80 to 0x9F in Windows-1252. All other bytes have the same value as their code point.
+static WINDOWS_1252_C1: [u16; 32] = [
+    0x20AC, 0x0081, 0x201A, 0x0192, 0x201E, 0x2026, 0x2020, 0x2021, 0x02C6, 0x2030, 0x0160, 0x2039,
+    0x0152, 0x008D, 0x017D, 0x008F, 0x0090, 0x2018, 0x2019, 0x201C, 0x201D, 0x2022, 0x2013, 0x2014,
+    0x02DC, 0x2122, 0x0161, 0x203A, 0x0153, 0x009D, 0x017E, 0x0178,
+];
+
+impl Encoding {
+    /// Gets the encoding for a label such as `utf-8` or `latin1`, as used in `<meta charset>`. Only the encodings represented by `Encoding` are supported.
+    pub fn for_label(label: &[u8]) -> Option<Encoding> {
+        let start = label.iter().position(|&c| !WHITESPACE[c])?;
+        let end = label.iter().rposition(|&c| !WHITESPACE[c])? + 1;
+        let label = &label[start..end];
+        LABELS
+            .iter()
+            .find(|(l, _)| l.eq_ignore_ascii_case(label))
+            .map(|&(_, e)| e)
+    }
+
+    // Whether `c` can be written in this encoding without a character reference or escape.
+    pub(crate) fn can_encode(self, c: char) -> bool {
+        match self {
+            Encoding::Windows1252 => windows_1252_byte(c).is_some(),
+            _ => true,
+        }
+    }
+
+    fn bom(self) -> &'static [u8] {
+        match self {
+            Encoding::Utf8 => b"\xEF\xBB\xBF",
+            Encoding::Utf16Be => b"\xFE\xFF",
+            Encoding::Utf16Le => b"\xFF\xFE",
+            Encoding::Windows1252 => b"",
+        }
+    }
+}
+
+// Finds the range of the label in the first `<meta charset>` or `<meta http-equiv=content-type content="...;charset=...">` in the first 1024 bytes, like the [prescan](https://html.spec.whatwg.org/multipage/parsing.html#prescan-a-byte-stream-to-determine-its-encoding) browsers do. This is simpler than the spec, but handles well-formed declarations.
+fn prescan_meta_charset(src: &[u8]) -> Option<(usize, usize)> {
+    let src = &src[..src.len().min(1024)];
+    let mut pos = 0;
+    while pos < src.len() {
+        let rest = &src[pos..];
+        if rest.starts_with(b"<!--") {
+            pos += rest[4..]
+                .windows(3)
+                .position(|w| w == b"-->")
+                .map_or(rest.len(), |i| i + 7);
+            continue;
+        };
+        if rest.len() > 5
+            && rest[..5].eq_ignore_ascii_case(b"<meta")
+            && (WHITESPACE[rest[5]] || rest[5] == b'/')
+        {
+            pos += 5;
+            let mut charset = None;
+            let mut content = None;
+            let mut is_content_type = false;
+            loop {
+                while pos < src.len() && (WHITESPACE[src[pos]] || src[pos] == b'/') {
+                    pos += 1;
+                }
+                if pos >= src.len() || src[pos] == b'>' {
+                    break;
+                };
+                let name_start = pos;
+                while pos < src.len()
+                    && !WHITESPACE[src[pos]]
+                    && !matches!(src[pos], b'/' | b'>' | b'=')
+                {
+                    pos += 1;
+                }
+                let name = &src[name_start..pos];
+                let mut value = (pos, pos);
+                if src.get(pos) == Some(&b'=') {
+                    pos += 1;
+                    match src.get(pos) {
+                        Some(&q) if q == b'"' || q == b'\'' => {
+                            pos += 1;
+                            let end = src[pos..]
+                                .iter()
+                                .position(|&c| c == q)
+                                .map_or(src.len(), |i| pos + i);
+                            value = (pos, end);
+                            pos = (end + 1).min(src.len());
+                        }
+                        _ => {
+                            let start = pos;
+                            while pos < src.len() && !WHITESPACE[src[pos]] && src[pos] != b'>' {
+                                pos += 1;
+                            }
+                            value = (start, pos);
+                        }
+                    };
+                };
+                let value_bytes = &src[value.0..value.1];
+                if name.eq_ignore_ascii_case(b"charset") {
+                    charset = Some(value);
+                } else if name.eq_ignore_ascii_case(b"http-equiv") {
+                    is_content_type = value_bytes.eq_ignore_ascii_case(b"content-type");
+                } else if name.eq_ignore_ascii_case(b"content") {
+                    content = value_bytes
+                        .windows(7)
+                        .position(|w| w.eq_ignore_ascii_case(b"charset"))
+                        .map(|i| value.0 + i + 7)
+                        .and_then(|mut start| {
+                            while start < value.1 && WHITESPACE[src[start]] {
+                                start += 1;
+                            }
+                            if src.get(start) != Some(&b'=') {
+                                return None;
+                            };
+                            start += 1;
+                            while start < value.1
+                                && (WHITESPACE[src[start]] || matches!(src[start], b'"' | b'\''))
+                            {
+                                start += 1;
+                            }
+                            let end = src[start..value.1]
+                                .iter()
+                                .position(|&c| matches!(c, b';' | b'"' | b'\'') || WHITESPACE[c])
+                                .map_or(value.1, |i| start + i);
+                            Some((start, end))
+                        });
+                };
+            }
+            if let Some(range) = charset
+                .or(content.filter(|_| is_content_type))
+                .filter(|(start, end)| start < end)
+            {
+                return Some(range);
+            };
+            continue;
+        };
+        pos += 1;
+    }
+    None
+}
+
+fn decode_utf8<'s>(src: &'s [u8], malformed: &mut usize) -> Cow<'s, [u8]> {
+    if from_utf8(src).is_ok() {
+        return Cow::Borrowed(src);
+    };
+    let mut res = Vec::with_capacity(src.len() + 16);
+    let mut rest = src;
+    while !rest.is_empty() {
+        match from_utf8(rest) {
+            Ok(_) => {
+                res.extend_from_slice(rest);
+                break;
+            }
+            Err(e) => {
+                let valid = e.valid_up_to();
+                res.extend_from_slice(&rest[..valid]);
+                res.extend_from_slice("\u{FFFD}".as_bytes());
+                *malformed += 1;
+                rest = &rest[valid + e.error_len().unwrap_or(rest.len() - valid)..];
+            }
+        };
+    }
+    Cow::Owned(res)
+}
+
+fn decode_utf16_bytes(src: &[u8], big_endian: bool, malformed: &mut usize) -> Vec<u8> {
+    let units = src.chunks(2).map(|c| match c {
+        [a, b] if big_endian => u16::from_be_bytes([*a, *b]),
+        [a, b] => u16::from_le_bytes([*a, *b]),
+        // An odd trailing byte can't form a code unit, so use a lone surrogate to ensure it's replaced.
+        _ => 0xDC00,
+    });
+    let mut res = String::with_capacity(src.len());
+    for c in decode_utf16(units) {
+        res.push(c.unwrap_or_else(|_| {
+            *malformed += 1;
+            REPLACEMENT_CHARACTER
+        }));
+    }
+    res.into_bytes()
+}
+
+fn decode_windows_1252(src: &[u8]) -> Vec<u8> {
+    let mut res = String::with_capacity(src.len());
+    for &b in src {
+        let c = match b {
+            0x80..=0x9F => WINDOWS_1252_C1[(b - 0x80) as usize] as u32,
+            _ => b as u32,
+        };
+        res.push(std::char::from_u32(c).unwrap());
+    }
+    res.into_bytes()
+}
+
+fn windows_1252_byte(c: char) -> Option<u8> {
+    match c as u32 {
+        cp @ 0x00..=0x7F | cp @ 0xA0..=0xFF => Some(cp as u8),
+        cp => WINDOWS_1252_C1
+            .iter()
+            .position(|&m| m as u32 == cp)
+            .map(|i| 0x80 + i as u8),
+    }
+}
+
+// Characters that can't be represented are written as numeric character references, so those in `<script>` and `<style>` must already have been escaped.
+fn encode_windows_1252(code: &str) -> Vec<u8> {
+    let mut res = Vec::with_capacity(code.len());
+    for c in code.chars() {
+        match windows_1252_byte(c) {
+            Some(b) => res.push(b),
+            None => res.extend_from_slice(format!("&#{};", c as u32).as_bytes()),
+        };
+    }
+    res
+}
+
+// Input code transcoded to UTF-8 for processing, and how to encode the output.
+pub struct Input<'s> {
+    pub code: Cow<'s, [u8]>,
+    pub encoding: Encoding,
+    // The number of sequences that were invalid in the input encoding and replaced with U+FFFD.
+    pub malformed: usize,
+    // The label in `<meta charset>` if it isn't supported, in which case the code is left as is.
+    pub unsupported_label: Option<Vec<u8>>,
+    has_bom: bool,
+}
+
+impl<'s> Input<'s> {
+    // Decodes `src` according to the first of: its BOM, `fallback`, its `<meta charset>`, or UTF-8.
+    // If `rewrite_meta_charset`, a `<meta charset>` label that isn't UTF-8 is replaced with `utf-8` to match the transcoded code.
+    pub fn decode(
+        src: &'s [u8],
+        fallback: Option<Encoding>,
+        rewrite_meta_charset: bool,
+    ) -> Input<'s> {
+        let (bom_encoding, src) = if src.starts_with(b"\xEF\xBB\xBF") {
+            (Some(Encoding::Utf8), &src[3..])
+        } else if src.starts_with(b"\xFE\xFF") {
+            (Some(Encoding::Utf16Be), &src[2..])
+        } else if src.starts_with(b"\xFF\xFE") {
+            (Some(Encoding::Utf16Le), &src[2..])
+        } else {
+            (None, src)
+        };
+        let meta = match bom_encoding {
+            Some(_) => None,
+            None => prescan_meta_charset(src),
+        };
+        let mut unsupported_label = None;
+        let meta_encoding = meta.and_then(|(start, end)| {
+            match Encoding::for_label(&src[start..end]) {
+                // UTF-16 can't be declared in a `<meta>` that was readable as ASCII, so browsers use UTF-8 instead.
+                Some(Encoding::Utf16Be) | Some(Encoding::Utf16Le) => Some(Encoding::Utf8),
+                Some(e) => Some(e),
+                None => {
+                    unsupported_label = Some(src[start..end].to_vec());
+                    None
+                }
+            }
+        });
+        let encoding = bom_encoding
+            .or(fallback)
+            .or(meta_encoding)
+            .unwrap_or(Encoding::Utf8);
+        // We can't decode an unsupported encoding, so leave the code as is to avoid losing any characters. Everything that processes the code must handle invalid UTF-8 safely.
+        let pass_through =
+            encoding == Encoding::Utf8 && unsupported_label.is_some() && fallback.is_none();
+
+        let mut malformed = 0;
+        let code = match encoding {
+            _ if pass_through => Cow::Borrowed(src),
+            Encoding::Utf16Be | Encoding::Utf16Le => Cow::Owned(decode_utf16_bytes(
+                src,
+                encoding == Encoding::Utf16Be,
+                &mut malformed,
+            )),
+            _ => {
+                let src: Cow<[u8]> = match meta {
+                    Some((start, end))
+                        if rewrite_meta_charset && meta_encoding != Some(Encoding::Utf8) =>
+                    {
+                        Cow::Owned([&src[..start], &b"utf-8"[..], &src[end..]].concat())
+                    }
+                    _ => Cow::Borrowed(src),
+                };
+                match (encoding, src) {
+                    (Encoding::Windows1252, src) => Cow::Owned(decode_windows_1252(&src)),
+                    (_, Cow::Borrowed(src)) => decode_utf8(src, &mut malformed),
+                    (_, Cow::Owned(src)) => {
+                        Cow::Owned(decode_utf8(&src, &mut malformed).into_owned())
+                    }
+                }
+            }
+        };
+        Input {
+            code,
+            encoding,
+            malformed,
+            unsupported_label,
+            has_bom: bom_encoding.is_some(),
+        }
+    }
+
+    // Encodes UTF-8 output in the input encoding if `keep_encoding`, and otherwise leaves it as UTF-8. A BOM is added if `keep_bom` and the input had one, and is always added to UTF-16 output.
+    pub fn encode(&self, out: Vec<u8>, keep_encoding: bool, keep_bom: bool) -> Vec<u8> {
+        let encoding = if keep_encoding {
+            self.encoding
+        } else {
+            Encoding::Utf8
+        };
+        let bom = match encoding {
+            Encoding::Utf16Be | Encoding::Utf16Le => true,
+            _ => keep_bom && self.has_bom,
+        };
+        if encoding == Encoding::Utf8 && !bom {
+            return out;
+        };
+        let mut res = Vec::with_capacity(out.len() + 3);
+        if bom {
+            res.extend_from_slice(encoding.bom());
+        };
+        match encoding {
+            Encoding::Utf8 => res.extend_from_slice(&out),
+            Encoding::Utf16Be => String::from_utf8_lossy(&out)
+                .encode_utf16()
+                .for_each(|u| res.extend_from_slice(&u.to_be_bytes())),
+            Encoding::Utf16Le => String::from_utf8_lossy(&out)
+                .encode_utf16()
+                .for_each(|u| res.extend_from_slice(&u.to_le_bytes())),
+            Encoding::Windows1252 => {
+                res.extend_from_slice(&encode_windows_1252(&String::from_utf8_lossy(&out)))
+            }
+        };
+        res
+    }
+}
@@ -13,6 +13,7 @@
 // - For a numeric entity, browsers actually consume an unlimited amount of digits, but decode to 0xFFFD if not a valid
 //   Unicode Scalar Value.
 
+use std::borrow::Cow;
 use std::char::from_u32;
 
 use memchr::memchr;
@@ -136,7 +137,11 @@ fn parse_entity(code: &[u8], in_attr_val: bool) -> ParsedEntity {
     }
 }
 
-pub fn decode_entities(mut code: &[u8], in_attr_val: bool) -> Vec<u8> {
+// Only allocates if `code` contains an ampersand.
+pub fn decode_entities(mut code: &[u8], in_attr_val: bool) -> Cow<'_, [u8]> {
+    if memchr(b'&', code).is_none() {
+        return Cow::Borrowed(code);
+    };
     let mut res = Vec::<u8>::new();
     while !code.is_empty() {
         let (before, matched) = match memchr(b'&', code) {
@@ -159,5 +164,5 @@ pub fn decode_entities(mut code: &[u8], in_attr_val: bool) -> Vec<u8> {
             code = &code[read_len..];
         };
     }
-    res
+    Cow::Owned(res)
 }
@@ -45,8 +45,9 @@ pub fn encode_entities(mut code: &[u8], in_attr_val: bool) -> Vec<u8> {
                             0
                         }
                         _ => {
-                            res.extend_from_slice(b"&amp");
-                            // Skip the leading ampersand, as it will be replaced by `&amp`.
+                            // `&amp` without a semicolon isn't decoded in an attribute value if followed by an alphanumeric. Entity names start with a letter or `#`, so `&#38` never needs a semicolon.
+                            res.extend_from_slice(if in_attr_val { b"&#38" } else { b"&amp" });
+                            // Skip the leading ampersand, as it will be replaced.
                             1
                         }
                     },
@@ -60,3 +61,17 @@ pub fn encode_entities(mut code: &[u8], in_attr_val: bool) -> Vec<u8> {
     SHORTER_ENCODED_ENTITIES_ENCODED_SEARCHER
         .replace_all_bytes(&res, SHORTER_ENCODED_ENTITIES_ENCODED)
 }
+
+// Encodes all ampersands as `&amp;`, and nothing else. This is longer than `encode_entities`, but doesn't rely on consumers handling unterminated or ambiguous entities correctly, or knowing less common named entities.
+// Does not handle context-specific escaping e.g. `<`, `>`, `'`, `"`.
+pub fn encode_entities_conservatively(code: &[u8]) -> Vec<u8> {
+    let mut res = Vec::<u8>::with_capacity(code.len());
+    for &c in code {
+        if c == b'&' {
+            res.extend_from_slice(b"&amp;");
+        } else {
+            res.push(c);
+        };
+    }
+    res
+}
@@ -1,4 +1,4 @@
-use crate::entity::encode::encode_entities;
+use crate::entity::encode::{encode_entities, encode_entities_conservatively};
 
 #[test]
 fn test_encode_entities_encodes_ampersands_when_they_form_valid_entities() {
@@ -15,7 +15,7 @@ fn test_encode_entities_does_not_encode_valid_named_entities_inside_an_attr_valu
     let out = encode_entities(b"https://a.com/b?c  = d&param=123&param;&lt&mdash;", true);
     assert_eq!(
         std::str::from_utf8(&out).unwrap(),
-        "https://a.com/b?c  = d&param=123&param;&amplt&ampmdash;"
+        "https://a.com/b?c  = d&param=123&param;&#38lt&#38mdash;"
     );
 }
 
@@ -24,3 +24,12 @@ fn test_encode_entities_encodes_utf8_sequences_that_are_shorter_encoded() {
     let out = encode_entities("\u{226A}\u{20D2}".as_bytes(), false);
     assert_eq!(std::str::from_utf8(&out).unwrap(), "&nLt;");
 }
+
+#[test]
+fn test_encode_entities_conservatively_encodes_all_ampersands() {
+    let out = encode_entities_conservatively("a&b &amp; &lt \u{226A}\u{20D2}".as_bytes());
+    assert_eq!(
+        std::str::from_utf8(&out).unwrap(),
+        "a&amp;b &amp;amp; &amp;lt \u{226A}\u{20D2}"
+    );
+}
@@ -1,23 +1,72 @@
 use std::io::Write;
 
-use crate::ast::c14n::c14n_serialise_ast;
-pub use crate::cfg::Cfg;
-use crate::common::spec::tag::ns::Namespace;
+use bumpalo::collections::Vec as BumpVec;
+use bumpalo::Bump;
+
+use crate::ast::c14n::c14n_serialise_nodes;
+use crate::ast::normalise::{normalise, restore_closing_tags};
+use crate::ast::NodeData;
+pub use crate::cfg::attr::AttrRules;
+pub use crate::cfg::canonicalise::{AttributeQuotes, CanonicaliseCfg, EntityPolicy};
+pub use crate::cfg::format::FormatCfg;
+pub use crate::cfg::whitespace::{WhitespaceCategory, WhitespaceRules};
+pub use crate::cfg::{Cfg, DEFAULT_MAX_DEPTH};
+pub use crate::common::gen::attrs::AttributeMinification;
+pub use crate::common::spec::tag::ns::Namespace;
 use crate::common::spec::tag::EMPTY_SLICE;
+pub use crate::diagnostic::{DepthLimitExceeded, Diagnostic, EmbeddedLanguage};
+use crate::diff::{diff_nodes, NodeHash};
+pub use crate::diff::{Difference, DifferenceKind};
+pub use crate::encoding::Encoding;
+use crate::encoding::Input;
+use crate::lint::locate_problems;
+pub use crate::lint::{Problem, ProblemKind, Severity};
+pub use crate::minifier::Minifier;
 use crate::minify::content::minify_content;
+use crate::minify::embedded::EmbeddedResults;
+use crate::minify::line_breaks::LineBreaks;
+use crate::minify::style_rules::StyleRules;
 use crate::parse::content::parse_content;
 use crate::parse::Code;
 
 mod ast;
 mod cfg;
+// Shared with minify-html-onepass, which uses different parts of it.
+#[allow(dead_code)]
 mod common;
+mod diagnostic;
+mod diff;
+mod encoding;
 mod entity;
+mod lint;
+mod minifier;
 mod minify;
 mod parse;
 #[cfg(test)]
 mod tests;
 
-/// Minifies UTF-8 HTML code, represented as an array of bytes.
+// Parses decoded code for functions other than minification, which can't process it at all if elements are nested deeper than `max_depth`.
+fn parse_document<'c>(
+    src: &'c [u8],
+    arena: &'c Bump,
+    noscript_as_raw_text: bool,
+    max_depth: Option<usize>,
+) -> Result<BumpVec<'c, NodeData<'c>>, DepthLimitExceeded> {
+    let mut code = Code::new(src, arena);
+    code.noscript_as_raw_text = noscript_as_raw_text;
+    code.max_depth = max_depth;
+    let nodes = parse_content(&mut code, Namespace::Html, EMPTY_SLICE).children;
+    if code.depth_limit_exceeded {
+        return Err(DepthLimitExceeded {
+            max: max_depth.unwrap(),
+        });
+    };
+    Ok(nodes)
+}
+
+/// Minifies HTML code, represented as an array of bytes.
+///
+/// The code is decoded according to its BOM, `Cfg::input_encoding`, or its `<meta charset>`, and is UTF-8 otherwise. The output is UTF-8 unless `Cfg::keep_input_encoding` is set.
 ///
 /// # Arguments
 ///
@@ -36,25 +85,272 @@ mod tests;
 /// assert_eq!(minified, b"<p>Hello, world!".to_vec());
 /// ```
 pub fn minify(src: &[u8], cfg: &Cfg) -> Vec<u8> {
-    let mut code = Code::new(src);
-    let parsed = parse_content(&mut code, Namespace::Html, EMPTY_SLICE, EMPTY_SLICE);
-    let mut out = Vec::with_capacity(src.len());
+    minify_with_diagnostics(src, cfg).0
+}
+
+/// Minifies HTML code like [`minify`], but also returns any problems that were found, such as output lines that could not be kept within `Cfg::max_line_length`.
+///
+/// # Examples
+///
+/// ```
+/// use minify_html::{Cfg, Diagnostic, minify_with_diagnostics};
+///
+/// let mut cfg = Cfg::new();
+/// cfg.max_line_length = Some(8);
+/// let (minified, diagnostics) = minify_with_diagnostics(b"<p>Hello, world!</p>", &cfg);
+/// assert_eq!(minified, b"<p>Hello,\nworld!".to_vec());
+/// assert_eq!(diagnostics, vec![Diagnostic::LineTooLong { line: 1, length: 9 }]);
+/// ```
+pub fn minify_with_diagnostics(src: &[u8], cfg: &Cfg) -> (Vec<u8>, Vec<Diagnostic>) {
+    let arena = Bump::new();
+    minify_using(src, cfg, &arena, Vec::with_capacity(src.len()))
+}
+
+// Allocates nodes in `arena` and writes to `out`, which must be empty, so that both can be reused across calls by `Minifier`.
+fn minify_using(
+    src: &[u8],
+    cfg: &Cfg,
+    arena: &Bump,
+    mut out: Vec<u8>,
+) -> (Vec<u8>, Vec<Diagnostic>) {
+    debug_assert!(out.is_empty());
+    if let Some(max) = cfg.max_input_size.filter(|&max| src.len() > max) {
+        out.extend_from_slice(src);
+        return (
+            out,
+            vec![Diagnostic::InputTooLarge {
+                size: src.len(),
+                max,
+            }],
+        );
+    };
+    let input = Input::decode(src, cfg.input_encoding, !cfg.keep_input_encoding);
+    let mut diagnostics = Vec::new();
+    if input.malformed > 0 {
+        diagnostics.push(Diagnostic::MalformedInput {
+            encoding: input.encoding,
+            count: input.malformed,
+        });
+    };
+    if let Some(label) = &input.unsupported_label {
+        diagnostics.push(Diagnostic::UnsupportedEncoding {
+            label: String::from_utf8_lossy(label).into_owned(),
+        });
+    };
+    let mut code = Code::new(&input.code, arena);
+    code.noscript_as_raw_text = cfg.parse_noscript_as_raw_text;
+    code.max_depth = cfg.max_depth;
+    let parsed = parse_content(&mut code, Namespace::Html, EMPTY_SLICE);
+    if code.depth_limit_exceeded {
+        diagnostics.push(Diagnostic::TooDeeplyNested {
+            max: cfg.max_depth.unwrap(),
+        });
+        out.extend_from_slice(src);
+        return (out, diagnostics);
+    };
+    let style_rules = if cfg.infer_whitespace_from_css {
+        StyleRules::from_nodes(&parsed.children)
+    } else {
+        StyleRules::default()
+    };
+    // Embedded code is minified ahead of serialisation if it can be done in parallel, otherwise as it's serialised.
+    #[cfg(feature = "rayon")]
+    let mut embedded = EmbeddedResults::compute(cfg, &parsed.children);
+    #[cfg(not(feature = "rayon"))]
+    let mut embedded = EmbeddedResults::default();
+    embedded.output_encoding =
+        Some(input.encoding).filter(|e| cfg.keep_input_encoding && *e != Encoding::Utf8);
+    let mut line_breaks = LineBreaks::new(cfg.max_line_length.is_some());
     minify_content(
         cfg,
         &mut out,
+        &style_rules,
+        &mut line_breaks,
+        &mut diagnostics,
+        &embedded,
         Namespace::Html,
         false,
         EMPTY_SLICE,
+        None,
         parsed.children,
     );
-    out
+    let out = match cfg.max_line_length {
+        Some(max) => {
+            let (out, line_diagnostics) = line_breaks.apply(out, max);
+            diagnostics.extend(line_diagnostics);
+            out
+        }
+        None => out,
+    };
+    (
+        input.encode(out, cfg.keep_input_encoding, cfg.keep_bom),
+        diagnostics,
+    )
 }
 
+/// Writes the canonical form of UTF-8 HTML code, in which equivalent ways of writing the same document (e.g. attribute order, case of tag names, and character references) are made the same, which is useful for comparing documents. Uses the default [`CanonicaliseCfg`].
+///
+/// The code is always decoded as UTF-8, even if it declares another encoding in a `<meta charset>`. Returns an [`InvalidData`](std::io::ErrorKind::InvalidData) error if elements are nested deeper than `DEFAULT_MAX_DEPTH`, as well as any error from writing to `out`.
 pub fn canonicalise<T: Write>(out: &mut T, src: &[u8]) -> std::io::Result<()> {
-    let mut code = Code::new(src);
-    let parsed = parse_content(&mut code, Namespace::Html, EMPTY_SLICE, EMPTY_SLICE);
-    for c in parsed.children {
-        c14n_serialise_ast(out, &c)?;
-    }
+    canonicalise_with_cfg(out, src, &CanonicaliseCfg::default())
+}
+
+/// Writes the canonical form of UTF-8 HTML code like [`canonicalise`], with configurable attribute order, quotes, character references, whitespace, and indentation.
+///
+/// # Examples
+///
+/// ```
+/// use minify_html::{AttributeQuotes, CanonicaliseCfg, canonicalise_with_cfg};
+///
+/// let mut cfg = CanonicaliseCfg::new();
+/// cfg.attribute_quotes = AttributeQuotes::Double;
+/// cfg.normalise_whitespace = true;
+/// cfg.indent = Some(2);
+/// let mut out = Vec::new();
+/// canonicalise_with_cfg(&mut out, b"<UL id=a class=b><li> One  two <li>Three</ul>", &cfg).unwrap();
+/// assert_eq!(out, b"<ul class=\"b\" id=\"a\">\n  <li>One two\n  <li>Three\n</ul>\n".to_vec());
+/// ```
+pub fn canonicalise_with_cfg<T: Write>(
+    out: &mut T,
+    src: &[u8],
+    cfg: &CanonicaliseCfg,
+) -> std::io::Result<()> {
+    let input = Input::decode(src, Some(Encoding::Utf8), true);
+    let arena = Bump::new();
+    let mut nodes = parse_document(&input.code, &arena, false, cfg.max_depth)
+        .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?;
+    let whitespace_rules = WhitespaceRules::default();
+    normalise(
+        &mut nodes,
+        Some(&whitespace_rules).filter(|_| cfg.normalise_whitespace),
+        false,
+    );
+    c14n_serialise_nodes(
+        out,
+        cfg,
+        &whitespace_rules,
+        Namespace::Html,
+        false,
+        EMPTY_SLICE,
+        0,
+        &nodes,
+    )?;
     Ok(())
 }
+
+/// Adds line breaks and indentation to HTML code, such as minified output, to make it easier to read. The input is decoded like [`minify`](fn@crate::minify), and the output is UTF-8.
+///
+/// Line breaks are only added between nodes where whitespace is removed entirely when minifying (e.g. between `<li>` elements, but not between `<span>` elements in a `<p>`), so minifying the output gives the same result as minifying the input. The contents of `<pre>`, `<textarea>`, `<script>`, `<style>`, and other raw text elements are left as is. Whitespace-only text where line breaks are added is replaced, but other text and attribute values are written as they are in the source, including their character references.
+///
+/// Returns an error if elements are nested deeper than `FormatCfg::max_depth`.
+///
+/// # Examples
+///
+/// ```
+/// use minify_html::{format, FormatCfg};
+///
+/// let mut cfg = FormatCfg::new();
+/// cfg.restore_closing_tags = true;
+/// let out = format(b"<ul><li>One <b>two</b><li>Three</ul><pre> a\n b</pre>", &cfg).unwrap();
+/// assert_eq!(out, b"<ul>\n  <li>One <b>two</b></li>\n  <li>Three</li>\n</ul>\n<pre> a\n b</pre>\n".to_vec());
+/// ```
+pub fn format(src: &[u8], cfg: &FormatCfg) -> Result<Vec<u8>, DepthLimitExceeded> {
+    let input = Input::decode(src, cfg.input_encoding, true);
+    let arena = Bump::new();
+    let mut nodes = parse_document(&input.code, &arena, false, cfg.max_depth)?;
+    if cfg.restore_closing_tags {
+        restore_closing_tags(&mut nodes);
+    };
+    // Keep everything other than whitespace as close to the source as the canonical form allows.
+    let c14n_cfg = CanonicaliseCfg {
+        sort_attributes: false,
+        attribute_quotes: AttributeQuotes::Source,
+        entities: EntityPolicy::Source,
+        normalise_whitespace: false,
+        indent: Some(cfg.indent),
+        max_depth: None,
+    };
+    let mut out = Vec::with_capacity(src.len());
+    c14n_serialise_nodes(
+        &mut out,
+        &c14n_cfg,
+        &cfg.whitespace_rules,
+        Namespace::Html,
+        false,
+        EMPTY_SLICE,
+        0,
+        &nodes,
+    )
+    .unwrap();
+    Ok(out)
+}
+
+/// Parses HTML code without minifying it, and returns the authoring errors that were found, in the order they appear. These are mistakes that browsers and the minifier recover from, but that may make the minified output have a different structure from what was intended.
+///
+/// Only `Cfg::input_encoding`, `Cfg::max_depth`, and `Cfg::parse_noscript_as_raw_text` are used.
+///
+/// # Examples
+///
+/// ```
+/// use minify_html::{Cfg, lint, ProblemKind, Severity};
+///
+/// let problems = lint(b"<p>Hello,\n<b>world!</i></b>", &Cfg::new());
+/// assert_eq!(problems.len(), 1);
+/// assert_eq!(problems[0].kind, ProblemKind::UnmatchedClosingTag { name: "i".to_string() });
+/// assert_eq!(problems[0].severity(), Severity::Error);
+/// assert_eq!((problems[0].line, problems[0].column), (2, 10));
+/// ```
+pub fn lint(src: &[u8], cfg: &Cfg) -> Vec<Problem> {
+    let input = Input::decode(src, cfg.input_encoding, false);
+    let arena = Bump::new();
+    let mut code = Code::new(&input.code, &arena);
+    code.noscript_as_raw_text = cfg.parse_noscript_as_raw_text;
+    code.max_depth = cfg.max_depth;
+    parse_content(&mut code, Namespace::Html, EMPTY_SLICE);
+    locate_problems(&input.code, code.problems)
+}
+
+/// Compares two HTML documents, ignoring differences in formatting, and returns the differences in their elements, attributes, and text, in document order.
+///
+/// Formatting that is ignored includes whitespace that would be removed or collapsed by minification, attribute order and quotes, character references, and omitted closing tags. Comments, bangs, and processing instructions are compared like other nodes.
+///
+/// Only `Cfg::input_encoding`, `Cfg::max_depth`, `Cfg::parse_noscript_as_raw_text`, and `Cfg::whitespace_rules` are used. If elements in either document are nested deeper than `Cfg::max_depth`, they aren't compared and a `DepthLimitExceeded` error is returned.
+///
+/// # Examples
+///
+/// ```
+/// use minify_html::{Cfg, diff, DifferenceKind};
+///
+/// let differences = diff(
+///     b"<ul>\n  <li class=a>One\n  <li>Two\n</ul>",
+///     b"<ul><li class='b'>One</li><li>Two</li><li>Three</li></ul>",
+///     &Cfg::new(),
+/// )
+/// .unwrap();
+/// assert_eq!(differences.len(), 2);
+/// assert_eq!(differences[0].path, "ul > li[1]");
+/// assert_eq!(differences[0].to_string(), r#"~ ul > li[1] [class]: "a" -> "b""#);
+/// assert_eq!(differences[1].kind, DifferenceKind::Added { node: "<li>Three</li>".to_string() });
+/// ```
+pub fn diff(a: &[u8], b: &[u8], cfg: &Cfg) -> Result<Vec<Difference>, DepthLimitExceeded> {
+    let a = Input::decode(a, cfg.input_encoding, false);
+    let b = Input::decode(b, cfg.input_encoding, false);
+    let arena = Bump::new();
+    let mut parsed = Vec::with_capacity(2);
+    for src in [&a.code, &b.code] {
+        let mut nodes =
+            parse_document(src, &arena, cfg.parse_noscript_as_raw_text, cfg.max_depth)?;
+        normalise(&mut nodes, Some(&cfg.whitespace_rules), true);
+        parsed.push(nodes);
+    }
+    let mut differences = Vec::new();
+    let a_hashes = NodeHash::of_nodes(&parsed[0]);
+    let b_hashes = NodeHash::of_nodes(&parsed[1]);
+    diff_nodes(
+        "",
+        (&parsed[0], &a_hashes),
+        (&parsed[1], &b_hashes),
+        &mut differences,
+    );
+    Ok(differences)
+}
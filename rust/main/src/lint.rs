@@ -0,0 +1,135 @@
+use std::fmt;
+
+use crate::common::location::{Location, Locator};
+
+/// How likely a [`Problem`] is to make the minified output differ in structure from what was intended.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Severity {
+    /// The code is ambiguous or is handled differently by browsers and the minifier, so the minified output may not have the intended structure.
+    Error,
+    /// The code is invalid, but is handled the same way by browsers and the minifier.
+    Warning,
+}
+
+/// An authoring error found while parsing.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ProblemKind {
+    /// An attribute appears more than once in a tag. Browsers use the first value, but the minifier keeps the last.
+    DuplicateAttribute { name: String },
+    /// A second `<html>`, `<head>`, or `<body>` opening tag, which is dropped along with its attributes.
+    DuplicateTag { name: String },
+    /// `</` is not followed by a tag name, so it and everything until the next `>` is dropped.
+    MalformedClosingTag,
+    /// Elements are nested deeper than `Cfg::max_depth`, so the rest of the code was not checked.
+    TooDeeplyNested { max: usize },
+    /// A comment is not closed before the end of the code, so it contains the rest of the code.
+    UnclosedComment,
+    /// A `<script>`, `<style>`, `<textarea>`, `<title>`, or other raw text element is not closed before the end of the code, so it contains the rest of the code as text.
+    UnclosedRawText { name: String },
+    /// A closing tag does not match any open element that it could close, so it's dropped.
+    UnmatchedClosingTag { name: String },
+    /// A closing tag for a void element like `<br>` or `<img>`, which is dropped.
+    VoidClosingTag { name: String },
+}
+
+impl ProblemKind {
+    pub fn severity(&self) -> Severity {
+        match self {
+            ProblemKind::DuplicateTag { .. }
+            | ProblemKind::MalformedClosingTag
+            | ProblemKind::VoidClosingTag { .. } => Severity::Warning,
+            _ => Severity::Error,
+        }
+    }
+}
+
+impl fmt::Display for ProblemKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProblemKind::DuplicateAttribute { name } => {
+                write!(f, "Duplicate {:?} attribute; only the last value is kept", name)
+            }
+            ProblemKind::DuplicateTag { name } => {
+                write!(f, "Duplicate <{}> opening tag is dropped", name)
+            }
+            ProblemKind::MalformedClosingTag => {
+                write!(f, "Closing tag has no name and is dropped")
+            }
+            ProblemKind::TooDeeplyNested { max } => write!(
+                f,
+                "Elements are nested deeper than the maximum of {}, so the rest of the code was not checked",
+                max
+            ),
+            ProblemKind::UnclosedComment => write!(f, "Comment is not closed"),
+            ProblemKind::UnclosedRawText { name } => write!(
+                f,
+                "<{}> is not closed, so the rest of the code is its text",
+                name
+            ),
+            ProblemKind::UnmatchedClosingTag { name } => write!(
+                f,
+                "Closing tag </{}> does not match any open element and is dropped",
+                name
+            ),
+            ProblemKind::VoidClosingTag { name } => write!(
+                f,
+                "Closing tag </{}> is for a void element and is dropped",
+                name
+            ),
+        }
+    }
+}
+
+/// A problem found by [`lint`](fn@crate::lint), and where it is in the code.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Problem {
+    pub kind: ProblemKind,
+    /// Byte offset of the start of the problem in the code, after it has been decoded to UTF-8 and any byte order mark has been removed.
+    pub position: usize,
+    /// One-based line number.
+    pub line: usize,
+    /// One-based column number, in characters.
+    pub column: usize,
+}
+
+impl Problem {
+    pub fn severity(&self) -> Severity {
+        self.kind.severity()
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity() {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(
+            f,
+            "{}:{}: {}: {}",
+            self.line, self.column, severity, self.kind
+        )
+    }
+}
+
+pub(crate) fn name_to_string(name: &[u8]) -> String {
+    String::from_utf8_lossy(name).into_owned()
+}
+
+// Sorts problems by position and adds their line and column numbers in `code`.
+pub(crate) fn locate_problems(code: &[u8], mut found: Vec<(usize, ProblemKind)>) -> Vec<Problem> {
+    found.sort_by_key(|(position, _)| *position);
+    let mut locator = Locator::new(code);
+    found
+        .into_iter()
+        .map(|(position, kind)| {
+            let Location { line, column, .. } = locator.locate(position);
+            Problem {
+                kind,
+                position,
+                line,
+                column,
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,99 @@
+use std::io::Write;
+use std::mem::take;
+
+use bumpalo::Bump;
+
+use crate::{minify_using, Cfg, Diagnostic};
+
+/// A minifier that owns its configuration and reuses its internal buffers and node arena across calls, for minifying many documents with the same configuration, such as responses in a server.
+///
+/// A `Minifier` needs `&mut self` to minify, so it can't be shared across threads. To use one per thread, keep it in a thread local:
+///
+/// ```
+/// use std::cell::RefCell;
+/// use minify_html::{Cfg, Minifier};
+///
+/// thread_local! {
+///     static MINIFIER: RefCell<Minifier> = RefCell::new(Minifier::new(Cfg::new()));
+/// }
+///
+/// let mut out = Vec::new();
+/// MINIFIER.with(|m| m.borrow_mut().minify_into(b"<p>  Hello, world!  </p>", &mut out));
+/// assert_eq!(out, b"<p>Hello, world!".to_vec());
+/// ```
+///
+/// The memory retained between calls is proportional to the largest document minified so far. Drop the `Minifier` (or replace it with a new one) to release it.
+pub struct Minifier {
+    cfg: Cfg,
+    arena: Bump,
+    buf: Vec<u8>,
+}
+
+impl Minifier {
+    pub fn new(cfg: Cfg) -> Minifier {
+        Minifier {
+            cfg,
+            arena: Bump::new(),
+            buf: Vec::new(),
+        }
+    }
+
+    pub fn cfg(&self) -> &Cfg {
+        &self.cfg
+    }
+
+    // Calls `minify_using` with a buffer that's empty, then resets the arena so its memory can be reused by the next call.
+    fn minify_using(&mut self, src: &[u8], mut buf: Vec<u8>) -> (Vec<u8>, Vec<Diagnostic>) {
+        buf.clear();
+        let res = minify_using(src, &self.cfg, &self.arena, buf);
+        self.arena.reset();
+        res
+    }
+
+    /// Minifies `src` and appends the output to `out`, returning any diagnostics. See [`minify_with_diagnostics`](crate::minify_with_diagnostics).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use minify_html::{Cfg, Minifier};
+    ///
+    /// let mut minifier = Minifier::new(Cfg::new());
+    /// let mut out = Vec::new();
+    /// minifier.minify_into(b"<p>  Hello  </p>", &mut out);
+    /// minifier.minify_into(b"<p>  world!  </p>", &mut out);
+    /// assert_eq!(out, b"<p>Hello<p>world!".to_vec());
+    /// ```
+    pub fn minify_into(&mut self, src: &[u8], out: &mut Vec<u8>) -> Vec<Diagnostic> {
+        if out.is_empty() {
+            // Write directly into `out` to avoid a copy.
+            let (res, diagnostics) = self.minify_using(src, take(out));
+            *out = res;
+            return diagnostics;
+        };
+        let buf = take(&mut self.buf);
+        let (res, diagnostics) = self.minify_using(src, buf);
+        out.extend_from_slice(&res);
+        self.buf = res;
+        diagnostics
+    }
+
+    /// Minifies `src` and writes the output to `out`, returning any diagnostics. See [`minify_with_diagnostics`](crate::minify_with_diagnostics).
+    pub fn minify_to<W: Write>(
+        &mut self,
+        src: &[u8],
+        out: &mut W,
+    ) -> std::io::Result<Vec<Diagnostic>> {
+        let buf = take(&mut self.buf);
+        let (res, diagnostics) = self.minify_using(src, buf);
+        let written = out.write_all(&res);
+        self.buf = res;
+        written.map(|_| diagnostics)
+    }
+
+    /// Minifies `src` and returns the output. Prefer [`minify_into`](Minifier::minify_into) or [`minify_to`](Minifier::minify_to) to also reuse the output buffer.
+    pub fn minify(&mut self, src: &[u8]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(src.len());
+        self.minify_into(src, &mut out);
+        out
+    }
+}
@@ -0,0 +1,126 @@
+use std::str::from_utf8;
+
+use crate::common::gen::entities::SHORTEST_NAMED_ENTITIES;
+
+// Calls `escape` with every non-ASCII scalar in `code` that `keep` returns false for and the byte following it, and copies everything else as is. Bytes that aren't part of a valid UTF-8 sequence are also copied as is, as they can't be represented.
+fn escape_non_ascii(
+    code: &[u8],
+    keep: impl Fn(char) -> bool,
+    mut escape: impl FnMut(&mut Vec<u8>, char, Option<u8>),
+) -> Vec<u8> {
+    let mut res = Vec::with_capacity(code.len());
+    let mut i = 0;
+    while i < code.len() {
+        let width = match code[i] {
+            0x00..=0x7f => 1,
+            0xc0..=0xdf => 2,
+            0xe0..=0xef => 3,
+            0xf0..=0xf7 => 4,
+            _ => 0,
+        };
+        match code
+            .get(i..i + width)
+            .filter(|_| width > 1)
+            .and_then(|seq| from_utf8(seq).ok())
+            .and_then(|seq| seq.chars().next())
+        {
+            Some(c) if !keep(c) => {
+                escape(&mut res, c, code.get(i + width).copied());
+                i += width;
+            }
+            _ => {
+                res.push(code[i]);
+                i += 1;
+            }
+        };
+    }
+    res
+}
+
+// Replaces every non-ASCII scalar with the shortest named or numeric character reference, preferring numeric references if they're the same length.
+// The semicolon is omitted when the reference can't be continued by the next character, unless `conservative`. Named references can only omit it if they're legacy references, which also aren't decoded in attribute values if followed by `=`.
+pub fn encode_non_ascii_as_refs(code: &[u8], conservative: bool) -> Vec<u8> {
+    escape_non_ascii(
+        code,
+        |_| false,
+        |out, c, next| {
+            let can_omit_semicolon = |named: bool| {
+                !conservative
+                    && next
+                        .filter(|c| {
+                            c.is_ascii_alphanumeric() || *c == b';' || (named && *c == b'=')
+                        })
+                        .is_none()
+            };
+            let dec = (c as u32).to_string();
+            let hex = format!("{:x}", c as u32);
+            let mut numeric = b"&#".to_vec();
+            if hex.len() + 1 < dec.len() {
+                numeric.push(b'x');
+                numeric.extend_from_slice(hex.as_bytes());
+            } else {
+                numeric.extend_from_slice(dec.as_bytes());
+            };
+            if !can_omit_semicolon(false) {
+                numeric.push(b';');
+            };
+            let named = SHORTEST_NAMED_ENTITIES
+                .binary_search_by_key(&c, |(c, _, _)| *c)
+                .ok()
+                .map(|i| {
+                    let (_, name, legacy) = SHORTEST_NAMED_ENTITIES[i];
+                    let mut named = b"&".to_vec();
+                    named.extend_from_slice(name);
+                    if !legacy || !can_omit_semicolon(true) {
+                        named.push(b';');
+                    };
+                    named
+                });
+            match named {
+                Some(named) if named.len() < numeric.len() => out.extend_from_slice(&named),
+                _ => out.extend_from_slice(&numeric),
+            };
+        },
+    )
+}
+
+// Removes a backslash before a scalar that is about to be escaped, if it escapes the scalar (i.e. it's not itself escaped), and returns whether it did. An escaped scalar that isn't a special character represents itself, so its escape must replace the backslash rather than follow it.
+fn remove_escaping_backslash(out: &mut Vec<u8>) -> bool {
+    let backslashes = out.iter().rev().take_while(|&&c| c == b'\\').count();
+    if backslashes % 2 == 1 {
+        out.pop();
+        true
+    } else {
+        false
+    }
+}
+
+// Replaces every non-ASCII scalar that `keep` returns false for with a `\u` escape, using surrogate pairs for scalars outside the BMP. This is valid in strings, template literals, regular expressions, and identifiers (except for scalars outside the BMP).
+pub fn escape_non_ascii_in_js(code: &[u8], keep: impl Fn(char) -> bool) -> Vec<u8> {
+    escape_non_ascii(code, keep, |out, c, _| {
+        if remove_escaping_backslash(out) && matches!(c, '\u{2028}' | '\u{2029}') {
+            // A line continuation, which represents nothing.
+            return;
+        };
+        let mut units = [0u16; 2];
+        for &unit in c.encode_utf16(&mut units).iter() {
+            out.extend_from_slice(format!("\\u{:04x}", unit).as_bytes());
+        }
+    })
+}
+
+// Replaces every non-ASCII scalar that `keep` returns false for with a `\` hex escape. A space is added to terminate the escape if the next character could otherwise be interpreted as part of it.
+pub fn escape_non_ascii_in_css(code: &[u8], keep: impl Fn(char) -> bool) -> Vec<u8> {
+    escape_non_ascii(code, keep, |out, c, next| {
+        remove_escaping_backslash(out);
+        out.extend_from_slice(format!("\\{:x}", c as u32).as_bytes());
+        if next
+            .filter(|c| {
+                c.is_ascii_hexdigit() || matches!(c, b' ' | b'\t' | b'\n' | b'\r' | b'\x0c')
+            })
+            .is_some()
+        {
+            out.push(b' ');
+        };
+    })
+}
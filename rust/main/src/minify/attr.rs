@@ -1,10 +1,10 @@
-use std::str::from_utf8_unchecked;
+use std::borrow::Cow;
+use std::str::from_utf8;
 
 use aho_corasick::{AhoCorasickBuilder, MatchKind};
-use css_minify::optimizations::{Level, Minifier};
 use lazy_static::lazy_static;
 
-use crate::common::gen::attrs::ATTRS;
+use crate::common::gen::attrs::AttributeMinification;
 use crate::common::gen::codepoints::DIGIT;
 use crate::common::pattern::Replacer;
 use crate::common::spec::script::JAVASCRIPT_MIME_TYPES;
@@ -12,7 +12,10 @@ use crate::common::spec::tag::ns::Namespace;
 use crate::common::whitespace::{
     collapse_whitespace, left_trim, remove_all_whitespace, right_trim,
 };
-use crate::entity::encode::encode_entities;
+use crate::diagnostic::{Diagnostic, EmbeddedLanguage};
+use crate::entity::encode::{encode_entities, encode_entities_conservatively};
+use crate::minify::ascii::encode_non_ascii_as_refs;
+use crate::minify::embedded::{minify_embedded_code, EmbeddedResults};
 use crate::Cfg;
 
 fn build_double_quoted_replacer() -> Replacer {
@@ -113,7 +116,7 @@ static WHATWG_UNQUOTED: &[(u8, &[u8])] = &[
     (b'"', b"&#34"),
     (b'\'', b"&#39"),
     (b'=', b"&#61"),
-    (b'`', b"&#6"),
+    (b'`', b"&#96"),
 ];
 
 fn build_whatwg_unquoted_replacer() -> Replacer {
@@ -181,11 +184,44 @@ fn build_whatwg_unquoted_replacer() -> Replacer {
     )
 }
 
+// Replaces each character with its entity terminated with a semicolon, for consumers that don't handle unterminated or uppercase entities (e.g. email clients).
+fn build_conservative_replacer(entities: &[(u8, &[u8])]) -> Replacer {
+    let mut patterns = Vec::<Vec<u8>>::new();
+    let mut replacements = Vec::<Vec<u8>>::new();
+
+    for &(c, rep) in entities {
+        patterns.push(vec![c]);
+        replacements.push({
+            let mut ent = rep.to_vec();
+            ent.push(b';');
+            ent
+        });
+    }
+
+    Replacer::new(
+        AhoCorasickBuilder::new()
+            .dfa(true)
+            .match_kind(MatchKind::LeftmostLongest)
+            .build(patterns),
+        replacements,
+    )
+}
+
+static CHEVRONS: &[(u8, &[u8])] = &[(b'<', b"&lt"), (b'>', b"&gt")];
+
 lazy_static! {
     static ref DOUBLE_QUOTED_REPLACER: Replacer = build_double_quoted_replacer();
     static ref SINGLE_QUOTED_REPLACER: Replacer = build_single_quoted_replacer();
     static ref UNQUOTED_REPLACER: Replacer = build_unquoted_replacer();
     static ref WHATWG_UNQUOTED_REPLACER: Replacer = build_whatwg_unquoted_replacer();
+    static ref CONSERVATIVE_DOUBLE_QUOTED_REPLACER: Replacer =
+        build_conservative_replacer(&[(b'"', b"&#34")]);
+    static ref CONSERVATIVE_SINGLE_QUOTED_REPLACER: Replacer =
+        build_conservative_replacer(&[(b'\'', b"&#39")]);
+    static ref CONSERVATIVE_UNQUOTED_REPLACER: Replacer =
+        build_conservative_replacer(&[WS, &CHEVRONS[1..]].concat());
+    static ref CONSERVATIVE_WHATWG_UNQUOTED_REPLACER: Replacer =
+        build_conservative_replacer(&[WS, WHATWG_UNQUOTED, CHEVRONS].concat());
 }
 
 pub struct AttrMinifiedValue {
@@ -219,46 +255,65 @@ impl AttrMinifiedValue {
     }
 }
 
-pub fn encode_using_double_quotes(val: &[u8]) -> AttrMinifiedValue {
+pub fn encode_using_double_quotes(val: &[u8], conservative: bool) -> AttrMinifiedValue {
+    let replacer: &Replacer = if conservative {
+        &CONSERVATIVE_DOUBLE_QUOTED_REPLACER
+    } else {
+        &DOUBLE_QUOTED_REPLACER
+    };
     AttrMinifiedValue {
         quoted: true,
         prefix: b"\"",
-        data: DOUBLE_QUOTED_REPLACER.replace_all(val),
+        data: replacer.replace_all(val),
         start: 0,
         suffix: b"\"",
     }
 }
 
-pub fn encode_using_single_quotes(val: &[u8]) -> AttrMinifiedValue {
+pub fn encode_using_single_quotes(val: &[u8], conservative: bool) -> AttrMinifiedValue {
+    let replacer: &Replacer = if conservative {
+        &CONSERVATIVE_SINGLE_QUOTED_REPLACER
+    } else {
+        &SINGLE_QUOTED_REPLACER
+    };
     AttrMinifiedValue {
         quoted: true,
         prefix: b"'",
-        data: SINGLE_QUOTED_REPLACER.replace_all(val),
+        data: replacer.replace_all(val),
         start: 0,
         suffix: b"'",
     }
 }
 
-pub fn encode_unquoted(val: &[u8], whatwg: bool) -> AttrMinifiedValue {
+pub fn encode_unquoted(val: &[u8], whatwg: bool, conservative: bool) -> AttrMinifiedValue {
     if whatwg {
+        let replacer: &Replacer = if conservative {
+            &CONSERVATIVE_WHATWG_UNQUOTED_REPLACER
+        } else {
+            &WHATWG_UNQUOTED_REPLACER
+        };
         AttrMinifiedValue {
             quoted: false,
             prefix: b"",
-            data: WHATWG_UNQUOTED_REPLACER.replace_all(val),
+            data: replacer.replace_all(val),
             start: 0,
             suffix: b"",
         }
     } else {
-        let data = UNQUOTED_REPLACER.replace_all(val);
-        let prefix: &'static [u8] = match data.get(0) {
-            Some(b'"') => match data.get(1) {
-                Some(&c2) if DIGIT[c2] || c2 == b';' => b"&#34;",
-                _ => b"&#34",
-            },
-            Some(b'\'') => match data.get(1) {
-                Some(&c2) if DIGIT[c2] || c2 == b';' => b"&#39;",
-                _ => b"&#39",
-            },
+        let replacer: &Replacer = if conservative {
+            &CONSERVATIVE_UNQUOTED_REPLACER
+        } else {
+            &UNQUOTED_REPLACER
+        };
+        let data = replacer.replace_all(val);
+        // A leading quote must be encoded, with a semicolon if followed by a digit or semicolon.
+        let terminate =
+            conservative || data.get(1).filter(|&&c2| DIGIT[c2] || c2 == b';').is_some();
+        let prefix: &'static [u8] = match (data.first(), terminate) {
+            (Some(b'"'), true) => b"&#34;",
+            (Some(b'"'), false) => b"&#34",
+            (Some(b'\''), true) => b"&#39;",
+            (Some(b'\''), false) => b"&#39",
             _ => b"",
         };
         let start = if !prefix.is_empty() { 1 } else { 0 };
@@ -278,6 +333,54 @@ pub enum AttrMinified {
     Value(AttrMinifiedValue),
 }
 
+fn minify_whitespace(
+    cfg: &Cfg,
+    attr_cfg: Option<&AttributeMinification>,
+    is_meta_viewport: bool,
+    value: &mut Cow<[u8]>,
+) {
+    if is_meta_viewport {
+        remove_all_whitespace(value);
+    } else {
+        // Trim before checking is_boolean as the entire attribute could be redundant post-minification.
+        if attr_cfg.filter(|attr| attr.trim).is_some() {
+            right_trim(value);
+            left_trim(value);
+        };
+        if attr_cfg.filter(|attr| attr.collapse).is_some() {
+            collapse_whitespace(value, cfg.preserve_line_breaks);
+        };
+    };
+}
+
+// Wraps a `style` attribute value in a rule so it can be minified as a stylesheet.
+// The value may not be valid UTF-8 if the input's encoding isn't supported, in which case it's left as is.
+fn wrap_style(value: &[u8]) -> Option<Vec<u8>> {
+    from_utf8(value).ok()?;
+    let mut wrapped = Vec::with_capacity(value.len() + 3);
+    // TODO This isn't safe for invalid input e.g. `a}/*`.
+    wrapped.extend_from_slice(b"x{");
+    wrapped.extend_from_slice(value);
+    wrapped.push(b'}');
+    Some(wrapped)
+}
+
+// Returns the CSS that `minify_attr` would give to the CSS minifier for a `style` attribute with `value`, if any.
+#[cfg(feature = "rayon")]
+pub fn style_attr_css_source(
+    cfg: &Cfg,
+    ns: Namespace,
+    tag: &[u8],
+    is_meta_viewport: bool,
+    value: &[u8],
+) -> Option<Vec<u8>> {
+    let attr_cfg = cfg.attribute_rules.get_or_builtin(ns, tag, b"style");
+    let mut value = Cow::Borrowed(value);
+    minify_whitespace(cfg, attr_cfg, is_meta_viewport, &mut value);
+    wrap_style(&value)
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn minify_attr(
     cfg: &Cfg,
     ns: Namespace,
@@ -285,85 +388,88 @@ pub fn minify_attr(
     // True if element is <meta> and has an attribute `name` equal to `viewport`.
     is_meta_viewport: bool,
     name: &[u8],
-    mut value_raw: Vec<u8>,
+    mut value_raw: Cow<[u8]>,
+    diagnostics: &mut Vec<Diagnostic>,
+    embedded: &EmbeddedResults,
 ) -> AttrMinified {
-    let attr_cfg = ATTRS.get(ns, tag, name);
+    let attr_cfg = cfg.attribute_rules.get_or_builtin(ns, tag, name);
 
-    let should_collapse = attr_cfg.filter(|attr| attr.collapse).is_some();
-    let should_trim = attr_cfg.filter(|attr| attr.trim).is_some();
     let should_lowercase = attr_cfg.filter(|attr| attr.case_insensitive).is_some();
     let is_boolean = attr_cfg.filter(|attr| attr.boolean).is_some();
     // An attribute can have both redundant_if_empty and default_value, which means it has two default values: "" and default_value.
     let redundant_if_empty = attr_cfg.filter(|attr| attr.redundant_if_empty).is_some();
-    let default_value = attr_cfg.and_then(|attr| attr.default_value);
+    let default_value = attr_cfg.and_then(|attr| attr.default_value.as_deref());
 
-    if is_meta_viewport {
-        remove_all_whitespace(&mut value_raw);
-    } else {
-        // Trim before checking is_boolean as the entire attribute could be redundant post-minification.
-        if should_trim {
-            right_trim(&mut value_raw);
-            left_trim(&mut value_raw);
-        };
-        if should_collapse {
-            collapse_whitespace(&mut value_raw);
-        };
-    };
+    minify_whitespace(cfg, attr_cfg, is_meta_viewport, &mut value_raw);
 
     if name == b"style" && cfg.minify_css {
-        let mut value_raw_wrapped = String::with_capacity(value_raw.len() + 3);
-        // TODO This isn't safe for invalid input e.g. `a}/*`.
-        value_raw_wrapped.push_str("x{");
-        value_raw_wrapped.push_str(unsafe { from_utf8_unchecked(&value_raw) });
-        value_raw_wrapped.push('}');
-        let result = Minifier::default().minify(&value_raw_wrapped, Level::Three);
-        // TODO Collect error as warning.
-        if let Ok(min) = result {
-            let mut value_raw_wrapped_min = min.into_bytes();
-            // TODO If input was invalid, wrapper syntax may not exist anymore.
-            if value_raw_wrapped_min.starts_with(b"x{") {
-                value_raw_wrapped_min.drain(0..2);
+        if let Some(value_raw_wrapped) = wrap_style(&value_raw) {
+            let result = minify_embedded_code(
+                diagnostics,
+                embedded,
+                EmbeddedLanguage::Css,
+                cfg.max_embedded_minification_time,
+                &value_raw_wrapped,
+            );
+            if let Some(mut value_raw_wrapped_min) = result {
+                // TODO If input was invalid, wrapper syntax may not exist anymore.
+                if value_raw_wrapped_min.starts_with(b"x{") {
+                    value_raw_wrapped_min.drain(0..2);
+                };
+                if value_raw_wrapped_min.ends_with(b"}") {
+                    value_raw_wrapped_min.pop();
+                };
+                value_raw = Cow::Owned(value_raw_wrapped_min);
             };
-            if value_raw_wrapped_min.ends_with(b"}") {
-                value_raw_wrapped_min.pop();
-            };
-            value_raw = value_raw_wrapped_min;
         };
     }
 
     // Make lowercase before checking against default value or JAVASCRIPT_MIME_TYPES.
-    if should_lowercase {
-        value_raw.make_ascii_lowercase();
+    if should_lowercase && value_raw.iter().any(|c| c.is_ascii_uppercase()) {
+        value_raw.to_mut().make_ascii_lowercase();
     };
 
     if (value_raw.is_empty() && redundant_if_empty)
-        || default_value.filter(|dv| dv == &value_raw).is_some()
+        || default_value.filter(|dv| *dv == &*value_raw).is_some()
         || (tag == b"script"
             && name == b"type"
-            && JAVASCRIPT_MIME_TYPES.contains(value_raw.as_slice())
-            && value_raw.as_slice() != b"module")
+            && JAVASCRIPT_MIME_TYPES.contains(value_raw.as_ref())
+            && value_raw.as_ref() != b"module")
     {
         return AttrMinified::Redundant;
     };
 
-    if is_boolean || value_raw.is_empty() {
+    if is_boolean || (value_raw.is_empty() && !cfg.always_quote_attribute_values) {
         return AttrMinified::NoValue;
     };
 
-    let encoded = encode_entities(&value_raw, true);
+    let conservative = cfg.conservative_entities || cfg.ensure_no_parse_errors;
+    let encoded = if conservative {
+        encode_entities_conservatively(&value_raw)
+    } else {
+        encode_entities(&value_raw, true)
+    };
+    let encoded = if cfg.ascii_only {
+        encode_non_ascii_as_refs(&encoded, conservative)
+    } else {
+        encoded
+    };
 
     // When lengths are equal, prefer double quotes to all and single quotes to unquoted.
-    let mut min = encode_using_double_quotes(&encoded);
-    let sq = encode_using_single_quotes(&encoded);
+    let mut min = encode_using_double_quotes(&encoded, conservative);
+    let sq = encode_using_single_quotes(&encoded, conservative);
     if sq.len() < min.len() {
         min = sq;
     };
-    let uq = encode_unquoted(
-        &encoded,
-        cfg.ensure_spec_compliant_unquoted_attribute_values,
-    );
-    if uq.len() < min.len() {
-        min = uq;
+    if !cfg.always_quote_attribute_values {
+        let uq = encode_unquoted(
+            &encoded,
+            cfg.ensure_spec_compliant_unquoted_attribute_values || cfg.ensure_no_parse_errors,
+            conservative,
+        );
+        if uq.len() < min.len() {
+            min = uq;
+        };
     };
     AttrMinified::Value(min)
 }
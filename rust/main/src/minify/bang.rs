@@ -1,7 +1,12 @@
 use crate::cfg::Cfg;
 
+pub fn is_bang_kept(cfg: &Cfg) -> bool {
+    // Bangs are always parse errors.
+    !cfg.remove_bangs && !cfg.ensure_no_parse_errors
+}
+
 pub fn minify_bang(cfg: &Cfg, out: &mut Vec<u8>, code: &[u8], ended: bool) {
-    if !cfg.remove_bangs {
+    if is_bang_kept(cfg) {
         out.extend_from_slice(b"<!");
         out.extend_from_slice(code);
         if ended {
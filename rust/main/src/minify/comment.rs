@@ -1,10 +1,28 @@
 use crate::cfg::Cfg;
 
+// Conditional comments (e.g. `<!--[if mso]>...<![endif]-->`) and the comments that wrap downlevel-revealed content (e.g. `<!--[if !mso]><!-->` and `<!--<![endif]-->`) are interpreted by some clients such as Outlook.
+fn is_conditional_comment(code: &[u8]) -> bool {
+    code.starts_with(b"[if") || code.starts_with(b"<![endif]")
+}
+
+// https://html.spec.whatwg.org/multipage/syntax.html#comments
+fn is_conforming_comment(code: &[u8]) -> bool {
+    !code.starts_with(b">")
+        && !code.starts_with(b"->")
+        && !code.ends_with(b"<!-")
+        && !code.windows(4).any(|w| w == b"<!--" || w == b"--!>")
+}
+
+pub fn is_comment_kept(cfg: &Cfg, code: &[u8]) -> bool {
+    (cfg.keep_comments || (cfg.keep_conditional_comments && is_conditional_comment(code)))
+        && (!cfg.ensure_no_parse_errors || is_conforming_comment(code))
+}
+
 pub fn minify_comment(cfg: &Cfg, out: &mut Vec<u8>, code: &[u8], ended: bool) {
-    if cfg.keep_comments {
+    if is_comment_kept(cfg, code) {
         out.extend_from_slice(b"<!--");
         out.extend_from_slice(code);
-        if ended {
+        if ended || cfg.ensure_no_parse_errors {
             out.extend_from_slice(b"-->");
         };
     };
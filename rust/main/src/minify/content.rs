@@ -1,4 +1,7 @@
+use std::borrow::Cow;
+
 use aho_corasick::{AhoCorasickBuilder, MatchKind};
+use bumpalo::collections::Vec as BumpVec;
 use lazy_static::lazy_static;
 
 use crate::ast::{NodeData, ScriptOrStyleLang};
@@ -9,26 +12,38 @@ use crate::common::spec::tag::ns::Namespace;
 use crate::common::spec::tag::whitespace::{
     get_whitespace_minification_for_tag, WhitespaceMinification,
 };
-use crate::common::whitespace::{collapse_whitespace, is_all_whitespace, left_trim, right_trim};
-use crate::entity::encode::encode_entities;
-use crate::minify::bang::minify_bang;
-use crate::minify::comment::minify_comment;
+use crate::common::whitespace::{
+    collapse_whitespace, contains_line_break, is_all_whitespace, left_trim,
+    left_trim_to_line_break, right_trim, right_trim_to_line_break,
+};
+use crate::diagnostic::Diagnostic;
+use crate::entity::encode::{encode_entities, encode_entities_conservatively};
+use crate::minify::ascii::{
+    encode_non_ascii_as_refs, escape_non_ascii_in_css, escape_non_ascii_in_js,
+};
+use crate::minify::bang::{is_bang_kept, minify_bang};
+use crate::minify::comment::{is_comment_kept, minify_comment};
 use crate::minify::css::minify_css;
 use crate::minify::doctype::minify_doctype;
 use crate::minify::element::minify_element;
-use crate::minify::instruction::minify_instruction;
+use crate::minify::embedded::EmbeddedResults;
+use crate::minify::instruction::{is_instruction_kept, minify_instruction};
 use crate::minify::js::minify_js;
+use crate::minify::line_breaks::LineBreaks;
+use crate::minify::style_rules::StyleRules;
 
-fn build_chevron_replacer() -> Replacer {
+fn build_chevron_replacer(conservative: bool) -> Replacer {
     let mut patterns = Vec::<Vec<u8>>::new();
     let mut replacements = Vec::<Vec<u8>>::new();
 
     // Replace all `<` with a `&LT` if it's followed by a TAG_NAME_CHAR, `/`, `!`, or `?`.
+    // If conservative, use `&lt;` instead, which is longer but understood by more consumers.
+    let entity: &[u8] = if conservative { b"&lt;" } else { b"&LT" };
     for c in 0u8..128u8 {
         // TODO Create single lookup.
         if TAG_NAME_CHAR[c] || c == b'/' || c == b'!' || c == b'?' {
             patterns.push(vec![b'<', c]);
-            replacements.push(vec![b'&', b'L', b'T', c]);
+            replacements.push([entity, &[c][..]].concat());
         };
     }
 
@@ -42,85 +57,143 @@ fn build_chevron_replacer() -> Replacer {
 }
 
 lazy_static! {
-    static ref CHEVRON_REPLACER: Replacer = build_chevron_replacer();
+    static ref CHEVRON_REPLACER: Replacer = build_chevron_replacer(false);
+    static ref CONSERVATIVE_CHEVRON_REPLACER: Replacer = build_chevron_replacer(true);
+    static ref STRICT_CHEVRON_REPLACER: Replacer = Replacer::new(
+        AhoCorasickBuilder::new()
+            .dfa(true)
+            .build(vec![b"<".to_vec()]),
+        vec![b"&lt;".to_vec()],
+    );
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn minify_content(
     cfg: &Cfg,
     out: &mut Vec<u8>,
+    style_rules: &StyleRules,
+    line_breaks: &mut LineBreaks,
+    diagnostics: &mut Vec<Diagnostic>,
+    embedded: &EmbeddedResults,
     ns: Namespace,
     descendant_of_pre: bool,
     // Use empty slice if none.
     parent: &[u8],
-    mut nodes: Vec<NodeData>,
+    // Overrides the whitespace minification for `parent`, if provided.
+    whitespace_override: Option<&'static WhitespaceMinification>,
+    mut nodes: BumpVec<NodeData>,
 ) {
     let &WhitespaceMinification {
         collapse,
         destroy_whole,
         trim,
-    } = get_whitespace_minification_for_tag(ns, parent, descendant_of_pre);
+    } = match (
+        whitespace_override,
+        cfg.whitespace_rules.get_minification(parent),
+    ) {
+        (Some(min), _) => min,
+        (None, Some(min)) if ns == Namespace::Html && !descendant_of_pre => min,
+        _ => get_whitespace_minification_for_tag(ns, parent, descendant_of_pre),
+    };
 
-    // TODO Document or fix: even though bangs/comments/etc. don't affect layout, we don't collapse/destroy-whole/trim combined text nodes across bangs/comments/etc., as that's too complex and is ambiguous about which nodes should whitespace be deleted from.
-    let mut found_first_text_or_elem = false;
-    let mut index_of_last_nonempty_text_or_elem: isize = -1;
-    let mut index_of_last_text_or_elem: isize = -1;
-    for i in 0..nodes.len() {
-        let (previous_nodes, next_nodes) = nodes.split_at_mut(i);
-        let n = &mut next_nodes[0];
-        match n {
-            NodeData::Element { name, .. } => {
-                if index_of_last_nonempty_text_or_elem > -1 {
-                    if let NodeData::Element {
-                        next_sibling_element_name,
-                        ..
-                    } = &mut previous_nodes[index_of_last_nonempty_text_or_elem as usize]
-                    {
-                        debug_assert!(next_sibling_element_name.is_empty());
-                        next_sibling_element_name.extend_from_slice(name);
+    // Remove bangs/comments/etc. that won't be written first, and join the text around them, so that it's minified as one text node. Otherwise, whitespace wouldn't be minified across them, and the end of one text node could form a tag or entity with the start of the next (e.g. `&a<!---->mp`).
+    nodes.retain(|n| match n {
+        NodeData::Bang { .. } => is_bang_kept(cfg),
+        NodeData::Comment { code, .. } => is_comment_kept(cfg, code),
+        NodeData::Instruction { .. } => is_instruction_kept(cfg),
+        _ => true,
+    });
+    let mut i = 1;
+    while i < nodes.len() {
+        match (&nodes[i - 1], &nodes[i]) {
+            (NodeData::Text { .. }, NodeData::Text { .. }) => {
+                if let NodeData::Text { value: next, .. } = nodes.remove(i) {
+                    if let NodeData::Text { value, .. } = &mut nodes[i - 1] {
+                        value.to_mut().extend_from_slice(&next);
                     };
                 };
-                found_first_text_or_elem = true;
-                index_of_last_nonempty_text_or_elem = i as isize;
-                index_of_last_text_or_elem = i as isize;
             }
-            NodeData::Text { value } => {
+            _ => i += 1,
+        };
+    }
+
+    // TODO Document or fix: even though kept bangs/comments/etc. don't affect layout, we don't collapse/destroy-whole combined text nodes across them, as that's too complex and is ambiguous about which nodes should whitespace be deleted from.
+    let mut found_first_text_or_elem = false;
+    for n in nodes.iter_mut() {
+        match n {
+            NodeData::Element { .. } => found_first_text_or_elem = true,
+            NodeData::Text { value, .. } => {
                 if !found_first_text_or_elem {
                     // This is the first element or text node, and it's a text node.
-                    found_first_text_or_elem = true;
                     if trim {
-                        left_trim(value);
+                        if cfg.preserve_line_breaks {
+                            left_trim_to_line_break(value);
+                        } else {
+                            left_trim(value);
+                        };
                     };
+                    // If it's now empty, the next text node (e.g. after a comment) is the first.
+                    found_first_text_or_elem = !value.is_empty();
                 };
                 // Our parser is guaranteed to output contiguous text as a single node,
                 // so the adjacent nodes to a text node (not counting comments/bangs/etc.) should be elements.
                 // TODO debug_assert this and add tests.
                 if destroy_whole && is_all_whitespace(value) {
-                    value.clear();
+                    let keep_line_break = cfg.preserve_line_breaks && contains_line_break(value);
+                    *value = Cow::Borrowed(if keep_line_break { b"\n" } else { b"" });
                 } else if collapse {
-                    collapse_whitespace(value);
-                };
-                // Set AFTER processing.
-                index_of_last_text_or_elem = i as isize;
-                if !value.is_empty() {
-                    index_of_last_nonempty_text_or_elem = i as isize;
+                    collapse_whitespace(value, cfg.preserve_line_breaks);
                 };
             }
             _ => {}
         };
     }
-    if trim && index_of_last_text_or_elem > -1 {
-        if let NodeData::Text { value } =
-            nodes.get_mut(index_of_last_text_or_elem as usize).unwrap()
-        {
-            right_trim(value);
+    if trim {
+        // Trim the last text node, and if it's now empty, the one before it (e.g. before a comment), and so on.
+        for n in nodes.iter_mut().rev() {
+            match n {
+                NodeData::Element { .. } => break,
+                NodeData::Text { value, .. } => {
+                    if cfg.preserve_line_breaks {
+                        right_trim_to_line_break(value);
+                    } else {
+                        right_trim(value);
+                    };
+                    if !value.is_empty() {
+                        break;
+                    };
+                }
+                _ => {}
+            };
+        }
+    };
+
+    // Whether an element's closing tag can be omitted depends on the next node that's written after it, so find them now that text has been minified.
+    let mut index_of_last_nonempty_node: isize = -1;
+    for i in 0..nodes.len() {
+        let (previous_nodes, next_nodes) = nodes.split_at_mut(i);
+        match &next_nodes[0] {
+            NodeData::Text { value, .. } if value.is_empty() => continue,
+            NodeData::Element { name, .. } if index_of_last_nonempty_node > -1 => {
+                if let NodeData::Element {
+                    next_sibling_element_name,
+                    ..
+                } = &mut previous_nodes[index_of_last_nonempty_node as usize]
+                {
+                    debug_assert!(next_sibling_element_name.is_empty());
+                    *next_sibling_element_name = name.clone();
+                };
+            }
+            _ => {}
         };
+        index_of_last_nonempty_node = i as isize;
     }
 
     for (i, c) in nodes.into_iter().enumerate() {
         match c {
-            NodeData::Bang { code, ended } => minify_bang(cfg, out, &code, ended),
-            NodeData::Comment { code, ended } => minify_comment(cfg, out, &code, ended),
-            NodeData::Doctype { legacy, ended } => minify_doctype(cfg, out, &legacy, ended),
+            NodeData::Bang { code, ended } => minify_bang(cfg, out, code, ended),
+            NodeData::Comment { code, ended } => minify_comment(cfg, out, code, ended),
+            NodeData::Doctype { legacy, ended } => minify_doctype(cfg, out, legacy, ended),
             NodeData::Element {
                 attributes,
                 children,
@@ -131,24 +204,69 @@ pub fn minify_content(
             } => minify_element(
                 cfg,
                 out,
+                style_rules,
+                line_breaks,
+                diagnostics,
+                embedded,
                 descendant_of_pre,
                 child_ns,
                 parent,
                 &next_sibling_element_name,
-                (i as isize) == index_of_last_nonempty_text_or_elem,
+                (i as isize) == index_of_last_nonempty_node,
                 &name,
                 attributes,
                 closing_tag,
                 children,
             ),
-            NodeData::Instruction { code, ended } => minify_instruction(cfg, out, &code, ended),
-            NodeData::ScriptOrStyleContent { code, lang } => match lang {
-                ScriptOrStyleLang::CSS => minify_css(cfg, out, &code),
-                ScriptOrStyleLang::Data => out.extend_from_slice(&code),
-                ScriptOrStyleLang::JS => minify_js(cfg, out, &code),
-            },
-            NodeData::Text { value } => out
-                .extend_from_slice(&CHEVRON_REPLACER.replace_all(&encode_entities(&value, false))),
+            NodeData::Instruction { code, ended } => minify_instruction(cfg, out, code, ended),
+            NodeData::ScriptOrStyleContent { code, lang } => {
+                let start = out.len();
+                match lang {
+                    ScriptOrStyleLang::CSS => minify_css(cfg, out, diagnostics, embedded, code),
+                    ScriptOrStyleLang::Data => out.extend_from_slice(code),
+                    ScriptOrStyleLang::JS => minify_js(cfg, out, diagnostics, embedded, code),
+                };
+                // Character references aren't decoded here, so characters that can't be output must be escaped.
+                if (cfg.ascii_only || embedded.output_encoding.is_some())
+                    && lang != ScriptOrStyleLang::Data
+                {
+                    let keep = |c: char| {
+                        !cfg.ascii_only
+                            && embedded
+                                .output_encoding
+                                .filter(|e| !e.can_encode(c))
+                                .is_none()
+                    };
+                    let escaped = if lang == ScriptOrStyleLang::CSS {
+                        escape_non_ascii_in_css(&out[start..], keep)
+                    } else {
+                        escape_non_ascii_in_js(&out[start..], keep)
+                    };
+                    out.truncate(start);
+                    out.extend_from_slice(&escaped);
+                };
+            }
+            NodeData::Text { value, .. } => {
+                let start = out.len();
+                let conservative = cfg.conservative_entities || cfg.ensure_no_parse_errors;
+                let encoded = if cfg.ensure_no_parse_errors {
+                    // A `<` that isn't followed by a tag name character or `/!?` is still a parse error.
+                    STRICT_CHEVRON_REPLACER.replace_all(&encode_entities_conservatively(&value))
+                } else if conservative {
+                    CONSERVATIVE_CHEVRON_REPLACER
+                        .replace_all(&encode_entities_conservatively(&value))
+                } else {
+                    CHEVRON_REPLACER.replace_all(&encode_entities(&value, false))
+                };
+                if cfg.ascii_only {
+                    out.extend_from_slice(&encode_non_ascii_as_refs(&encoded, conservative));
+                } else {
+                    out.extend_from_slice(&encoded);
+                };
+                if collapse {
+                    line_breaks.replaceable_spaces(out, start);
+                };
+            }
         };
     }
 }
@@ -1,18 +1,29 @@
-use std::str::from_utf8_unchecked;
+use std::str::from_utf8;
 
 use crate::cfg::Cfg;
 use crate::common::whitespace::trimmed;
-use css_minify::optimizations::{Level, Minifier};
+use crate::diagnostic::{Diagnostic, EmbeddedLanguage};
+use crate::minify::embedded::{minify_embedded_code, EmbeddedResults};
 
-pub fn minify_css(cfg: &Cfg, out: &mut Vec<u8>, code: &[u8]) {
-    if cfg.minify_css {
-        let result = Minifier::default().minify(unsafe { from_utf8_unchecked(code) }, Level::Three);
-        // TODO Collect error as warning.
-        if let Ok(min) = result {
-            if min.len() < code.len() {
-                out.extend_from_slice(min.as_bytes());
-                return;
-            };
+pub fn minify_css(
+    cfg: &Cfg,
+    out: &mut Vec<u8>,
+    diagnostics: &mut Vec<Diagnostic>,
+    embedded: &EmbeddedResults,
+    code: &[u8],
+) {
+    // The code may not be valid UTF-8 if the input's encoding isn't supported, in which case it's left as is.
+    if cfg.minify_css && from_utf8(code).is_ok() {
+        let result = minify_embedded_code(
+            diagnostics,
+            embedded,
+            EmbeddedLanguage::Css,
+            cfg.max_embedded_minification_time,
+            code,
+        );
+        if let Some(min) = result.filter(|min| min.len() < code.len()) {
+            out.extend_from_slice(&min);
+            return;
         };
     }
     out.extend_from_slice(trimmed(code));
@@ -2,7 +2,7 @@ use crate::cfg::Cfg;
 
 pub fn minify_doctype(cfg: &Cfg, out: &mut Vec<u8>, legacy: &[u8], ended: bool) {
     out.extend_from_slice(b"<!doctype");
-    if cfg.do_not_minify_doctype {
+    if cfg.do_not_minify_doctype || cfg.ensure_no_parse_errors {
         out.push(b' ');
     }
     out.extend_from_slice(b"html");
@@ -10,7 +10,7 @@ pub fn minify_doctype(cfg: &Cfg, out: &mut Vec<u8>, legacy: &[u8], ended: bool)
         out.push(b' ');
         out.extend_from_slice(legacy);
     };
-    if ended {
+    if ended || cfg.ensure_no_parse_errors {
         out.extend_from_slice(b">");
     };
 }
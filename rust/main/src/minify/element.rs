@@ -1,40 +1,77 @@
-use std::collections::HashMap;
+use bumpalo::collections::Vec as BumpVec;
 
-use crate::ast::{AttrVal, ElementClosingTag, NodeData};
+use crate::ast::{Attributes, ElementClosingTag, NodeData};
 use crate::cfg::Cfg;
 use crate::common::spec::tag::ns::Namespace;
 use crate::common::spec::tag::omission::{can_omit_as_before, can_omit_as_last_node};
+use crate::diagnostic::Diagnostic;
 use crate::minify::attr::{minify_attr, AttrMinified};
 use crate::minify::content::minify_content;
+use crate::minify::embedded::EmbeddedResults;
+use crate::minify::line_breaks::LineBreaks;
+use crate::minify::style_rules::{ElementWhitespace, StyleRules};
 
+// Attribute names that start with `=` or contain `"`, `'`, or `<` are parse errors.
+fn is_conforming_attr_name(name: &[u8]) -> bool {
+    !name.starts_with(b"=") && !name.iter().any(|&c| c == b'"' || c == b'\'' || c == b'<')
+}
+
+// True if the element is `<meta>` and has an attribute `name` equal to `viewport`.
+pub fn is_meta_viewport(tag_name: &[u8], attributes: &Attributes) -> bool {
+    tag_name == b"meta"
+        && attributes
+            .get(b"name".as_ref())
+            .filter(|a| a.value.eq_ignore_ascii_case(b"viewport"))
+            .is_some()
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn minify_element(
     cfg: &Cfg,
     out: &mut Vec<u8>,
+    style_rules: &StyleRules,
+    line_breaks: &mut LineBreaks,
+    diagnostics: &mut Vec<Diagnostic>,
+    embedded: &EmbeddedResults,
     descendant_of_pre: bool,
     ns: Namespace,
     // Use an empty slice if none.
     parent: &[u8],
     // Use an empty slice if the next element or text sibling node is not an element.
     next_sibling_as_element_tag_name: &[u8],
-    // If the last node of the parent is an element and it's this one.
-    is_last_child_text_or_element_node: bool,
+    // Whether this is the last node of the parent that's written, not counting empty text.
+    is_last_nonempty_child: bool,
     tag_name: &[u8],
-    attributes: HashMap<Vec<u8>, AttrVal>,
+    attributes: Attributes,
     closing_tag: ElementClosingTag,
-    children: Vec<NodeData>,
+    children: BumpVec<NodeData>,
 ) {
     // Output quoted attributes, followed by unquoted, to optimise space omission between attributes.
     let mut quoted = Vec::new();
     let mut unquoted = Vec::new();
 
-    let is_meta_viewport = tag_name == b"meta"
-        && attributes
-            .get(b"name".as_ref())
-            .filter(|a| a.value.eq_ignore_ascii_case(b"viewport"))
-            .is_some();
+    let is_meta_viewport = is_meta_viewport(tag_name, &attributes);
+
+    // This must be done before attributes are minified, as `class` and `style` values are needed.
+    let ElementWhitespace {
+        content: whitespace_override,
+        preserve: preserve_whitespace,
+    } = style_rules.element_whitespace(ns, tag_name, &attributes, descendant_of_pre);
 
     for (name, value) in attributes {
-        match minify_attr(cfg, ns, tag_name, is_meta_viewport, &name, value.value) {
+        if cfg.ensure_no_parse_errors && !is_conforming_attr_name(&name) {
+            continue;
+        };
+        match minify_attr(
+            cfg,
+            ns,
+            tag_name,
+            is_meta_viewport,
+            &name,
+            value.value,
+            diagnostics,
+            embedded,
+        ) {
             AttrMinified::Redundant => {}
             a @ AttrMinified::NoValue => unquoted.push((name, a)),
             AttrMinified::Value(v) => {
@@ -53,22 +90,32 @@ pub fn minify_element(
     unquoted.sort_unstable_by(|a, b| a.0.cmp(&b.0));
 
     // Attributes list could become empty after minification, so check opening tag omission eligibility after attributes minification.
-    let can_omit_opening_tag = (tag_name == b"html" || tag_name == b"head")
-        && quoted.len() + unquoted.len() == 0
-        && !cfg.keep_html_and_head_opening_tags;
+    // Browsers treat a tag whose name doesn't start with an ASCII letter as text, so it can't be written without a parse error.
+    let is_nonconforming_tag = cfg.ensure_no_parse_errors
+        && tag_name
+            .first()
+            .filter(|c| c.is_ascii_alphabetic())
+            .is_none();
+    let can_omit_opening_tag = is_nonconforming_tag
+        || ((tag_name == b"html" || tag_name == b"head")
+            && quoted.len() + unquoted.len() == 0
+            && !cfg.keep_html_and_head_opening_tags);
     let can_omit_closing_tag = !cfg.keep_closing_tags
         && (can_omit_as_before(tag_name, next_sibling_as_element_tag_name)
-            || (is_last_child_text_or_element_node && can_omit_as_last_node(parent, tag_name)));
+            || (is_last_nonempty_child && can_omit_as_last_node(parent, tag_name)));
 
     if !can_omit_opening_tag {
         out.push(b'<');
         out.extend_from_slice(tag_name);
 
         for (i, (name, value)) in quoted.iter().enumerate() {
-            if i == 0 || cfg.keep_spaces_between_attributes {
+            if i == 0 || cfg.keep_spaces_between_attributes || cfg.ensure_no_parse_errors {
+                line_breaks.replaceable(out.len());
                 out.push(b' ');
+            } else {
+                line_breaks.insertable(out.len());
             };
-            out.extend_from_slice(&name);
+            out.extend_from_slice(name);
             out.push(b'=');
             debug_assert!(value.quoted());
             value.out(out);
@@ -77,10 +124,17 @@ pub fn minify_element(
             // Write a space between unquoted attributes,
             // or after the tag name if it wasn't written already during `quoted` processing,
             // or if forced by Cfg.
-            if i > 0 || (i == 0 && quoted.len() == 0) || cfg.keep_spaces_between_attributes {
+            if i > 0
+                || (i == 0 && quoted.is_empty())
+                || cfg.keep_spaces_between_attributes
+                || cfg.ensure_no_parse_errors
+            {
+                line_breaks.replaceable(out.len());
                 out.push(b' ');
+            } else {
+                line_breaks.insertable(out.len());
             };
-            out.extend_from_slice(&name);
+            out.extend_from_slice(name);
             if let AttrMinified::Value(v) = value {
                 out.push(b'=');
                 v.out(out);
@@ -89,7 +143,7 @@ pub fn minify_element(
 
         if closing_tag == ElementClosingTag::SelfClosing {
             // Write a space only if the last attribute is unquoted.
-            if unquoted.len() > 0 {
+            if !unquoted.is_empty() {
                 out.push(b' ');
             };
             out.push(b'/');
@@ -105,17 +159,27 @@ pub fn minify_element(
     minify_content(
         cfg,
         out,
+        style_rules,
+        line_breaks,
+        diagnostics,
+        embedded,
         if tag_name == b"svg" {
             Namespace::Svg
         } else {
             ns
         },
-        descendant_of_pre || (ns == Namespace::Html && tag_name == b"pre"),
+        preserve_whitespace.unwrap_or(
+            descendant_of_pre
+                || (ns == Namespace::Html
+                    && (tag_name == b"pre" || cfg.whitespace_rules.is_pre_like(tag_name))),
+        ),
         tag_name,
+        whitespace_override,
         children,
     );
 
-    if closing_tag != ElementClosingTag::Present || can_omit_closing_tag {
+    // A closing tag without its opening tag would be dropped when parsed, so it's omitted too, even if `keep_closing_tags`.
+    if closing_tag != ElementClosingTag::Present || can_omit_closing_tag || can_omit_opening_tag {
         return;
     };
     out.extend_from_slice(b"</");
@@ -0,0 +1,268 @@
+use std::collections::HashMap;
+#[cfg(feature = "rayon")]
+use std::collections::HashSet;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::channel;
+use std::thread;
+use std::time::Duration;
+
+use css_minify::optimizations::{Level, Minifier};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+#[cfg(feature = "rayon")]
+use crate::ast::{NodeData, ScriptOrStyleLang};
+#[cfg(feature = "rayon")]
+use crate::cfg::Cfg;
+use crate::common::panic::panic_message;
+use crate::diagnostic::{Diagnostic, EmbeddedLanguage};
+use crate::encoding::Encoding;
+#[cfg(feature = "rayon")]
+use crate::minify::attr::style_attr_css_source;
+#[cfg(feature = "rayon")]
+use crate::minify::element::is_meta_viewport;
+
+#[derive(Clone, Debug)]
+pub enum Failure {
+    Error(String),
+    Panicked(String),
+    TimedOut,
+    Busy,
+}
+
+// The maximum number of threads running minifiers with a timeout at once, including those that timed out but haven't finished yet.
+const MAX_TIMED_WORKERS: usize = 64;
+
+static TIMED_WORKERS: AtomicUsize = AtomicUsize::new(0);
+
+// Releases a slot in a worker count when dropped, even if the worker panics.
+struct WorkerSlot(&'static AtomicUsize);
+
+impl WorkerSlot {
+    fn acquire(workers: &'static AtomicUsize, max: usize) -> Option<WorkerSlot> {
+        workers
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| {
+                if n < max {
+                    Some(n + 1)
+                } else {
+                    None
+                }
+            })
+            .ok()
+            .map(|_| WorkerSlot(workers))
+    }
+}
+
+impl Drop for WorkerSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+// Runs `f`, catching any panic, and waiting at most `timeout` for it to finish. When there's a timeout, `f` runs on a new thread, which can't be interrupted, so it's left to finish in the background and its result is discarded.
+fn run_isolated<T: Send + 'static>(
+    timeout: Option<Duration>,
+    f: impl FnOnce() -> Result<T, String> + Send + 'static,
+) -> Result<T, Failure> {
+    run_isolated_with_workers(&TIMED_WORKERS, MAX_TIMED_WORKERS, timeout, f)
+}
+
+// Like `run_isolated`, but limits the threads that can be running at once to `max`, counted in `workers`, so that minifiers that never finish can't use up all threads. If there are too many, `f` isn't run.
+pub fn run_isolated_with_workers<T: Send + 'static>(
+    workers: &'static AtomicUsize,
+    max: usize,
+    timeout: Option<Duration>,
+    f: impl FnOnce() -> Result<T, String> + Send + 'static,
+) -> Result<T, Failure> {
+    let run = move || match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(res)) => Ok(res),
+        Ok(Err(error)) => Err(Failure::Error(error)),
+        Err(payload) => Err(Failure::Panicked(panic_message(payload))),
+    };
+    let timeout = match timeout {
+        Some(t) => t,
+        None => return run(),
+    };
+    let slot = match WorkerSlot::acquire(workers, max) {
+        Some(slot) => slot,
+        None => return Err(Failure::Busy),
+    };
+    let (sender, receiver) = channel();
+    thread::spawn(move || {
+        let _slot = slot;
+        // The receiver may have already given up.
+        let _ = sender.send(run());
+    });
+    receiver
+        .recv_timeout(timeout)
+        .unwrap_or(Err(Failure::TimedOut))
+}
+
+// Returns the output of an embedded minifier, or None after recording a diagnostic if it failed, panicked, or timed out.
+fn record<T>(
+    diagnostics: &mut Vec<Diagnostic>,
+    language: EmbeddedLanguage,
+    result: Result<T, Failure>,
+) -> Option<T> {
+    match result {
+        Ok(res) => Some(res),
+        Err(failure) => {
+            diagnostics.push(match failure {
+                Failure::Error(error) => Diagnostic::EmbeddedMinificationFailed { language, error },
+                Failure::Panicked(message) => {
+                    Diagnostic::EmbeddedMinifierPanicked { language, message }
+                }
+                Failure::TimedOut => Diagnostic::EmbeddedMinificationTimedOut { language },
+                Failure::Busy => Diagnostic::EmbeddedMinifierBusy { language },
+            });
+            None
+        }
+    }
+}
+
+// Runs the embedded minifier `f`, returning its output, or None after recording a diagnostic if it failed, panicked, or timed out. The caller should then fall back to the original code.
+pub fn minify_embedded<T: Send + 'static>(
+    diagnostics: &mut Vec<Diagnostic>,
+    language: EmbeddedLanguage,
+    timeout: Option<Duration>,
+    f: impl FnOnce() -> Result<T, String> + Send + 'static,
+) -> Option<T> {
+    record(diagnostics, language, run_isolated(timeout, f))
+}
+
+// Minifies JS, or CSS, which must be valid UTF-8.
+fn run_minifier(language: EmbeddedLanguage, source: Vec<u8>) -> Result<Vec<u8>, String> {
+    match language {
+        EmbeddedLanguage::Css => {
+            let source = String::from_utf8(source).map_err(|err| err.to_string())?;
+            Minifier::default()
+                .minify(&source, Level::Three)
+                .map(String::into_bytes)
+                .map_err(|err| format!("{:?}", err))
+        }
+        EmbeddedLanguage::Js => {
+            let mut output = Vec::new();
+            match minify_js::minify(source, &mut output) {
+                Ok(_) => Ok(output),
+                Err(err) => Err(format!("{:?}", err)),
+            }
+        }
+    }
+}
+
+// Results of minifying embedded code before serialisation, keyed by the exact source given to the minifier. As the minifiers are deterministic, looking up a result gives the same output (and diagnostics) as minifying during serialisation.
+#[derive(Default)]
+pub struct EmbeddedResults {
+    css: HashMap<Vec<u8>, Result<Vec<u8>, Failure>>,
+    js: HashMap<Vec<u8>, Result<Vec<u8>, Failure>>,
+    // The encoding of the output if it isn't UTF-8, so that characters it can't represent are escaped in embedded code instead of written as character references.
+    pub output_encoding: Option<Encoding>,
+}
+
+#[cfg(feature = "rayon")]
+fn collect_sources(
+    cfg: &Cfg,
+    nodes: &[NodeData],
+    sources: &mut HashSet<(EmbeddedLanguage, Vec<u8>)>,
+) {
+    for n in nodes {
+        match n {
+            NodeData::Element {
+                attributes,
+                children,
+                name,
+                namespace,
+                ..
+            } => {
+                if cfg.minify_css {
+                    if let Some(source) = attributes.get(b"style".as_ref()).and_then(|style| {
+                        style_attr_css_source(
+                            cfg,
+                            *namespace,
+                            name,
+                            is_meta_viewport(name, attributes),
+                            &style.value,
+                        )
+                    }) {
+                        sources.insert((EmbeddedLanguage::Css, source));
+                    };
+                };
+                collect_sources(cfg, children, sources);
+            }
+            NodeData::ScriptOrStyleContent {
+                code,
+                lang: ScriptOrStyleLang::CSS,
+            } if cfg.minify_css && std::str::from_utf8(code).is_ok() => {
+                sources.insert((EmbeddedLanguage::Css, code.to_vec()));
+            }
+            NodeData::ScriptOrStyleContent {
+                code,
+                lang: ScriptOrStyleLang::JS,
+            } if cfg.minify_js => {
+                sources.insert((EmbeddedLanguage::Js, code.to_vec()));
+            }
+            _ => {}
+        };
+    }
+}
+
+impl EmbeddedResults {
+    // Minifies all embedded code in `nodes` in parallel. Identical code is only minified once.
+    #[cfg(feature = "rayon")]
+    pub fn compute(cfg: &Cfg, nodes: &[NodeData]) -> EmbeddedResults {
+        let mut sources = HashSet::new();
+        collect_sources(cfg, nodes, &mut sources);
+        let timeout = cfg.max_embedded_minification_time;
+        let computed: Vec<_> = sources
+            .into_par_iter()
+            .map(|(language, source)| {
+                let input = source.clone();
+                let result = run_isolated(timeout, move || run_minifier(language, input));
+                (language, source, result)
+            })
+            .collect();
+        let mut results = EmbeddedResults::default();
+        for (language, source, result) in computed {
+            results.for_language(language).insert(source, result);
+        }
+        results
+    }
+
+    #[cfg(feature = "rayon")]
+    fn for_language(
+        &mut self,
+        language: EmbeddedLanguage,
+    ) -> &mut HashMap<Vec<u8>, Result<Vec<u8>, Failure>> {
+        match language {
+            EmbeddedLanguage::Css => &mut self.css,
+            EmbeddedLanguage::Js => &mut self.js,
+        }
+    }
+
+    fn get(&self, language: EmbeddedLanguage, source: &[u8]) -> Option<&Result<Vec<u8>, Failure>> {
+        match language {
+            EmbeddedLanguage::Css => self.css.get(source),
+            EmbeddedLanguage::Js => self.js.get(source),
+        }
+    }
+}
+
+// Like `minify_embedded`, but minifies `source` using the minifier for `language`, or uses its result from `embedded` if it was already minified.
+pub fn minify_embedded_code(
+    diagnostics: &mut Vec<Diagnostic>,
+    embedded: &EmbeddedResults,
+    language: EmbeddedLanguage,
+    timeout: Option<Duration>,
+    source: &[u8],
+) -> Option<Vec<u8>> {
+    match embedded.get(language, source) {
+        Some(result) => record(diagnostics, language, result.clone()),
+        None => {
+            let source = source.to_vec();
+            minify_embedded(diagnostics, language, timeout, move || {
+                run_minifier(language, source)
+            })
+        }
+    }
+}
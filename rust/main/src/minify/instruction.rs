@@ -1,7 +1,12 @@
 use crate::cfg::Cfg;
 
+pub fn is_instruction_kept(cfg: &Cfg) -> bool {
+    // Processing instructions are always parse errors.
+    !cfg.remove_processing_instructions && !cfg.ensure_no_parse_errors
+}
+
 pub fn minify_instruction(cfg: &Cfg, out: &mut Vec<u8>, code: &[u8], ended: bool) {
-    if !cfg.remove_processing_instructions {
+    if is_instruction_kept(cfg) {
         out.extend_from_slice(b"<?");
         out.extend_from_slice(code);
         if ended {
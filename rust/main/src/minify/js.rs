@@ -1,15 +1,34 @@
 use crate::cfg::Cfg;
+use crate::common::spec::script::escape_script_end_tags;
 use crate::common::whitespace::trimmed;
-use minify_js::minify as minifier;
+use crate::diagnostic::{Diagnostic, EmbeddedLanguage};
+use crate::minify::embedded::{minify_embedded_code, EmbeddedResults};
 
-pub fn minify_js(cfg: &Cfg, out: &mut Vec<u8>, code: &[u8]) {
+pub fn minify_js(
+    cfg: &Cfg,
+    out: &mut Vec<u8>,
+    diagnostics: &mut Vec<Diagnostic>,
+    embedded: &EmbeddedResults,
+    code: &[u8],
+) {
     if cfg.minify_js {
-        let source = code.to_vec();
         // TODO Write to `out` directly, but only if we can guarantee that the length will never exceed the input.
-        let mut output = Vec::new();
-        let result = minifier(source, &mut output);
-        // TODO Collect error as warning.
-        if !result.is_err() && output.len() < code.len() {
+        let result = minify_embedded_code(
+            diagnostics,
+            embedded,
+            EmbeddedLanguage::Js,
+            cfg.max_embedded_minification_time,
+            code,
+        );
+        let escaped = result.and_then(|output| {
+            let escaped = escape_script_end_tags(output);
+            if escaped.is_none() {
+                diagnostics.push(Diagnostic::MinifiedJsNotEscapable);
+            };
+            escaped
+        });
+        // Check the length after escaping, as escaping can make the output longer.
+        if let Some(output) = escaped.filter(|output| output.len() < code.len()) {
             out.extend_from_slice(output.as_slice());
             return;
         };
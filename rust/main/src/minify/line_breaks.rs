@@ -0,0 +1,124 @@
+use memchr::memchr_iter;
+
+use crate::diagnostic::Diagnostic;
+
+#[derive(Copy, Clone)]
+enum BreakPoint {
+    // The space at this position can be replaced with a line break.
+    Replace(usize),
+    // A line break can be inserted before this position.
+    Insert(usize),
+}
+
+impl BreakPoint {
+    fn pos(self) -> usize {
+        match self {
+            BreakPoint::Replace(pos) | BreakPoint::Insert(pos) => pos,
+        }
+    }
+
+    fn next_line_start(self) -> usize {
+        match self {
+            BreakPoint::Replace(pos) => pos + 1,
+            BreakPoint::Insert(pos) => pos,
+        }
+    }
+}
+
+/// Positions in the output where a line break can be placed without changing the semantics of the document, used to enforce `Cfg::max_line_length`.
+///
+/// Positions must be recorded in ascending order, which is naturally the case as the output is only ever appended to.
+pub struct LineBreaks {
+    enabled: bool,
+    points: Vec<BreakPoint>,
+}
+
+impl LineBreaks {
+    pub fn new(enabled: bool) -> LineBreaks {
+        LineBreaks {
+            enabled,
+            points: Vec::new(),
+        }
+    }
+
+    // `pos` must be the position of a space that can be any whitespace, such as the space between attributes.
+    pub fn replaceable(&mut self, pos: usize) {
+        if self.enabled {
+            self.points.push(BreakPoint::Replace(pos));
+        };
+    }
+
+    // `pos` must be a position where whitespace can be added, such as between a quoted attribute value and the next attribute.
+    pub fn insertable(&mut self, pos: usize) {
+        if self.enabled {
+            self.points.push(BreakPoint::Insert(pos));
+        };
+    }
+
+    // Marks all spaces in `out[start..]` as replaceable. Only use for text whose whitespace is collapsible.
+    pub fn replaceable_spaces(&mut self, out: &[u8], start: usize) {
+        if self.enabled {
+            self.points
+                .extend(memchr_iter(b' ', &out[start..]).map(|i| BreakPoint::Replace(start + i)));
+        };
+    }
+
+    // Breaks lines longer than `max` bytes at the latest break point that keeps the line within the limit, and returns a diagnostic for every line that remains too long.
+    pub fn apply(self, out: Vec<u8>, max: usize) -> (Vec<u8>, Vec<Diagnostic>) {
+        // Existing line breaks and the end of the output are represented as `None`, and are ordered before break points at the same position.
+        let mut events: Vec<(usize, Option<BreakPoint>)> = self
+            .points
+            .into_iter()
+            .map(|p| (p.pos(), Some(p)))
+            .chain(memchr_iter(b'\n', &out).map(|pos| (pos, None)))
+            .chain(std::iter::once((out.len(), None)))
+            .collect();
+        events.sort_by_key(|(pos, point)| (*pos, point.is_some()));
+
+        let mut chosen = Vec::new();
+        let mut line_start = 0;
+        let mut candidate: Option<BreakPoint> = None;
+        for (pos, point) in events {
+            // `candidate` is always within the limit, as it was checked when it was the current event.
+            while pos.saturating_sub(line_start) > max {
+                match candidate.take() {
+                    Some(c) => {
+                        line_start = c.next_line_start();
+                        chosen.push(c);
+                    }
+                    None => break,
+                };
+            }
+            match point {
+                // Don't create empty lines.
+                Some(p @ BreakPoint::Insert(p_pos)) if p_pos > line_start => candidate = Some(p),
+                Some(p @ BreakPoint::Replace(p_pos)) if p_pos >= line_start => candidate = Some(p),
+                Some(_) => {}
+                None => {
+                    line_start = pos + 1;
+                    candidate = None;
+                }
+            };
+        }
+
+        let mut result = Vec::with_capacity(out.len() + chosen.len());
+        let mut last = 0;
+        for c in chosen {
+            result.extend_from_slice(&out[last..c.pos()]);
+            result.push(b'\n');
+            last = c.next_line_start();
+        }
+        result.extend_from_slice(&out[last..]);
+
+        let diagnostics = result
+            .split(|&c| c == b'\n')
+            .enumerate()
+            .filter(|(_, line)| line.len() > max)
+            .map(|(i, line)| Diagnostic::LineTooLong {
+                line: i + 1,
+                length: line.len(),
+            })
+            .collect();
+        (result, diagnostics)
+    }
+}
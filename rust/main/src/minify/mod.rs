@@ -1,3 +1,4 @@
+pub mod ascii;
 pub mod attr;
 pub mod bang;
 pub mod comment;
@@ -5,7 +6,10 @@ pub mod content;
 pub mod css;
 pub mod doctype;
 pub mod element;
+pub mod embedded;
 pub mod instruction;
 pub mod js;
+pub mod line_breaks;
+pub mod style_rules;
 #[cfg(test)]
 mod tests;
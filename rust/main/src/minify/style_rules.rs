@@ -0,0 +1,596 @@
+use std::collections::HashMap;
+
+use crate::ast::{Attributes, NodeData, ScriptOrStyleLang};
+use crate::common::gen::codepoints::{HEX_DIGIT, WHITESPACE};
+use crate::common::spec::tag::ns::Namespace;
+use crate::common::spec::tag::whitespace::{
+    get_whitespace_minification_for_tag, WhitespaceMinification, CONTENT, CONTENT_FIRST,
+    DEFAULT_HTML, FORMATTING, LAYOUT, WHITESPACE_SENSITIVE,
+};
+use crate::common::whitespace::trimmed;
+
+// This is deliberately not a CSS engine. Only rules whose selector is a single tag, class, or ID (optionally qualified by a tag) are understood precisely.
+// Any other rule that declares a relevant property makes the matched elements "unsure", in which case the default tag-based whitespace minification is used.
+// Stylesheets not in the document (e.g. `<link>`, `@import`) cannot be seen, so this is only safe if the document's own styles are the ones that matter.
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+enum Prop {
+    Display,
+    WhiteSpace,
+}
+
+#[derive(Clone, Eq, PartialEq, Hash)]
+enum Key {
+    Class(Vec<u8>),
+    Id(Vec<u8>),
+    Tag(Vec<u8>),
+}
+
+#[derive(Clone)]
+enum Value {
+    Known(Vec<u8>),
+    Unsure,
+}
+
+struct Entry {
+    prop: Prop,
+    // For selectors like `pre.code`, which are keyed by class but must also match the tag.
+    tag: Option<Vec<u8>>,
+    // Compared lexicographically: importance, specificity, source order.
+    priority: (bool, (usize, usize, usize), usize),
+    value: Value,
+}
+
+enum Computed {
+    NotDeclared,
+    Unsure,
+    Value(Vec<u8>),
+}
+
+/// How CSS affects the whitespace minification of an element.
+pub struct ElementWhitespace {
+    // Overrides the whitespace minification of the element's content, if known.
+    pub content: Option<&'static WhitespaceMinification>,
+    // Whether the element's content and descendants preserve whitespace, if `white-space` is declared and understood.
+    pub preserve: Option<bool>,
+}
+
+#[derive(Default)]
+pub struct StyleRules {
+    enabled: bool,
+    entries: HashMap<Key, Vec<Entry>>,
+    // Set when a rule declares the property but we can't determine which elements it matches (e.g. `* {}`, `[lang] {}`).
+    unsure_display: bool,
+    unsure_white_space: bool,
+    next_order: usize,
+}
+
+fn is_ident_char(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || c == b'-' || c == b'_' || c >= 0x80
+}
+
+// Returns the index of the first byte in `stops` at `from` or later that is not inside a string, parentheses, or brackets.
+fn find_unnested(css: &[u8], from: usize, stops: &[u8]) -> usize {
+    let mut depth = 0usize;
+    let mut quote: Option<u8> = None;
+    let mut i = from;
+    while i < css.len() {
+        let c = css[i];
+        match quote {
+            Some(q) => {
+                if c == b'\\' {
+                    i += 1;
+                } else if c == q {
+                    quote = None;
+                };
+            }
+            None => match c {
+                b'\\' => i += 1,
+                b'"' | b'\'' => quote = Some(c),
+                b'(' | b'[' => depth += 1,
+                b')' | b']' => depth = depth.saturating_sub(1),
+                c if depth == 0 && stops.contains(&c) => return i,
+                _ => {}
+            },
+        };
+        i += 1;
+    }
+    css.len()
+}
+
+// `pos` should be at an opening parenthesis or bracket. Returns the position after the matching closing one.
+fn skip_nested(css: &[u8], pos: usize) -> usize {
+    debug_assert!(css[pos] == b'(' || css[pos] == b'[');
+    let mut depth = 0usize;
+    let mut quote: Option<u8> = None;
+    let mut i = pos;
+    while i < css.len() {
+        let c = css[i];
+        match quote {
+            Some(q) => {
+                if c == b'\\' {
+                    i += 1;
+                } else if c == q {
+                    quote = None;
+                };
+            }
+            None => match c {
+                b'\\' => i += 1,
+                b'"' | b'\'' => quote = Some(c),
+                b'(' | b'[' => depth += 1,
+                b')' | b']' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return i + 1;
+                    };
+                }
+                _ => {}
+            },
+        };
+        i += 1;
+    }
+    css.len()
+}
+
+// `pos` should be after the opening `{`. Returns the position after the matching `}`.
+fn skip_block(css: &[u8], mut pos: usize) -> usize {
+    let mut depth = 1;
+    while depth > 0 && pos < css.len() {
+        pos = find_unnested(css, pos, b"{}");
+        match css.get(pos) {
+            Some(b'{') => depth += 1,
+            Some(b'}') => depth -= 1,
+            _ => {}
+        };
+        pos += 1;
+    }
+    pos.min(css.len())
+}
+
+fn strip_comments(css: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(css.len());
+    let mut i = 0;
+    while i < css.len() {
+        if css[i..].starts_with(b"/*") {
+            i = match css[i + 2..].windows(2).position(|w| w == b"*/") {
+                Some(p) => i + 2 + p + 2,
+                None => css.len(),
+            };
+            // Comments separate tokens.
+            out.push(b' ');
+        } else {
+            out.push(css[i]);
+            i += 1;
+        };
+    }
+    out
+}
+
+// Returns declarations of relevant properties as (property, value, important).
+fn parse_declarations(body: &[u8]) -> Vec<(Prop, Value, bool)> {
+    let mut decls = Vec::new();
+    let mut start = 0;
+    while start < body.len() {
+        let end = find_unnested(body, start, b";");
+        let decl = &body[start..end];
+        start = end + 1;
+        let colon = match decl.iter().position(|&c| c == b':') {
+            Some(p) => p,
+            None => continue,
+        };
+        let name = trimmed(&decl[..colon]).to_ascii_lowercase();
+        let mut value = trimmed(&decl[colon + 1..]).to_ascii_lowercase();
+        let mut important = false;
+        if let Some(p) = value.iter().rposition(|&c| c == b'!') {
+            if trimmed(&value[p + 1..]) == b"important" {
+                important = true;
+                value.truncate(p);
+                value = trimmed(&value).to_vec();
+            };
+        };
+        let prop = match name.as_slice() {
+            b"display" => Prop::Display,
+            b"white-space" => Prop::WhiteSpace,
+            // Newer longhands of `white-space`.
+            b"white-space-collapse" | b"text-wrap-mode" => {
+                decls.push((Prop::WhiteSpace, Value::Unsure, important));
+                continue;
+            }
+            _ => continue,
+        };
+        let value = if value.is_empty() || !value.iter().all(|&c| is_ident_char(c)) {
+            // Multiple keywords, functions like `var()`, etc.
+            Value::Unsure
+        } else {
+            Value::Known(value)
+        };
+        decls.push((prop, value, important));
+    }
+    decls
+}
+
+struct Compound {
+    tag: Option<Vec<u8>>,
+    classes: Vec<Vec<u8>>,
+    ids: Vec<Vec<u8>>,
+    // Universal selector, attribute selectors, or pseudo-classes.
+    other: bool,
+    pseudo_element: bool,
+    unparseable: bool,
+}
+
+fn parse_ident(sel: &[u8], i: &mut usize, unparseable: &mut bool) -> Vec<u8> {
+    let mut ident = Vec::new();
+    while let Some(&c) = sel.get(*i) {
+        if c == b'\\' {
+            match sel.get(*i + 1) {
+                Some(&e) if !HEX_DIGIT[e] && e != b'\n' => {
+                    ident.push(e);
+                    *i += 2;
+                }
+                _ => {
+                    *unparseable = true;
+                    *i += 1;
+                }
+            };
+        } else if is_ident_char(c) {
+            ident.push(c);
+            *i += 1;
+        } else {
+            break;
+        };
+    }
+    ident
+}
+
+fn parse_compound(sel: &[u8]) -> Compound {
+    let mut compound = Compound {
+        tag: None,
+        classes: Vec::new(),
+        ids: Vec::new(),
+        other: false,
+        pseudo_element: false,
+        unparseable: false,
+    };
+    let mut i = 0;
+    while i < sel.len() {
+        match sel[i] {
+            b'*' => {
+                compound.other = true;
+                i += 1;
+            }
+            b'.' => {
+                i += 1;
+                let class = parse_ident(sel, &mut i, &mut compound.unparseable);
+                compound.classes.push(class);
+            }
+            b'#' => {
+                i += 1;
+                let id = parse_ident(sel, &mut i, &mut compound.unparseable);
+                compound.ids.push(id);
+            }
+            b'[' => {
+                compound.other = true;
+                i = skip_nested(sel, i);
+            }
+            b':' => {
+                i += 1;
+                let double = sel.get(i) == Some(&b':');
+                if double {
+                    i += 1;
+                };
+                let name = parse_ident(sel, &mut i, &mut compound.unparseable).to_ascii_lowercase();
+                if double
+                    || matches!(
+                        name.as_slice(),
+                        b"before" | b"after" | b"first-line" | b"first-letter"
+                    )
+                {
+                    compound.pseudo_element = true;
+                } else {
+                    compound.other = true;
+                };
+                if sel.get(i) == Some(&b'(') {
+                    i = skip_nested(sel, i);
+                };
+            }
+            c if is_ident_char(c) || c == b'\\' => {
+                let tag = parse_ident(sel, &mut i, &mut compound.unparseable).to_ascii_lowercase();
+                compound.tag = Some(tag);
+            }
+            _ => {
+                compound.unparseable = true;
+                i += 1;
+            }
+        };
+    }
+    compound
+}
+
+impl StyleRules {
+    pub fn from_nodes(nodes: &[NodeData]) -> StyleRules {
+        let mut rules = StyleRules {
+            enabled: true,
+            ..StyleRules::default()
+        };
+        rules.collect(nodes);
+        rules
+    }
+
+    fn collect(&mut self, nodes: &[NodeData]) {
+        for n in nodes {
+            match n {
+                NodeData::Element { children, .. } => self.collect(children),
+                NodeData::ScriptOrStyleContent {
+                    code,
+                    lang: ScriptOrStyleLang::CSS,
+                } => self.add_stylesheet(code),
+                _ => {}
+            };
+        }
+    }
+
+    fn add_stylesheet(&mut self, css: &[u8]) {
+        let css = strip_comments(css);
+        self.parse_rules(&css, 0, false);
+    }
+
+    fn set_unsure(&mut self, prop: Prop) {
+        match prop {
+            Prop::Display => self.unsure_display = true,
+            Prop::WhiteSpace => self.unsure_white_space = true,
+        };
+    }
+
+    // Parses rules until the end of `css` or an unmatched `}`, and returns the position after it.
+    fn parse_rules(&mut self, css: &[u8], mut pos: usize, conditional: bool) -> usize {
+        loop {
+            while css.get(pos).filter(|&&c| WHITESPACE[c]).is_some() {
+                pos += 1;
+            }
+            match css.get(pos) {
+                None => return pos,
+                Some(b'}') => return pos + 1,
+                Some(b'@') => {
+                    let end = find_unnested(css, pos, b"{;}");
+                    let prelude = &css[pos + 1..end];
+                    let name_len = prelude
+                        .iter()
+                        .position(|&c| !is_ident_char(c))
+                        .unwrap_or(prelude.len());
+                    let name = prelude[..name_len].to_ascii_lowercase();
+                    match css.get(end) {
+                        Some(b'{') => {
+                            pos = match name.as_slice() {
+                                // These contain rules that may or may not apply.
+                                b"media" | b"supports" | b"container" | b"layer" | b"document"
+                                | b"scope" => self.parse_rules(css, end + 1, true),
+                                _ => skip_block(css, end + 1),
+                            };
+                        }
+                        Some(b';') => pos = end + 1,
+                        _ => pos = end,
+                    };
+                }
+                Some(_) => {
+                    let end = find_unnested(css, pos, b"{}");
+                    if css.get(end) != Some(&b'{') {
+                        pos = end;
+                        continue;
+                    };
+                    let body_end = find_unnested(css, end + 1, b"{}");
+                    if css.get(body_end) == Some(&b'{') {
+                        // Nested rules, which we don't understand.
+                        self.add_rule(&css[pos..end], &css[end + 1..body_end], true);
+                        pos = skip_block(css, end + 1);
+                    } else {
+                        self.add_rule(&css[pos..end], &css[end + 1..body_end], conditional);
+                        pos = (body_end + 1).min(css.len());
+                    };
+                }
+            };
+        }
+    }
+
+    fn add_rule(&mut self, prelude: &[u8], body: &[u8], conditional: bool) {
+        let decls = parse_declarations(body);
+        if decls.is_empty() {
+            return;
+        };
+        let order = self.next_order;
+        self.next_order += 1;
+        let mut start = 0;
+        while start < prelude.len() {
+            let end = find_unnested(prelude, start, b",");
+            let selector = trimmed(&prelude[start..end]);
+            start = end + 1;
+
+            // Find the subject (last compound selector).
+            let mut compound_start = 0;
+            let mut complex = false;
+            let mut i = 0;
+            while i < selector.len() {
+                let next = find_unnested(selector, i, b" \t\n\r\x0c>+~");
+                if next < selector.len() {
+                    complex = true;
+                    compound_start = next + 1;
+                };
+                i = next + 1;
+            }
+            let subject = parse_compound(&selector[compound_start.min(selector.len())..]);
+            if subject.pseudo_element {
+                // Only affects generated content.
+                continue;
+            };
+
+            let key = if subject.unparseable {
+                None
+            } else if let Some(id) = subject.ids.first() {
+                Some(Key::Id(id.clone()))
+            } else if let Some(class) = subject.classes.first() {
+                Some(Key::Class(class.clone()))
+            } else {
+                subject.tag.clone().map(Key::Tag)
+            };
+            let precise = !conditional
+                && !complex
+                && !subject.other
+                && subject.ids.len() + subject.classes.len() <= 1;
+            let specificity = (
+                subject.ids.len(),
+                subject.classes.len(),
+                subject.tag.is_some() as usize,
+            );
+
+            for (prop, value, important) in decls.iter() {
+                match &key {
+                    None => self.set_unsure(*prop),
+                    Some(key) => self.entries.entry(key.clone()).or_default().push(Entry {
+                        prop: *prop,
+                        tag: match key {
+                            Key::Tag(_) => None,
+                            _ => subject.tag.clone(),
+                        },
+                        priority: (*important, specificity, order),
+                        value: if precise {
+                            value.clone()
+                        } else {
+                            Value::Unsure
+                        },
+                    }),
+                };
+            }
+        }
+    }
+
+    fn compute(
+        &self,
+        prop: Prop,
+        tag: &[u8],
+        attributes: &Attributes,
+        inline: &[(Prop, Value, bool)],
+    ) -> Computed {
+        if match prop {
+            Prop::Display => self.unsure_display,
+            Prop::WhiteSpace => self.unsure_white_space,
+        } {
+            return Computed::Unsure;
+        };
+
+        let mut keys = vec![Key::Tag(tag.to_vec())];
+        if let Some(id) = attributes.get(b"id".as_ref()) {
+            keys.push(Key::Id(id.value.to_vec()));
+        };
+        if let Some(class) = attributes.get(b"class".as_ref()) {
+            for c in class
+                .value
+                .split(|&c| WHITESPACE[c])
+                .filter(|c| !c.is_empty())
+            {
+                keys.push(Key::Class(c.to_vec()));
+            }
+        };
+
+        let mut best: Option<&Entry> = None;
+        for key in keys.iter() {
+            for e in self.entries.get(key).into_iter().flatten() {
+                if e.prop != prop || e.tag.as_ref().filter(|t| t.as_slice() != tag).is_some() {
+                    continue;
+                };
+                if let Value::Unsure = e.value {
+                    return Computed::Unsure;
+                };
+                if best.filter(|b| b.priority >= e.priority).is_none() {
+                    best = Some(e);
+                };
+            }
+        }
+
+        // Inline declarations beat all non-important declarations; later inline declarations win.
+        let inline = inline.iter().rev().find(|(p, _, _)| *p == prop);
+        let value = match (inline, best) {
+            (Some((_, v, important)), Some(b)) if *important || !b.priority.0 => v,
+            (Some((_, v, _)), None) => v,
+            (_, Some(b)) => &b.value,
+            (None, None) => return Computed::NotDeclared,
+        };
+        match value {
+            Value::Known(v) => Computed::Value(v.clone()),
+            Value::Unsure => Computed::Unsure,
+        }
+    }
+
+    pub fn element_whitespace(
+        &self,
+        ns: Namespace,
+        tag: &[u8],
+        attributes: &Attributes,
+        descendant_of_pre: bool,
+    ) -> ElementWhitespace {
+        // `textarea` and `title` contents are not rendered as part of the document.
+        if !self.enabled || ns != Namespace::Html || tag == b"textarea" || tag == b"title" {
+            return ElementWhitespace {
+                content: None,
+                preserve: None,
+            };
+        };
+        let inline = attributes
+            .get(b"style".as_ref())
+            .map(|s| parse_declarations(&s.value))
+            .unwrap_or_default();
+
+        let preserve = match self.compute(Prop::WhiteSpace, tag, attributes, &inline) {
+            Computed::Value(v) => match v.as_slice() {
+                b"pre" | b"pre-wrap" | b"pre-line" | b"break-spaces" => Some(true),
+                b"normal" | b"nowrap" => Some(false),
+                _ => None,
+            },
+            // The element may preserve whitespace, so don't risk breaking code samples and the like.
+            Computed::Unsure => Some(true),
+            Computed::NotDeclared => None,
+        };
+        if preserve == Some(true) {
+            return ElementWhitespace {
+                content: Some(WHITESPACE_SENSITIVE),
+                preserve,
+            };
+        };
+        if preserve.is_none() && descendant_of_pre {
+            // Whitespace is preserved by an ancestor.
+            return ElementWhitespace {
+                content: None,
+                preserve,
+            };
+        };
+
+        let content = match self.compute(Prop::Display, tag, attributes, &inline) {
+            Computed::Value(v) => match v.as_slice() {
+                b"block"
+                | b"flex"
+                | b"flow-root"
+                | b"grid"
+                | b"inline-flex"
+                | b"inline-grid"
+                | b"table"
+                | b"table-column-group"
+                | b"table-footer-group"
+                | b"table-header-group"
+                | b"table-row"
+                | b"table-row-group" => Some(LAYOUT),
+                b"list-item" | b"table-cell" => Some(CONTENT_FIRST),
+                b"inline-block" | b"table-caption" => Some(CONTENT),
+                b"inline" => Some(FORMATTING),
+                _ => None,
+            },
+            Computed::NotDeclared | Computed::Unsure => None,
+        };
+        let content = content.or_else(|| {
+            // The tag is whitespace sensitive by default (e.g. `pre`), but CSS says otherwise.
+            Some(DEFAULT_HTML).filter(|_| {
+                preserve == Some(false)
+                    && !get_whitespace_minification_for_tag(Namespace::Html, tag, false).collapse
+            })
+        });
+        ElementWhitespace { content, preserve }
+    }
+}
@@ -0,0 +1,32 @@
+use crate::encoding::Encoding;
+use crate::minify::ascii::{escape_non_ascii_in_css, escape_non_ascii_in_js};
+
+#[test]
+fn test_escape_unencodable_in_js() {
+    let keep = |c| Encoding::Windows1252.can_encode(c);
+    assert_eq!(
+        escape_non_ascii_in_js("s=\"caf\u{e9} \u{20ac}\u{3b1}\"".as_bytes(), keep),
+        "s=\"caf\u{e9} \u{20ac}\\u03b1\"".as_bytes().to_vec()
+    );
+    assert_eq!(
+        escape_non_ascii_in_js("s=\"\u{1f600}\"".as_bytes(), keep),
+        b"s=\"\\ud83d\\ude00\"".to_vec()
+    );
+    assert_eq!(
+        escape_non_ascii_in_js("s=\"\u{e9}\"".as_bytes(), |_| false),
+        b"s=\"\\u00e9\"".to_vec()
+    );
+}
+
+#[test]
+fn test_escape_unencodable_in_css() {
+    let keep = |c| Encoding::Windows1252.can_encode(c);
+    assert_eq!(
+        escape_non_ascii_in_css("a::after{content:\"\u{e9}\u{3b1}b\"}".as_bytes(), keep),
+        "a::after{content:\"\u{e9}\\3b1 b\"}".as_bytes().to_vec()
+    );
+    assert_eq!(
+        escape_non_ascii_in_css("a::after{content:\"\u{3b1}z\"}".as_bytes(), keep),
+        b"a::after{content:\"\\3b1z\"}".to_vec()
+    );
+}
@@ -0,0 +1,79 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::channel;
+use std::thread;
+use std::time::Duration;
+
+use crate::diagnostic::{Diagnostic, EmbeddedLanguage};
+use crate::minify::embedded::{minify_embedded, run_isolated_with_workers, Failure};
+
+#[test]
+fn test_minify_embedded() {
+    let mut diagnostics = Vec::new();
+    assert_eq!(
+        minify_embedded(&mut diagnostics, EmbeddedLanguage::Js, None, || Ok(1)),
+        Some(1)
+    );
+    assert_eq!(diagnostics, vec![]);
+
+    let res: Option<()> = minify_embedded(&mut diagnostics, EmbeddedLanguage::Css, None, || {
+        Err("bad".to_string())
+    });
+    assert_eq!(res, None);
+    let res: Option<()> = minify_embedded(&mut diagnostics, EmbeddedLanguage::Js, None, || {
+        panic!("minifier {}", "bug")
+    });
+    assert_eq!(res, None);
+    let res: Option<()> = minify_embedded(
+        &mut diagnostics,
+        EmbeddedLanguage::Js,
+        Some(Duration::from_secs(60)),
+        || panic!("bug"),
+    );
+    assert_eq!(res, None);
+    assert_eq!(
+        diagnostics,
+        vec![
+            Diagnostic::EmbeddedMinificationFailed {
+                language: EmbeddedLanguage::Css,
+                error: "bad".to_string(),
+            },
+            Diagnostic::EmbeddedMinifierPanicked {
+                language: EmbeddedLanguage::Js,
+                message: "minifier bug".to_string(),
+            },
+            Diagnostic::EmbeddedMinifierPanicked {
+                language: EmbeddedLanguage::Js,
+                message: "bug".to_string(),
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_timed_workers_are_limited() {
+    static WORKERS: AtomicUsize = AtomicUsize::new(0);
+    let timeout = Some(Duration::from_millis(10));
+    // Workers that never finish in time keep their slots until they do.
+    let mut releases = Vec::new();
+    for _ in 0..2 {
+        let (release, released) = channel::<()>();
+        releases.push(release);
+        let res = run_isolated_with_workers(&WORKERS, 2, timeout, move || {
+            let _ = released.recv();
+            Ok(())
+        });
+        assert!(matches!(res, Err(Failure::TimedOut)));
+    }
+    let res = run_isolated_with_workers(&WORKERS, 2, timeout, || Ok(1));
+    assert!(matches!(res, Err(Failure::Busy)));
+    // Work without a timeout runs on the current thread, so isn't limited.
+    let res = run_isolated_with_workers(&WORKERS, 2, None, || Ok(1));
+    assert!(matches!(res, Ok(1)));
+
+    drop(releases);
+    while WORKERS.load(Ordering::Acquire) > 0 {
+        thread::sleep(Duration::from_millis(1));
+    }
+    let res = run_isolated_with_workers(&WORKERS, 2, Some(Duration::from_secs(60)), || Ok(1));
+    assert!(matches!(res, Ok(1)));
+}
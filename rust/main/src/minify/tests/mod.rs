@@ -1 +1,3 @@
+mod ascii;
 mod attr;
+mod embedded;
@@ -2,22 +2,27 @@ use aho_corasick::AhoCorasick;
 use lazy_static::lazy_static;
 
 use crate::ast::NodeData;
+use crate::lint::ProblemKind;
 use crate::parse::Code;
 
 lazy_static! {
-    static ref COMMENT_END: AhoCorasick = AhoCorasick::new(&["-->"]);
+    static ref COMMENT_END: AhoCorasick = AhoCorasick::new(["-->"]);
 }
 
-pub fn parse_comment(code: &mut Code) -> NodeData {
+pub fn parse_comment<'c>(code: &mut Code<'c>) -> NodeData<'c> {
     debug_assert!(code.as_slice().starts_with(b"<!--"));
+    let start = code.take_checkpoint();
     code.shift(4);
     let (len, matched) = match COMMENT_END.find(code.as_slice()) {
         Some(m) => (m.start(), m.end() - m.start()),
         None => (code.rem(), 0),
     };
-    let data = code.copy_and_shift(len);
+    let data = code.slice_and_shift(len);
     // It might be EOF.
     code.shift(matched);
+    if matched == 0 {
+        code.report(start, ProblemKind::UnclosedComment);
+    };
     NodeData::Comment {
         code: data,
         ended: matched > 0,
@@ -1,13 +1,17 @@
+use std::borrow::Cow;
+
 use aho_corasick::{AhoCorasick, AhoCorasickBuilder, MatchKind};
+use bumpalo::collections::Vec as BumpVec;
 use lazy_static::lazy_static;
-use memchr::memrchr;
+use memchr::memchr;
 
-use crate::ast::NodeData;
+use crate::ast::{NodeData, SourceCode};
 use crate::common::gen::codepoints::TAG_NAME_CHAR;
 use crate::common::spec::tag::ns::Namespace;
 use crate::common::spec::tag::omission::{can_omit_as_before, can_omit_as_last_node};
 use crate::common::spec::tag::void::VOID_TAGS;
 use crate::entity::decode::decode_entities;
+use crate::lint::{name_to_string, ProblemKind};
 use crate::parse::bang::parse_bang;
 use crate::parse::comment::parse_comment;
 use crate::parse::content::ContentType::*;
@@ -30,6 +34,12 @@ enum ContentType {
     Text,
 }
 
+// Reports a problem for the tag that is next.
+fn report_tag(code: &mut Code, kind: impl FnOnce(String) -> ProblemKind, name: &[u8]) {
+    let at = code.take_checkpoint();
+    code.report(at, kind(name_to_string(name)));
+}
+
 fn maybe_ignore_html_head_body(
     code: &mut Code,
     typ: ContentType,
@@ -39,6 +49,7 @@ fn maybe_ignore_html_head_body(
     match (typ, name, parent) {
         (OpeningTag, b"html", _) => {
             if code.seen_html_open {
+                report_tag(code, |name| ProblemKind::DuplicateTag { name }, name);
                 IgnoredTag
             } else {
                 code.seen_html_open = true;
@@ -47,6 +58,7 @@ fn maybe_ignore_html_head_body(
         }
         (OpeningTag, b"head", _) => {
             if code.seen_head_open {
+                report_tag(code, |name| ProblemKind::DuplicateTag { name }, name);
                 IgnoredTag
             } else {
                 code.seen_head_open = true;
@@ -55,6 +67,7 @@ fn maybe_ignore_html_head_body(
         }
         (ClosingTag, b"head", _) => {
             if code.seen_head_close {
+                report_tag(code, |name| ProblemKind::UnmatchedClosingTag { name }, name);
                 IgnoredTag
             } else {
                 code.seen_head_close = true;
@@ -67,6 +80,7 @@ fn maybe_ignore_html_head_body(
         }
         (OpeningTag, b"body", _) => {
             if code.seen_body_open {
+                report_tag(code, |name| ProblemKind::DuplicateTag { name }, name);
                 IgnoredTag
             } else {
                 code.seen_body_open = true;
@@ -77,6 +91,16 @@ fn maybe_ignore_html_head_body(
     }
 }
 
+// Whether an upcoming tag implicitly closes the current element. This is the case if it implicitly closes the nearest open element that `closes` returns true for, and every element nested in that one can be omitted as the last node of its parent e.g. `<li><p>(current position)<li>` or `<ul><li><p>(current position)</ul>`.
+fn closes_current(open_elements: &[Cow<[u8]>], closes: impl Fn(&[u8]) -> bool) -> bool {
+    match open_elements.iter().rposition(|n| closes(n)) {
+        Some(i) => open_elements[i..]
+            .windows(2)
+            .all(|w| can_omit_as_last_node(&w[0], &w[1])),
+        None => false,
+    }
+}
+
 fn build_content_type_matcher() -> (AhoCorasick, Vec<ContentType>) {
     let mut patterns = Vec::<Vec<u8>>::new();
     let mut types = Vec::<ContentType>::new();
@@ -120,33 +144,32 @@ lazy_static! {
     static ref CONTENT_TYPE_MATCHER: (AhoCorasick, Vec<ContentType>) = build_content_type_matcher();
 }
 
-pub struct ParsedContent {
-    pub children: Vec<NodeData>,
+pub struct ParsedContent<'c> {
+    pub children: BumpVec<'c, NodeData<'c>>,
     pub closing_tag_omitted: bool,
 }
 
-// Use empty slice for `grandparent` or `parent` if none.
-pub fn parse_content(
-    code: &mut Code,
-    ns: Namespace,
-    grandparent: &[u8],
-    parent: &[u8],
-) -> ParsedContent {
+// Use empty slice for `parent` if none.
+pub fn parse_content<'c>(code: &mut Code<'c>, ns: Namespace, parent: &[u8]) -> ParsedContent<'c> {
     // We assume the closing tag has been omitted until we see one explicitly before EOF (or it has been omitted as per the spec).
     let mut closing_tag_omitted = true;
-    let mut nodes = Vec::<NodeData>::new();
+    let mut nodes = BumpVec::new_in(code.arena);
     loop {
-        let (text_len, mut typ) = match CONTENT_TYPE_MATCHER.0.find(&code.as_slice()) {
+        let (text_len, mut typ) = match CONTENT_TYPE_MATCHER.0.find(code.as_slice()) {
             Some(m) => (m.start(), CONTENT_TYPE_MATCHER.1[m.pattern()]),
             None => (code.rem(), Text),
         };
         // Due to dropped malformed code, it's possible for two or more text nodes to be contiguous. Ensure they always get merged into one.
         // NOTE: Even though bangs/comments/etc. have no effect on layout, they still split text (e.g. `&am<!-- -->p`).
         if text_len > 0 {
-            let text = decode_entities(code.slice_and_shift(text_len), false);
+            let source = code.slice_and_shift(text_len);
+            let text = decode_entities(source, false);
             match nodes.last_mut() {
-                Some(NodeData::Text { value }) => value.extend_from_slice(&text),
-                _ => nodes.push(NodeData::Text { value: text }),
+                Some(NodeData::Text { value, .. }) => value.to_mut().extend_from_slice(&text),
+                _ => nodes.push(NodeData::Text {
+                    value: text,
+                    source: SourceCode(source),
+                }),
             };
         };
         // Check using Parsing.md tag rules.
@@ -154,25 +177,32 @@ pub fn parse_content(
             let name = peek_tag_name(code);
             if typ == OpeningTag {
                 debug_assert!(!name.is_empty());
-                if can_omit_as_before(parent, &name) {
+                if closes_current(&code.open_elements, |n| can_omit_as_before(n, &name)) {
                     // The upcoming opening tag implicitly closes the current element e.g. `<tr><td>(current position)<td>`.
                     typ = OmittedClosingTag;
                 };
             } else {
                 if name.is_empty() {
                     // Malformed code, drop until and including next `>`.
+                    let at = code.take_checkpoint();
+                    code.report(at, ProblemKind::MalformedClosingTag);
                     typ = MalformedLeftChevronSlash;
-                } else if grandparent == name.as_slice()
-                    && can_omit_as_last_node(grandparent, parent)
+                } else if parent != name.as_ref()
+                    && closes_current(&code.open_elements, |n| n == name.as_ref())
                 {
-                    // The upcoming closing tag implicitly closes the current element e.g. `<tr><td>(current position)</tr>`.
-                    // This DOESN'T handle when grandparent doesn't exist (represented by an empty slice). However, in that case it's irrelevant, as it would mean we would be at EOF, and our parser simply auto-closes everything anyway. (Normally we'd have to determine if `<p>Hello` is an error or allowed.)
+                    // The upcoming closing tag is for an ancestor, so it implicitly closes the current element e.g. `<tr><td>(current position)</tr>`.
                     typ = OmittedClosingTag;
-                } else if VOID_TAGS.contains(name.as_slice()) {
+                } else if VOID_TAGS.contains(name.as_ref()) {
                     // Closing tag for void element, drop.
+                    report_tag(code, |name| ProblemKind::VoidClosingTag { name }, &name);
                     typ = IgnoredTag;
-                } else if parent.is_empty() || parent != name.as_slice() {
+                } else if parent.is_empty() || parent != name.as_ref() {
                     // Closing tag mismatch, drop.
+                    report_tag(
+                        code,
+                        |name| ProblemKind::UnmatchedClosingTag { name },
+                        &name,
+                    );
                     typ = IgnoredTag;
                 };
             };
@@ -180,7 +210,19 @@ pub fn parse_content(
         };
         match typ {
             Text => break,
-            OpeningTag => nodes.push(parse_element(code, ns, parent)),
+            OpeningTag => {
+                if code.max_depth.filter(|&max| code.depth >= max).is_some() {
+                    // Stop parsing, as the result won't be used.
+                    let at = code.take_checkpoint();
+                    code.report(at, ProblemKind::TooDeeplyNested { max: code.depth });
+                    code.depth_limit_exceeded = true;
+                    code.shift(code.rem());
+                    break;
+                };
+                code.depth += 1;
+                nodes.push(parse_element(code, ns));
+                code.depth -= 1;
+            }
             ClosingTag => {
                 closing_tag_omitted = false;
                 break;
@@ -189,7 +231,7 @@ pub fn parse_content(
             Bang => nodes.push(parse_bang(code)),
             Comment => nodes.push(parse_comment(code)),
             Doctype => nodes.push(parse_doctype(code)),
-            MalformedLeftChevronSlash => code.shift(match memrchr(b'>', code.as_slice()) {
+            MalformedLeftChevronSlash => code.shift(match memchr(b'>', code.as_slice()) {
                 Some(m) => m + 1,
                 None => code.rem(),
             }),
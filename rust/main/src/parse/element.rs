@@ -1,33 +1,36 @@
-use std::collections::HashMap;
+use std::borrow::Cow;
 
-use crate::ast::{AttrVal, ElementClosingTag, NodeData, ScriptOrStyleLang};
+use bumpalo::collections::Vec as BumpVec;
+
+use crate::ast::{AttrVal, Attributes, ElementClosingTag, NodeData, ScriptOrStyleLang, SourceCode};
 use crate::common::gen::codepoints::{
     ATTR_QUOTE, DOUBLE_QUOTE, NOT_UNQUOTED_ATTR_VAL_CHAR, SINGLE_QUOTE, TAG_NAME_CHAR, WHITESPACE,
     WHITESPACE_OR_SLASH, WHITESPACE_OR_SLASH_OR_EQUALS_OR_RIGHT_CHEVRON,
 };
 use crate::common::spec::script::JAVASCRIPT_MIME_TYPES;
 use crate::common::spec::tag::ns::Namespace;
+use crate::common::spec::tag::raw_text::RAW_TEXT_TAGS;
 use crate::common::spec::tag::void::VOID_TAGS;
 use crate::entity::decode::decode_entities;
+use crate::lint::{name_to_string, ProblemKind};
 use crate::parse::content::{parse_content, ParsedContent};
+use crate::parse::raw_text::{parse_plaintext_content, parse_raw_text_content};
 use crate::parse::script::parse_script_content;
 use crate::parse::style::parse_style_content;
 use crate::parse::textarea::parse_textarea_content;
 use crate::parse::title::parse_title_content;
-use crate::parse::Code;
+use crate::parse::{lowercase, Code};
 use std::fmt::{Debug, Formatter};
 use std::str::from_utf8;
 
-fn parse_tag_name(code: &mut Code) -> Vec<u8> {
+fn parse_tag_name<'c>(code: &mut Code<'c>) -> Cow<'c, [u8]> {
     debug_assert!(code.as_slice().starts_with(b"<"));
     code.shift(1);
     code.shift_if_next(b'/');
-    let mut name = code.copy_and_shift_while_in_lookup(TAG_NAME_CHAR);
-    name.make_ascii_lowercase();
-    name
+    lowercase(code.slice_and_shift_while_in_lookup(TAG_NAME_CHAR))
 }
 
-pub fn peek_tag_name(code: &mut Code) -> Vec<u8> {
+pub fn peek_tag_name<'c>(code: &mut Code<'c>) -> Cow<'c, [u8]> {
     let cp = code.take_checkpoint();
     let name = parse_tag_name(code);
     code.restore_checkpoint(cp);
@@ -36,13 +39,13 @@ pub fn peek_tag_name(code: &mut Code) -> Vec<u8> {
 
 // Derive Eq for testing.
 #[derive(Eq, PartialEq)]
-pub struct ParsedTag {
-    pub attributes: HashMap<Vec<u8>, AttrVal>,
-    pub name: Vec<u8>,
+pub struct ParsedTag<'c> {
+    pub attributes: Attributes<'c>,
+    pub name: Cow<'c, [u8]>,
     pub self_closing: bool,
 }
 
-impl Debug for ParsedTag {
+impl<'c> Debug for ParsedTag<'c> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         f.write_fmt(format_args!("<{}", from_utf8(&self.name).unwrap()))?;
         let mut attrs = self.attributes.iter().collect::<Vec<_>>();
@@ -58,10 +61,9 @@ impl Debug for ParsedTag {
 }
 
 // While not valid, attributes in closing tags still need to be parsed (and then discarded) as attributes e.g. `</div x=">">`, which is why this function is used for both opening and closing tags.
-// TODO Use generics to create version that doesn't create a HashMap.
-pub fn parse_tag(code: &mut Code) -> ParsedTag {
+pub fn parse_tag<'c>(code: &mut Code<'c>) -> ParsedTag<'c> {
     let elem_name = parse_tag_name(code);
-    let mut attributes = HashMap::new();
+    let mut attributes = Attributes::new_in(code.arena);
     let self_closing;
     loop {
         // At the beginning of this loop, the last parsed unit was either the tag name or an attribute (including its value, if it had one).
@@ -71,18 +73,12 @@ pub fn parse_tag(code: &mut Code) -> ParsedTag {
             // End of tag.
             break;
         };
-        let mut attr_name = Vec::new();
         // An attribute name can start with `=`, but ends at the next whitespace, `=`, `/`, or `>`.
-        if let Some(c) = code.shift_if_next_not_in_lookup(WHITESPACE_OR_SLASH) {
-            attr_name.push(c);
-        };
-        attr_name.extend_from_slice(
-            code.slice_and_shift_while_not_in_lookup(
-                WHITESPACE_OR_SLASH_OR_EQUALS_OR_RIGHT_CHEVRON,
-            ),
-        );
+        let attr_start = code.take_checkpoint();
+        code.shift_if_next_not_in_lookup(WHITESPACE_OR_SLASH);
+        code.slice_and_shift_while_not_in_lookup(WHITESPACE_OR_SLASH_OR_EQUALS_OR_RIGHT_CHEVRON);
+        let attr_name = lowercase(code.slice_since(attr_start));
         debug_assert!(!attr_name.is_empty());
-        attr_name.make_ascii_lowercase();
         // See comment for WHITESPACE_OR_SLASH in codepoints.ts for details of complex attr parsing.
         code.shift_while_in_lookup(WHITESPACE);
         let has_value = code.shift_if_next(b'=');
@@ -90,7 +86,9 @@ pub fn parse_tag(code: &mut Code) -> ParsedTag {
         let attr_value = if !has_value {
             AttrVal {
                 quote: None,
-                value: Vec::new(),
+                index: attributes.len(),
+                value: Cow::Borrowed(&[]),
+                source: SourceCode(&[]),
             }
         } else {
             // TODO Replace ATTR_QUOTE with direct comparison.
@@ -102,19 +100,27 @@ pub fn parse_tag(code: &mut Code) -> ParsedTag {
                 None => NOT_UNQUOTED_ATTR_VAL_CHAR,
                 _ => unreachable!(),
             };
-            let attr_value = decode_entities(
-                code.slice_and_shift_while_not_in_lookup(attr_delim_pred),
-                true,
-            );
+            let source = code.slice_and_shift_while_not_in_lookup(attr_delim_pred);
+            let attr_value = decode_entities(source, true);
             if let Some(c) = attr_delim {
                 // It might not be next if EOF (i.e. attribute value not closed).
                 code.shift_if_next(c);
             };
             AttrVal {
                 quote: attr_delim,
+                index: attributes.len(),
                 value: attr_value,
+                source: SourceCode(source),
             }
         };
+        if attributes.contains_key(&attr_name) {
+            code.report(
+                attr_start,
+                ProblemKind::DuplicateAttribute {
+                    name: name_to_string(&attr_name),
+                },
+            );
+        };
         attributes.insert(attr_name, attr_value);
     }
     ParsedTag {
@@ -124,8 +130,9 @@ pub fn parse_tag(code: &mut Code) -> ParsedTag {
     }
 }
 
-// `<` must be next. `parent` should be an empty slice if it doesn't exist.
-pub fn parse_element(code: &mut Code, ns: Namespace, parent: &[u8]) -> NodeData {
+// `<` must be next.
+pub fn parse_element<'c>(code: &mut Code<'c>, ns: Namespace) -> NodeData<'c> {
+    let start = code.take_checkpoint();
     let ParsedTag {
         name: elem_name,
         attributes,
@@ -133,7 +140,7 @@ pub fn parse_element(code: &mut Code, ns: Namespace, parent: &[u8]) -> NodeData
     } = parse_tag(code);
 
     // Embedded svg tags are immediately in the svg namespace and must be parsed as such.
-    let ns = if elem_name == b"svg" {
+    let ns = if elem_name.as_ref() == b"svg" {
         Namespace::Svg
     } else {
         ns
@@ -143,28 +150,29 @@ pub fn parse_element(code: &mut Code, ns: Namespace, parent: &[u8]) -> NodeData
     if self_closing && ns != Namespace::Html {
         return NodeData::Element {
             attributes,
-            children: Vec::new(),
+            children: BumpVec::new_in(code.arena),
             closing_tag: ElementClosingTag::SelfClosing,
             name: elem_name,
             namespace: ns,
-            next_sibling_element_name: Vec::new(),
+            next_sibling_element_name: Cow::Borrowed(&[]),
         };
     };
-    if VOID_TAGS.contains(elem_name.as_slice()) {
+    if VOID_TAGS.contains(elem_name.as_ref()) {
         return NodeData::Element {
             attributes,
-            children: Vec::new(),
+            children: BumpVec::new_in(code.arena),
             closing_tag: ElementClosingTag::Void,
             name: elem_name,
             namespace: ns,
-            next_sibling_element_name: Vec::new(),
+            next_sibling_element_name: Cow::Borrowed(&[]),
         };
     };
 
+    let mut raw_text = true;
     let ParsedContent {
         closing_tag_omitted,
         children,
-    } = match elem_name.as_slice() {
+    } = match elem_name.as_ref() {
         b"script" => match attributes.get(b"type".as_ref()) {
             Some(mime) if !JAVASCRIPT_MIME_TYPES.contains(mime.as_slice()) => {
                 parse_script_content(code, ScriptOrStyleLang::Data)
@@ -174,12 +182,37 @@ pub fn parse_element(code: &mut Code, ns: Namespace, parent: &[u8]) -> NodeData
         b"style" => parse_style_content(code),
         b"textarea" => parse_textarea_content(code),
         b"title" => parse_title_content(code),
-        _ => parse_content(code, ns, parent, &elem_name),
+        b"plaintext" if ns == Namespace::Html => {
+            // Always ends at EOF, as it can't be closed.
+            raw_text = false;
+            parse_plaintext_content(code)
+        }
+        b"noscript" if ns == Namespace::Html && code.noscript_as_raw_text => {
+            parse_raw_text_content(code, &elem_name)
+        }
+        name if ns == Namespace::Html && RAW_TEXT_TAGS.contains(name) => {
+            parse_raw_text_content(code, &elem_name)
+        }
+        _ => {
+            raw_text = false;
+            code.open_elements.push(elem_name.clone());
+            let content = parse_content(code, ns, &elem_name);
+            code.open_elements.pop();
+            content
+        }
     };
 
     if !closing_tag_omitted {
         let closing_tag = parse_tag(code);
         debug_assert_eq!(closing_tag.name, elem_name);
+    } else if raw_text {
+        // Raw text can only end at its closing tag, so it must have reached EOF.
+        code.report(
+            start,
+            ProblemKind::UnclosedRawText {
+                name: name_to_string(&elem_name),
+            },
+        );
     };
 
     NodeData::Element {
@@ -192,6 +225,6 @@ pub fn parse_element(code: &mut Code, ns: Namespace, parent: &[u8]) -> NodeData
         },
         name: elem_name,
         namespace: ns,
-        next_sibling_element_name: Vec::new(),
+        next_sibling_element_name: Cow::Borrowed(&[]),
     }
 }
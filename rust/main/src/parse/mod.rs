@@ -1,4 +1,9 @@
+use std::borrow::Cow;
+
+use bumpalo::Bump;
+
 use crate::common::gen::codepoints::Lookup;
+use crate::lint::ProblemKind;
 
 pub mod bang;
 pub mod comment;
@@ -6,6 +11,7 @@ pub mod content;
 pub mod doctype;
 pub mod element;
 pub mod instruction;
+pub mod raw_text;
 pub mod script;
 pub mod style;
 #[cfg(test)]
@@ -13,28 +19,57 @@ mod tests;
 pub mod textarea;
 pub mod title;
 
+// Tag and attribute names are case insensitive, so they're lowercased, but only copied if they contain uppercase characters.
+pub fn lowercase(name: &[u8]) -> Cow<'_, [u8]> {
+    if name.iter().any(u8::is_ascii_uppercase) {
+        Cow::Owned(name.to_ascii_lowercase())
+    } else {
+        Cow::Borrowed(name)
+    }
+}
+
 pub struct Code<'c> {
     code: &'c [u8],
     next: usize,
+    // Nodes are allocated in this, and borrow from `code`.
+    pub arena: &'c Bump,
 
     pub seen_html_open: bool,
     pub seen_head_open: bool,
     pub seen_head_close: bool,
     pub seen_body_open: bool,
+    // Whether `<noscript>` contains raw text, as it does when scripting is enabled.
+    pub noscript_as_raw_text: bool,
+    // Elements nested deeper than this aren't parsed; instead, the rest of the code is skipped and `depth_limit_exceeded` is set.
+    pub max_depth: Option<usize>,
+    pub depth_limit_exceeded: bool,
+    // Number of currently open elements.
+    pub depth: usize,
+    // Names of the currently open elements with parsed content, outermost first.
+    pub open_elements: Vec<Cow<'c, [u8]>>,
+    // Authoring errors found so far, and the positions they start at, for linting.
+    pub problems: Vec<(usize, ProblemKind)>,
 }
 
 #[derive(Copy, Clone)]
 pub struct Checkpoint(usize);
 
 impl<'c> Code<'c> {
-    pub fn new(code: &[u8]) -> Code {
+    pub fn new(code: &'c [u8], arena: &'c Bump) -> Code<'c> {
         Code {
             code,
             next: 0,
+            arena,
             seen_html_open: false,
             seen_head_open: false,
             seen_head_close: false,
             seen_body_open: false,
+            noscript_as_raw_text: false,
+            max_depth: None,
+            depth_limit_exceeded: false,
+            depth: 0,
+            open_elements: Vec::new(),
+            problems: Vec::new(),
         }
     }
 
@@ -50,6 +85,14 @@ impl<'c> Code<'c> {
         self.next = cp.0;
     }
 
+    pub fn report(&mut self, at: Checkpoint, kind: ProblemKind) {
+        self.problems.push((at.0, kind));
+    }
+
+    pub fn slice_since(&self, cp: Checkpoint) -> &'c [u8] {
+        &self.code[cp.0..self.next]
+    }
+
     pub fn at_end(&self) -> bool {
         debug_assert!(self.next <= self.code.len());
         self.next == self.code.len()
@@ -98,17 +141,13 @@ impl<'c> Code<'c> {
         self.next += n;
     }
 
-    pub fn slice_and_shift(&mut self, n: usize) -> &[u8] {
+    pub fn slice_and_shift(&mut self, n: usize) -> &'c [u8] {
         let str = &self.code[self.next..self.next + n];
         self.next += n;
         str
     }
 
-    pub fn copy_and_shift(&mut self, n: usize) -> Vec<u8> {
-        self.slice_and_shift(n).to_vec()
-    }
-
-    pub fn copy_and_shift_while_in_lookup(&mut self, lookup: &'static Lookup) -> Vec<u8> {
+    pub fn slice_and_shift_while_in_lookup(&mut self, lookup: &'static Lookup) -> &'c [u8] {
         let mut len = 0;
         loop {
             match self.code.get(self.next + len) {
@@ -116,10 +155,10 @@ impl<'c> Code<'c> {
                 _ => break,
             };
         }
-        self.copy_and_shift(len)
+        self.slice_and_shift(len)
     }
 
-    pub fn slice_and_shift_while_not_in_lookup(&mut self, lookup: &'static Lookup) -> &[u8] {
+    pub fn slice_and_shift_while_not_in_lookup(&mut self, lookup: &'static Lookup) -> &'c [u8] {
         let mut len = 0;
         loop {
             match self.code.get(self.next + len) {
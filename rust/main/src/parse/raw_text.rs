@@ -0,0 +1,31 @@
+use bumpalo::vec;
+use bumpalo::Bump;
+
+use crate::ast::{NodeData, ScriptOrStyleLang};
+use crate::common::spec::tag::raw_text::find_raw_text_end;
+use crate::parse::content::ParsedContent;
+use crate::parse::Code;
+
+fn raw_text<'c>(arena: &'c Bump, code: &'c [u8], closing_tag_omitted: bool) -> ParsedContent<'c> {
+    ParsedContent {
+        closing_tag_omitted,
+        // Entities aren't decoded in raw text, so it's represented the same way as the contents of a `<script>` that isn't JavaScript, and output as is.
+        children: vec![in arena; NodeData::ScriptOrStyleContent {
+            code,
+            lang: ScriptOrStyleLang::Data,
+        }],
+    }
+}
+
+pub fn parse_raw_text_content<'c>(code: &mut Code<'c>, name: &[u8]) -> ParsedContent<'c> {
+    let (len, closing_tag_omitted) = match find_raw_text_end(code.as_slice(), name) {
+        Some(len) => (len, false),
+        None => (code.rem(), true),
+    };
+    raw_text(code.arena, code.slice_and_shift(len), closing_tag_omitted)
+}
+
+// `<plaintext>` can't be closed, so everything after it is its contents.
+pub fn parse_plaintext_content<'c>(code: &mut Code<'c>) -> ParsedContent<'c> {
+    raw_text(code.arena, code.slice_and_shift(code.rem()), true)
+}
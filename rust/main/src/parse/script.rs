@@ -1,27 +1,21 @@
-use aho_corasick::AhoCorasick;
-use aho_corasick::AhoCorasickBuilder;
-use lazy_static::lazy_static;
+use bumpalo::vec;
 
 use crate::ast::{NodeData, ScriptOrStyleLang};
+use crate::common::spec::script::{find_script_data_end, ScriptDataEnd};
 use crate::parse::content::ParsedContent;
 use crate::parse::Code;
 
-lazy_static! {
-    static ref END: AhoCorasick = AhoCorasickBuilder::new()
-        .ascii_case_insensitive(true)
-        .build(&["</script"]);
-}
-
-pub fn parse_script_content(code: &mut Code, lang: ScriptOrStyleLang) -> ParsedContent {
-    let (len, closing_tag_omitted) = match END.find(code.as_slice()) {
-        Some(m) => (m.start(), false),
-        None => (code.rem(), true),
+pub fn parse_script_content<'c>(code: &mut Code<'c>, lang: ScriptOrStyleLang) -> ParsedContent<'c> {
+    let (len, closing_tag_omitted) = match find_script_data_end(code.as_slice()) {
+        ScriptDataEnd::Found(len) => (len, false),
+        ScriptDataEnd::NotFound { .. } => (code.rem(), true),
+    };
+    let content = NodeData::ScriptOrStyleContent {
+        code: code.slice_and_shift(len),
+        lang,
     };
     ParsedContent {
         closing_tag_omitted,
-        children: vec![NodeData::ScriptOrStyleContent {
-            code: code.copy_and_shift(len),
-            lang,
-        }],
+        children: vec![in code.arena; content],
     }
 }
@@ -1,5 +1,6 @@
 use aho_corasick::AhoCorasick;
 use aho_corasick::AhoCorasickBuilder;
+use bumpalo::vec;
 use lazy_static::lazy_static;
 
 use crate::ast::{NodeData, ScriptOrStyleLang};
@@ -9,19 +10,20 @@ use crate::parse::Code;
 lazy_static! {
     static ref END: AhoCorasick = AhoCorasickBuilder::new()
         .ascii_case_insensitive(true)
-        .build(&["</style"]);
+        .build(["</style"]);
 }
 
-pub fn parse_style_content(code: &mut Code) -> ParsedContent {
+pub fn parse_style_content<'c>(code: &mut Code<'c>) -> ParsedContent<'c> {
     let (len, closing_tag_omitted) = match END.find(code.as_slice()) {
         Some(m) => (m.start(), false),
         None => (code.rem(), true),
     };
+    let content = NodeData::ScriptOrStyleContent {
+        code: code.slice_and_shift(len),
+        lang: ScriptOrStyleLang::CSS,
+    };
     ParsedContent {
         closing_tag_omitted,
-        children: vec![NodeData::ScriptOrStyleContent {
-            code: code.copy_and_shift(len),
-            lang: ScriptOrStyleLang::CSS,
-        }],
+        children: vec![in code.arena; content],
     }
 }
@@ -1,49 +1,55 @@
-use std::collections::HashMap;
+use std::borrow::Cow;
 
-use crate::ast::{AttrVal, ElementClosingTag, NodeData};
+use bumpalo::collections::Vec as BumpVec;
+use bumpalo::Bump;
+
+use crate::ast::{AttrVal, Attributes, ElementClosingTag, NodeData, SourceCode};
 use crate::common::spec::tag::ns::Namespace;
-use crate::common::spec::tag::EMPTY_SLICE;
 use crate::parse::element::{parse_element, parse_tag, ParsedTag};
 use crate::parse::Code;
 
-fn val(v: &[u8]) -> AttrVal {
+fn val(v: &[u8]) -> AttrVal<'_> {
     AttrVal {
-        value: v.to_vec(),
+        value: Cow::Borrowed(v),
         quote: None,
+        index: 0,
+        source: SourceCode(v),
     }
 }
 
 #[test]
 fn test_parse_tag() {
+    let arena = Bump::new();
     let mut code = Code::new(
         br###"<input type
 
 
 				 =
 			"password"  "a"  = "  b  "   :cd  /e /=fg 	= /\h /i/ /j/k/l m=n=o q==\r/s/ / t] = /u  / w=//>"###,
+        &arena,
     );
     let tag = parse_tag(&mut code);
     assert_eq!(
         tag,
         ParsedTag {
             attributes: {
-                let mut map = HashMap::<Vec<u8>, AttrVal>::new();
-                map.insert(b"type".to_vec(), val(b"password"));
-                map.insert(b"\"a\"".to_vec(), val(b"  b  "));
-                map.insert(b":cd".to_vec(), val(b""));
-                map.insert(b"e".to_vec(), val(b""));
-                map.insert(b"=fg".to_vec(), val(b"/\\h"));
-                map.insert(b"i".to_vec(), val(b""));
-                map.insert(b"j".to_vec(), val(b""));
-                map.insert(b"k".to_vec(), val(b""));
-                map.insert(b"l".to_vec(), val(b""));
-                map.insert(b"m".to_vec(), val(b"n=o"));
-                map.insert(b"q".to_vec(), val(b"=\\r/s/"));
-                map.insert(b"t]".to_vec(), val(b"/u"));
-                map.insert(b"w".to_vec(), val(b"//"));
+                let mut map = Attributes::new_in(&arena);
+                map.insert(Cow::Borrowed(b"type"), val(b"password"));
+                map.insert(Cow::Borrowed(b"\"a\""), val(b"  b  "));
+                map.insert(Cow::Borrowed(b":cd"), val(b""));
+                map.insert(Cow::Borrowed(b"e"), val(b""));
+                map.insert(Cow::Borrowed(b"=fg"), val(b"/\\h"));
+                map.insert(Cow::Borrowed(b"i"), val(b""));
+                map.insert(Cow::Borrowed(b"j"), val(b""));
+                map.insert(Cow::Borrowed(b"k"), val(b""));
+                map.insert(Cow::Borrowed(b"l"), val(b""));
+                map.insert(Cow::Borrowed(b"m"), val(b"n=o"));
+                map.insert(Cow::Borrowed(b"q"), val(b"=\\r/s/"));
+                map.insert(Cow::Borrowed(b"t]"), val(b"/u"));
+                map.insert(Cow::Borrowed(b"w"), val(b"//"));
                 map
             },
-            name: b"input".to_vec(),
+            name: Cow::Borrowed(b"input"),
             self_closing: false,
         }
     );
@@ -51,21 +57,22 @@ fn test_parse_tag() {
 
 #[test]
 fn test_parse_element() {
-    let mut code = Code::new(br#"<a b=\"c\"></a>"#);
-    let elem = parse_element(&mut code, Namespace::Html, EMPTY_SLICE);
+    let arena = Bump::new();
+    let mut code = Code::new(br#"<a b=\"c\"></a>"#, &arena);
+    let elem = parse_element(&mut code, Namespace::Html);
     assert_eq!(
         elem,
         NodeData::Element {
             attributes: {
-                let mut map = HashMap::<Vec<u8>, AttrVal>::new();
-                map.insert(b"b".to_vec(), val(br#"\"c\""#));
+                let mut map = Attributes::new_in(&arena);
+                map.insert(Cow::Borrowed(b"b"), val(br#"\"c\""#));
                 map
             },
-            children: vec![],
+            children: BumpVec::new_in(&arena),
             closing_tag: ElementClosingTag::Present,
-            name: b"a".to_vec(),
+            name: Cow::Borrowed(b"a"),
             namespace: Namespace::Html,
-            next_sibling_element_name: Vec::new(),
+            next_sibling_element_name: Cow::Borrowed(&[]),
         }
     );
 }
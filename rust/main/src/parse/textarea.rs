@@ -1,8 +1,9 @@
 use aho_corasick::AhoCorasick;
 use aho_corasick::AhoCorasickBuilder;
+use bumpalo::vec;
 use lazy_static::lazy_static;
 
-use crate::ast::NodeData;
+use crate::ast::{NodeData, SourceCode};
 use crate::entity::decode::decode_entities;
 use crate::parse::content::ParsedContent;
 use crate::parse::Code;
@@ -10,18 +11,21 @@ use crate::parse::Code;
 lazy_static! {
     static ref END: AhoCorasick = AhoCorasickBuilder::new()
         .ascii_case_insensitive(true)
-        .build(&["</textarea"]);
+        .build(["</textarea"]);
 }
 
-pub fn parse_textarea_content(code: &mut Code) -> ParsedContent {
+pub fn parse_textarea_content<'c>(code: &mut Code<'c>) -> ParsedContent<'c> {
     let (len, closing_tag_omitted) = match END.find(code.as_slice()) {
         Some(m) => (m.start(), false),
         None => (code.rem(), true),
     };
+    let source = code.slice_and_shift(len);
+    let text = NodeData::Text {
+        value: decode_entities(source, false),
+        source: SourceCode(source),
+    };
     ParsedContent {
         closing_tag_omitted,
-        children: vec![NodeData::Text {
-            value: decode_entities(code.slice_and_shift(len), false),
-        }],
+        children: vec![in code.arena; text],
     }
 }
@@ -0,0 +1,131 @@
+use std::str::from_utf8;
+
+use crate::cfg::Cfg;
+use crate::minify;
+
+fn count(haystack: &str, needle: &str) -> usize {
+    haystack.matches(needle).count()
+}
+
+// Removes comments and `<style>` contents, so that only markup remains.
+fn strip_comments_and_styles(mut html: &str) -> String {
+    let mut out = String::new();
+    loop {
+        let next = [("<!--", "-->"), ("<style", "</style>")]
+            .iter()
+            .filter_map(|&(open, close)| html.find(open).map(|i| (i, close)))
+            .min();
+        match next {
+            Some((i, close)) => {
+                out.push_str(&html[..i]);
+                html = &html[i..];
+                html = &html[html.find(close).unwrap() + close.len()..];
+            }
+            None => {
+                out.push_str(html);
+                return out;
+            }
+        };
+    }
+}
+
+// Email output is checked for the properties that clients rely on, rather than compared exactly, so that unrelated minification improvements don't break these tests.
+fn eval_email_fixture(src: &'static [u8], legacy_attrs: &[&str]) {
+    let src = from_utf8(src).unwrap();
+    let out = String::from_utf8(minify(src.as_bytes(), &Cfg::email())).unwrap();
+    assert!(out.len() < src.len());
+
+    // Conditional comments are kept verbatim.
+    for (i, _) in src.match_indices("<!--[if") {
+        let end = i + src[i..].find("-->").unwrap() + 3;
+        assert!(out.contains(&src[i..end]), "missing {}", &src[i..end]);
+    }
+    assert_eq!(
+        count(&out, "<!--<![endif]-->"),
+        count(src, "<!--<![endif]-->")
+    );
+
+    // Closing tags and optional opening tags are never omitted.
+    for tag in &[
+        "body", "head", "html", "li", "p", "table", "td", "th", "tr", "ul",
+    ] {
+        let closing = format!("</{}>", tag);
+        assert_eq!(count(&out, &closing), count(src, &closing), "{}", closing);
+    }
+    assert!(out.contains("<html"));
+    assert!(out.contains("<head>"));
+    assert!(out.contains("<body"));
+
+    // Legacy attributes are untouched.
+    for attr in legacy_attrs {
+        assert!(out.contains(attr), "missing {}", attr);
+    }
+
+    let markup = strip_comments_and_styles(&out);
+    // All attribute values are quoted.
+    let bytes = markup.as_bytes();
+    let mut in_tag = false;
+    let mut quote: Option<u8> = None;
+    for (i, &c) in bytes.iter().enumerate() {
+        match quote {
+            Some(q) => {
+                if c == q {
+                    quote = None;
+                };
+            }
+            None if in_tag => match c {
+                b'"' | b'\'' => quote = Some(c),
+                b'=' => assert!(
+                    bytes[i + 1] == b'"' || bytes[i + 1] == b'\'',
+                    "unquoted attribute value at {}",
+                    &markup[i.saturating_sub(20)..]
+                ),
+                b'>' => in_tag = false,
+                _ => {}
+            },
+            None => {
+                if c == b'<' {
+                    in_tag = true;
+                };
+            }
+        };
+    }
+    // All character references are terminated and use lowercase names.
+    for (i, _) in markup.match_indices('&') {
+        let len = markup[i + 1..]
+            .find(|c: char| !c.is_ascii_alphanumeric() && c != '#')
+            .unwrap();
+        let name = &markup[i + 1..i + 1 + len];
+        assert_eq!(&markup[i + 1 + len..i + 2 + len], ";", "&{}", name);
+        assert_eq!(name, name.to_ascii_lowercase(), "&{}", name);
+    }
+}
+
+#[test]
+fn test_email_newsletter() {
+    eval_email_fixture(
+        include_bytes!("email/newsletter.html"),
+        &[
+            r##"bgcolor="#F4F4F4""##,
+            r#"align="center""#,
+            r#"cellpadding="0""#,
+            r#"face="Arial""#,
+            r##"color="#999999""##,
+            "XHTML 1.0 Transitional",
+        ],
+    );
+}
+
+#[test]
+fn test_email_receipt() {
+    eval_email_fixture(
+        include_bytes!("email/receipt.html"),
+        &[
+            r##"bgcolor="#0B3954""##,
+            r#"border="1""#,
+            r#"rules="rows""#,
+            r#"frame="void""#,
+            r#"align="right""#,
+        ],
+    );
+}
@@ -1,20 +1,37 @@
+use std::borrow::Cow;
 use std::str::from_utf8;
 
-use crate::cfg::Cfg;
-use crate::minify;
+use crate::cfg::canonicalise::{AttributeQuotes, CanonicaliseCfg, EntityPolicy};
+use crate::cfg::format::FormatCfg;
+use crate::cfg::whitespace::WhitespaceCategory;
+use crate::cfg::{Cfg, DEFAULT_MAX_DEPTH};
+use crate::common::gen::attrs::AttributeMinification;
+use crate::common::spec::tag::ns::Namespace;
+use crate::diagnostic::{DepthLimitExceeded, Diagnostic, EmbeddedLanguage};
+use crate::diff::DifferenceKind;
+use crate::encoding::Encoding;
+use crate::lint::{ProblemKind, Severity};
+use crate::{
+    canonicalise, canonicalise_with_cfg, diff, format, lint, minify, minify_with_diagnostics,
+    Minifier,
+};
+
+mod email;
+mod parse_errors;
+mod properties;
 
 pub fn eval_with_cfg(src: &'static [u8], expected: &'static [u8], cfg: &Cfg) {
-    let min = minify(&src, cfg);
+    let min = minify(src, cfg);
     assert_eq!(from_utf8(&min).unwrap(), from_utf8(expected).unwrap(),);
 }
 
-pub fn eval_with_js_min(src: &'static [u8], expected: &'static [u8]) -> () {
+pub fn eval_with_js_min(src: &'static [u8], expected: &'static [u8]) {
     let mut cfg = Cfg::new();
     cfg.minify_js = true;
     eval_with_cfg(src, expected, &cfg);
 }
 
-pub fn eval_with_css_min(src: &'static [u8], expected: &'static [u8]) -> () {
+pub fn eval_with_css_min(src: &'static [u8], expected: &'static [u8]) {
     let mut cfg = Cfg::new();
     cfg.minify_css = true;
     eval_with_cfg(src, expected, &cfg);
@@ -27,7 +44,7 @@ pub fn eval(src: &'static [u8], expected: &'static [u8]) {
     eval_with_cfg(src, expected, &cfg);
 }
 
-fn eval_without_keep_html_head(src: &'static [u8], expected: &'static [u8]) -> () {
+fn eval_without_keep_html_head(src: &'static [u8], expected: &'static [u8]) {
     eval_with_cfg(src, expected, &Cfg::new());
 }
 
@@ -89,6 +106,8 @@ fn test_unmatched_closing_tag() {
         b"<html><head><body><ul><li><rt>a</p>",
         b"<html><head><body><ul><li><rt>a",
     );
+    // Malformed closing tags are dropped up to the next `>`, not the last one.
+    eval(b"<div></   div x=\">\">5<p>b>", b"<div>\">5<p>b>");
 }
 
 #[test]
@@ -153,3 +172,945 @@ fn test_style_attr_minification() {
     // `style` attributes are removed if fully minified away.
     eval_with_css_min(br#"<div style="  /*  */   "></div>"#, br#"<div></div>"#);
 }
+
+#[test]
+fn test_custom_attr_rules() {
+    let mut cfg = Cfg::new();
+    cfg.attribute_rules.insert(
+        Namespace::Html,
+        b"ui-dialog",
+        b"modal",
+        AttributeMinification {
+            boolean: true,
+            case_insensitive: false,
+            collapse: false,
+            default_value: None,
+            redundant_if_empty: false,
+            trim: false,
+        },
+    );
+    cfg.attribute_rules.insert(
+        Namespace::Html,
+        b"ui-button",
+        b"variant",
+        AttributeMinification {
+            boolean: false,
+            case_insensitive: true,
+            collapse: false,
+            // Built at runtime, so it can't be borrowed for `'static`.
+            default_value: Some(Cow::Owned(String::from("primary").into_bytes())),
+            redundant_if_empty: true,
+            trim: true,
+        },
+    );
+    // Rules for all elements can disable built-in rules.
+    cfg.attribute_rules.insert(
+        Namespace::Html,
+        b"",
+        b"class",
+        AttributeMinification {
+            boolean: false,
+            case_insensitive: false,
+            collapse: false,
+            default_value: None,
+            redundant_if_empty: false,
+            trim: false,
+        },
+    );
+    eval_with_cfg(
+        b"<ui-dialog modal=false></ui-dialog>",
+        b"<ui-dialog modal></ui-dialog>",
+        &cfg,
+    );
+    eval_with_cfg(
+        b"<ui-button variant=' PRIMARY '></ui-button><ui-button variant=''></ui-button>",
+        b"<ui-button></ui-button><ui-button></ui-button>",
+        &cfg,
+    );
+    eval_with_cfg(
+        b"<ui-button variant=' Secondary '></ui-button>",
+        b"<ui-button variant=secondary></ui-button>",
+        &cfg,
+    );
+    eval_with_cfg(
+        b"<div class='  a   b '></div>",
+        b"<div class=\"  a   b \"></div>",
+        &cfg,
+    );
+    // Rules are specific to the tag.
+    eval_with_cfg(
+        b"<ui-card modal=false></ui-card>",
+        b"<ui-card modal=false></ui-card>",
+        &cfg,
+    );
+}
+
+#[test]
+fn test_custom_whitespace_rules() {
+    let mut cfg = Cfg::new();
+    cfg.whitespace_rules
+        .insert_prefix(b"ui-", WhitespaceCategory::Layout);
+    cfg.whitespace_rules
+        .insert_prefix(b"ui-text", WhitespaceCategory::Content);
+    cfg.whitespace_rules
+        .insert_tag(b"ui-code", WhitespaceCategory::WhitespaceSensitive);
+    eval_with_cfg(
+        b"<ui-stack> <ui-card> a </ui-card>\n <ui-card>  b  </ui-card> </ui-stack>",
+        b"<ui-stack><ui-card>a</ui-card><ui-card>b</ui-card></ui-stack>",
+        &cfg,
+    );
+    // Longest prefix wins.
+    eval_with_cfg(
+        b"<ui-text-block> a  <b>b</b>  </ui-text-block>",
+        b"<ui-text-block>a <b>b</b></ui-text-block>",
+        &cfg,
+    );
+    // Exact tag names take precedence over prefixes.
+    eval_with_cfg(
+        b"<ui-code>  a\n  b  </ui-code>",
+        b"<ui-code>  a\n  b  </ui-code>",
+        &cfg,
+    );
+    // Descendants of whitespace sensitive elements are whitespace sensitive too, like those of `<pre>`.
+    eval_with_cfg(
+        b"<ui-code> <b> a </b> <ui-card> b </ui-card></ui-code>",
+        b"<ui-code> <b> a </b> <ui-card> b </ui-card></ui-code>",
+        &cfg,
+    );
+    // Elements without a rule use the default.
+    eval_with_cfg(
+        b"<my-card> <my-card> a </my-card> </my-card>",
+        b"<my-card> <my-card> a </my-card> </my-card>",
+        &cfg,
+    );
+    // Descendants of `<pre>` are still whitespace sensitive.
+    eval_with_cfg(
+        b"<pre> <ui-card> a </ui-card> </pre>",
+        b"<pre> <ui-card> a </ui-card> </pre>",
+        &cfg,
+    );
+}
+
+#[test]
+fn test_infer_whitespace_from_css() {
+    let mut cfg = Cfg::new();
+    cfg.infer_whitespace_from_css = true;
+    // Inline styles.
+    eval_with_cfg(
+        b"<div style=\"white-space: pre\">  a  b  </div>",
+        b"<div style=\"white-space: pre\">  a  b  </div>",
+        &cfg,
+    );
+    eval_with_cfg(
+        b"<span style=display:block> <span>a</span> </span>",
+        b"<span style=display:block><span>a</span></span>",
+        &cfg,
+    );
+    eval_with_cfg(
+        b"<div style=display:inline> a  b </div>",
+        b"<div style=display:inline> a b </div>",
+        &cfg,
+    );
+    // Simple rules from `<style>` elements.
+    eval_with_cfg(
+        b"<style>.code{white-space:pre}</style><div class=code>  a  </div>",
+        b"<style>.code{white-space:pre}</style><div class=code>  a  </div>",
+        &cfg,
+    );
+    eval_with_cfg(
+        b"<style>.code{white-space:pre}</style><div class=code style=white-space:normal>  a  </div>",
+        b"<style>.code{white-space:pre}</style><div class=code style=white-space:normal>a</div>",
+        &cfg,
+    );
+    // Whitespace is preserved if `white-space` can't be determined statically.
+    eval_with_cfg(
+        b"<style>main .code{white-space:pre}</style><div class=code>  a  </div>",
+        b"<style>main .code{white-space:pre}</style><div class=code>  a  </div>",
+        &cfg,
+    );
+    eval_with_cfg(
+        b"<style>@media print{.code{white-space:pre}}</style><div class=code>  a  <b> b </b></div>",
+        b"<style>@media print{.code{white-space:pre}}</style><div class=code>  a  <b> b </b></div>",
+        &cfg,
+    );
+    eval_with_cfg(
+        b"<div style=\"white-space:var(--ws)\">  a  </div>",
+        b"<div style=white-space:var(--ws)>  a  </div>",
+        &cfg,
+    );
+    // `display` falls back to the defaults instead.
+    eval_with_cfg(
+        b"<style>main span{display:block}</style><p><span> a </span> <span> b </span>",
+        b"<style>main span{display:block}</style><p><span> a </span> <span> b </span>",
+        &cfg,
+    );
+    // Disabled by default.
+    eval(
+        b"<div style=\"white-space: pre\">  a  b  </div>",
+        b"<div style=\"white-space: pre\">a b</div>",
+    );
+}
+
+#[test]
+fn test_preserve_line_breaks() {
+    let mut cfg = Cfg::new();
+    cfg.preserve_line_breaks = true;
+    eval_with_cfg(
+        b"<div>\n  <div>  a \n  b  </div>\n  <div>c</div>\n</div>",
+        b"<div>\n<div>a\nb</div>\n<div>c</div>\n</div>",
+        &cfg,
+    );
+    eval_with_cfg(
+        b"<span>a  \r\n\n  b   c</span>",
+        b"<span>a\nb c</span>",
+        &cfg,
+    );
+    eval_with_cfg(
+        b"<div class=\"a \n b  c\"></div>",
+        b"<div class=\"a\nb c\"></div>",
+        &cfg,
+    );
+    eval(
+        b"<div>\n  <div>  a \n  b  </div>\n</div>",
+        b"<div><div>a b</div></div>",
+    );
+}
+
+#[test]
+fn test_max_line_length() {
+    let mut cfg = Cfg::new();
+    cfg.max_line_length = Some(10);
+    // Collapsible whitespace in text.
+    eval_with_cfg(b"<p>aaa bbb ccc ddd</p>", b"<p>aaa bbb\nccc ddd", &cfg);
+    // Between attributes, including where no space is written after a quoted value.
+    eval_with_cfg(
+        b"<div a=1 b=\"x y\" c=2></div>",
+        b"<div\nb=\"x y\"a=1\nc=2></div>",
+        &cfg,
+    );
+    // No safe break points.
+    let (min, diagnostics) = minify_with_diagnostics(b"<p>abcdefghijklmnop</p>", &cfg);
+    assert_eq!(min, b"<p>abcdefghijklmnop".to_vec());
+    assert_eq!(
+        diagnostics,
+        vec![Diagnostic::LineTooLong {
+            line: 1,
+            length: 19
+        }]
+    );
+    // Whitespace in `pre` is significant.
+    let (min, diagnostics) = minify_with_diagnostics(b"<pre>a b c d e f g</pre>", &cfg);
+    assert_eq!(min, b"<pre>a b c d e f g</pre>".to_vec());
+    assert_eq!(
+        diagnostics,
+        vec![Diagnostic::LineTooLong {
+            line: 1,
+            length: 24
+        }]
+    );
+}
+
+#[test]
+fn test_email_safe_mode() {
+    let cfg = Cfg::email();
+    eval_with_cfg(
+        b"<!--[if mso]><table><tr><td><![endif]--><p align=center class=x>A &amp; B &lt;div&gt;</p><!-- note --><img alt=Logo src=a.png>",
+        b"<!--[if mso]><table><tr><td><![endif]--><p align=\"center\" class=\"x\">A &amp; B &lt;div></p><img alt=\"Logo\" src=\"a.png\">",
+        &cfg,
+    );
+    eval_with_cfg(
+        b"<!--[if !mso]><!--><a href=\"?a=1&b=2\">x</a><!--<![endif]-->",
+        b"<!--[if !mso]><!--><a href=\"?a=1&amp;b=2\">x</a><!--<![endif]-->",
+        &cfg,
+    );
+    eval_with_cfg(
+        b"<table bgcolor=\"#FFFFFF\"><tr><td align=\"LEFT\" valign=\" top \">x</td></tr></table>",
+        b"<table bgcolor=\"#FFFFFF\"><tr><td align=\"LEFT\" valign=\" top \">x</td></tr></table>",
+        &cfg,
+    );
+}
+
+#[test]
+fn test_ascii_only() {
+    let mut cfg = Cfg::new();
+    cfg.ascii_only = true;
+    eval_with_cfg(
+        "<p>caf\u{e9} \u{2014} \u{1F600}</p>".as_bytes(),
+        b"<p>caf&#233 &#8212 &#128512",
+        &cfg,
+    );
+    eval_with_cfg(
+        "<p>\u{e9}1\u{e9}a\u{e9};</p>".as_bytes(),
+        b"<p>&#233;1&#233;a&#233;;",
+        &cfg,
+    );
+    eval_with_cfg(
+        "<a title=\"\u{fc}\u{e9}1\">x</a>".as_bytes(),
+        b"<a title=&#252&#233;1>x</a>",
+        &cfg,
+    );
+    eval_with_cfg(
+        "<script>let a = \"\u{e9}\u{1F600}\";</script>".as_bytes(),
+        b"<script>let a = \"\\u00e9\\ud83d\\ude00\";</script>",
+        &cfg,
+    );
+    eval_with_cfg(
+        "<style>a::after{content:\"\u{e9}\" \"\u{e9}a\"}</style>".as_bytes(),
+        b"<style>a::after{content:\"\\e9\" \"\\e9 a\"}</style>",
+        &cfg,
+    );
+    // Escaped scalars are escaped in place of the backslash.
+    eval_with_cfg(
+        "<script>let a = \"\\\u{e9}\\\\\u{e9}a\\\u{2028}b\";</script>".as_bytes(),
+        b"<script>let a = \"\\u00e9\\\\\\u00e9ab\";</script>",
+        &cfg,
+    );
+    eval_with_cfg(
+        "<style>a::after{content:\"\\\u{e9}\\\\\u{e9}\"}</style>".as_bytes(),
+        b"<style>a::after{content:\"\\e9\\\\\\e9\"}</style>",
+        &cfg,
+    );
+    // Named references are used if they're shorter, and legacy ones can omit the semicolon.
+    eval_with_cfg(
+        "<p>\u{226b} \u{ac} \u{ac}a \u{2192}</p>".as_bytes(),
+        b"<p>&gg; &not &not;a &#8594",
+        &cfg,
+    );
+    eval_with_cfg(
+        "<a title=\"\u{ac}=\u{ac}\">x</a>".as_bytes(),
+        b"<a title=&#172=&not>x</a>",
+        &cfg,
+    );
+    cfg.conservative_entities = true;
+    eval_with_cfg("<p>caf\u{e9}</p>".as_bytes(), b"<p>caf&#233;", &cfg);
+    eval_with_cfg(
+        "<p>\u{ac} \u{2192}</p>".as_bytes(),
+        b"<p>&not; &rarr;",
+        &cfg,
+    );
+}
+
+#[test]
+fn test_input_encoding() {
+    let mut cfg = Cfg::new();
+    assert_eq!(minify(b"\xEF\xBB\xBF<p> a </p>", &cfg), b"<p>a".to_vec());
+    let utf16le: Vec<u8> = b"\xFF\xFE"
+        .iter()
+        .copied()
+        .chain(
+            "<p> \u{e9} </p>"
+                .encode_utf16()
+                .flat_map(|u| u.to_le_bytes()),
+        )
+        .collect();
+    assert_eq!(minify(&utf16le, &cfg), "<p>\u{e9}".as_bytes().to_vec());
+    assert_eq!(
+        minify(b"<meta charset=windows-1252><p>caf\xE9 \x80</p>", &cfg),
+        "<meta charset=utf-8><p>caf\u{e9} \u{20ac}"
+            .as_bytes()
+            .to_vec(),
+    );
+    let out = minify(
+        b"<meta http-equiv=Content-Type content=\"text/html; charset=ISO-8859-1\"><p>\xE9",
+        &cfg,
+    );
+    assert!(out.windows(13).any(|w| w == b"charset=utf-8"));
+    assert!(out.ends_with("<p>\u{e9}".as_bytes()));
+    assert_eq!(
+        minify_with_diagnostics(b"<p>a\xFFb</p>", &cfg),
+        (
+            "<p>a\u{FFFD}b".as_bytes().to_vec(),
+            vec![Diagnostic::MalformedInput {
+                encoding: Encoding::Utf8,
+                count: 1,
+            }]
+        ),
+    );
+    assert_eq!(
+        minify_with_diagnostics(b"<meta charset=shift_jis><p>\x82\xa0</p>", &cfg),
+        (
+            b"<meta charset=shift_jis><p>\x82\xa0".to_vec(),
+            vec![Diagnostic::UnsupportedEncoding {
+                label: "shift_jis".to_string(),
+            }]
+        ),
+    );
+
+    // Takes precedence over `<meta charset>`, as in bindings where the input is already a UTF-8 string.
+    cfg.input_encoding = Some(Encoding::Utf8);
+    assert_eq!(
+        minify(
+            "<meta charset=windows-1252><p>caf\u{e9}</p>".as_bytes(),
+            &cfg
+        ),
+        "<meta charset=utf-8><p>caf\u{e9}".as_bytes().to_vec(),
+    );
+
+    cfg.input_encoding = Some(Encoding::Windows1252);
+    assert_eq!(
+        minify(b"<p>\x93a\x94</p>", &cfg),
+        "<p>\u{201c}a\u{201d}".as_bytes().to_vec()
+    );
+
+    cfg.keep_bom = true;
+    cfg.keep_input_encoding = true;
+    assert_eq!(
+        minify(b"<p>\x93&alpha;\x94</p>", &cfg),
+        b"<p>\x93&#945;\x94".to_vec()
+    );
+    assert_eq!(
+        minify(b"\xEF\xBB\xBF<p> a </p>", &cfg),
+        b"\xEF\xBB\xBF<p>a".to_vec()
+    );
+}
+
+#[test]
+fn test_implicitly_closed_ancestors() {
+    eval(b"<ul><li><p>a</p></ul>b", b"<ul><li><p>a</ul>b");
+    eval(b"<ul><li><p>a</ul>b", b"<ul><li><p>a</ul>b");
+    eval(b"<ul><li><p>a</p><li>b</ul>", b"<ul><li><p>a<li>b</ul>");
+    // `</div>` can't be omitted, so `</ul>` is dropped.
+    eval(b"<ul><li><div>a</ul>b", b"<ul><li><div>ab");
+}
+
+#[test]
+fn test_closing_tags_before_kept_nodes() {
+    let mut cfg = Cfg::new();
+    cfg.keep_comments = true;
+    eval_with_cfg(
+        b"<ul><li>a</li><!-- c --></ul>",
+        b"<ul><li>a</li><!-- c --></ul>",
+        &cfg,
+    );
+    eval_with_cfg(
+        b"<ul><li>a</li> <!-- c --> </ul>",
+        b"<ul><li>a</li><!-- c --></ul>",
+        &cfg,
+    );
+    // Whitespace that's only separated from the start or end by kept nodes is trimmed too.
+    eval_with_cfg(b"<div> <!-- c --> a</div>", b"<div><!-- c -->a</div>", &cfg);
+    eval_with_cfg(b"<div>a <!-- c --> </div>", b"<div>a<!-- c --></div>", &cfg);
+}
+
+#[test]
+fn test_closing_tags_without_opening_tags() {
+    let mut cfg = Cfg::new();
+    cfg.keep_closing_tags = true;
+    eval_with_cfg(
+        b"<html><head><title>t</title></head><body></body></html>",
+        b"<title>t</title><body></body>",
+        &cfg,
+    );
+    cfg.keep_html_and_head_opening_tags = true;
+    eval_with_cfg(
+        b"<html><head><title>t</title></head><body></body></html>",
+        b"<html><head><title>t</title></head><body></body></html>",
+        &cfg,
+    );
+}
+
+#[test]
+fn test_text_around_removed_nodes() {
+    eval(b"&a<!-- c -->mp", b"&ampamp");
+    eval(
+        b"&a<!-- akd--sj\n <!-- \t\0f--ajk--df->lafj -->mp",
+        b"&ampamp",
+    );
+    eval(b"<div>a<!---->&LT<!---->p</div>", b"<div>a&LTp</div>");
+    eval(b"<div> <!----> a <!----> b </div>", b"<div>a b</div>");
+}
+
+#[test]
+fn test_limits() {
+    let mut cfg = Cfg::new();
+    cfg.max_input_size = Some(8);
+    assert_eq!(
+        minify_with_diagnostics(b"<p> a </p>", &cfg),
+        (
+            b"<p> a </p>".to_vec(),
+            vec![Diagnostic::InputTooLarge { size: 10, max: 8 }]
+        )
+    );
+    cfg.max_input_size = Some(10);
+    eval_with_cfg(b"<p> a </p>", b"<p>a", &cfg);
+
+    let mut cfg = Cfg::new();
+    cfg.max_depth = Some(2);
+    eval_with_cfg(b"<div><p> a </p></div>", b"<div><p>a</div>", &cfg);
+    assert_eq!(
+        minify_with_diagnostics(b"<div><p> <b>a</b> </p></div>", &cfg),
+        (
+            b"<div><p> <b>a</b> </p></div>".to_vec(),
+            vec![Diagnostic::TooDeeplyNested { max: 2 }]
+        )
+    );
+    // Deep nesting doesn't overflow the stack.
+    cfg.max_depth = Some(100);
+    let deep = b"<div>".repeat(100_000);
+    assert_eq!(
+        minify_with_diagnostics(&deep, &cfg),
+        (deep.clone(), vec![Diagnostic::TooDeeplyNested { max: 100 }])
+    );
+    // The default limit is safe too.
+    assert_eq!(
+        minify_with_diagnostics(&deep, &Cfg::new()),
+        (
+            deep.clone(),
+            vec![Diagnostic::TooDeeplyNested {
+                max: DEFAULT_MAX_DEPTH
+            }]
+        )
+    );
+
+    let mut cfg = Cfg::new();
+    cfg.minify_css = true;
+    cfg.max_embedded_minification_time = Some(std::time::Duration::from_secs(60));
+    eval_with_cfg(
+        br#"<style>div { color: yellow }</style><div style="color: yellow;"></div>"#,
+        br#"<style>div{color:yellow}</style><div style=color:yellow></div>"#,
+        &cfg,
+    );
+}
+
+#[test]
+fn test_js_minification_error() {
+    let mut cfg = Cfg::new();
+    cfg.minify_js = true;
+    let (min, diagnostics) = minify_with_diagnostics(b"<script> let a = ); </script><p>a", &cfg);
+    assert_eq!(min, b"<script>let a = );</script><p>a".to_vec());
+    assert!(matches!(
+        diagnostics.as_slice(),
+        [Diagnostic::EmbeddedMinificationFailed {
+            language: EmbeddedLanguage::Js,
+            ..
+        }]
+    ));
+}
+
+#[test]
+fn test_embedded_minification_is_deterministic() {
+    // Embedded code may be minified in parallel ahead of serialisation, but output and diagnostics must be in document order, including for identical code.
+    let mut cfg = Cfg::new();
+    cfg.minify_js = true;
+    let src = b"<script> let a = ); </script><script> let b = 1; </script><script> let a = ); </script><script> let b = 1; </script>";
+    let (min, diagnostics) = minify_with_diagnostics(src, &cfg);
+    assert_eq!(
+        from_utf8(&min).unwrap(),
+        "<script>let a = );</script><script>let b=1</script><script>let a = );</script><script>let b=1</script>"
+    );
+    assert_eq!(diagnostics.len(), 2);
+    for _ in 0..10 {
+        assert_eq!(
+            minify_with_diagnostics(src, &cfg),
+            (min.clone(), diagnostics.clone())
+        );
+    }
+}
+
+#[test]
+fn test_minifier() {
+    let mut cfg = Cfg::new();
+    cfg.keep_closing_tags = true;
+    let mut minifier = Minifier::new(cfg);
+    let mut out = b"prefix:".to_vec();
+    minifier.minify_into(b"<p>  a  </p>", &mut out);
+    minifier.minify_into(b"<div> <p>b</p> </div>", &mut out);
+    assert_eq!(out, b"prefix:<p>a</p><div><p>b</p></div>".to_vec());
+    // Reused buffers and arena don't leak into later outputs, including when the output is transformed after minification.
+    let mut cfg = Cfg::new();
+    cfg.keep_closing_tags = true;
+    cfg.max_line_length = Some(3);
+    let mut minifier = Minifier::new(cfg);
+    assert_eq!(minifier.minify(b"<p>a b c</p>"), b"<p>a\nb\nc</p>".to_vec());
+    let mut written = Vec::<u8>::new();
+    assert_eq!(
+        minifier.minify_to(b"<p> a </p>", &mut written).unwrap(),
+        vec![Diagnostic::LineTooLong { line: 1, length: 8 }]
+    );
+    assert_eq!(written, b"<p>a</p>".to_vec());
+}
+
+fn lint_kinds(src: &'static [u8], cfg: &Cfg) -> Vec<(usize, usize, ProblemKind)> {
+    lint(src, cfg)
+        .into_iter()
+        .map(|p| (p.line, p.column, p.kind))
+        .collect()
+}
+
+#[test]
+fn test_lint() {
+    let cfg = Cfg::new();
+    let name = |n: &str| n.to_string();
+    assert_eq!(lint_kinds(b"<div><p>a</p></div>", &cfg), vec![]);
+    // Omitted closing tags are valid.
+    assert_eq!(lint_kinds(b"<ul><li>a<li>b</ul><p>c", &cfg), vec![]);
+    assert_eq!(
+        lint_kinds(b"<div>\n  <span>a</div>\n</span></div></b>", &cfg),
+        vec![
+            (
+                2,
+                10,
+                ProblemKind::UnmatchedClosingTag { name: name("div") }
+            ),
+            (3, 14, ProblemKind::UnmatchedClosingTag { name: name("b") }),
+        ]
+    );
+    assert_eq!(
+        lint_kinds(b"<p>a<br></br></ >b</>", &cfg),
+        vec![
+            (1, 9, ProblemKind::VoidClosingTag { name: name("br") }),
+            (1, 14, ProblemKind::MalformedClosingTag),
+            (1, 19, ProblemKind::MalformedClosingTag),
+        ]
+    );
+    assert_eq!(
+        lint_kinds(
+            b"<html><head></head><head></head><body><body class=a></html>",
+            &cfg
+        ),
+        vec![
+            (1, 20, ProblemKind::DuplicateTag { name: name("head") }),
+            (
+                1,
+                26,
+                ProblemKind::UnmatchedClosingTag { name: name("head") }
+            ),
+            (1, 39, ProblemKind::DuplicateTag { name: name("body") }),
+        ]
+    );
+    assert_eq!(
+        lint_kinds(b"<a href=x title=y HREF=z>", &cfg),
+        vec![(
+            1,
+            19,
+            ProblemKind::DuplicateAttribute { name: name("href") }
+        )]
+    );
+    // Columns are counted in characters, and problems are ordered by position even when found later.
+    assert_eq!(
+        lint_kinds("<p>é<script>a<!-- b".as_bytes(), &cfg),
+        vec![(
+            1,
+            5,
+            ProblemKind::UnclosedRawText {
+                name: name("script")
+            }
+        )]
+    );
+    assert_eq!(
+        lint_kinds(b"<textarea></div><!-- a", &cfg),
+        vec![(
+            1,
+            1,
+            ProblemKind::UnclosedRawText {
+                name: name("textarea")
+            }
+        )]
+    );
+    assert_eq!(
+        lint_kinds(b"<div><!-- a</div>", &cfg),
+        vec![(1, 6, ProblemKind::UnclosedComment)]
+    );
+    // Plaintext can't be closed.
+    assert_eq!(lint_kinds(b"<plaintext></plaintext>", &cfg), vec![]);
+
+    let problems = lint(b"<br></br><p a a>", &cfg);
+    assert_eq!(problems[0].severity(), Severity::Warning);
+    assert_eq!(problems[1].severity(), Severity::Error);
+    assert_eq!(problems[1].position, 14);
+    assert_eq!(
+        problems[1].to_string(),
+        "1:15: error: Duplicate \"a\" attribute; only the last value is kept"
+    );
+
+    let mut cfg = Cfg::new();
+    cfg.max_depth = Some(2);
+    assert_eq!(
+        lint_kinds(b"<div><div></a><div>", &cfg),
+        vec![
+            (1, 11, ProblemKind::UnmatchedClosingTag { name: name("a") }),
+            (1, 15, ProblemKind::TooDeeplyNested { max: 2 }),
+        ]
+    );
+}
+
+fn diff_strings(a: &'static [u8], b: &'static [u8]) -> Vec<String> {
+    diff(a, b, &Cfg::new())
+        .unwrap()
+        .iter()
+        .map(|d| d.to_string())
+        .collect()
+}
+
+#[test]
+fn test_diff() {
+    // Formatting-only differences are ignored.
+    assert_eq!(
+        diff_strings(
+            b"<!DOCTYPE html>\n<html>\n  <body>\n    <p id=x class='a &amp; b'>Hello,   world!\n    <p>Two\n  </body>\n</html>",
+            b"<!doctype html><body><P class=\"a &#38; b\" id=\"x\">Hello, world!</p><p>Two</p>",
+        ),
+        Vec::<String>::new()
+    );
+    // Whitespace is significant in `<pre>` and between inline elements.
+    assert_eq!(
+        diff_strings(b"<pre> a </pre><p>a <b>b</b>", b"<pre>a</pre><p>a<b>b</b>"),
+        vec![
+            "~ pre > #text: \" a \" -> \"a\"",
+            "~ p > #text: \"a \" -> \"a\"",
+        ]
+    );
+    assert_eq!(
+        diff_strings(
+            b"<div><p>One<p>Two<p>Three</div><span title=t>x</span>",
+            b"<div><p>One<p>Three<p>Four</div><span class=c>x</span>",
+        ),
+        vec![
+            "- div > p[2]: <p>Two</p>",
+            "+ div > p[3]: <p>Four</p>",
+            "+ span [class=\"c\"]",
+            "- span [title=\"t\"]",
+        ]
+    );
+    // Unchanged subtrees are matched even when they're nested, and only the changed node is reported.
+    assert_eq!(
+        diff_strings(
+            b"<ul><li><b>a</b><li><b>b</b><li><b>c</b></ul><ul><li><b>a</b></ul>",
+            b"<ul><li><b>a</b><li><b>x</b><li><b>c</b></ul><ul><li><b>a</b></ul>",
+        ),
+        vec!["~ ul[1] > li[2] > b > #text: \"b\" -> \"x\""]
+    );
+    // Nodes with the same name are compared instead of replaced.
+    assert_eq!(
+        diff(
+            b"<div><a href=x>a<!--c--></a>",
+            b"<div><a href=y>b</a>",
+            &Cfg::new()
+        )
+        .unwrap()
+        .into_iter()
+        .map(|d| (d.path, d.kind))
+        .collect::<Vec<_>>(),
+        vec![
+            (
+                "div > a".to_string(),
+                DifferenceKind::AttributeChanged {
+                    name: "href".to_string(),
+                    old: "x".to_string(),
+                    new: "y".to_string(),
+                }
+            ),
+            (
+                "div > a > #text".to_string(),
+                DifferenceKind::Changed {
+                    old: "a".to_string(),
+                    new: "b".to_string(),
+                }
+            ),
+            (
+                "div > a > #comment".to_string(),
+                DifferenceKind::Removed {
+                    node: "<!--c-->".to_string(),
+                }
+            ),
+        ]
+    );
+
+    // Whitespace is normalised according to the parent before `<body>` is unwrapped.
+    let mut cfg = Cfg::new();
+    cfg.whitespace_rules
+        .insert_tag(b"body", WhitespaceCategory::WhitespaceSensitive);
+    assert_eq!(
+        diff(b"<body> a </body>", b"<body>a</body>", &cfg)
+            .unwrap()
+            .iter()
+            .map(|d| d.to_string())
+            .collect::<Vec<_>>(),
+        vec!["~ #text: \" a \" -> \"a\""]
+    );
+
+    // Deep nesting doesn't overflow the stack, and is reported instead of compared.
+    let mut cfg = Cfg::new();
+    cfg.max_depth = Some(100);
+    let deep = [&b"<p>a</p>"[..], &b"<div>".repeat(100_000)].concat();
+    let deep_changed = [&b"<p>b</p>"[..], &b"<div>".repeat(100_000), b"c"].concat();
+    assert_eq!(
+        diff(&deep, &deep_changed, &cfg).unwrap_err(),
+        DepthLimitExceeded { max: 100 }
+    );
+    assert_eq!(
+        diff(&deep, &deep_changed, &Cfg::new()).unwrap_err(),
+        DepthLimitExceeded {
+            max: DEFAULT_MAX_DEPTH
+        }
+    );
+}
+
+fn eval_canonicalised(src: &'static [u8], expected: &'static str, cfg: &CanonicaliseCfg) {
+    let mut out = Vec::new();
+    canonicalise_with_cfg(&mut out, src, cfg).unwrap();
+    assert_eq!(from_utf8(&out).unwrap(), expected);
+}
+
+#[test]
+fn test_canonicalise() {
+    let src: &[u8] = b"<P z=1 A='x&quot;' b=\"\xC3\xA9 <>\" c>a &lt; &gt; \xC3\xA9  b</p>";
+    let mut out = Vec::new();
+    canonicalise(&mut out, src).unwrap();
+    let expected = "<p a='x\"' b=\"\u{e9} <>\" c z=1>a &lt; > \u{e9}  b</p>";
+    assert_eq!(from_utf8(&out).unwrap(), expected);
+    eval_canonicalised(src, expected, &CanonicaliseCfg::new());
+    // The code is UTF-8 regardless of the declared encoding.
+    eval_canonicalised(
+        "<meta charset=windows-1252><p>\u{e9}".as_bytes(),
+        "<meta charset=utf-8><p>\u{e9}",
+        &CanonicaliseCfg::new(),
+    );
+
+    let mut cfg = CanonicaliseCfg::new();
+    cfg.sort_attributes = false;
+    cfg.attribute_quotes = AttributeQuotes::Minimal;
+    eval_canonicalised(
+        src,
+        "<p z=1 a=\"x&#34;\" b=\"\u{e9} <>\" c>a &lt; > \u{e9}  b</p>",
+        &cfg,
+    );
+    cfg.attribute_quotes = AttributeQuotes::Single;
+    cfg.entities = EntityPolicy::Conservative;
+    eval_canonicalised(
+        src,
+        "<p z='1' a='x&#34;' b='\u{e9} &lt;&gt;' c>a &lt; &gt; \u{e9}  b</p>",
+        &cfg,
+    );
+    cfg.attribute_quotes = AttributeQuotes::Double;
+    cfg.entities = EntityPolicy::Ascii;
+    cfg.normalise_whitespace = true;
+    eval_canonicalised(
+        src,
+        "<p z=\"1\" a=\"x&#34;\" b=\"&#233; &lt;&gt;\" c>a &lt; &gt; &#233; b</p>",
+        &cfg,
+    );
+    cfg.attribute_quotes = AttributeQuotes::Source;
+    cfg.entities = EntityPolicy::Source;
+    cfg.normalise_whitespace = false;
+    eval_canonicalised(
+        src,
+        "<p z=1 a='x&quot;' b=\"\u{e9} <>\" c>a &lt; &gt; \u{e9}  b</p>",
+        &cfg,
+    );
+    // Values that are changed after parsing are written like `Minimal`.
+    cfg.normalise_whitespace = true;
+    eval_canonicalised(
+        src,
+        "<p z=1 a='x&quot;' b=\"\u{e9} <>\" c>a &lt; > \u{e9} b</p>",
+        &cfg,
+    );
+    // As is text joined from around dropped malformed code.
+    eval_canonicalised(b"&am</p>p;", "&amp;amp;", &cfg);
+
+    let mut cfg = CanonicaliseCfg::new();
+    cfg.indent = Some(2);
+    // Indentation is only added where whitespace is insignificant, and whitespace-only text there is dropped.
+    eval_canonicalised(
+        b"<!doctype html><html><head><title>T</title></head><body><div> <p>a <b>b</b></p><ul><li>c</ul></div><pre>\n<div><span>d</span></div></pre></body></html>",
+        "<!DOCTYPE html>\n<html>\n  <head>\n    <title>T</title>\n  </head>\n  <body>\n    <div>\n      <p>a <b>b</b></p>\n      <ul>\n        <li>c\n      </ul>\n    </div>\n    <pre>\n<div><span>d</span></div></pre>\n  </body>\n</html>\n",
+        &cfg,
+    );
+    // Text that isn't whitespace prevents indentation, as line breaks next to it would be significant.
+    eval_canonicalised(b"<div>a<p>b</p></div>", "<div>a<p>b</p></div>\n", &cfg);
+
+    let mut cfg = CanonicaliseCfg::new();
+    cfg.max_depth = Some(100);
+    let deep = b"<div>".repeat(100_000);
+    let err = canonicalise_with_cfg(&mut Vec::new(), &deep, &cfg).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    let err = canonicalise_with_cfg(&mut Vec::new(), &deep, &CanonicaliseCfg::new()).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    eval_canonicalised(b"<div><div>a</div></div>", "<div><div>a</div></div>", &cfg);
+}
+
+#[test]
+fn test_format() {
+    let src: &[u8] = b"<!doctype html><html lang=en><head><title>a  b</title><script>if (a) {\n b()\n}</script></head><body><nav><ul><li><a href=/>Home</a><li>Two <b>2</b></ul></nav><p>Text <span>x</span><span>y</span><div>a<p>b</p></div><pre>\n <i>c</i>\n</pre><textarea> d\n</textarea></body></html>";
+    let mut cfg = FormatCfg::new();
+    assert_eq!(
+        from_utf8(&format(src, &cfg).unwrap()).unwrap(),
+        "<!DOCTYPE html>\n<html lang=en>\n  <head>\n    <title>a  b</title>\n    <script>if (a) {\n b()\n}</script>\n  </head>\n  <body>\n    <nav>\n      <ul>\n        <li><a href=/>Home</a>\n        <li>Two <b>2</b>\n      </ul>\n    </nav>\n    <p>Text <span>x</span><span>y</span>\n    <div>a<p>b</p></div>\n    <pre>\n <i>c</i>\n</pre>\n    <textarea> d\n</textarea>\n  </body>\n</html>\n",
+    );
+    // Minifying the formatted code gives the same result as minifying the original code.
+    let min_cfg = Cfg::new();
+    assert_eq!(
+        from_utf8(&minify(&format(src, &cfg).unwrap(), &min_cfg)).unwrap(),
+        from_utf8(&minify(src, &min_cfg)).unwrap(),
+    );
+
+    // Character references are kept as they are in the source.
+    assert_eq!(
+        from_utf8(
+            &format(
+                b"<p title='&amp;&quot;' lang=en&#45;GB>a&nbsp;&lt;b&gt; &amp",
+                &cfg
+            )
+            .unwrap()
+        )
+        .unwrap(),
+        "<p title='&amp;&quot;' lang=en&#45;GB>a&nbsp;&lt;b&gt; &amp\n",
+    );
+
+    cfg.indent = 1;
+    cfg.restore_closing_tags = true;
+    assert_eq!(
+        from_utf8(&format(b"<ul><li>a<li><p>b<p>c</ul>", &cfg).unwrap()).unwrap(),
+        "<ul>\n <li>a</li>\n <li><p>b</p><p>c</p></li>\n</ul>\n",
+    );
+
+    // Deep nesting doesn't overflow the stack, and is reported instead of formatted.
+    cfg.max_depth = Some(100);
+    let deep = b"<div>".repeat(100_000);
+    assert_eq!(
+        format(&deep, &cfg).unwrap_err(),
+        DepthLimitExceeded { max: 100 }
+    );
+    assert_eq!(
+        format(&deep, &FormatCfg::new()).unwrap_err(),
+        DepthLimitExceeded {
+            max: DEFAULT_MAX_DEPTH
+        }
+    );
+    cfg.max_depth = Some(DEFAULT_MAX_DEPTH);
+
+    // Whitespace rules decide where line breaks can be added, like when minifying.
+    let src: &[u8] = b"<ui-list><ui-item>a</ui-item><ui-item>b</ui-item></ui-list><ui-code><div><p>c</p></div></ui-code>";
+    assert_eq!(
+        from_utf8(&format(src, &cfg).unwrap()).unwrap(),
+        "<ui-list><ui-item>a</ui-item><ui-item>b</ui-item></ui-list>\n<ui-code><div>\n  <p>c</p>\n </div></ui-code>\n",
+    );
+    let mut min_cfg = Cfg::new();
+    min_cfg
+        .whitespace_rules
+        .insert_tag(b"ui-list", WhitespaceCategory::Layout);
+    min_cfg
+        .whitespace_rules
+        .insert_tag(b"ui-code", WhitespaceCategory::WhitespaceSensitive);
+    cfg.whitespace_rules = min_cfg.whitespace_rules.clone();
+    assert_eq!(
+        from_utf8(&format(src, &cfg).unwrap()).unwrap(),
+        "<ui-list>\n <ui-item>a</ui-item>\n <ui-item>b</ui-item>\n</ui-list>\n<ui-code><div><p>c</p></div></ui-code>\n",
+    );
+    assert_eq!(
+        from_utf8(&minify(&format(src, &cfg).unwrap(), &min_cfg)).unwrap(),
+        from_utf8(&minify(src, &min_cfg)).unwrap(),
+    );
+
+    // The input is decoded like when minifying, and the output is UTF-8.
+    let mut cfg = FormatCfg::new();
+    assert_eq!(
+        from_utf8(&format(b"<meta charset=windows-1252><p>caf\xe9", &cfg).unwrap()).unwrap(),
+        "<meta charset=utf-8>\n<p>caf\u{e9}\n",
+    );
+    cfg.input_encoding = Some(Encoding::Windows1252);
+    assert_eq!(
+        from_utf8(&format(b"<p>caf\xe9", &cfg).unwrap()).unwrap(),
+        "<p>caf\u{e9}\n",
+    );
+}
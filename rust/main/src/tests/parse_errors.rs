@@ -0,0 +1,484 @@
+use crate::cfg::Cfg;
+use crate::common::gen::entities::{EntityType, ENTITY};
+use crate::common::pattern::TrieNodeMatch;
+use crate::minify;
+
+// A checker for the tokenizer parse errors listed at https://html.spec.whatwg.org/multipage/parsing.html#parse-errors, returning the codes of any found.
+// It follows the tokenizer states closely enough for minified output, but doesn't implement tree construction, script data escape states, or the preprocessing of the input stream.
+struct Checker<'s> {
+    src: &'s [u8],
+    pos: usize,
+    errors: Vec<&'static str>,
+}
+
+const RAW_TEXT: &[&[u8]] = &[
+    b"iframe",
+    b"noembed",
+    b"noframes",
+    b"script",
+    b"style",
+    b"xmp",
+];
+const RCDATA: &[&[u8]] = &[b"textarea", b"title"];
+const VOID: &[&[u8]] = &[
+    b"area", b"base", b"br", b"col", b"embed", b"hr", b"img", b"input", b"link", b"meta",
+    b"source", b"track", b"wbr",
+];
+
+fn is_ws(c: u8) -> bool {
+    matches!(c, b'\t' | b'\n' | b'\x0c' | b'\r' | b' ')
+}
+
+impl<'s> Checker<'s> {
+    fn peek(&self, offset: usize) -> Option<u8> {
+        self.src.get(self.pos + offset).copied()
+    }
+
+    fn rest(&self) -> &'s [u8] {
+        &self.src[self.pos..]
+    }
+
+    fn error(&mut self, code: &'static str) {
+        self.errors.push(code);
+    }
+
+    fn check(mut self) -> Vec<&'static str> {
+        let mut foreign_depth = 0usize;
+        while self.pos < self.src.len() {
+            match self.src[self.pos] {
+                b'&' => self.char_ref(false),
+                b'<' => {
+                    if let Some(name) = self.tag_open(foreign_depth > 0) {
+                        if name.starts_with(b"/") {
+                            if (name == b"/svg" || name == b"/math") && foreign_depth > 0 {
+                                foreign_depth -= 1;
+                            };
+                        } else if name == b"svg" || name == b"math" {
+                            foreign_depth += 1;
+                        } else if foreign_depth == 0 && RAW_TEXT.contains(&name.as_slice()) {
+                            self.raw_text(&name, false);
+                        } else if foreign_depth == 0 && RCDATA.contains(&name.as_slice()) {
+                            self.raw_text(&name, true);
+                        };
+                    };
+                }
+                _ => self.pos += 1,
+            };
+        }
+        self.errors
+    }
+
+    // Skips to the appropriate end tag, checking character references if RCDATA.
+    fn raw_text(&mut self, name: &[u8], rcdata: bool) {
+        let mut end = b"</".to_vec();
+        end.extend_from_slice(name);
+        while self.pos < self.src.len() && !self.rest().starts_with(&end) {
+            if rcdata && self.src[self.pos] == b'&' {
+                self.char_ref(false);
+            } else {
+                self.pos += 1;
+            };
+        }
+    }
+
+    fn char_ref(&mut self, in_attr_val: bool) {
+        // Skip `&`.
+        self.pos += 1;
+        if self.peek(0) == Some(b'#') {
+            self.pos += 1;
+            let hex = matches!(self.peek(0), Some(b'x') | Some(b'X'));
+            if hex {
+                self.pos += 1;
+            };
+            let mut value: u64 = 0;
+            let mut digits = 0;
+            while let Some(c) = self.peek(0) {
+                let d = match c {
+                    b'0'..=b'9' => c - b'0',
+                    b'a'..=b'f' if hex => c - b'a' + 10,
+                    b'A'..=b'F' if hex => c - b'A' + 10,
+                    _ => break,
+                };
+                value = (value * if hex { 16 } else { 10 } + d as u64).min(0x110000);
+                digits += 1;
+                self.pos += 1;
+            }
+            if digits == 0 {
+                return self.error("absence-of-digits-in-numeric-character-reference");
+            };
+            if self.peek(0) == Some(b';') {
+                self.pos += 1;
+            } else {
+                self.error("missing-semicolon-after-character-reference");
+            };
+            match value {
+                0 => self.error("null-character-reference"),
+                v if v > 0x10FFFF => self.error("character-reference-outside-unicode-range"),
+                0xD800..=0xDFFF => self.error("surrogate-character-reference"),
+                0xFDD0..=0xFDEF => self.error("noncharacter-character-reference"),
+                v if v & 0xFFFE == 0xFFFE => self.error("noncharacter-character-reference"),
+                0x0D | 0x7F..=0x9F => self.error("control-character-reference"),
+                v if v < 0x20 && !is_ws(v as u8) => self.error("control-character-reference"),
+                _ => {}
+            };
+            return;
+        };
+        match ENTITY.longest_matching_prefix(&self.src[self.pos - 1..]) {
+            TrieNodeMatch::Found {
+                len,
+                value: EntityType::Named(_),
+            } => {
+                let terminated = self.src[self.pos - 1 + len - 1] == b';';
+                let next = self.src.get(self.pos - 1 + len).copied();
+                if !terminated
+                    && in_attr_val
+                    && next
+                        .filter(|&c| c == b'=' || c.is_ascii_alphanumeric())
+                        .is_some()
+                {
+                    // Not a character reference.
+                } else if !terminated {
+                    self.error("missing-semicolon-after-character-reference");
+                };
+                self.pos += len - 1;
+            }
+            _ => {
+                let len = self
+                    .rest()
+                    .iter()
+                    .take_while(|c| c.is_ascii_alphanumeric())
+                    .count();
+                if len > 0 && self.peek(len) == Some(b';') {
+                    self.error("unknown-named-character-reference");
+                };
+                self.pos += len;
+            }
+        };
+    }
+
+    // `<` is next. Returns the tag name (prefixed with `/` for end tags) if a tag was opened.
+    fn tag_open(&mut self, foreign: bool) -> Option<Vec<u8>> {
+        let rest = self.rest();
+        if rest.starts_with(b"<!--") {
+            self.comment();
+            return None;
+        };
+        if rest.len() >= 9 && rest[..9].eq_ignore_ascii_case(b"<!doctype") {
+            self.doctype();
+            return None;
+        };
+        self.pos += 1;
+        let end_tag = match self.peek(0) {
+            Some(b'!') => {
+                self.error("incorrectly-opened-comment");
+                self.skip_to(b'>');
+                return None;
+            }
+            Some(b'?') => {
+                self.error("unexpected-question-mark-instead-of-tag-name");
+                self.skip_to(b'>');
+                return None;
+            }
+            Some(b'/') => {
+                self.pos += 1;
+                match self.peek(0) {
+                    Some(c) if c.is_ascii_alphabetic() => {}
+                    Some(b'>') => {
+                        self.error("missing-end-tag-name");
+                        self.pos += 1;
+                        return None;
+                    }
+                    None => {
+                        self.error("eof-before-tag-name");
+                        return None;
+                    }
+                    Some(_) => {
+                        self.error("invalid-first-character-of-tag-name");
+                        self.skip_to(b'>');
+                        return None;
+                    }
+                };
+                true
+            }
+            Some(c) if c.is_ascii_alphabetic() => false,
+            None => {
+                self.error("eof-before-tag-name");
+                return None;
+            }
+            Some(_) => {
+                self.error("invalid-first-character-of-tag-name");
+                return None;
+            }
+        };
+        let mut name = if end_tag { b"/".to_vec() } else { Vec::new() };
+        while let Some(c) = self
+            .peek(0)
+            .filter(|&c| !is_ws(c) && c != b'/' && c != b'>')
+        {
+            name.push(c.to_ascii_lowercase());
+            self.pos += 1;
+        }
+        let mut attr_names = Vec::<Vec<u8>>::new();
+        // Whether the last attribute ended with a quoted value, after which whitespace is required.
+        let mut after_quoted_value = false;
+        loop {
+            let ws_start = self.pos;
+            while self.peek(0).filter(|&c| is_ws(c)).is_some() {
+                self.pos += 1;
+            }
+            let had_ws = self.pos > ws_start;
+            match self.peek(0) {
+                None => {
+                    self.error("eof-in-tag");
+                    return None;
+                }
+                Some(b'>') => {
+                    self.pos += 1;
+                    break;
+                }
+                Some(b'/') => {
+                    self.pos += 1;
+                    if self.peek(0) == Some(b'>') {
+                        self.pos += 1;
+                        if end_tag {
+                            self.error("end-tag-with-trailing-solidus");
+                        } else if !foreign
+                            && name != b"svg"
+                            && name != b"math"
+                            && !VOID.contains(&name.as_slice())
+                        {
+                            self.error("non-void-html-element-start-tag-with-trailing-solidus");
+                        };
+                        break;
+                    };
+                    self.error("unexpected-solidus-in-tag");
+                    continue;
+                }
+                Some(_) => {}
+            };
+            if after_quoted_value && !had_ws {
+                self.error("missing-whitespace-between-attributes");
+            };
+            after_quoted_value = false;
+            if end_tag {
+                self.error("end-tag-with-attributes");
+            };
+            // Attribute name.
+            let mut attr_name = Vec::new();
+            if self.peek(0) == Some(b'=') {
+                self.error("unexpected-equals-sign-before-attribute-name");
+                attr_name.push(b'=');
+                self.pos += 1;
+            };
+            while let Some(c) = self
+                .peek(0)
+                .filter(|&c| !is_ws(c) && c != b'/' && c != b'>' && c != b'=')
+            {
+                if c == b'"' || c == b'\'' || c == b'<' {
+                    self.error("unexpected-character-in-attribute-name");
+                };
+                attr_name.push(c.to_ascii_lowercase());
+                self.pos += 1;
+            }
+            if attr_names.contains(&attr_name) {
+                self.error("duplicate-attribute");
+            };
+            attr_names.push(attr_name);
+            while self.peek(0).filter(|&c| is_ws(c)).is_some() {
+                self.pos += 1;
+            }
+            if self.peek(0) != Some(b'=') {
+                continue;
+            };
+            self.pos += 1;
+            while self.peek(0).filter(|&c| is_ws(c)).is_some() {
+                self.pos += 1;
+            }
+            match self.peek(0) {
+                Some(q @ b'"') | Some(q @ b'\'') => {
+                    self.pos += 1;
+                    loop {
+                        match self.peek(0) {
+                            None => {
+                                self.error("eof-in-tag");
+                                return None;
+                            }
+                            Some(c) if c == q => {
+                                self.pos += 1;
+                                break;
+                            }
+                            Some(b'&') => self.char_ref(true),
+                            Some(_) => self.pos += 1,
+                        };
+                    }
+                    after_quoted_value = true;
+                }
+                Some(b'>') => {
+                    self.error("missing-attribute-value");
+                }
+                _ => loop {
+                    match self.peek(0) {
+                        None => {
+                            self.error("eof-in-tag");
+                            return None;
+                        }
+                        Some(c) if is_ws(c) || c == b'>' => break,
+                        Some(b'&') => self.char_ref(true),
+                        Some(c) => {
+                            if matches!(c, b'"' | b'\'' | b'<' | b'=' | b'`') {
+                                self.error("unexpected-character-in-unquoted-attribute-value");
+                            };
+                            self.pos += 1;
+                        }
+                    };
+                },
+            };
+        }
+        Some(name)
+    }
+
+    fn skip_to(&mut self, c: u8) {
+        while self.pos < self.src.len() && self.src[self.pos] != c {
+            self.pos += 1;
+        }
+        self.pos = (self.pos + 1).min(self.src.len());
+    }
+
+    fn comment(&mut self) {
+        self.pos += 4;
+        for abrupt_end in [&b">"[..], b"->"].iter() {
+            if self.rest().starts_with(abrupt_end) {
+                self.pos += abrupt_end.len();
+                return self.error("abrupt-closing-of-empty-comment");
+            };
+        }
+        loop {
+            let rest = self.rest();
+            if rest.is_empty() {
+                return self.error("eof-in-comment");
+            };
+            if rest.starts_with(b"-->") {
+                self.pos += 3;
+                return;
+            };
+            if rest.starts_with(b"--!>") {
+                self.error("incorrectly-closed-comment");
+                self.pos += 4;
+                return;
+            };
+            if rest.starts_with(b"<!--") {
+                self.error("nested-comment");
+            };
+            self.pos += 1;
+        }
+    }
+
+    fn doctype(&mut self) {
+        self.pos += 9;
+        if self.peek(0).filter(|&c| is_ws(c)).is_none() {
+            self.error("missing-whitespace-before-doctype-name");
+        };
+        while let Some(c) = self.peek(0) {
+            self.pos += 1;
+            if c == b'>' {
+                return;
+            };
+        }
+        self.error("eof-in-doctype");
+    }
+}
+
+fn parse_errors(html: &[u8]) -> Vec<&'static str> {
+    Checker {
+        src: html,
+        pos: 0,
+        errors: Vec::new(),
+    }
+    .check()
+}
+
+fn eval_no_parse_errors(src: &'static [u8]) {
+    let out = minify(src, &Cfg::strict());
+    assert_eq!(
+        parse_errors(&out),
+        Vec::<&str>::new(),
+        "{}",
+        String::from_utf8_lossy(&out)
+    );
+}
+
+#[test]
+fn test_checker_finds_parse_errors() {
+    // The default configuration is expected to produce these, which ensures the checker isn't vacuous.
+    let out = minify(
+        b"<p>&lt;div&gt; 1 < 2</p><a b=\"c d\" e=\"f g\">",
+        &Cfg::new(),
+    );
+    let errors = parse_errors(&out);
+    assert!(errors.contains(&"missing-semicolon-after-character-reference"));
+    assert!(errors.contains(&"invalid-first-character-of-tag-name"));
+    assert!(errors.contains(&"missing-whitespace-between-attributes"));
+    assert_eq!(parse_errors(b"<!---->"), Vec::<&str>::new());
+    assert_eq!(
+        parse_errors(b"<!-->"),
+        vec!["abrupt-closing-of-empty-comment"]
+    );
+    assert_eq!(
+        parse_errors(b"&#6;&#x;"),
+        vec![
+            "control-character-reference",
+            "absence-of-digits-in-numeric-character-reference"
+        ]
+    );
+    assert_eq!(
+        parse_errors(b"<a x=\"&notit;\" y=&ampz>&nope;"),
+        vec!["unknown-named-character-reference"]
+    );
+}
+
+#[test]
+fn test_strict_character_references() {
+    eval_no_parse_errors(b"<p>1 < 2 &amp; 3 > 2 &lt;div&gt; &copy 2022 &nLt; x&amp;y &amp;lt;</p>");
+    eval_no_parse_errors(b"<p>a <3 b </ c <? d <! e </>");
+    eval_no_parse_errors(
+        b"<a title='\"q\"' data-x=\"a&b\" href=?a=1&b=2&copy=3 class=\" a  b \">link</a>",
+    );
+    eval_no_parse_errors(b"<textarea>a &lt; b &amp c</textarea><title>&lt;T&gt;</title>");
+}
+
+#[test]
+fn test_strict_attributes() {
+    eval_no_parse_errors(b"<div a=1 b=\"2\" c='3' d=\"x y\" e=\"a`b\" f=\"=c\" g=\"<>\"></div>");
+    eval_no_parse_errors(b"<div \"a=1 b<c=2 =d=3 e'=4></div>");
+    eval_no_parse_errors(b"<svg><path d=\"M0 0\"/><circle r=1 /></svg>");
+    eval_no_parse_errors(b"<img src=a.png alt=\"\"><input value=\"\" disabled>");
+}
+
+#[test]
+fn test_strict_markup_declarations() {
+    eval_no_parse_errors(b"<!DOCTYPE html><![CDATA[x]]><?php echo 1 ?><p>x");
+    eval_no_parse_errors(b"<!doctype html");
+    let mut cfg = Cfg::strict();
+    cfg.keep_comments = true;
+    for src in [
+        b"<!-- ok --><!-- a -- b --><!--x<!--y--><!---->".as_ref(),
+        b"<!--> a --><!---> b -->",
+        b"<p>x<!-- unterminated",
+    ]
+    .iter()
+    {
+        let out = minify(src, &cfg);
+        assert_eq!(
+            parse_errors(&out),
+            Vec::<&str>::new(),
+            "{}",
+            String::from_utf8_lossy(&out)
+        );
+    }
+}
+
+#[test]
+fn test_strict_email_fixtures() {
+    eval_no_parse_errors(include_bytes!("email/newsletter.html"));
+    eval_no_parse_errors(include_bytes!("email/receipt.html"));
+}
@@ -0,0 +1,565 @@
+use std::borrow::Cow;
+
+use bumpalo::collections::Vec as BumpVec;
+use bumpalo::Bump;
+use proptest::prelude::*;
+use proptest::sample::{select, subsequence};
+
+use crate::ast::c14n::c14n_serialise_nodes;
+use crate::ast::normalise::normalise;
+use crate::ast::{NodeData, ScriptOrStyleLang};
+use crate::cfg::canonicalise::CanonicaliseCfg;
+use crate::cfg::whitespace::WhitespaceRules;
+use crate::cfg::Cfg;
+use crate::common::spec::script::JAVASCRIPT_MIME_TYPES;
+use crate::common::spec::tag::ns::Namespace;
+use crate::common::spec::tag::EMPTY_SLICE;
+use crate::common::tests::options::{enable_option, OPTIONS};
+use crate::encoding::Input;
+use crate::minify;
+use crate::parse::content::parse_content;
+use crate::parse::Code;
+
+fn cfg(options: &[&str]) -> Cfg {
+    let mut cfg = Cfg::new();
+    for &opt in options {
+        enable_option!(cfg, opt);
+    }
+    cfg
+}
+
+// Options that can make the output longer than the source, such as by adding quotes, semicolons, or character
+// references.
+const LENGTHENING_OPTIONS: &[&str] = &[
+    "always_quote_attribute_values",
+    "ascii_only",
+    "conservative_entities",
+    "ensure_no_parse_errors",
+];
+
+fn options() -> impl Strategy<Value = Vec<&'static str>> {
+    subsequence(OPTIONS, 0..=OPTIONS.len())
+}
+
+fn text() -> impl Strategy<Value = String> {
+    prop_oneof![
+        "[a-zA-Z0-9 \n\t]{1,8}",
+        select(
+            &[
+                "&amp;",
+                "&amp",
+                "&ampx",
+                "&lt;p&gt;",
+                "&LT",
+                "&nbsp;",
+                "&#x20;",
+                "&#0;",
+                "1 < 2",
+                "a &",
+                "é",
+                "]]>",
+            ][..]
+        )
+        .prop_map(String::from),
+    ]
+}
+
+fn attr_value() -> impl Strategy<Value = String> {
+    prop_oneof![
+        "[a-zA-Z0-9&;# ]{0,6}",
+        select(
+            &[
+                "",
+                "  a  b  ",
+                "TEXT",
+                "a\"b",
+                "a'b",
+                "'\"",
+                "x=y",
+                "a > b",
+                "`a`",
+                "&amp;&lt;",
+                "&ampx",
+                "&#32;1",
+                "_self",
+                "text",
+                "é",
+            ][..]
+        )
+        .prop_map(String::from),
+    ]
+}
+
+fn attrs() -> impl Strategy<Value = String> {
+    let names = select(
+        &[
+            "class", "id", "title", "type", "href", "alt", "data-x", "hidden", "target", "style",
+            "CLASS",
+        ][..],
+    );
+    // The value is unquoted, single quoted, double quoted, or missing.
+    prop::collection::vec((names, attr_value(), 0..4u8), 0..4).prop_map(|attrs| {
+        let mut out = String::new();
+        for (name, value, quote) in attrs {
+            out.push(' ');
+            out.push_str(name);
+            match quote {
+                0 if !value.is_empty()
+                    && !value
+                        .bytes()
+                        .any(|c| c.is_ascii_whitespace() || b"\"'=<>`".contains(&c)) =>
+                {
+                    out.push('=');
+                    out.push_str(&value);
+                }
+                1 => {
+                    out.push_str("='");
+                    out.push_str(&value.replace('\'', "&#39;"));
+                    out.push('\'');
+                }
+                3 => {}
+                _ => {
+                    out.push_str("=\"");
+                    out.push_str(&value.replace('"', "&quot;"));
+                    out.push('"');
+                }
+            };
+        }
+        out
+    })
+}
+
+fn leaf() -> impl Strategy<Value = String> {
+    prop_oneof![
+        3 => text(),
+        1 => select(&[
+            "<!-- c -->",
+            "<!---->",
+            "<!--[if IE]><p>a<![endif]-->",
+            "<!x>",
+            "<?y?>",
+            "<br/>",
+            "<svg><path d=\" M 0 0 \"/></svg>",
+            "<svg viewBox=\"0 0 1 1\"><circle r=\"1\"></circle><text> a </text></svg>",
+            "<noscript><p>a</p></noscript>",
+            "<table><tr><td>a<td> b </table>",
+            "<script></script>",
+            "<script> let a = 1; </script>",
+            "<script>a</scr + ipt></script>",
+            "<script type=text/template> <p>a</p> </script>",
+            "<style> a { b: c } </style>",
+            "<textarea> a  b </textarea>",
+            "<textarea><p>&amp;</textarea>",
+            "<title> a &lt; </title>",
+        ][..])
+        .prop_map(String::from),
+        1 => (select(&["br", "img", "input", "meta", "hr"][..]), attrs())
+            .prop_map(|(tag, attrs)| format!("<{}{}>", tag, attrs)),
+    ]
+}
+
+// Generates content from arbitrarily nested elements, including ones that are implicitly closed or have omitted closing
+// tags.
+fn content() -> impl Strategy<Value = String> {
+    let tags = select(
+        &[
+            "div", "p", "span", "b", "a", "ul", "li", "pre", "label", "button", "svg", "DIV",
+        ][..],
+    );
+    let node = leaf().prop_recursive(4, 64, 6, move |inner| {
+        (
+            tags.clone(),
+            attrs(),
+            prop::collection::vec(inner, 0..6),
+            any::<bool>(),
+        )
+            .prop_map(|(tag, attrs, children, closed)| {
+                let mut out = format!("<{}{}>", tag, attrs);
+                out.push_str(&children.concat());
+                if closed {
+                    out.push_str(&format!("</{}>", tag));
+                };
+                out
+            })
+    });
+    prop::collection::vec(node, 0..8).prop_map(|nodes| nodes.concat())
+}
+
+fn document() -> impl Strategy<Value = String> {
+    (any::<bool>(), any::<bool>(), content()).prop_map(|(doctype, wrap, content)| {
+        let mut out = String::new();
+        if doctype {
+            out.push_str("<!DOCTYPE html>");
+        };
+        if wrap {
+            out.push_str("<html><head><title>t</title></head><body>");
+        };
+        out.push_str(&content);
+        if wrap {
+            out.push_str("</body></html>");
+        };
+        out
+    })
+}
+
+fn lossy(v: &[u8]) -> String {
+    String::from_utf8_lossy(v).into_owned()
+}
+
+// The oracle below decides what the minifier is allowed to change from the specs and the documented whitespace groups,
+// without using the minifier's own rules, so that mistakes in those rules are found instead of being applied to both
+// sides.
+
+// https://infra.spec.whatwg.org/#ascii-whitespace
+fn is_whitespace(c: u8) -> bool {
+    matches!(c, b'\t' | b'\n' | b'\x0c' | b'\r' | b' ')
+}
+
+fn trim(v: &[u8]) -> &[u8] {
+    let start = v.iter().position(|&c| !is_whitespace(c)).unwrap_or(v.len());
+    let end = v
+        .iter()
+        .rposition(|&c| !is_whitespace(c))
+        .map_or(start, |i| i + 1);
+    &v[start..end]
+}
+
+// Replaces each run of whitespace with a single space.
+fn collapse(v: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(v.len());
+    for &c in v {
+        if !is_whitespace(c) {
+            out.push(c);
+        } else if out.last() != Some(&b' ') {
+            out.push(b' ');
+        };
+    }
+    out
+}
+
+#[derive(Default)]
+struct AttrSpec {
+    boolean: bool,
+    case_insensitive: bool,
+    collapse: bool,
+    default_value: Option<&'static [u8]>,
+    redundant_if_empty: bool,
+    trim: bool,
+}
+
+// How the values of the attributes that the generator uses can be changed without changing their meaning.
+fn attr_spec(ns: Namespace, tag: &[u8], name: &[u8]) -> AttrSpec {
+    match (ns, tag, name) {
+        // https://html.spec.whatwg.org/multipage/dom.html#classes
+        (_, _, b"class") => AttrSpec {
+            collapse: true,
+            redundant_if_empty: true,
+            trim: true,
+            ..AttrSpec::default()
+        },
+        // https://html.spec.whatwg.org/multipage/dom.html#the-id-attribute
+        (Namespace::Html, _, b"id") => AttrSpec {
+            redundant_if_empty: true,
+            trim: true,
+            ..AttrSpec::default()
+        },
+        // https://html.spec.whatwg.org/multipage/dom.html#the-style-attribute
+        (Namespace::Html, _, b"style") => AttrSpec {
+            redundant_if_empty: true,
+            trim: true,
+            ..AttrSpec::default()
+        },
+        // https://html.spec.whatwg.org/multipage/interaction.html#the-hidden-attribute
+        (Namespace::Html, _, b"hidden") => AttrSpec {
+            boolean: true,
+            ..AttrSpec::default()
+        },
+        // https://url.spec.whatwg.org/#concept-basic-url-parser
+        (Namespace::Html, b"a", b"href") => AttrSpec {
+            trim: true,
+            ..AttrSpec::default()
+        },
+        // https://html.spec.whatwg.org/multipage/document-sequences.html#valid-navigable-target-name-or-keyword
+        (Namespace::Html, b"a", b"target") => AttrSpec {
+            case_insensitive: true,
+            default_value: Some(b"_self"),
+            ..AttrSpec::default()
+        },
+        // Invalid values are in the same state as the default.
+        // https://html.spec.whatwg.org/multipage/form-elements.html#attr-button-type
+        (Namespace::Html, b"button", b"type") => AttrSpec {
+            case_insensitive: true,
+            default_value: Some(b"submit"),
+            trim: true,
+            ..AttrSpec::default()
+        },
+        // https://html.spec.whatwg.org/multipage/input.html#attr-input-type
+        (Namespace::Html, b"input", b"type") => AttrSpec {
+            case_insensitive: true,
+            default_value: Some(b"text"),
+            trim: true,
+            ..AttrSpec::default()
+        },
+        // https://www.w3.org/TR/SVG2/paths.html#PathDataBNF
+        (Namespace::Svg, _, b"d") => AttrSpec {
+            collapse: true,
+            redundant_if_empty: true,
+            trim: true,
+            ..AttrSpec::default()
+        },
+        _ => AttrSpec::default(),
+    }
+}
+
+// Returns the value that's equivalent to all values that are equivalent to `value`, or None if the attribute can be removed.
+fn normalise_attr(
+    cfg: &Cfg,
+    ns: Namespace,
+    tag: &[u8],
+    name: &[u8],
+    value: &[u8],
+) -> Option<Vec<u8>> {
+    if name == b"style" && cfg.minify_css {
+        // Minified CSS is checked by the minifier's own tests.
+        return None;
+    };
+    let spec = attr_spec(ns, tag, name);
+    if spec.boolean {
+        return Some(Vec::new());
+    };
+    let mut value = if spec.trim { trim(value) } else { value }.to_vec();
+    if spec.collapse {
+        value = collapse(&value);
+    };
+    if spec.case_insensitive {
+        value.make_ascii_lowercase();
+    };
+    if (value.is_empty() && spec.redundant_if_empty)
+        || spec.default_value == Some(&value[..])
+        || (ns == Namespace::Html
+            && tag == b"script"
+            && name == b"type"
+            && JAVASCRIPT_MIME_TYPES.contains(&value[..]))
+    {
+        return None;
+    };
+    Some(value)
+}
+
+// Conditional comments, as well as the comments that wrap downlevel-revealed content, which some clients interpret.
+// https://learn.microsoft.com/en-us/previous-versions/windows/internet-explorer/ie-developer/compatibility/ms537512(v=vs.85)
+fn is_conditional_comment(code: &[u8]) -> bool {
+    code.starts_with(b"[if") || code.starts_with(b"<![endif]")
+}
+
+// Removes nodes and attributes that the minifier is allowed to remove, and normalises attribute values and embedded code that it's allowed to change.
+fn remove_minified(cfg: &Cfg, nodes: &mut BumpVec<NodeData>) {
+    // Bangs and processing instructions are always parse errors, and comments can be.
+    nodes.retain(|n| match n {
+        NodeData::Bang { .. } => !cfg.remove_bangs && !cfg.ensure_no_parse_errors,
+        NodeData::Comment { code, .. } => {
+            (cfg.keep_comments || (cfg.keep_conditional_comments && is_conditional_comment(code)))
+                && !cfg.ensure_no_parse_errors
+        }
+        NodeData::Instruction { .. } => {
+            !cfg.remove_processing_instructions && !cfg.ensure_no_parse_errors
+        }
+        _ => true,
+    });
+    for n in nodes.iter_mut() {
+        match n {
+            NodeData::Element {
+                attributes,
+                children,
+                name,
+                namespace,
+                ..
+            } => {
+                attributes.retain(|attr, value| {
+                    match normalise_attr(cfg, *namespace, name, attr, &value.value) {
+                        Some(normalised) => {
+                            value.value = Cow::Owned(normalised);
+                            true
+                        }
+                        None => false,
+                    }
+                });
+                remove_minified(cfg, children);
+            }
+            // Minified code is checked by the minifiers' own tests.
+            NodeData::ScriptOrStyleContent {
+                code,
+                lang: ScriptOrStyleLang::CSS,
+            } if cfg.minify_css => *code = EMPTY_SLICE,
+            NodeData::ScriptOrStyleContent {
+                code,
+                lang: ScriptOrStyleLang::JS,
+            } if cfg.minify_js => *code = EMPTY_SLICE,
+            _ => {}
+        };
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum WhitespaceGroup {
+    Content,
+    Formatting,
+    Layout,
+    Sensitive,
+}
+
+// The whitespace groups documented in the README for the elements that the generator uses, as well as those they imply.
+// Other HTML elements are treated as formatting elements.
+fn whitespace_group(ns: Namespace, parent: &[u8]) -> WhitespaceGroup {
+    match ns {
+        Namespace::Html => match parent {
+            b"pre" | b"textarea" => WhitespaceGroup::Sensitive,
+            b"button" | b"label" | b"li" | b"noscript" | b"p" | b"td" => WhitespaceGroup::Content,
+            b"" | b"body" | b"div" | b"head" | b"html" | b"table" | b"tbody" | b"tr" | b"ul" => {
+                WhitespaceGroup::Layout
+            }
+            _ => WhitespaceGroup::Formatting,
+        },
+        Namespace::Svg => match parent {
+            b"text" => WhitespaceGroup::Content,
+            // Other SVG elements can't contain text.
+            _ => WhitespaceGroup::Layout,
+        },
+    }
+}
+
+// Collapses whitespace in text, trims it at the start and end of content and layout elements, removes whitespace-only
+// text in layout elements, and trims `<script>` and `<style>` contents.
+fn normalise_whitespace(
+    ns: Namespace,
+    descendant_of_pre: bool,
+    parent: &[u8],
+    nodes: &mut BumpVec<NodeData>,
+) {
+    let group = match whitespace_group(ns, parent) {
+        // Whitespace isn't rendered in SVG elements other than text, even in `<pre>`.
+        WhitespaceGroup::Layout if ns == Namespace::Svg => WhitespaceGroup::Layout,
+        _ if descendant_of_pre => WhitespaceGroup::Sensitive,
+        group => group,
+    };
+    let mut merged = BumpVec::new_in(nodes.bump());
+    for n in nodes.drain(..) {
+        match (merged.last_mut(), n) {
+            (Some(NodeData::Text { value, .. }), NodeData::Text { value: next, .. }) => {
+                value.to_mut().extend_from_slice(&next)
+            }
+            (_, n) => merged.push(n),
+        };
+    }
+    *nodes = merged;
+
+    for n in nodes.iter_mut() {
+        match n {
+            NodeData::Element {
+                children,
+                name,
+                namespace,
+                ..
+            } => normalise_whitespace(
+                *namespace,
+                descendant_of_pre || (*namespace == Namespace::Html && name.as_ref() == b"pre"),
+                name,
+                children,
+            ),
+            NodeData::ScriptOrStyleContent { code, .. } => *code = trim(code),
+            NodeData::Text { value, .. } if group != WhitespaceGroup::Sensitive => {
+                *value = Cow::Owned(collapse(value));
+                if group == WhitespaceGroup::Layout && value.as_ref() == b" " {
+                    *value = Cow::Borrowed(EMPTY_SLICE);
+                };
+            }
+            _ => {}
+        };
+    }
+    if matches!(group, WhitespaceGroup::Content | WhitespaceGroup::Layout) {
+        // Only text before the first element or after the last one is at the start or end.
+        for n in nodes.iter_mut() {
+            match n {
+                NodeData::Element { .. } => break,
+                NodeData::Text { value, .. } => {
+                    *value = Cow::Owned(trim(value).to_vec());
+                    if !value.is_empty() {
+                        break;
+                    };
+                }
+                _ => {}
+            };
+        }
+        for n in nodes.iter_mut().rev() {
+            match n {
+                NodeData::Element { .. } => break,
+                NodeData::Text { value, .. } => {
+                    *value = Cow::Owned(trim(value).to_vec());
+                    if !value.is_empty() {
+                        break;
+                    };
+                }
+                _ => {}
+            };
+        }
+    };
+    nodes.retain(|n| !matches!(n, NodeData::Text { value, .. } if value.is_empty()));
+}
+
+// Returns the canonical form of the code after removing and normalising what the minifier is allowed to change.
+fn canonicalised(cfg: &Cfg, src: &[u8]) -> String {
+    let input = Input::decode(src, None, true);
+    let arena = Bump::new();
+    let mut code = Code::new(&input.code, &arena);
+    code.noscript_as_raw_text = cfg.parse_noscript_as_raw_text;
+    let mut nodes = parse_content(&mut code, Namespace::Html, EMPTY_SLICE).children;
+    remove_minified(cfg, &mut nodes);
+    normalise_whitespace(Namespace::Html, false, EMPTY_SLICE, &mut nodes);
+    normalise(&mut nodes, None, true);
+    let mut out = Vec::new();
+    c14n_serialise_nodes(
+        &mut out,
+        &CanonicaliseCfg::default(),
+        &WhitespaceRules::default(),
+        Namespace::Html,
+        false,
+        EMPTY_SLICE,
+        0,
+        &nodes,
+    )
+    .unwrap();
+    lossy(&out)
+}
+
+proptest! {
+    #[test]
+    fn test_minification_is_idempotent(src in document(), options in options()) {
+        // The JS and CSS minifiers aren't necessarily idempotent.
+        let options = options
+            .into_iter()
+            .filter(|&o| o != "minify_css" && o != "minify_js")
+            .collect::<Vec<_>>();
+        let cfg = cfg(&options);
+        let once = minify(src.as_bytes(), &cfg);
+        let twice = minify(&once, &cfg);
+        prop_assert_eq!(lossy(&twice), lossy(&once));
+    }
+
+    #[test]
+    fn test_output_is_not_longer_than_source(src in document(), options in options()) {
+        let options = options
+            .into_iter()
+            .filter(|o| !LENGTHENING_OPTIONS.contains(o))
+            .collect::<Vec<_>>();
+        let cfg = cfg(&options);
+        let min = minify(src.as_bytes(), &cfg);
+        prop_assert!(min.len() <= src.len(), "output is longer: {:?}", lossy(&min));
+    }
+
+    #[test]
+    fn test_output_is_equivalent_to_source(src in document(), options in options()) {
+        let cfg = cfg(&options);
+        let min = minify(src.as_bytes(), &cfg);
+        prop_assert_eq!(canonicalised(&cfg, &min), canonicalised(&cfg, src.as_bytes()));
+    }
+}
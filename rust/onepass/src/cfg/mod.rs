@@ -1,6 +1,23 @@
 /// Configuration settings that can be adjusted and passed to a minification function to change the
 /// minification approach.
+#[derive(Clone, Default)]
 pub struct Cfg {
+    /// Do not minify DOCTYPEs. Minified DOCTYPEs may not be spec compliant.
+    pub do_not_minify_doctype: bool,
+    /// Ensure all unquoted attribute values in the output do not contain any characters prohibited by the [WHATWG specification](https://html.spec.whatwg.org/multipage/syntax.html#attributes-2).
+    ///
+    /// As minification is done in place, a value that's too short to be quoted is left as is, which is reported as `Diagnostic::UnquotedAttributeValueKept`.
+    pub ensure_spec_compliant_unquoted_attribute_values: bool,
+    /// Do not omit closing tags when possible.
+    pub keep_closing_tags: bool,
+    /// Do not omit `<html>` and `<head>` opening tags when they don't have attributes.
+    pub keep_html_and_head_opening_tags: bool,
+    /// Keep spaces between attributes when possible to conform to HTML standards.
+    ///
+    /// As minification is done in place, a space can't be added after a quoted value that isn't followed by one in the source, which is reported as `Diagnostic::SpaceBetweenAttributesOmitted`.
+    pub keep_spaces_between_attributes: bool,
+    /// Keep all comments.
+    pub keep_comments: bool,
     /// If enabled, JavaScript in `<script>` tags are minified using
     /// [minify-js](https://github.com/wilsonzlin/minify-js).
     ///
@@ -8,16 +25,33 @@ pub struct Cfg {
     /// [MIME type](https://mimesniff.spec.whatwg.org/#javascript-mime-type) is considered to
     /// contain JavaScript, as per the specification.
     pub minify_js: bool,
-
     /// If enabled, CSS in `<style>` tags are minified.
     pub minify_css: bool,
+    /// Parse the contents of `<noscript>` as raw text and output them as is, like browsers with scripting enabled do. Otherwise, they're parsed and minified as HTML, like browsers with scripting disabled (and most other HTML consumers) do.
+    pub parse_noscript_as_raw_text: bool,
+    /// Recover from malformed code the same way as the main variant instead of returning an error: closing tags that
+    /// don't close an open element (e.g. mismatched or for void elements) are dropped, elements still open at the end
+    /// are closed, comments, bangs, and processing instructions that aren't terminated are ended at the end of the code,
+    /// and `<html>`, `<head>`, and `<body>` opening tags after the first are ignored. Each recovery is reported as a
+    /// diagnostic from `in_place_with_diagnostics`.
+    pub recover_from_errors: bool,
+    /// Remove all bangs.
+    pub remove_bangs: bool,
+    /// Remove all processing_instructions.
+    pub remove_processing_instructions: bool,
 }
 
 impl Cfg {
     pub fn new() -> Cfg {
+        Cfg::default()
+    }
+
+    pub fn spec_compliant() -> Cfg {
         Cfg {
-            minify_css: false,
-            minify_js: false,
+            do_not_minify_doctype: true,
+            ensure_spec_compliant_unquoted_attribute_values: true,
+            keep_spaces_between_attributes: true,
+            ..Cfg::default()
         }
     }
 }
@@ -0,0 +1,178 @@
+use std::fmt;
+
+/// A language of code embedded in HTML.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum EmbeddedLanguage {
+    Css,
+    Js,
+}
+
+/// A problem found during minification that didn't prevent output from being produced.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Diagnostic {
+    /// The JS or CSS minifier returned an error for the contents of a `<script>` or `<style>`, so they were output as is. This usually means the code is malformed.
+    EmbeddedMinificationFailed {
+        language: EmbeddedLanguage,
+        /// Position of the contents in the source code.
+        position: usize,
+        error: String,
+    },
+    /// The JS or CSS minifier panicked on the contents of a `<script>` or `<style>`, so they were output as is. This is a bug in the minifier.
+    EmbeddedMinifierPanicked {
+        language: EmbeddedLanguage,
+        /// Position of the contents in the source code.
+        position: usize,
+        message: String,
+    },
+    /// The minified contents of a `<script>` had a `</script` that couldn't be escaped, as it wasn't in a string, template literal, regular expression, or comment (e.g. `x</script/.source`), or would otherwise not end with the script, so they were output as is.
+    MinifiedJsNotEscapable {
+        /// Position of the contents in the source code.
+        position: usize,
+    },
+    /// A closing tag that didn't close an open element (e.g. mismatched or for a void element) was dropped, as `recover_from_errors` is enabled.
+    ClosingTagDropped {
+        /// Lowercase tag name, which is empty if the `</` wasn't followed by one.
+        name: String,
+        /// Position of the closing tag in the source code.
+        position: usize,
+    },
+    /// An `<html>`, `<head>`, or `<body>` opening tag after the first was dropped, as `recover_from_errors` is enabled.
+    DuplicateTagDropped {
+        /// Lowercase tag name.
+        name: String,
+        /// Position of the opening tag in the source code.
+        position: usize,
+    },
+    /// An element was still open at the end of the code and its closing tag can't be omitted, so it was closed there, as `recover_from_errors` is enabled.
+    UnclosedElementClosed {
+        /// Lowercase tag name.
+        name: String,
+        /// Position of the element's opening tag in the source code.
+        position: usize,
+    },
+    /// A comment, bang, or processing instruction wasn't terminated before the end of the code, so it was ended there, as `recover_from_errors` is enabled.
+    UnterminatedNodeEnded {
+        /// What wasn't found, e.g. `comment end`.
+        expected: &'static str,
+        /// Position of the node in the source code.
+        position: usize,
+    },
+    /// An unquoted attribute value has characters prohibited by the spec, but was too short to be quoted in place, so it was left as is even though `ensure_spec_compliant_unquoted_attribute_values` is enabled.
+    UnquotedAttributeValueKept {
+        /// Position of the value in the source code.
+        position: usize,
+    },
+    /// The source had no space after a quoted attribute value, so there was no room to write one before the next attribute even though `keep_spaces_between_attributes` is enabled.
+    SpaceBetweenAttributesOmitted {
+        /// Position of the next attribute in the source code.
+        position: usize,
+    },
+}
+
+// Diagnostics, each with the element path where it occurred.
+pub(crate) type DiagnosticsWithElementPaths = Vec<(Diagnostic, String)>;
+
+impl Diagnostic {
+    // Position in the source code that the diagnostic refers to.
+    pub(crate) fn position(&self) -> usize {
+        match self {
+            Diagnostic::EmbeddedMinificationFailed { position, .. }
+            | Diagnostic::EmbeddedMinifierPanicked { position, .. }
+            | Diagnostic::MinifiedJsNotEscapable { position }
+            | Diagnostic::ClosingTagDropped { position, .. }
+            | Diagnostic::DuplicateTagDropped { position, .. }
+            | Diagnostic::UnclosedElementClosed { position, .. }
+            | Diagnostic::UnterminatedNodeEnded { position, .. }
+            | Diagnostic::UnquotedAttributeValueKept { position }
+            | Diagnostic::SpaceBetweenAttributesOmitted { position } => *position,
+        }
+    }
+
+    // If this is an error that was recovered from, an English message describing it in the style of `ErrorType::message`.
+    pub(crate) fn error_message(&self) -> Option<String> {
+        match self {
+            Diagnostic::ClosingTagDropped { name, .. } if name.is_empty() => {
+                Some("Expected closing tag name.".to_string())
+            }
+            Diagnostic::ClosingTagDropped { name, .. } => Some(format!(
+                "Closing tag does not match any open element (got \"{}\").",
+                name
+            )),
+            Diagnostic::DuplicateTagDropped { name, .. } => {
+                Some(format!("Duplicate \"{}\" opening tag.", name))
+            }
+            Diagnostic::UnclosedElementClosed { name, .. } => Some(format!(
+                "Element is not closed before the end of source code (expected \"{}\").",
+                name
+            )),
+            Diagnostic::UnterminatedNodeEnded { expected, .. } => {
+                Some(format!("Expected {}.", expected))
+            }
+            Diagnostic::EmbeddedMinificationFailed { .. }
+            | Diagnostic::EmbeddedMinifierPanicked { .. }
+            | Diagnostic::MinifiedJsNotEscapable { .. }
+            | Diagnostic::UnquotedAttributeValueKept { .. }
+            | Diagnostic::SpaceBetweenAttributesOmitted { .. } => None,
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Diagnostic::EmbeddedMinificationFailed {
+                language,
+                position,
+                error,
+            } => write!(
+                f,
+                "Could not minify embedded {:?} at {}, so it was left as is: {}",
+                language, position, error
+            ),
+            Diagnostic::EmbeddedMinifierPanicked {
+                language,
+                position,
+                message,
+            } => write!(
+                f,
+                "Minifier panicked on embedded {:?} at {}, so it was left as is: {}",
+                language, position, message
+            ),
+            Diagnostic::MinifiedJsNotEscapable { position } => write!(
+                f,
+                "Minified JS at {} has a `</script` that can't be escaped, so it was left as is",
+                position
+            ),
+            Diagnostic::ClosingTagDropped { name, position } => write!(
+                f,
+                "Dropped closing tag `</{}>` at {} as it doesn't close an open element",
+                name, position
+            ),
+            Diagnostic::DuplicateTagDropped { name, position } => write!(
+                f,
+                "Dropped duplicate `<{}>` opening tag at {}",
+                name, position
+            ),
+            Diagnostic::UnclosedElementClosed { name, position } => write!(
+                f,
+                "Closed `<{}>` element opened at {} at the end of the code",
+                name, position
+            ),
+            Diagnostic::UnterminatedNodeEnded { expected, position } => write!(
+                f,
+                "Ended the node at {} at the end of the code, as there's no {}",
+                position, expected
+            ),
+            Diagnostic::UnquotedAttributeValueKept { position } => write!(
+                f,
+                "Unquoted attribute value at {} has characters prohibited by the spec, but is too short to be quoted in place, so it was left as is",
+                position
+            ),
+            Diagnostic::SpaceBetweenAttributesOmitted { position } => write!(
+                f,
+                "No space was kept before the attribute at {} as there was none in the source",
+                position
+            ),
+        }
+    }
+}
@@ -1,3 +1,7 @@
+use memchr::{memchr, memrchr};
+
+use crate::common::location::Location;
+
 /// Represents the type of minification error.
 #[derive(Debug, Eq, PartialEq)]
 pub enum ErrorType {
@@ -20,12 +24,8 @@ impl ErrorType {
             ErrorType::NotFound(exp) => {
                 format!("Expected {}.", exp)
             }
-            ErrorType::UnexpectedEnd => {
-                format!("Unexpected end of source code.")
-            }
-            ErrorType::UnexpectedClosingTag => {
-                format!("Unexpected closing tag.")
-            }
+            ErrorType::UnexpectedEnd => "Unexpected end of source code.".to_string(),
+            ErrorType::UnexpectedClosingTag => "Unexpected closing tag.".to_string(),
         }
     }
 }
@@ -41,12 +41,74 @@ pub struct Error {
 /// the reason, and generated printable contextual representation of the code where the error
 /// occurred.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct FriendlyError {
+    /// Position of the error in the source code, in bytes.
     pub position: usize,
+    /// Line of the error in the source code, starting from 1.
+    pub line: usize,
+    /// Column of the error in its line, in Unicode characters, starting from 1.
+    pub column: usize,
+    /// Column of the error in its line, in UTF-16 code units, starting from 1.
+    pub column_utf16: usize,
+    /// Names of the elements that were open where the error occurred, outermost first, separated by ` > ` e.g.
+    /// `html > body > table > tr`. Only elements with an opening tag in the source code are included.
+    pub element_path: String,
     pub message: String,
+    /// The line of the error and up to two lines before it, with line numbers, followed by a line marking the error's
+    /// column.
     pub code_context: String,
 }
 
+impl FriendlyError {
+    pub(crate) fn new(
+        src: &[u8],
+        position: usize,
+        location: Location,
+        message: String,
+        element_path: String,
+    ) -> FriendlyError {
+        FriendlyError {
+            position,
+            line: location.line,
+            column: location.column,
+            column_utf16: location.column_utf16,
+            element_path,
+            message,
+            code_context: code_context(src, position, location),
+        }
+    }
+}
+
+// Number of lines before the line of an error that are included in its `code_context`.
+const CONTEXT_LINES: usize = 2;
+
+fn code_context(src: &[u8], position: usize, location: Location) -> String {
+    let first_line = location.line.saturating_sub(CONTEXT_LINES).max(1);
+    let mut start = location.line_start;
+    for _ in first_line..location.line {
+        // `start - 1` is the line break at the end of the previous line.
+        start = memrchr(b'\n', &src[..start - 1]).map_or(0, |i| i + 1);
+    }
+    let end = memchr(b'\n', &src[position..]).map_or(src.len(), |i| position + i);
+    let line_no_col_width = location.line.to_string().len();
+    let mut res = String::new();
+    for (i, line) in src[start..end].split(|c| *c == b'\n').enumerate() {
+        res.push_str(&format!(
+            "{:>indent$}|{}\n",
+            first_line + i,
+            String::from_utf8_lossy(line),
+            indent = line_no_col_width,
+        ));
+    }
+    res.push_str(&format!(
+        "{}|{}^ \n",
+        ">".repeat(line_no_col_width),
+        " ".repeat(location.column - 1),
+    ));
+    res
+}
+
 pub type ProcessingResult<T> = Result<T, ErrorType>;
 
 #[inline(always)]
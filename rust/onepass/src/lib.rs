@@ -1,12 +1,17 @@
 pub use crate::cfg::Cfg;
+use crate::common::location::Locator;
 use crate::common::spec::tag::ns::Namespace;
-use crate::err::debug_repr;
+use crate::diagnostic::DiagnosticsWithElementPaths;
+pub use crate::diagnostic::{Diagnostic, EmbeddedLanguage};
 pub use crate::err::{Error, ErrorType, FriendlyError};
 use crate::proc::Processor;
 use crate::unit::content::process_content;
 
 mod cfg;
+// Shared with minify-html, which uses different parts of it.
+#[allow(dead_code)]
 mod common;
+mod diagnostic;
 mod err;
 #[macro_use]
 mod proc;
@@ -31,6 +36,7 @@ mod unit;
 /// let cfg = &Cfg {
 ///     minify_js: false,
 ///     minify_css: false,
+///     ..Cfg::new()
 /// };
 /// match in_place(&mut code, cfg) {
 ///     Ok(minified_len) => assert_eq!(&code, b"<p>Hello, world!d!  </p>"),
@@ -38,20 +44,69 @@ mod unit;
 /// };
 /// ```
 pub fn in_place(code: &mut [u8], cfg: &Cfg) -> Result<usize, Error> {
+    in_place_with_diagnostics(code, cfg).map(|(min_len, _)| min_len)
+}
+
+/// Minifies a slice in-place and returns the new minified length, along with any problems found
+/// that didn't prevent minification (e.g. JS that couldn't be minified and was left as is).
+/// Any original code after the end of the minified code is left intact.
+///
+/// # Arguments
+///
+/// * `code` - A mutable slice of bytes representing the source code to minify.
+/// * `cfg` - Configuration object to adjust minification approach.
+///
+/// # Examples
+///
+/// ```
+/// use minify_html_onepass::{Cfg, in_place_with_diagnostics};
+///
+/// let mut code = b"<p>  Hello, world!  </p>".to_vec();
+/// let cfg = &Cfg {
+///     minify_js: false,
+///     minify_css: false,
+///     ..Cfg::new()
+/// };
+/// let (minified_len, diagnostics) = in_place_with_diagnostics(&mut code, cfg).unwrap();
+/// assert_eq!(&code[..minified_len], b"<p>Hello, world!");
+/// assert_eq!(diagnostics, vec![]);
+/// ```
+pub fn in_place_with_diagnostics(
+    code: &mut [u8],
+    cfg: &Cfg,
+) -> Result<(usize, Vec<Diagnostic>), Error> {
+    process(code, cfg)
+        .map(|(min_len, diagnostics)| (min_len, diagnostics.into_iter().map(|(d, _)| d).collect()))
+        .map_err(|(err, _, _)| err)
+}
+
+// Minifies a slice in-place, returning each diagnostic with the element path where it occurred. On error, also returns the
+// element path where it occurred and the diagnostics so far.
+fn process(
+    code: &mut [u8],
+    cfg: &Cfg,
+) -> Result<(usize, DiagnosticsWithElementPaths), (Error, String, DiagnosticsWithElementPaths)> {
     let mut proc = Processor::new(code);
-    process_content(&mut proc, cfg, Namespace::Html, None, false)
-        .and_then(|_| {
-            if !proc.at_end() {
-                Err(ErrorType::UnexpectedClosingTag)
-            } else {
-                Ok(())
-            }
-        })
-        .map_err(|error_type| Error {
-            error_type,
-            position: proc.read_len(),
-        })?;
-    proc.finish()
+    let res = process_content(&mut proc, cfg, Namespace::Html, None, false).and_then(|_| {
+        if !proc.at_end() {
+            Err(ErrorType::UnexpectedClosingTag)
+        } else {
+            Ok(())
+        }
+    });
+    match res {
+        Ok(()) => proc
+            .finish()
+            .map_err(|err| (err, String::new(), Vec::new())),
+        Err(error_type) => {
+            let err = Error {
+                error_type,
+                position: proc.read_len(),
+            };
+            let element_path = proc.element_path();
+            Err((err, element_path, proc.into_diagnostics()))
+        }
+    }
 }
 
 /// Minifies a str in-place and returns the new minified str.
@@ -71,6 +126,7 @@ pub fn in_place(code: &mut [u8], cfg: &Cfg) -> Result<usize, Error> {
 /// let cfg = &Cfg {
 ///     minify_js: false,
 ///     minify_css: false,
+///     ..Cfg::new()
 /// };
 /// match in_place_str(&mut code, cfg) {
 ///     Ok(minified_len) => assert_eq!(&code, "<p>Hello, world!d!  </p>"),
@@ -101,6 +157,7 @@ pub fn in_place_str<'s>(code: &'s mut str, cfg: &Cfg) -> Result<&'s str, Error>
 /// let cfg = &Cfg {
 ///     minify_js: false,
 ///     minify_css: false,
+///     ..Cfg::new()
 /// };
 /// match truncate(&mut code, cfg) {
 ///     Ok(()) => assert_eq!(code, b"<p>Hello, world!".to_vec()),
@@ -134,6 +191,7 @@ pub fn truncate(code: &mut Vec<u8>, cfg: &Cfg) -> Result<(), Error> {
 /// let cfg = &Cfg {
 ///     minify_js: false,
 ///     minify_css: false,
+///     ..Cfg::new()
 /// };
 /// match copy(&code, cfg) {
 ///     Ok(minified) => {
@@ -157,7 +215,9 @@ pub fn copy(code: &[u8], cfg: &Cfg) -> Result<Vec<u8>, Error> {
 /// This function is identical to `in_place` except it returns a `FriendlyError` on error instead.
 ///
 /// `FriendlyError` has a `code_context` field, which is a string of a visual representation of the
-/// source, with line numbers and position markers to aid in debugging syntax.
+/// lines around the error, with line numbers and a position marker to aid in debugging syntax. It also has the line,
+/// column, and enclosing elements of the error. To report these, a copy of the source code is kept
+/// while minifying.
 ///
 /// # Arguments
 ///
@@ -173,11 +233,14 @@ pub fn copy(code: &[u8], cfg: &Cfg) -> Result<Vec<u8>, Error> {
 /// let cfg = &Cfg {
 ///     minify_js: false,
 ///     minify_css: false,
+///     ..Cfg::new()
 /// };
 /// match with_friendly_error(&mut code, cfg) {
 ///     Ok(minified_len) => {}
-///     Err(FriendlyError { position, message, code_context }) => {
+///     Err(FriendlyError { position, line, column, element_path, message, code_context, .. }) => {
 ///         assert_eq!(position, 3);
+///         assert_eq!((line, column), (1, 4));
+///         assert_eq!(element_path, "");
 ///         assert_eq!(message, "Unexpected closing tag.");
 ///         assert_eq!(code_context, concat!(
 ///             "1|<p></div>\n",
@@ -187,9 +250,96 @@ pub fn copy(code: &[u8], cfg: &Cfg) -> Result<Vec<u8>, Error> {
 /// };
 /// ```
 pub fn with_friendly_error(code: &mut [u8], cfg: &Cfg) -> Result<usize, FriendlyError> {
-    in_place(code, cfg).map_err(|err| FriendlyError {
-        position: err.position,
-        message: err.error_type.message(),
-        code_context: debug_repr(code, err.position as isize, -1),
-    })
+    let src = code.to_vec();
+    process(code, cfg)
+        .map(|(min_len, _)| min_len)
+        .map_err(|(err, element_path, _)| {
+            FriendlyError::new(
+                &src,
+                err.position,
+                Locator::new(&src).locate(err.position),
+                err.error_type.message(),
+                element_path,
+            )
+        })
+}
+
+/// Minifies a slice in-place and returns the new minified length, or every error found.
+/// Any original code after the end of the minified code is left intact.
+///
+/// This function is like `with_friendly_error`, except it continues after errors it can recover from
+/// (as if `recover_from_errors` was enabled), so that all of them are found at once. They are returned
+/// in source code order, followed by any error that couldn't be recovered from. Enable the `serde`
+/// feature to make `FriendlyError` serialisable (e.g. to JSON).
+///
+/// # Arguments
+///
+/// * `code` - A mutable slice of bytes representing the source code to minify.
+/// * `cfg` - Configuration object to adjust minification approach.
+///
+/// # Examples
+///
+/// ```
+/// use minify_html_onepass::{Cfg, with_friendly_errors};
+///
+/// let mut code = b"<div>\n<p></span></div>\n<body><body>".to_vec();
+/// let cfg = &Cfg {
+///     minify_js: false,
+///     minify_css: false,
+///     ..Cfg::new()
+/// };
+/// let errors = with_friendly_errors(&mut code, cfg).unwrap_err();
+/// assert_eq!(errors.len(), 2);
+/// assert_eq!((errors[0].line, errors[0].column), (2, 4));
+/// assert_eq!(errors[0].element_path, "div > p");
+/// assert_eq!(errors[0].message, "Closing tag does not match any open element (got \"span\").");
+/// assert_eq!((errors[1].line, errors[1].column), (3, 7));
+/// assert_eq!(errors[1].message, "Duplicate \"body\" opening tag.");
+/// ```
+pub fn with_friendly_errors(code: &mut [u8], cfg: &Cfg) -> Result<usize, Vec<FriendlyError>> {
+    let src = code.to_vec();
+    let cfg = Cfg {
+        recover_from_errors: true,
+        ..cfg.clone()
+    };
+    let (min_len, diagnostics, err) = match process(code, &cfg) {
+        Ok((min_len, diagnostics)) => (Some(min_len), diagnostics, None),
+        Err((err, element_path, diagnostics)) => (None, diagnostics, Some((err, element_path))),
+    };
+    let mut found = diagnostics
+        .into_iter()
+        .filter_map(|(d, element_path)| {
+            d.error_message()
+                .map(|message| (d.position(), message, element_path))
+        })
+        .collect::<Vec<_>>();
+    // Sort so that all errors can be located in one pass over the source code.
+    found.sort_by_key(|(position, ..)| *position);
+    let mut locator = Locator::new(&src);
+    let mut errors = found
+        .into_iter()
+        .map(|(position, message, element_path)| {
+            FriendlyError::new(
+                &src,
+                position,
+                locator.locate(position),
+                message,
+                element_path,
+            )
+        })
+        .collect::<Vec<_>>();
+    if let Some((err, element_path)) = err {
+        // This error can come before the recovered ones, so it's located separately.
+        errors.push(FriendlyError::new(
+            &src,
+            err.position,
+            Locator::new(&src).locate(err.position),
+            err.error_type.message(),
+            element_path,
+        ));
+    };
+    match min_len {
+        Some(min_len) if errors.is_empty() => Ok(min_len),
+        _ => Err(errors),
+    }
 }
@@ -33,10 +33,19 @@ impl WriteCheckpoint {
 
     /// Discard characters written since checkpoint but keep source position.
     #[inline(always)]
-    pub fn erase_written(&self, proc: &mut Processor) -> () {
+    pub fn erase_written(&self, proc: &mut Processor) {
         proc.write_next = self.write_next;
     }
 
+    /// Discard the first `amount` characters written since checkpoint, moving any characters written after them back.
+    #[inline(always)]
+    pub fn erase_written_prefix(&self, proc: &mut Processor, amount: usize) {
+        let start = self.write_next;
+        proc.code
+            .copy_within(start + amount..proc.write_next, start);
+        proc.write_next -= amount;
+    }
+
     /// Get written characters since checkpoint as range.
     #[inline(always)]
     pub fn written_range(&self, proc: &mut Processor) -> ProcessorRange {
@@ -66,7 +75,7 @@ impl ReadCheckpoint {
     }
 
     #[inline(always)]
-    pub fn restore(&self, proc: &mut Processor) -> () {
+    pub fn restore(&self, proc: &mut Processor) {
         proc.read_next = self.read_next;
     }
 }
@@ -35,10 +35,10 @@ enum Parsed {
 
 #[inline(always)]
 fn parse_numeric_entity(
-    code: &mut [u8],
+    code: &[u8],
     read_start: usize,
     prefix_len: usize,
-    write_pos: usize,
+    out: &mut [u8],
     digit_lookup: &'static Lookup,
     on_digit: fn(u32, u8) -> u32,
     max_digits: usize,
@@ -69,17 +69,17 @@ fn parse_numeric_entity(
     // Browsers decode to a replacement character (U+FFFD) if malformed.
     let char = Some(value)
         .filter(|_| digits <= max_digits)
-        .and_then(|v| from_u32(v))
+        .and_then(from_u32)
         .unwrap_or('\u{FFFD}');
     Parsed::Decoded {
         read_len: read_next - read_start,
-        write_len: char.encode_utf8(&mut code[write_pos..]).len(),
+        write_len: char.encode_utf8(out).len(),
     }
 }
 
-// Parse the entity and write its decoded value at {@param write_pos}.
+// Parse the entity and write its decoded value to {@param out}.
 // If malformed, returns the longest matching entity prefix length, and does not write/decode anything.
-fn parse_entity(code: &mut [u8], read_pos: usize, write_pos: usize, in_attr_val: bool) -> Parsed {
+fn parse_entity(code: &[u8], read_pos: usize, out: &mut [u8], in_attr_val: bool) -> Parsed {
     match ENTITY.longest_matching_prefix(&code[read_pos..]) {
         TrieNodeMatch::Found {
             len: match_len,
@@ -90,7 +90,7 @@ fn parse_entity(code: &mut [u8], read_pos: usize, write_pos: usize, in_attr_val:
                 read_pos,
                 // Skip past '&#'. Note that match_len is 3 as it matches '&#[0-9]'.
                 2,
-                write_pos,
+                out,
                 DIGIT,
                 |value, c| value.wrapping_mul(10).wrapping_add((c - b'0') as u32),
                 7,
@@ -100,7 +100,7 @@ fn parse_entity(code: &mut [u8], read_pos: usize, write_pos: usize, in_attr_val:
                 read_pos,
                 // Skip past '&#x'. Note that match_len is 4 as it matches '&#x[0-9a-fA-F]'.
                 3,
-                write_pos,
+                out,
                 HEX_DIGIT,
                 |value, c| {
                     value.wrapping_mul(16).wrapping_add(match c {
@@ -124,7 +124,7 @@ fn parse_entity(code: &mut [u8], read_pos: usize, write_pos: usize, in_attr_val:
                 {
                     Parsed::LeftEncoded
                 } else {
-                    code[write_pos..write_pos + decoded.len()].copy_from_slice(decoded);
+                    out[..decoded.len()].copy_from_slice(decoded);
                     Parsed::Decoded {
                         read_len: match_len,
                         write_len: decoded.len(),
@@ -137,8 +137,46 @@ fn parse_entity(code: &mut [u8], read_pos: usize, write_pos: usize, in_attr_val:
     }
 }
 
+// The longest decoded value of an entity in UTF-8.
+const MAX_DECODED_LEN: usize = 6;
+
+// The longest entity name is 31 characters, so this is enough to find the longest matching entity after the `&` and the
+// character following it.
+const MAX_ENTITY_LOOKAHEAD: usize = 33;
+
+// Whether browsers leave `code`, which starts with `&`, as is in an attribute value, because its longest matching named
+// entity doesn't end with a semicolon and is followed by an alphanumeric or `=` character.
+fn is_left_encoded_in_attr_val(code: &[u8]) -> bool {
+    match ENTITY.longest_matching_prefix(code) {
+        TrieNodeMatch::Found {
+            len,
+            value: EntityType::Named(_),
+        } => {
+            code[len - 1] != b';'
+                && code
+                    .get(len)
+                    .filter(|c| ALPHANUMERIC_OR_EQUALS[**c])
+                    .is_some()
+        }
+        _ => false,
+    }
+}
+
+// Whether the entity at the current position decodes to `<`, `/`, or an ASCII letter, which could form a closing tag in
+// `<textarea>` or `<title>` contents, so it's kept as is there.
+pub fn entity_decodes_to_closing_tag_char(proc: &Processor) -> bool {
+    let mut decoded = [0u8; MAX_DECODED_LEN];
+    match parse_entity(proc.code, proc.read_next, &mut decoded, false) {
+        Parsed::Decoded { .. } => {
+            decoded[0] == b'<' || decoded[0] == b'/' || decoded[0].is_ascii_alphabetic()
+        }
+        _ => false,
+    }
+}
+
 // Normalise entity such that "&lt; hello" becomes "___< hello".
-// For something like "&a&#109;&#112; hello", it becomes "_______&ampamp hello".
+// For something like "&a&#109;&#112; hello", it becomes "_______&ampamp hello", or "_______&#38amp hello" in an
+// attribute value, where `&amp` without a semicolon isn't decoded if followed by an alphanumeric.
 pub fn maybe_normalise_entity(proc: &mut Processor, in_attr_val: bool) -> bool {
     if proc.peek(0).filter(|c| *c == b'&').is_none() {
         return false;
@@ -157,8 +195,9 @@ pub fn maybe_normalise_entity(proc: &mut Processor, in_attr_val: bool) -> bool {
             None => break,
             Some(b'&') => {
                 // Decode before checking to see if it continues current entity.
+                let mut decoded = [0u8; MAX_DECODED_LEN];
                 let (read_len, write_len) =
-                    match parse_entity(proc.code, read_next, write_next, in_attr_val) {
+                    match parse_entity(proc.code, read_next, &mut decoded, in_attr_val) {
                         Parsed::LeftEncoded => {
                             // Don't mistake an intentionally undecoded entity for an unintentional entity.
                             break;
@@ -169,6 +208,16 @@ pub fn maybe_normalise_entity(proc: &mut Processor, in_attr_val: bool) -> bool {
                         } => {
                             debug_assert!(read_len > 0);
                             debug_assert!(write_len > 0);
+                            let (_, match_len) =
+                                node.shortest_matching_prefix(&decoded[..write_len], 0);
+                            // An entity after the first is only consumed if it continues the unintentional entity, as
+                            // otherwise its decoded value would be read again as source code, e.g. the `<` in
+                            // `&amp;&lt;p>` as a tag, or the `&` in `&amp;&amp;lt;` as the start of another entity.
+                            if read_next != start && match_len < write_len {
+                                break;
+                            };
+                            proc.code[write_next..write_next + write_len]
+                                .copy_from_slice(&decoded[..write_len]);
                             (read_len, write_len)
                         }
                         Parsed::Invalid { len } => {
@@ -203,8 +252,7 @@ pub fn maybe_normalise_entity(proc: &mut Processor, in_attr_val: bool) -> bool {
                 };
             }
             Some(_) => {
-                let (new_node, new_read_next) =
-                    node.shortest_matching_prefix(&proc.code, read_next);
+                let (new_node, new_read_next) = node.shortest_matching_prefix(proc.code, read_next);
                 let len = new_read_next - read_next;
                 if len == 0 {
                     break;
@@ -216,18 +264,31 @@ pub fn maybe_normalise_entity(proc: &mut Processor, in_attr_val: bool) -> bool {
             }
         };
     }
-    // Check if we need to encode initial '&' and add 'amp'.
-    let undecodable = node.value.is_some();
+    // Check if we need to encode initial '&' as `&amp` or `&#38`. In an attribute value, browsers may not decode the entity
+    // anyway (e.g. `&sect` in `&section`), like the main variant checks. The code after `read_next` hasn't been decoded
+    // yet, but any `&` in it ends the match, so this errs towards encoding.
+    let undecodable = node.value.is_some()
+        && !(in_attr_val && {
+            let lookahead_end = (read_next + MAX_ENTITY_LOOKAHEAD).min(proc.code.len());
+            let mut lookahead = proc.code[start..write_next].to_vec();
+            lookahead.extend_from_slice(&proc.code[read_next..lookahead_end]);
+            is_left_encoded_in_attr_val(&lookahead)
+        });
     // Shift decoded value down so that it ends at read_next (exclusive).
     let mut shifted_start = read_next - (write_next - start - undecodable as usize);
     proc.code
         .copy_within(start + undecodable as usize..write_next, shifted_start);
     if undecodable {
         debug_assert_eq!(proc.code.get(start), Some(&b'&'));
-        proc.code[shifted_start - 4..shifted_start].copy_from_slice(b"&amp");
+        // Entity names start with a letter or `#`, so `&#38` never needs a semicolon.
+        proc.code[shifted_start - 4..shifted_start].copy_from_slice(if in_attr_val {
+            b"&#38"
+        } else {
+            b"&amp"
+        });
         shifted_start -= 4;
     };
 
     proc.read_next = shifted_start;
-    return true;
+    true
 }
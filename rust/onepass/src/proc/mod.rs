@@ -7,6 +7,7 @@ use memchr::memchr;
 
 use crate::common::gen::codepoints::Lookup;
 use crate::common::spec::tag::EMPTY_SLICE;
+use crate::diagnostic::{Diagnostic, DiagnosticsWithElementPaths};
 use crate::err::{debug_repr, Error, ErrorType, ProcessingResult};
 use crate::proc::range::ProcessorRange;
 use crate::proc::MatchAction::*;
@@ -36,9 +37,12 @@ pub enum MatchMode {
 
     IsSeq(&'static [u8]),
     WhileNotSeq(&'static AhoCorasick),
+    // Like WhileNotSeq, but the position is found by the function, which is given the remaining code.
+    WhileNotFound(fn(&[u8]) -> Option<usize>),
     ThroughSeq(&'static AhoCorasick),
 }
 
+#[derive(Copy, Clone)]
 pub enum MatchAction {
     Keep,
     Discard,
@@ -52,6 +56,13 @@ pub struct Processor<'d> {
     read_next: usize,
     // Index of the next unwritten space.
     write_next: usize,
+    diagnostics: DiagnosticsWithElementPaths,
+    // Names of currently open elements, outermost first, as written ranges.
+    pub open_elements: Vec<ProcessorRange>,
+    // Whether the first `<html>`, `<head>`, or `<body>` opening tag has been seen. Only tracked when recovering from errors.
+    pub seen_html_open: bool,
+    pub seen_head_open: bool,
+    pub seen_body_open: bool,
 }
 
 impl<'d> Index<ProcessorRange> for Processor<'d> {
@@ -75,11 +86,16 @@ impl<'d> IndexMut<ProcessorRange> for Processor<'d> {
 impl<'d> Processor<'d> {
     // Constructor.
     #[inline(always)]
-    pub fn new(code: &mut [u8]) -> Processor {
+    pub fn new(code: &mut [u8]) -> Processor<'_> {
         Processor {
             write_next: 0,
             read_next: 0,
             code,
+            diagnostics: Vec::new(),
+            open_elements: Vec::new(),
+            seen_html_open: false,
+            seen_head_open: false,
+            seen_body_open: false,
         }
     }
 
@@ -101,7 +117,7 @@ impl<'d> Processor<'d> {
 
     #[inline(always)]
     fn _maybe_read_offset(&self, offset: usize) -> Option<u8> {
-        self.code.get(self.read_next + offset).map(|c| *c)
+        self.code.get(self.read_next + offset).copied()
     }
 
     #[inline(always)]
@@ -113,7 +129,7 @@ impl<'d> Processor<'d> {
     /// Move next `amount` characters to output.
     /// Panics. Does not check bounds for performance (e.g. already checked).
     #[inline(always)]
-    fn _shift(&mut self, amount: usize) -> () {
+    fn _shift(&mut self, amount: usize) {
         // Optimisation: Don't shift if already there (but still update offsets).
         if self.read_next != self.write_next {
             self.code
@@ -178,9 +194,9 @@ impl<'d> Processor<'d> {
             WhileInLookup(lookup) => self._many(|n| lookup[n]),
             WhileNotInLookup(lookup) => self._many(|n| !lookup[n]),
 
-            IsPred(p) => self._one(|n| p(n)),
+            IsPred(p) => self._one(p),
             IsNotPred(p) => self._one(|n| !p(n)),
-            WhilePred(p) => self._many(|n| p(n)),
+            WhilePred(p) => self._many(p),
             WhileNotPred(p) => self._many(|n| !p(n)),
 
             IsSeq(seq) => self
@@ -190,6 +206,7 @@ impl<'d> Processor<'d> {
             WhileNotSeq(seq) => seq
                 .find(&self.code[self.read_next..])
                 .map_or(self._remaining(), |m| m.start()),
+            WhileNotFound(f) => f(&self.code[self.read_next..]).unwrap_or(self._remaining()),
             // Match.end is exclusive, so do not add one.
             ThroughSeq(seq) => seq
                 .find(&self.code[self.read_next..])
@@ -241,8 +258,14 @@ impl<'d> Processor<'d> {
         self.read_next
     }
 
+    /// Get how many characters can be written without overwriting unread source.
     #[inline(always)]
-    pub fn reserve_output(&mut self, amount: usize) -> () {
+    pub fn writable_len(&self) -> usize {
+        self.read_next - self.write_next
+    }
+
+    #[inline(always)]
+    pub fn reserve_output(&mut self, amount: usize) {
         self.write_next += amount;
     }
 
@@ -269,22 +292,19 @@ impl<'d> Processor<'d> {
     /// Will result in an error if exceeds bounds.
     #[inline(always)]
     pub fn skip(&mut self) -> ProcessingResult<u8> {
-        self._maybe_read_offset(0)
-            .map(|c| {
-                self.read_next += 1;
-                c
-            })
-            .ok_or(ErrorType::UnexpectedEnd)
+        let c = self._maybe_read_offset(0).ok_or(ErrorType::UnexpectedEnd)?;
+        self.read_next += 1;
+        Ok(c)
     }
 
     #[inline(always)]
-    pub fn skip_amount_expect(&mut self, amount: usize) -> () {
+    pub fn skip_amount_expect(&mut self, amount: usize) {
         debug_assert!(!self.at_end(), "skip known characters");
         self.read_next += amount;
     }
 
     #[inline(always)]
-    pub fn skip_expect(&mut self) -> () {
+    pub fn skip_expect(&mut self) {
         debug_assert!(!self.at_end(), "skip known character");
         self.read_next += 1;
     }
@@ -292,17 +312,17 @@ impl<'d> Processor<'d> {
     // Writing characters directly.
     /// Write `c` to output. Will panic if exceeds bounds.
     #[inline(always)]
-    pub fn write(&mut self, c: u8) -> () {
+    pub fn write(&mut self, c: u8) {
         self.code[self.write_next] = c;
         self.write_next += 1;
     }
 
     #[inline(always)]
-    pub fn make_lowercase(&mut self, range: ProcessorRange) -> () {
+    pub fn make_lowercase(&mut self, range: ProcessorRange) {
         self.code[range.start..range.end].make_ascii_lowercase();
     }
 
-    pub fn undo_write(&mut self, len: usize) -> () {
+    pub fn undo_write(&mut self, len: usize) {
         self.write_next -= len;
     }
 
@@ -320,13 +340,13 @@ impl<'d> Processor<'d> {
 
     /// Write `s` to output. Will panic if exceeds bounds.
     #[inline(always)]
-    pub fn write_slice(&mut self, s: &[u8]) -> () {
+    pub fn write_slice(&mut self, s: &[u8]) {
         self.code[self.write_next..self.write_next + s.len()].copy_from_slice(s);
         self.write_next += s.len();
     }
 
     #[inline(always)]
-    pub fn write_utf8(&mut self, c: char) -> () {
+    pub fn write_utf8(&mut self, c: char) {
         let mut encoded = [0u8; 4];
         self.write_slice(c.encode_utf8(&mut encoded).as_bytes());
     }
@@ -334,14 +354,11 @@ impl<'d> Processor<'d> {
     // Shifting characters.
     #[inline(always)]
     pub fn accept(&mut self) -> ProcessingResult<u8> {
-        self._maybe_read_offset(0)
-            .map(|c| {
-                self.code[self.write_next] = c;
-                self.read_next += 1;
-                self.write_next += 1;
-                c
-            })
-            .ok_or(ErrorType::UnexpectedEnd)
+        let c = self._maybe_read_offset(0).ok_or(ErrorType::UnexpectedEnd)?;
+        self.code[self.write_next] = c;
+        self.read_next += 1;
+        self.write_next += 1;
+        Ok(c)
     }
 
     #[inline(always)]
@@ -355,16 +372,35 @@ impl<'d> Processor<'d> {
     }
 
     #[inline(always)]
-    pub fn accept_amount_expect(&mut self, count: usize) -> () {
+    pub fn accept_amount_expect(&mut self, count: usize) {
         debug_assert!(self._in_bounds(count - 1));
         self._shift(count);
     }
 
+    pub fn diagnose(&mut self, diagnostic: Diagnostic) {
+        let element_path = self.element_path();
+        self.diagnostics.push((diagnostic, element_path));
+    }
+
+    /// Get the names of the currently open elements, outermost first, separated by ` > ` e.g. `html > body > table > tr`.
+    pub fn element_path(&self) -> String {
+        self.open_elements
+            .iter()
+            .map(|e| unsafe { std::str::from_utf8_unchecked(&self[*e]) })
+            .collect::<Vec<_>>()
+            .join(" > ")
+    }
+
     // Since we consume the Processor, we must provide a full Error with positions.
     #[inline(always)]
-    pub fn finish(self) -> Result<usize, Error> {
+    pub fn finish(self) -> Result<(usize, DiagnosticsWithElementPaths), Error> {
         debug_assert!(self.at_end());
-        Ok(self.write_next)
+        Ok((self.write_next, self.diagnostics))
+    }
+
+    /// Get the diagnostics so far, with the element path of each, if processing stopped due to an error.
+    pub fn into_diagnostics(self) -> DiagnosticsWithElementPaths {
+        self.diagnostics
     }
 }
 
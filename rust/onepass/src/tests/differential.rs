@@ -0,0 +1,322 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+
+use crate::cfg::Cfg;
+use crate::common::tests::options::enable_option;
+use crate::copy;
+
+// Runs both variants over the same documents and compares their canonicalised outputs, so that switching between them
+// doesn't change the meaning of the minified output. Known divergences are listed in `differential.expectations`, and
+// both unlisted divergences and listed cases that no longer diverge fail the test.
+
+const EXPECTATIONS: &str = include_str!("differential.expectations");
+
+// Options shared by both variants, as well as onepass's `recover_from_errors`, which is how main always behaves. Each is tested
+// on its own, as well as none and all of them.
+const OPTIONS: &[&str] = &[
+    "do_not_minify_doctype",
+    "ensure_spec_compliant_unquoted_attribute_values",
+    "keep_closing_tags",
+    "keep_comments",
+    "keep_html_and_head_opening_tags",
+    "keep_spaces_between_attributes",
+    "parse_noscript_as_raw_text",
+    "recover_from_errors",
+    "remove_bangs",
+    "remove_processing_instructions",
+];
+
+fn cfgs(name: &str) -> (minify_html::Cfg, Cfg) {
+    let mut main = minify_html::Cfg::new();
+    for &opt in OPTIONS {
+        if (name == "all" || name == opt) && opt != "recover_from_errors" {
+            enable_option!(main, opt);
+        };
+    }
+    let onepass = Cfg {
+        do_not_minify_doctype: main.do_not_minify_doctype,
+        ensure_spec_compliant_unquoted_attribute_values: main
+            .ensure_spec_compliant_unquoted_attribute_values,
+        keep_closing_tags: main.keep_closing_tags,
+        keep_comments: main.keep_comments,
+        keep_html_and_head_opening_tags: main.keep_html_and_head_opening_tags,
+        keep_spaces_between_attributes: main.keep_spaces_between_attributes,
+        parse_noscript_as_raw_text: main.parse_noscript_as_raw_text,
+        recover_from_errors: name == "all" || name == "recover_from_errors",
+        remove_bangs: main.remove_bangs,
+        remove_processing_instructions: main.remove_processing_instructions,
+        ..Cfg::new()
+    };
+    (main, onepass)
+}
+
+// Small deterministic PRNG so generated documents are the same on every run without extra dependencies.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        // xorshift64*.
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545F4914F6CDD1D)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str {
+        items[self.below(items.len())]
+    }
+}
+
+// Elements whose content can be anything, and elements whose content can only be phrasing content (as well as `p`). The
+// generator keeps to these so that no element is implicitly closed, which onepass rejects.
+const FLOW_CONTAINERS: &[&str] = &["div", "section", "pre"];
+const PHRASING_CONTAINERS: &[&str] = &["span", "b", "a", "label", "button"];
+const VOIDS: &[&str] = &["br", "img", "input", "meta"];
+const ATTR_NAMES: &[&str] = &[
+    "class", "id", "title", "type", "href", "alt", "data-x", "hidden", "target", "style",
+];
+const ATTR_VALUES: &[&str] = &[
+    "",
+    "a",
+    "  a  b  ",
+    "TEXT",
+    "text",
+    "a\"b",
+    "a'b",
+    "'\"",
+    "x=y",
+    "&amp;&lt;&gt;",
+    "&#32;1",
+    "a > b",
+    " ",
+    "`a`",
+    "_self",
+];
+const TEXTS: &[&str] = &[
+    "a",
+    " ",
+    "  \n\t ",
+    "Hello, world!",
+    "&amp;",
+    "&lt;p&gt;",
+    "a &nbsp; b",
+    "1 < 2",
+    "&#x20;",
+    "x\ny",
+];
+const OTHERS: &[&str] = &["<!-- c -->", "<!---->", "<!x>", "<?y?>", "<br/>"];
+const RAW: &[(&str, &[&str])] = &[
+    ("script", &["", "let a = 1;", "</scr + ipt>", "<!-- x -->"]),
+    ("style", &["", "a { b: c }"]),
+    ("textarea", &["", " a  b ", "<p>&amp;"]),
+    ("title", &["", " a ", "&lt;"]),
+];
+
+fn gen_attrs(rng: &mut Rng, out: &mut String) {
+    let mut names = Vec::new();
+    for _ in 0..rng.below(4) {
+        let name = rng.pick(ATTR_NAMES);
+        if names.contains(&name) {
+            continue;
+        };
+        names.push(name);
+        out.push_str(if rng.below(3) == 0 { "  " } else { " " });
+        out.push_str(name);
+        if rng.below(4) > 0 {
+            let value = rng.pick(ATTR_VALUES);
+            match rng.below(3) {
+                0 if !value.is_empty()
+                    && !value
+                        .bytes()
+                        .any(|c| c.is_ascii_whitespace() || b"\"'=<>`".contains(&c)) =>
+                {
+                    out.push('=');
+                    out.push_str(value);
+                }
+                1 if !value.contains('\'') => {
+                    out.push_str("='");
+                    out.push_str(value);
+                    out.push('\'');
+                }
+                _ => {
+                    out.push_str("=\"");
+                    out.push_str(&value.replace('"', "&quot;"));
+                    out.push('"');
+                }
+            };
+        };
+    }
+}
+
+fn gen_element(rng: &mut Rng, tag: &str, flow: bool, depth: usize, out: &mut String) {
+    out.push('<');
+    out.push_str(tag);
+    gen_attrs(rng, out);
+    out.push('>');
+    gen_content(rng, flow, depth + 1, out);
+    out.push_str("</");
+    out.push_str(tag);
+    out.push('>');
+}
+
+fn gen_content(rng: &mut Rng, flow: bool, depth: usize, out: &mut String) {
+    for _ in 0..rng.below(5) {
+        match rng.below(if depth < 4 { 11 } else { 4 }) {
+            0 | 1 => out.push_str(rng.pick(TEXTS)),
+            2 => out.push_str(rng.pick(OTHERS)),
+            3 => {
+                out.push('<');
+                out.push_str(rng.pick(VOIDS));
+                gen_attrs(rng, out);
+                out.push('>');
+            }
+            4 => {
+                let (tag, contents) = RAW[rng.below(RAW.len())];
+                out.push('<');
+                out.push_str(tag);
+                out.push('>');
+                out.push_str(rng.pick(contents));
+                out.push_str("</");
+                out.push_str(tag);
+                out.push('>');
+            }
+            5 => out.push_str(rng.pick(&[
+                "<svg><path d=\" M 0 0 \"/></svg>",
+                "<svg viewBox=\"0 0 1 1\"><circle r=\"1\"></circle><text> a </text></svg>",
+            ])),
+            6 if flow => {
+                out.push_str("<ul>");
+                for _ in 0..rng.below(3) {
+                    gen_element(rng, "li", true, depth, out);
+                }
+                out.push_str("</ul>");
+            }
+            7 if flow => gen_element(rng, "p", false, depth, out),
+            8 if flow => {
+                let tag = rng.pick(FLOW_CONTAINERS);
+                gen_element(rng, tag, true, depth, out)
+            }
+            _ => {
+                let tag = rng.pick(PHRASING_CONTAINERS);
+                gen_element(rng, tag, false, depth, out)
+            }
+        };
+    }
+}
+
+// Generates a well-formed document, so that onepass doesn't reject it.
+fn gen_document(seed: u64) -> Vec<u8> {
+    let mut rng = Rng(seed.wrapping_add(1).wrapping_mul(0x9E3779B97F4A7C15));
+    let mut out = String::new();
+    if rng.below(2) == 0 {
+        out.push_str("<!DOCTYPE html>");
+    };
+    let wrap = rng.below(2) == 0;
+    if wrap {
+        out.push_str("<html><head><title>t</title></head><body>");
+    };
+    gen_content(&mut rng, true, 0, &mut out);
+    if wrap {
+        out.push_str("</body></html>");
+    };
+    out.into_bytes()
+}
+
+fn corpus() -> Vec<(String, Vec<u8>)> {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("../..");
+    let mut cases = Vec::new();
+    for dir in &["fuzz/in", "bench/inputs"] {
+        let mut paths = fs::read_dir(root.join(dir))
+            .unwrap()
+            .map(|e| e.unwrap().path())
+            .collect::<Vec<_>>();
+        paths.sort();
+        for path in paths {
+            let name = format!("{}/{}", dir, path.file_name().unwrap().to_str().unwrap());
+            cases.push((name, fs::read(path).unwrap()));
+        }
+    }
+    for seed in 0..500 {
+        cases.push((format!("generated/{}", seed), gen_document(seed)));
+    }
+    cases
+}
+
+fn canonicalise(code: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    minify_html::canonicalise(&mut out, code).unwrap();
+    out
+}
+
+// Shows the first difference with some surrounding context.
+fn describe_difference(a: &[u8], b: &[u8]) -> String {
+    let at = a.iter().zip(b).take_while(|(a, b)| a == b).count();
+    let context = |s: &[u8]| {
+        String::from_utf8_lossy(&s[at.saturating_sub(40)..(at + 40).min(s.len())]).to_string()
+    };
+    format!(
+        "first difference at {}:\n    main:    {:?}\n    onepass: {:?}",
+        at,
+        context(a),
+        context(b)
+    )
+}
+
+fn expectations() -> BTreeSet<(String, String)> {
+    EXPECTATIONS
+        .lines()
+        .map(|l| l.split('#').next().unwrap().trim())
+        .filter(|l| !l.is_empty())
+        .map(|l| {
+            let (cfg, case) = l.split_at(l.find(' ').unwrap());
+            (cfg.to_string(), case.trim().to_string())
+        })
+        .collect()
+}
+
+#[test]
+fn test_onepass_and_main_are_equivalent() {
+    let expected = expectations();
+    let mut unexpected = Vec::new();
+    let mut diverged = BTreeSet::new();
+    let cfg_names = ["default", "all"].iter().chain(OPTIONS.iter());
+    let corpus = corpus();
+    for &cfg_name in cfg_names {
+        let (main_cfg, onepass_cfg) = cfgs(cfg_name);
+        for (case, src) in corpus.iter() {
+            let main = canonicalise(&minify_html::minify(src, &main_cfg));
+            let problem = match copy(src, &onepass_cfg) {
+                Ok(onepass) => {
+                    let onepass = canonicalise(&onepass);
+                    if main == onepass {
+                        continue;
+                    };
+                    describe_difference(&main, &onepass)
+                }
+                Err(err) => format!("onepass failed with {:?}", err),
+            };
+            if !expected.contains(&(cfg_name.to_string(), case.clone()))
+                && !expected.contains(&("*".to_string(), case.clone()))
+            {
+                unexpected.push(format!("{} {}: {}", cfg_name, case, problem));
+            };
+            diverged.insert((cfg_name.to_string(), case.clone()));
+            diverged.insert(("*".to_string(), case.clone()));
+        }
+    }
+    let stale = expected
+        .difference(&diverged)
+        .map(|(cfg, case)| format!("{} {}", cfg, case))
+        .collect::<Vec<_>>();
+    assert!(
+        unexpected.is_empty() && stale.is_empty(),
+        "unexpected divergences:\n{}\n\nexpected divergences that no longer occur:\n{}",
+        unexpected.join("\n"),
+        stale.join("\n")
+    );
+}
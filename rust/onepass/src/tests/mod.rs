@@ -1,8 +1,13 @@
 use std::str::from_utf8;
 
 use crate::cfg::Cfg;
+use crate::common::spec::tag::raw_text::RAW_TEXT_TAGS;
+use crate::diagnostic::{Diagnostic, EmbeddedLanguage};
 use crate::err::ErrorType;
-use crate::{in_place, with_friendly_error};
+use crate::unit::raw_text::raw_text_len;
+use crate::{in_place, in_place_with_diagnostics, with_friendly_error, with_friendly_errors};
+
+mod differential;
 
 pub fn eval_with_cfg(src: &'static [u8], expected: &'static [u8], cfg: &Cfg) {
     let mut code = src.to_vec();
@@ -18,30 +23,31 @@ pub fn eval_with_cfg(src: &'static [u8], expected: &'static [u8], cfg: &Cfg) {
             message,
             ..
         }) => {
-            println!("{}", message);
-            println!("{}", code_context);
-            assert!(false);
+            panic!("{}\n{}", message, code_context);
         }
     };
 }
 
-pub fn eval_with_js_min(src: &'static [u8], expected: &'static [u8]) -> () {
+pub fn eval_with_js_min(src: &'static [u8], expected: &'static [u8]) {
     let mut cfg = Cfg::new();
     cfg.minify_js = true;
     eval_with_cfg(src, expected, &cfg);
 }
 
-pub fn eval_with_css_min(src: &'static [u8], expected: &'static [u8]) -> () {
+pub fn eval_with_css_min(src: &'static [u8], expected: &'static [u8]) {
     let mut cfg = Cfg::new();
     cfg.minify_css = true;
     eval_with_cfg(src, expected, &cfg);
 }
 
 pub fn eval(src: &'static [u8], expected: &'static [u8]) {
-    eval_with_cfg(src, expected, &Cfg::new());
+    let mut cfg = Cfg::new();
+    // Most common tests assume the following minifications aren't done.
+    cfg.keep_html_and_head_opening_tags = true;
+    eval_with_cfg(src, expected, &cfg);
 }
 
-fn eval_error(src: &'static [u8], expected: ErrorType) -> () {
+fn eval_error(src: &'static [u8], expected: ErrorType) {
     let mut code = src.to_vec();
     assert_eq!(
         in_place(
@@ -49,6 +55,7 @@ fn eval_error(src: &'static [u8], expected: ErrorType) -> () {
             &Cfg {
                 minify_js: false,
                 minify_css: false,
+                ..Cfg::new()
             }
         )
         .unwrap_err()
@@ -74,6 +81,45 @@ fn test_space_between_attrs_minification() {
     eval(b"<div a=\"a\"b=\"b\"></div>", b"<div a=a b=b></div>");
 }
 
+#[test]
+fn test_malformed_attr_names() {
+    // Like browsers, an attribute name can start with `=`, and a `/` not followed by `>` is ignored.
+    eval(b"<div =x a=1 ===></div>", b"<div =x a=1 ===></div>");
+    eval(b"<div / a / b=1 / ></div>", b"<div a b=1></div>");
+}
+
+#[test]
+fn test_viewport_attr_minification() {
+    eval(
+        b"<meta name=viewport content='width=device-width, initial-scale=1'>",
+        b"<meta name=viewport content=width=device-width,initial-scale=1>",
+    );
+    // Attributes before `name` have already been written.
+    eval(
+        b"<meta content='width=device-width, initial-scale=1' name=viewport>",
+        b"<meta content=\"width=device-width, initial-scale=1\"name=viewport>",
+    );
+}
+
+#[test]
+fn test_rcdata() {
+    // Unlike the main variant, which decodes and re-encodes them, entities that could form a closing tag are kept as is.
+    eval(
+        b"<title><title>&lt;/title></titl></TITLE>",
+        b"<title><title>&lt;/title></titl></title>",
+    );
+    eval(
+        b"<textarea> <b>&#32;</b>&amp;\n</textarea>",
+        b"<textarea> <b> </b>&\n</textarea>",
+    );
+}
+
+#[test]
+fn test_text_around_removed_comment() {
+    // Unlike the main variant, text on either side of a removed comment isn't minified as one node, so it can form an unintentional entity.
+    eval(b"&a<!-- akd--sj\n <!-- \t\0f--ajk--df->lafj -->mp", b"&amp");
+}
+
 #[test]
 fn test_unmatched_closing_tag() {
     eval_error(b"Hello</p>Goodbye", ErrorType::UnexpectedClosingTag);
@@ -107,3 +153,245 @@ fn test_unmatched_closing_tag() {
         },
     );
 }
+
+fn eval_recovered(src: &'static [u8], expected: &'static [u8], diagnostics: &[Diagnostic]) {
+    let mut code = src.to_vec();
+    let (len, actual) = in_place_with_diagnostics(
+        &mut code,
+        &Cfg {
+            recover_from_errors: true,
+            ..Cfg::new()
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        from_utf8(&code[..len]).unwrap(),
+        from_utf8(expected).unwrap()
+    );
+    assert_eq!(actual, diagnostics);
+}
+
+fn dropped(name: &str, position: usize) -> Diagnostic {
+    Diagnostic::ClosingTagDropped {
+        name: name.to_string(),
+        position,
+    }
+}
+
+fn duplicate(name: &str, position: usize) -> Diagnostic {
+    Diagnostic::DuplicateTagDropped {
+        name: name.to_string(),
+        position,
+    }
+}
+
+fn unclosed(name: &str, position: usize) -> Diagnostic {
+    Diagnostic::UnclosedElementClosed {
+        name: name.to_string(),
+        position,
+    }
+}
+
+#[test]
+// NOTE: Keep inputs in sync with the rules in notes/Parsing.md, which the main variant follows.
+fn test_error_recovery() {
+    eval_recovered(b"Hello</p>Goodbye", b"HelloGoodbye", &[dropped("p", 5)]);
+    eval_recovered(
+        b"Hello<br></BR>Goodbye",
+        b"Hello<br>Goodbye",
+        &[dropped("br", 9)],
+    );
+    eval_recovered(
+        b"<div>Hello</p> Goodbye</div>",
+        b"<div>Hello Goodbye</div>",
+        &[dropped("p", 10)],
+    );
+    eval_recovered(b"<ul><li>a</p></ul>", b"<ul><li>a</ul>", &[dropped("p", 9)]);
+    eval_recovered(b"<ul><li><p>a</ul>b", b"<ul><li><p>a</ul>b", &[]);
+    eval_recovered(
+        b"<div><span></div></span>5",
+        b"<div><span></span>5",
+        &[dropped("div", 11), unclosed("div", 0)],
+    );
+    eval_recovered(b"<div></div x=\">\">5", b"<div></div>5", &[]);
+    eval_recovered(
+        b"<div></   div x=\">\">5",
+        b"<div>\">5",
+        &[dropped("", 5), unclosed("div", 0)],
+    );
+    eval_recovered(
+        b"<html><head><meta><body><div><head><span><body>",
+        b"<meta><body><div><span>",
+        &[
+            duplicate("head", 29),
+            duplicate("body", 41),
+            unclosed("span", 35),
+            unclosed("div", 24),
+        ],
+    );
+    eval_recovered(
+        b"<html><head><meta><body><div></body><span></body><input></html><a>",
+        b"<meta><body><div><span><input><a>",
+        &[
+            dropped("body", 29),
+            dropped("body", 42),
+            dropped("html", 56),
+            unclosed("a", 63),
+            unclosed("span", 36),
+            unclosed("div", 24),
+        ],
+    );
+    eval_recovered(
+        b"<p>a<textarea>b",
+        b"<p>a<textarea>b",
+        &[unclosed("textarea", 4)],
+    );
+}
+
+#[test]
+fn test_friendly_error_location() {
+    let mut code = "<div>\n<table><tr><td>\u{e9}\u{1f600}</span>"
+        .as_bytes()
+        .to_vec();
+    let err = with_friendly_error(&mut code, &Cfg::new()).unwrap_err();
+    // The error is found after the closing tag name is read.
+    assert_eq!(err.position, 33);
+    assert_eq!(err.line, 2);
+    assert_eq!(err.column, 24);
+    assert_eq!(err.column_utf16, 25);
+    assert_eq!(err.element_path, "div > table");
+    assert_eq!(
+        err.message,
+        "Closing tag name does not match opening tag (expected \"table\", got \"span\")."
+    );
+}
+
+#[test]
+fn test_friendly_error_code_context() {
+    // Only the line of the error and the two lines before it are shown.
+    let mut code = b"<div>\n".repeat(12);
+    code.extend_from_slice(b"a\nb\n<a> </b>\n</div>");
+    let err = with_friendly_error(&mut code, &Cfg::new()).unwrap_err();
+    assert_eq!((err.line, err.column), (15, 8));
+    assert_eq!(err.code_context, "13|a\n14|b\n15|<a> </b>\n>>|       ^ \n");
+}
+
+#[test]
+fn test_friendly_errors() {
+    let mut code = b"<html><ul>\n<li></br>\n<li><a></p></a>\n</ul><html><div x=\"".to_vec();
+    let errors = with_friendly_errors(&mut code, &Cfg::new()).unwrap_err();
+    assert_eq!(
+        errors
+            .iter()
+            .map(|e| (
+                e.line,
+                e.column,
+                e.element_path.as_str(),
+                e.message.as_str()
+            ))
+            .collect::<Vec<_>>(),
+        vec![
+            (
+                2,
+                5,
+                "html > ul > li",
+                "Closing tag does not match any open element (got \"br\")."
+            ),
+            (
+                3,
+                8,
+                "html > ul > li > a",
+                "Closing tag does not match any open element (got \"p\")."
+            ),
+            (4, 6, "html", "Duplicate \"html\" opening tag."),
+            // Errors that can't be recovered from stop minification.
+            (4, 20, "html", "Unexpected end of source code."),
+        ]
+    );
+    let mut code = b"<p>a</p>".to_vec();
+    assert_eq!(with_friendly_errors(&mut code, &Cfg::new()).unwrap(), 4);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_friendly_error_serialisation() {
+    let mut code = b"<p>\n</div>".to_vec();
+    let err = with_friendly_error(&mut code, &Cfg::new()).unwrap_err();
+    assert_eq!(
+        serde_json::to_value(&err).unwrap(),
+        serde_json::json!({
+            "position": 4,
+            "line": 2,
+            "column": 1,
+            "column_utf16": 1,
+            "element_path": "",
+            "message": "Unexpected closing tag.",
+            "code_context": "1|<p>\n2|</div>\n>|^ \n",
+        })
+    );
+}
+
+#[test]
+fn test_output_longer_than_source() {
+    // Outputs can't be longer than their source when minifying in place, so a DOCTYPE without room for `html` is kept as is.
+    eval(b"<!doctype><p>a", b"<!doctype><p>a");
+    // Options that need more room are reported as diagnostics when there isn't any.
+    let mut code = br#"<div a="x y"b="z w" c="1 2"></div>"#.to_vec();
+    let mut cfg = Cfg::new();
+    cfg.keep_spaces_between_attributes = true;
+    let (len, diagnostics) = in_place_with_diagnostics(&mut code, &cfg).unwrap();
+    assert_eq!(
+        from_utf8(&code[..len]).unwrap(),
+        r#"<div a="x y"b="z w" c="1 2"></div>"#
+    );
+    assert_eq!(
+        diagnostics,
+        vec![Diagnostic::SpaceBetweenAttributesOmitted { position: 12 }]
+    );
+    let mut code = br#"<a x=a"b y=a&quot;b z=a"b>t</a>"#.to_vec();
+    let mut cfg = Cfg::new();
+    cfg.ensure_spec_compliant_unquoted_attribute_values = true;
+    let (len, diagnostics) = in_place_with_diagnostics(&mut code, &cfg).unwrap();
+    assert_eq!(
+        from_utf8(&code[..len]).unwrap(),
+        r#"<a x=a"b y='a"b'z='a"b'>t</a>"#
+    );
+    // Later values can use the room freed by minifying earlier code.
+    assert_eq!(
+        diagnostics,
+        vec![Diagnostic::UnquotedAttributeValueKept { position: 5 }]
+    );
+}
+
+#[test]
+fn test_js_minification_error() {
+    let mut code = b"<script> let a = ); </script><p>a".to_vec();
+    let (len, diagnostics) = in_place_with_diagnostics(
+        &mut code,
+        &Cfg {
+            minify_js: true,
+            minify_css: false,
+            ..Cfg::new()
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        from_utf8(&code[..len]).unwrap(),
+        "<script>let a = );</script><p>a"
+    );
+    assert!(matches!(
+        diagnostics.as_slice(),
+        [Diagnostic::EmbeddedMinificationFailed {
+            language: EmbeddedLanguage::Js,
+            position: 8,
+            ..
+        }]
+    ));
+}
+
+#[test]
+fn test_raw_text_len_covers_raw_text_tags() {
+    for name in RAW_TEXT_TAGS.iter() {
+        assert!(raw_text_len(name).is_some(), "{:?}", from_utf8(name));
+    }
+}
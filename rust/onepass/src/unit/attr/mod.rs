@@ -1,5 +1,6 @@
+use crate::cfg::Cfg;
 use crate::common::gen::attrs::ATTRS;
-use crate::common::gen::codepoints::{WHATWG_ATTR_NAME_CHAR, WHITESPACE};
+use crate::common::gen::codepoints::{WHITESPACE, WHITESPACE_OR_SLASH_OR_EQUALS_OR_RIGHT_CHEVRON};
 use crate::common::spec::tag::ns::Namespace;
 use crate::err::ProcessingResult;
 use crate::proc::checkpoint::WriteCheckpoint;
@@ -28,22 +29,25 @@ pub struct ProcessedAttr {
 
 pub fn process_attr(
     proc: &mut Processor,
+    cfg: &Cfg,
     ns: Namespace,
     element: ProcessorRange,
+    is_meta_viewport: bool,
 ) -> ProcessingResult<ProcessedAttr> {
-    // It's possible to expect attribute name but not be called at an attribute, e.g. due to whitespace between name and
-    // value, which causes name to be considered boolean attribute and `=` to be start of new (invalid) attribute name.
-    let name = proc
-        .m(WhileInLookup(WHATWG_ATTR_NAME_CHAR), Keep)
-        .require("attribute name")?;
+    // Like the main variant, an attribute name can start with any character (including `=`) except whitespace, `/`, or
+    // `>`, which the caller has already handled, and ends at the next whitespace, `=`, `/`, or `>`.
+    let name_start = WriteCheckpoint::new(proc);
+    proc.accept_expect();
+    proc.m(
+        WhileNotInLookup(WHITESPACE_OR_SLASH_OR_EQUALS_OR_RIGHT_CHEVRON),
+        Keep,
+    );
+    let name = name_start.written_range(proc);
     proc.make_lowercase(name);
     let attr_cfg = ATTRS.get(ns, &proc[element], &proc[name]);
     let is_boolean = attr_cfg.filter(|attr| attr.boolean).is_some();
     let after_name = WriteCheckpoint::new(proc);
 
-    // TODO Use attr cfg: collapse, trim, case_sensitive.
-    let should_collapse_and_trim_value_ws =
-        attr_cfg.filter(|attr| attr.collapse && attr.trim).is_some();
     proc.m(WhileInLookup(WHITESPACE), Discard);
     let has_value = proc.m(IsChar(b'='), Keep).nonempty();
 
@@ -58,7 +62,7 @@ pub fn process_attr(
             after_name.erase_written(proc);
             (AttrType::NoValue, None)
         } else {
-            match process_attr_value(proc, should_collapse_and_trim_value_ws)? {
+            match process_attr_value(proc, cfg, attr_cfg, is_meta_viewport)? {
                 ProcessedAttrValue { value: None, .. } => {
                     // Value is empty, which is equivalent to no value, so discard `=`.
                     debug_assert_eq!(after_name.written_count(proc), 1);
@@ -83,3 +87,21 @@ pub fn process_attr(
 
     Ok(ProcessedAttr { name, typ, value })
 }
+
+// Like `process_attr`, but discards the attribute without writing anything, for tags that are dropped. Like the main variant,
+// an attribute name can start with any character (including `=`) except whitespace, `/`, or `>`, and ends at the next
+// whitespace, `=`, `/`, or `>`.
+pub fn skip_attr(proc: &mut Processor) {
+    debug_assert!(!proc.at_end());
+    proc.skip_expect();
+    proc.m(
+        WhileNotInLookup(WHITESPACE_OR_SLASH_OR_EQUALS_OR_RIGHT_CHEVRON),
+        Discard,
+    );
+    proc.m(WhileInLookup(WHITESPACE), Discard);
+    if proc.m(IsChar(b'='), Discard).nonempty() {
+        proc.m(WhileInLookup(WHITESPACE), Discard);
+        // The only error is a missing closing quote at the end of the code, which is allowed here.
+        skip_attr_value(proc).ok();
+    };
+}
@@ -2,9 +2,12 @@ use std::collections::HashMap;
 
 use lazy_static::lazy_static;
 
+use crate::cfg::Cfg;
+use crate::common::gen::attrs::AttributeMinification;
 use crate::common::gen::codepoints::{
     ATTR_QUOTE, DIGIT, DOUBLE_QUOTE, NOT_UNQUOTED_ATTR_VAL_CHAR, SINGLE_QUOTE, WHITESPACE,
 };
+use crate::diagnostic::Diagnostic;
 use crate::err::ProcessingResult;
 use crate::proc::checkpoint::WriteCheckpoint;
 use crate::proc::entity::maybe_normalise_entity;
@@ -20,6 +23,10 @@ lazy_static! {
         m.insert(b'\'', b"&#39");
         m.insert(b'"', b"&#34");
         m.insert(b'>', b"&gt");
+        // Only encoded in unquoted values if `ensure_spec_compliant_unquoted_attribute_values` is enabled.
+        m.insert(b'<', b"&LT");
+        m.insert(b'=', b"&#61");
+        m.insert(b'`', b"&#96");
         // Whitespace characters as defined by spec in crate::common::spec::codepoint::is_whitespace.
         m.insert(b'\x09', b"&#9");
         m.insert(b'\x0a', b"&#10");
@@ -41,6 +48,9 @@ enum CharType {
     SingleQuote,
     DoubleQuote,
     Gt,
+    // Characters prohibited in unquoted values by the spec but otherwise harmless, so they need to be encoded only if
+    // spec compliance is required.
+    Prohibited(u8),
 }
 
 impl CharType {
@@ -49,6 +59,7 @@ impl CharType {
             b'"' => CharType::DoubleQuote,
             b'\'' => CharType::SingleQuote,
             b'>' => CharType::Gt,
+            b'<' | b'=' | b'`' => CharType::Prohibited(c),
             c => {
                 if WHITESPACE[c] {
                     CharType::Whitespace(c)
@@ -60,17 +71,11 @@ impl CharType {
     }
 
     fn is_start(&self) -> bool {
-        match self {
-            CharType::Start => true,
-            _ => false,
-        }
+        matches!(self, CharType::Start)
     }
 
     fn is_end(&self) -> bool {
-        match self {
-            CharType::End => true,
-            _ => false,
-        }
+        matches!(self, CharType::End)
     }
 }
 
@@ -95,9 +100,24 @@ struct Metrics {
     count_whitespace: usize,
     // Since whitespace characters have varying encoded lengths, also calculate total length if all of them had to be encoded.
     total_whitespace_encoded_length: usize,
+    count_prohibited: usize,
+    // Some encoded prohibited characters may require semicolons, so lengths vary.
+    total_prohibited_encoded_length: usize,
 }
 
 impl Metrics {
+    fn spec_compliant_unquoted_len(&self, raw_len: usize) -> usize {
+        // Replace all whitespace, `>`, quote, and prohibited chars with encoded versions.
+        raw_len - self.count_whitespace + self.total_whitespace_encoded_length - self.count_gt
+            + self.total_gt_encoded_length
+            - self.count_single_quotation
+            + self.total_single_quote_encoded_length
+            - self.count_double_quotation
+            + self.total_double_quote_encoded_length
+            - self.count_prohibited
+            + self.total_prohibited_encoded_length
+    }
+
     fn unquoted_len(&self, raw_val: &[u8]) -> usize {
         // TODO VERIFY (including control characters and Unicode noncharacters) Browsers seem to simply consider any characters until whitespace part of an unquoted attribute value, despite the spec having more restrictions on allowed characters.
         // Costs for encoding first and last characters if going with unquoted attribute value.
@@ -116,8 +136,7 @@ impl Metrics {
         // Replace all `>` chars with encoded versions.
         let raw_len = raw_len - self.count_gt + self.total_gt_encoded_length;
         // Replace first char with encoded version if necessary.
-        let raw_len = raw_len - (first_char_encoding_cost > 0) as usize + first_char_encoding_cost;
-        raw_len
+        raw_len - (first_char_encoding_cost > 0) as usize + first_char_encoding_cost
     }
 
     fn single_quoted_len(&self, raw_len: usize) -> usize {
@@ -125,8 +144,7 @@ impl Metrics {
         let raw_len =
             raw_len - self.count_single_quotation + self.total_single_quote_encoded_length;
         // Delimiter quotes.
-        let raw_len = raw_len + 2;
-        raw_len
+        raw_len + 2
     }
 
     fn double_quoted_len(&self, raw_len: usize) -> usize {
@@ -134,11 +152,14 @@ impl Metrics {
         let raw_len =
             raw_len - self.count_double_quotation + self.total_double_quote_encoded_length;
         // Delimiter quotes.
-        let raw_len = raw_len + 2;
-        raw_len
+        raw_len + 2
     }
 
-    fn get_optimal_delimiter_type(&self, raw_val: &[u8]) -> (DelimiterType, usize) {
+    fn get_optimal_delimiter_type(
+        &self,
+        raw_val: &[u8],
+        spec_compliant: bool,
+    ) -> (DelimiterType, usize) {
         // When all equal, prefer double quotes to all and single quotes to unquoted.
         let mut min = (DelimiterType::Double, self.double_quoted_len(raw_val.len()));
 
@@ -147,7 +168,14 @@ impl Metrics {
             min = single;
         };
 
-        let unquoted = (DelimiterType::Unquoted, self.unquoted_len(raw_val));
+        let unquoted = (
+            DelimiterType::Unquoted,
+            if spec_compliant {
+                self.spec_compliant_unquoted_len(raw_val.len())
+            } else {
+                self.unquoted_len(raw_val)
+            },
+        );
         if unquoted.1 < min.1 {
             min = unquoted;
         };
@@ -177,7 +205,7 @@ pub struct ProcessedAttrValue {
     pub value: Option<ProcessorRange>,
 }
 
-fn handle_whitespace_char_type(c: u8, proc: &mut Processor, metrics: &mut Metrics) -> () {
+fn handle_whitespace_char_type(c: u8, proc: &mut Processor, metrics: &mut Metrics) {
     proc.write(c);
     metrics.count_whitespace += 1;
     metrics.total_whitespace_encoded_length += ENCODED[&c].len();
@@ -201,8 +229,13 @@ fn handle_whitespace_char_type(c: u8, proc: &mut Processor, metrics: &mut Metric
 // Since the actual processed value would have a length equal or greater to it (e.g. it might be quoted, or some characters might get encoded), we can then read minimum value right to left and start writing from actual processed value length (which is calculated), quoting/encoding as necessary.
 pub fn process_attr_value(
     proc: &mut Processor,
-    should_collapse_and_trim_ws: bool,
+    cfg: &Cfg,
+    attr_cfg: Option<&AttributeMinification>,
+    remove_whitespace: bool,
 ) -> ProcessingResult<ProcessedAttrValue> {
+    let collapse = attr_cfg.filter(|attr| attr.collapse).is_some();
+    let trim = attr_cfg.filter(|attr| attr.trim).is_some();
+    let spec_compliant = cfg.ensure_spec_compliant_unquoted_attribute_values;
     let start = WriteCheckpoint::new(proc);
     let src_delimiter = proc.m(IsInLookup(ATTR_QUOTE), Discard).first(proc);
     let delim_lookup = match src_delimiter {
@@ -212,6 +245,16 @@ pub fn process_attr_value(
         _ => unreachable!(),
     };
 
+    // If the value can't be made spec compliant in place, it's left as is, so keep a copy of it before it's overwritten.
+    let src_value = match src_delimiter {
+        None if spec_compliant => {
+            let position = proc.read_len();
+            let range = proc.m(WhileNotInLookup(delim_lookup), MatchOnly);
+            Some((position, proc[range].to_vec()))
+        }
+        _ => None,
+    };
+
     // Stage 1: read and collect metrics on attribute value characters.
     let mut metrics = Metrics {
         count_double_quotation: 0,
@@ -222,9 +265,11 @@ pub fn process_attr_value(
         total_gt_encoded_length: 0,
         count_whitespace: 0,
         total_whitespace_encoded_length: 0,
+        count_prohibited: 0,
+        total_prohibited_encoded_length: 0,
     };
     // Set to true when one or more immediately previous characters were whitespace and deferred for processing after the contiguous whitespace.
-    // NOTE: Only used if `should_collapse_and_trim_ws`.
+    // NOTE: Only used if `collapse`, or `trim` for leading whitespace.
     let mut currently_in_whitespace = false;
 
     let mut last_char_type: CharType = CharType::Start;
@@ -240,28 +285,39 @@ pub fn process_attr_value(
             CharType::from_char(proc.skip()?)
         };
 
-        if should_collapse_and_trim_ws {
-            if let CharType::Whitespace(_) = char_type {
+        if let CharType::Whitespace(_) = char_type {
+            if remove_whitespace {
+                continue;
+            };
+            if collapse || trim && last_char_type.is_start() {
                 // Ignore this whitespace character, but mark the fact that we are currently in contiguous whitespace.
                 currently_in_whitespace = true;
                 continue;
             };
+        };
 
-            // Now past whitespace (e.g. moved to non-whitespace char or end of attribute value). Either:
-            // - ignore contiguous whitespace (i.e. do nothing) if we are currently at beginning or end of value; or
-            // - collapse contiguous whitespace (i.e. count as one whitespace char) otherwise.
-            if currently_in_whitespace && !(last_char_type.is_start() || char_type.is_end()) {
-                // Collect current collapsed contiguous whitespace that was ignored previously.
-                // Update `last_char_type` as this space character will become the new "previous character", important later when checking if previous character as an entity requires semicolon.
-                last_char_type = CharType::Whitespace(b' ');
-                handle_whitespace_char_type(b' ', proc, &mut metrics);
-            };
-            currently_in_whitespace = false;
+        // Now past whitespace (e.g. moved to non-whitespace char or end of attribute value). Either:
+        // - ignore contiguous whitespace (i.e. do nothing) if trimming and we are currently at beginning or end of value; or
+        // - collapse contiguous whitespace (i.e. count as one whitespace char) otherwise.
+        if currently_in_whitespace && !(trim && (last_char_type.is_start() || char_type.is_end())) {
+            // Collect current collapsed contiguous whitespace that was ignored previously.
+            // Update `last_char_type` as this space character will become the new "previous character", important later when checking if previous character as an entity requires semicolon.
+            last_char_type = CharType::Whitespace(b' ');
+            handle_whitespace_char_type(b' ', proc, &mut metrics);
         };
+        currently_in_whitespace = false;
 
         match char_type {
             CharType::Start => unreachable!(),
             CharType::End => {
+                if trim && !collapse {
+                    // Trailing whitespace isn't deferred when not collapsing, so erase it now.
+                    while let Some(c) = start.last_written(proc).filter(|c| WHITESPACE[*c]) {
+                        proc.undo_write(1);
+                        metrics.count_whitespace -= 1;
+                        metrics.total_whitespace_encoded_length -= ENCODED[&c].len();
+                    }
+                };
                 break;
             }
             CharType::Whitespace(c) => {
@@ -282,6 +338,11 @@ pub fn process_attr_value(
                 metrics.count_gt += 1;
                 metrics.total_gt_encoded_length += ENCODED[&b'>'].len();
             }
+            CharType::Prohibited(c) => {
+                proc.write(c);
+                metrics.count_prohibited += 1;
+                metrics.total_prohibited_encoded_length += ENCODED[&c].len();
+            }
             CharType::Normal(c) => {
                 proc.write(c);
                 // If the last char written was a quote or whitespace, and this character would require the previous character, encoded as an entity, to have a semicolon, then add one more character to encoded length in metrics.
@@ -293,6 +354,12 @@ pub fn process_attr_value(
                         metrics.total_double_quote_encoded_length += 1
                     }
                     CharType::Gt if c == b';' => metrics.total_gt_encoded_length += 1,
+                    CharType::Prohibited(b'<') if c == b';' => {
+                        metrics.total_prohibited_encoded_length += 1
+                    }
+                    CharType::Prohibited(p) if p != b'<' && (c == b';' || DIGIT[c]) => {
+                        metrics.total_prohibited_encoded_length += 1
+                    }
                     CharType::Whitespace(_) if c == b';' || DIGIT[c] => {
                         metrics.total_whitespace_encoded_length += 1
                     }
@@ -307,6 +374,10 @@ pub fn process_attr_value(
             .require("attribute value closing quote")?;
     };
     let minimum_value = start.written_range(proc);
+    // Make lowercase before checking against default value.
+    if attr_cfg.filter(|attr| attr.case_insensitive).is_some() {
+        proc.make_lowercase(minimum_value);
+    };
     // If minimum value is empty, return now before trying to read out of range later.
     // (Reading starts at one character before end of minimum value.)
     if minimum_value.empty() {
@@ -318,13 +389,26 @@ pub fn process_attr_value(
 
     // Stage 2: optimally minify attribute value using metrics.
     // TODO Optimise: don't do anything if minimum is already optimal.
-    let (optimal_delimiter, optimal_len) = metrics.get_optimal_delimiter_type(&proc[minimum_value]);
+    let (optimal_delimiter, optimal_len) =
+        metrics.get_optimal_delimiter_type(&proc[minimum_value], spec_compliant);
     let optimal_delimiter_char = match optimal_delimiter {
         DelimiterType::Double => Some(b'"'),
         DelimiterType::Single => Some(b'\''),
         _ => None,
     };
 
+    // Only possible if spec compliance is required and the source value is unquoted, as any other source value is at
+    // least as long as its optimal representation.
+    if optimal_len - minimum_value.len() > proc.writable_len() {
+        let (position, src_value) = src_value.unwrap();
+        start.erase_written(proc);
+        proc.write_slice(&src_value);
+        proc.diagnose(Diagnostic::UnquotedAttributeValueKept { position });
+        return Ok(ProcessedAttrValue {
+            delimiter: DelimiterType::Unquoted,
+            value: Some(start.written_range(proc)),
+        });
+    };
     proc.reserve_output(optimal_len - minimum_value.len());
     let optimal_slice = &mut proc[start.get_written_range_since(optimal_len)];
     let mut write = optimal_slice.len() - 1;
@@ -342,6 +426,9 @@ pub fn process_attr_value(
         // TODO Comment is_first and is_last could both be true,
         let should_encode = match (c, optimal_delimiter, is_first, is_last) {
             (b'>', DelimiterType::Unquoted, _, _) => true,
+            (c, DelimiterType::Unquoted, _, _) if spec_compliant => {
+                WHITESPACE[c] || ATTR_QUOTE[c] || c == b'<' || c == b'=' || c == b'`'
+            }
             (c, DelimiterType::Unquoted, true, _) => ATTR_QUOTE[c],
             (c, DelimiterType::Unquoted, _, _) => WHITESPACE[c],
             (b'\'', DelimiterType::Single, _, _) => true,
@@ -1,11 +1,17 @@
+use crate::cfg::Cfg;
 use crate::err::ProcessingResult;
 use crate::proc::MatchAction::*;
 use crate::proc::MatchMode::*;
 use crate::proc::Processor;
+use crate::unit::end_unterminated;
 
 #[inline(always)]
-pub fn process_bang(proc: &mut Processor) -> ProcessingResult<()> {
-    proc.m(IsSeq(b"<!"), Keep).expect();
-    proc.m(ThroughChar(b'>'), Keep).require("bang close")?;
+pub fn process_bang(proc: &mut Processor, cfg: &Cfg, keep: bool) -> ProcessingResult<()> {
+    let action = if keep { Keep } else { Discard };
+    let position = proc.read_len();
+    proc.m(IsSeq(b"<!"), action).expect();
+    if proc.m(ThroughChar(b'>'), action).empty() {
+        end_unterminated(proc, cfg, action, "bang close", position)?;
+    };
     Ok(())
 }
@@ -1,18 +1,23 @@
+use crate::cfg::Cfg;
 use crate::err::ProcessingResult;
 use crate::proc::MatchAction::*;
 use crate::proc::MatchMode::*;
 use crate::proc::Processor;
+use crate::unit::end_unterminated;
 use aho_corasick::AhoCorasick;
 use lazy_static::lazy_static;
 
 lazy_static! {
-    static ref COMMENT_END: AhoCorasick = AhoCorasick::new(&["-->"]);
+    static ref COMMENT_END: AhoCorasick = AhoCorasick::new(["-->"]);
 }
 
 #[inline(always)]
-pub fn process_comment(proc: &mut Processor) -> ProcessingResult<()> {
-    proc.m(IsSeq(b"<!--"), Discard).expect();
-    proc.m(ThroughSeq(&COMMENT_END), Discard)
-        .require("comment end")?;
+pub fn process_comment(proc: &mut Processor, cfg: &Cfg, keep: bool) -> ProcessingResult<()> {
+    let action = if keep { Keep } else { Discard };
+    let position = proc.read_len();
+    proc.m(IsSeq(b"<!--"), action).expect();
+    if proc.m(ThroughSeq(&COMMENT_END), action).empty() {
+        end_unterminated(proc, cfg, action, "comment end", position)?;
+    };
     Ok(())
 }
@@ -5,22 +5,25 @@ use crate::common::spec::tag::omission::{can_omit_as_before, can_omit_as_last_no
 use crate::common::spec::tag::whitespace::{
     get_whitespace_minification_for_tag, WhitespaceMinification,
 };
+use crate::diagnostic::Diagnostic;
 use crate::err::ProcessingResult;
 use crate::proc::checkpoint::ReadCheckpoint;
-use crate::proc::entity::maybe_normalise_entity;
+use crate::proc::entity::{entity_decodes_to_closing_tag_char, maybe_normalise_entity};
 use crate::proc::range::ProcessorRange;
 use crate::proc::MatchAction::*;
 use crate::proc::MatchMode::*;
 use crate::proc::Processor;
 use crate::unit::bang::process_bang;
 use crate::unit::comment::process_comment;
+use crate::unit::doctype::process_doctype;
 use crate::unit::instruction::process_instruction;
-use crate::unit::tag::{process_tag, MaybeClosingTag};
+use crate::unit::tag::{process_tag, skip_tag_rest, MaybeClosingTag};
 
 #[derive(Copy, Clone, PartialEq, Eq)]
 enum ContentType {
     Comment,
     Bang,
+    Doctype,
     Instruction,
     Tag,
 
@@ -39,6 +42,13 @@ impl ContentType {
                 Some(b'?') => ContentType::Instruction,
                 Some(b'!') => match proc.peek_many(2, 2) {
                     Some(b"--") => ContentType::Comment,
+                    _ if proc
+                        .peek_many(2, 7)
+                        .filter(|n| n.eq_ignore_ascii_case(b"doctype"))
+                        .is_some() =>
+                    {
+                        ContentType::Doctype
+                    }
                     _ => ContentType::Bang,
                 },
                 Some(c) if TAG_NAME_CHAR[c] => ContentType::Tag,
@@ -47,6 +57,121 @@ impl ContentType {
             Some(_) => ContentType::Text,
         }
     }
+
+    // In `<textarea>` and `<title>`, everything is text until the case-insensitive `</` followed by the tag name, like the
+    // main variant.
+    fn peek_rcdata(proc: &mut Processor, name: &[u8]) -> ContentType {
+        match proc.peek(0) {
+            None => ContentType::End,
+            Some(b'<')
+                if proc.peek(1) == Some(b'/')
+                    && proc
+                        .peek_many(2, name.len())
+                        .filter(|n| n.eq_ignore_ascii_case(name))
+                        .is_some() =>
+            {
+                ContentType::End
+            }
+            Some(_) => ContentType::Text,
+        }
+    }
+
+    // Kept comments, bangs, DOCTYPEs, and instructions are treated like tags for whitespace minification.
+    fn is_tag_like(self) -> bool {
+        matches!(
+            self,
+            ContentType::Comment
+                | ContentType::Bang
+                | ContentType::Doctype
+                | ContentType::Instruction
+                | ContentType::Tag
+        )
+    }
+}
+
+// Length of the tag name starting `offset` characters after the current position.
+fn peek_tag_name_len(proc: &Processor, offset: usize) -> usize {
+    let mut len = 0;
+    while proc
+        .peek(offset + len)
+        .filter(|&c| TAG_NAME_CHAR[c])
+        .is_some()
+    {
+        len += 1;
+    }
+    len
+}
+
+// When recovering from errors, drops the opening tag at the current position if it's an `<html>`, `<head>`, or `<body>` tag
+// after the first, like the main variant. Returns whether the tag was dropped.
+fn drop_duplicate_tag(proc: &mut Processor, parent: Option<ProcessorRange>) -> bool {
+    let len = peek_tag_name_len(proc, 1);
+    let name = match proc.peek_many(1, len) {
+        Some(n) if n.eq_ignore_ascii_case(b"html") => "html",
+        Some(n) if n.eq_ignore_ascii_case(b"head") => "head",
+        Some(n) if n.eq_ignore_ascii_case(b"body") => "body",
+        _ => return false,
+    };
+    // If the tag implicitly closes the parent, it will be processed again after the parent is closed.
+    if can_omit_as_before(proc.get_or_empty(parent), name.as_bytes()) {
+        return false;
+    };
+    let seen = match name {
+        "html" => &mut proc.seen_html_open,
+        "head" => &mut proc.seen_head_open,
+        _ => &mut proc.seen_body_open,
+    };
+    if !*seen {
+        *seen = true;
+        return false;
+    };
+    let position = proc.read_len();
+    proc.skip_amount_expect(1 + len);
+    skip_tag_rest(proc);
+    proc.diagnose(Diagnostic::DuplicateTagDropped {
+        name: name.to_string(),
+        position,
+    });
+    true
+}
+
+// When recovering from errors, drops the closing tag at the current position unless it closes an open element, like the
+// main variant. It closes an open element if it's for the parent, or an ancestor where every element nested in it can be
+// omitted as the last node of its parent e.g. `<ul><li><p>(current position)</ul>`. Returns whether the tag was dropped.
+fn drop_closing_tag(proc: &mut Processor) -> bool {
+    let len = peek_tag_name_len(proc, 2);
+    let closes_open_element = len > 0 && {
+        let name = proc.peek_many(2, len).unwrap();
+        let open_elements = &proc.open_elements;
+        match open_elements
+            .iter()
+            .rposition(|&e| proc[e].eq_ignore_ascii_case(name))
+        {
+            Some(i) => open_elements[i..]
+                .windows(2)
+                .all(|w| can_omit_as_last_node(&proc[w[0]], &proc[w[1]])),
+            None => false,
+        }
+    };
+    if closes_open_element {
+        return false;
+    };
+    let position = proc.read_len();
+    proc.skip_amount_expect(2);
+    let name = proc.m(WhileInLookup(TAG_NAME_CHAR), Discard);
+    proc.make_lowercase(name);
+    if name.empty() {
+        // Not a closing tag, so drop until and including the next `>`.
+        proc.m(WhileNotChar(b'>'), Discard);
+        proc.m(IsChar(b'>'), Discard);
+    } else {
+        skip_tag_rest(proc);
+    };
+    proc.diagnose(Diagnostic::ClosingTagDropped {
+        name: unsafe { String::from_utf8_unchecked(proc[name].to_vec()) },
+        position,
+    });
+    true
 }
 
 pub struct ProcessedContent {
@@ -67,8 +192,15 @@ pub fn process_content(
     } = get_whitespace_minification_for_tag(ns, proc.get_or_empty(parent), descendant_of_pre);
 
     let handle_ws = collapse || destroy_whole || trim;
+    let rcdata_name: Option<&'static [u8]> = match proc.get_or_empty(parent) {
+        b"textarea" if ns == Namespace::Html => Some(b"textarea"),
+        b"title" if ns == Namespace::Html => Some(b"title"),
+        _ => None,
+    };
 
     let mut last_written = ContentType::Start;
+    // Kept comments, bangs, etc. don't count when trimming leading whitespace, like the main variant.
+    let mut found_text_or_tag = false;
     // Whether or not currently in whitespace.
     let mut ws_skipped = false;
     let mut prev_sibling_closing_tag = MaybeClosingTag::none();
@@ -76,26 +208,43 @@ pub fn process_content(
     loop {
         // WARNING: Do not write anything until any previously ignored whitespace has been processed later.
 
-        // Process comments, bangs, and instructions, which are completely ignored and do not affect anything (previous
-        // element node's closing tag, unintentional entities, whitespace, etc.).
-        let next_content_type = ContentType::peek(proc);
+        // Process removed comments, bangs, and instructions, which are completely ignored and do not affect anything
+        // (previous element node's closing tag, unintentional entities, whitespace, etc.).
+        let next_content_type = match rcdata_name {
+            Some(name) => ContentType::peek_rcdata(proc, name),
+            None => ContentType::peek(proc),
+        };
         match next_content_type {
-            ContentType::Comment => {
-                process_comment(proc)?;
+            ContentType::Comment if !cfg.keep_comments => {
+                process_comment(proc, cfg, false)?;
                 continue;
             }
-            ContentType::Bang => {
-                process_bang(proc)?;
+            ContentType::Bang if cfg.remove_bangs => {
+                process_bang(proc, cfg, false)?;
                 continue;
             }
-            ContentType::Instruction => {
-                process_instruction(proc)?;
+            ContentType::Instruction if cfg.remove_processing_instructions => {
+                process_instruction(proc, cfg, false)?;
+                continue;
+            }
+            // Dropped tags are ignored like removed nodes.
+            ContentType::Tag if cfg.recover_from_errors && drop_duplicate_tag(proc, parent) => {
+                continue;
+            }
+            ContentType::End
+                if cfg.recover_from_errors && !proc.at_end() && drop_closing_tag(proc) =>
+            {
                 continue;
             }
             _ => {}
         };
 
-        maybe_normalise_entity(proc, false);
+        if rcdata_name.is_none()
+            || proc.peek(0) != Some(b'&')
+            || !entity_decodes_to_closing_tag_char(proc)
+        {
+            maybe_normalise_entity(proc, false);
+        };
 
         if handle_ws {
             if next_content_type == ContentType::Text
@@ -109,15 +258,10 @@ pub fn process_content(
 
             // Next character is not whitespace, so handle any previously ignored whitespace.
             if ws_skipped {
-                if destroy_whole
-                    && last_written == ContentType::Tag
-                    && next_content_type == ContentType::Tag
-                {
+                if destroy_whole && last_written.is_tag_like() && next_content_type.is_tag_like() {
                     // Whitespace is between two tags, instructions, or bangs.
                     // `destroy_whole` is on, so don't write it.
-                } else if trim
-                    && (last_written == ContentType::Start || next_content_type == ContentType::End)
-                {
+                } else if trim && (!found_text_or_tag || next_content_type == ContentType::End) {
                     // Whitespace is leading or trailing.
                     // `trim` is on, so don't write it.
                 } else if collapse {
@@ -126,7 +270,6 @@ pub fn process_content(
                     prev_sibling_closing_tag.write_if_exists(proc);
                     // Current contiguous whitespace needs to be reduced to a single space character.
                     proc.write(b' ');
-                    last_written = ContentType::Text;
                 } else {
                     unreachable!();
                 };
@@ -170,12 +313,26 @@ pub fn process_content(
             }
             ContentType::End => {
                 if prev_sibling_closing_tag.exists_and(|prev_tag| {
-                    !can_omit_as_last_node(proc.get_or_empty(parent), &proc[prev_tag])
+                    cfg.keep_closing_tags
+                        || !can_omit_as_last_node(proc.get_or_empty(parent), &proc[prev_tag])
                 }) {
                     prev_sibling_closing_tag.write(proc);
                 };
                 break;
             }
+            ContentType::Comment
+            | ContentType::Bang
+            | ContentType::Doctype
+            | ContentType::Instruction => {
+                // Kept nodes are siblings too, so write any immediate previous sibling element's closing tag.
+                prev_sibling_closing_tag.write_if_exists(proc);
+                match next_content_type {
+                    ContentType::Comment => process_comment(proc, cfg, true)?,
+                    ContentType::Bang => process_bang(proc, cfg, true)?,
+                    ContentType::Doctype => process_doctype(proc, cfg)?,
+                    _ => process_instruction(proc, cfg, true)?,
+                };
+            }
             ContentType::Text => {
                 // Immediate next sibling node is not an element, so write any immediate previous sibling element's closing tag.
                 if prev_sibling_closing_tag.exists() {
@@ -187,7 +344,11 @@ pub fn process_content(
                 // From the spec: https://html.spec.whatwg.org/multipage/parsing.html#tag-open-state
                 // After a `<`, a valid character is an ASCII alpha, `/`, `!`, or `?`. Anything
                 // else, and the `<` is treated as content.
-                if proc.last_is(b'<') && (TAG_NAME_CHAR[c] || c == b'?' || c == b'!' || c == b'/') {
+                // A `<` in `<textarea>` or `<title>` contents is always from the source, where it can't start a tag.
+                if rcdata_name.is_none()
+                    && proc.last_is(b'<')
+                    && (TAG_NAME_CHAR[c] || c == b'?' || c == b'!' || c == b'/')
+                {
                     // We need to encode the `<` that we just wrote as otherwise this char will
                     // cause it to be interpreted as something else (e.g. opening tag).
                     // NOTE: This conditional should mean that we never have to worry about a
@@ -206,8 +367,9 @@ pub fn process_content(
             _ => unreachable!(),
         };
 
-        // This should not be reached if ContentType::{Comment, End}.
+        // This should not be reached if ContentType::End or a removed node.
         last_written = next_content_type;
+        found_text_or_tag |= matches!(next_content_type, ContentType::Text | ContentType::Tag);
     }
 
     Ok(ProcessedContent {
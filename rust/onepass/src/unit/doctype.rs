@@ -0,0 +1,45 @@
+use crate::cfg::Cfg;
+use crate::common::gen::codepoints::WHITESPACE;
+use crate::err::ProcessingResult;
+use crate::proc::MatchAction::*;
+use crate::proc::MatchMode::*;
+use crate::proc::Processor;
+
+// The DOCTYPE is rewritten as `<!doctype html` followed by any legacy string, like the main variant.
+// If that's longer than the source (e.g. `<!doctype>`) and there's not enough room, the source is kept as is.
+pub fn process_doctype(proc: &mut Processor, cfg: &Cfg) -> ProcessingResult<()> {
+    let source = proc
+        .m(ThroughChar(b'>'), MatchOnly)
+        .require("doctype end")?;
+    proc.skip_amount_expect(9);
+    proc.m(WhileInLookup(WHITESPACE), Discard);
+    if proc
+        .peek_many(0, 4)
+        .filter(|n| n.eq_ignore_ascii_case(b"html"))
+        .is_some()
+    {
+        proc.skip_amount_expect(4);
+    };
+    proc.m(WhileInLookup(WHITESPACE), Discard);
+    let legacy = proc.m(WhileNotChar(b'>'), Discard);
+    proc.m(IsChar(b'>'), Discard).expect();
+
+    let space = cfg.do_not_minify_doctype;
+    let len = 9 + space as usize + 4 + legacy.nonempty() as usize + legacy.len() + 1;
+    if len > proc.writable_len() {
+        proc.write_range(source);
+        return Ok(());
+    };
+    let legacy = proc[legacy].to_vec();
+    proc.write_slice(b"<!doctype");
+    if space {
+        proc.write(b' ');
+    };
+    proc.write_slice(b"html");
+    if !legacy.is_empty() {
+        proc.write(b' ');
+        proc.write_slice(&legacy);
+    };
+    proc.write(b'>');
+    Ok(())
+}
@@ -0,0 +1,38 @@
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+use crate::common::panic::panic_message;
+use crate::common::whitespace::trimmed;
+use crate::diagnostic::{Diagnostic, EmbeddedLanguage};
+use crate::proc::range::ProcessorRange;
+use crate::proc::Processor;
+
+// Runs the embedded minifier `f` on `src`, which must have just been read, returning its output, or None after recording a diagnostic if it failed or panicked. The caller should then fall back to the original code.
+pub fn minify_embedded<T>(
+    proc: &mut Processor,
+    language: EmbeddedLanguage,
+    src: ProcessorRange,
+    f: impl FnOnce(&[u8]) -> Result<T, String>,
+) -> Option<T> {
+    let position = proc.read_len() - src.len();
+    let diagnostic = match catch_unwind(AssertUnwindSafe(|| f(&proc[src]))) {
+        Ok(Ok(res)) => return Some(res),
+        Ok(Err(error)) => Diagnostic::EmbeddedMinificationFailed {
+            language,
+            position,
+            error,
+        },
+        Err(payload) => Diagnostic::EmbeddedMinifierPanicked {
+            language,
+            position,
+            message: panic_message(payload),
+        },
+    };
+    proc.diagnose(diagnostic);
+    None
+}
+
+// Writes `src` without leading and trailing whitespace, for when it isn't minified.
+pub fn write_trimmed(proc: &mut Processor, src: ProcessorRange) {
+    let code = trimmed(&proc[src]).to_vec();
+    proc.write_slice(&code);
+}
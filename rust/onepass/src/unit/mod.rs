@@ -2,7 +2,34 @@ pub mod attr;
 pub mod bang;
 pub mod comment;
 pub mod content;
+pub mod doctype;
+pub mod embedded;
 pub mod instruction;
+pub mod raw_text;
 pub mod script;
 pub mod style;
 pub mod tag;
+
+use crate::cfg::Cfg;
+use crate::diagnostic::Diagnostic;
+use crate::err::{ErrorType, ProcessingResult};
+use crate::proc::MatchAction;
+use crate::proc::MatchMode::*;
+use crate::proc::Processor;
+
+// Handles a comment, bang, or instruction starting at `position` whose end wasn't found. Like the main variant, it's ended
+// at the end of the code if `recover_from_errors` is enabled.
+pub fn end_unterminated(
+    proc: &mut Processor,
+    cfg: &Cfg,
+    action: MatchAction,
+    expected: &'static str,
+    position: usize,
+) -> ProcessingResult<()> {
+    if !cfg.recover_from_errors {
+        return Err(ErrorType::NotFound(expected));
+    };
+    proc.m(WhileNotFound(|_| None), action);
+    proc.diagnose(Diagnostic::UnterminatedNodeEnded { expected, position });
+    Ok(())
+}
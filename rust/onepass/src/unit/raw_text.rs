@@ -0,0 +1,31 @@
+use crate::common::spec::tag::raw_text::find_raw_text_end;
+use crate::proc::MatchAction::*;
+use crate::proc::MatchMode::*;
+use crate::proc::Processor;
+
+pub type RawTextLen = fn(&[u8]) -> Option<usize>;
+
+// `WhileNotFound` needs a function pointer, so there's one per tag in `RAW_TEXT_TAGS`, as well as `noscript`, which is only raw text if `Cfg::parse_noscript_as_raw_text`. Returns None for any other tag, which must then be processed as normal content.
+pub fn raw_text_len(name: &[u8]) -> Option<RawTextLen> {
+    let len: RawTextLen = match name {
+        b"iframe" => |code| find_raw_text_end(code, b"iframe"),
+        b"noembed" => |code| find_raw_text_end(code, b"noembed"),
+        b"noframes" => |code| find_raw_text_end(code, b"noframes"),
+        b"noscript" => |code| find_raw_text_end(code, b"noscript"),
+        b"xmp" => |code| find_raw_text_end(code, b"xmp"),
+        _ => return None,
+    };
+    Some(len)
+}
+
+// Raw text is kept as is. `process_tag` will require closing tag.
+#[inline(always)]
+pub fn process_raw_text(proc: &mut Processor, len: RawTextLen) {
+    proc.m(WhileNotFound(len), Keep);
+}
+
+// `<plaintext>` can't be closed, so everything after it is kept as is.
+#[inline(always)]
+pub fn process_plaintext(proc: &mut Processor) {
+    proc.m(WhileNotFound(|_| None), Keep);
+}
@@ -1,36 +1,49 @@
-use aho_corasick::{AhoCorasick, AhoCorasickBuilder};
-use lazy_static::lazy_static;
-
 use crate::cfg::Cfg;
+use crate::common::spec::script::{escape_script_end_tags, find_script_data_end, ScriptDataEnd};
+use crate::diagnostic::{Diagnostic, EmbeddedLanguage};
 use crate::err::ProcessingResult;
-use crate::proc::checkpoint::WriteCheckpoint;
 use crate::proc::MatchAction::*;
 use crate::proc::MatchMode::*;
 use crate::proc::Processor;
+use crate::unit::embedded::{minify_embedded, write_trimmed};
 
-lazy_static! {
-    static ref SCRIPT_END: AhoCorasick = AhoCorasickBuilder::new()
-        .ascii_case_insensitive(true)
-        .build(&["</script"]);
+fn script_data_len(code: &[u8]) -> Option<usize> {
+    match find_script_data_end(code) {
+        ScriptDataEnd::Found(len) => Some(len),
+        ScriptDataEnd::NotFound { .. } => None,
+    }
 }
 
 #[inline(always)]
 pub fn process_script(proc: &mut Processor, cfg: &Cfg, js: bool) -> ProcessingResult<()> {
-    let start = WriteCheckpoint::new(proc);
     proc.require_not_at_end()?;
-    let src = proc.m(WhileNotSeq(&SCRIPT_END), Discard);
+    let src = proc.m(WhileNotFound(script_data_len), Discard);
     // `process_tag` will require closing tag.
 
     if js && cfg.minify_js {
         // TODO Write to `out` directly, but only if we can guarantee that the length will never exceed the input.
-        let mut output = Vec::new();
-        let result = minify_js::minify(proc[src].to_vec(), &mut output);
-        // TODO Collect error as warning.
-        if !result.is_err() && output.len() < src.len() {
-            proc.write_slice(output.as_slice());
-        } else {
-            proc.write_range(src);
+        let result = minify_embedded(proc, EmbeddedLanguage::Js, src, |code| {
+            let mut output = Vec::new();
+            match minify_js::minify(code.to_vec(), &mut output) {
+                Ok(_) => Ok(output),
+                Err(err) => Err(format!("{:?}", err)),
+            }
+        });
+        let escaped = result.and_then(|output| {
+            let escaped = escape_script_end_tags(output);
+            if escaped.is_none() {
+                let position = proc.read_len() - src.len();
+                proc.diagnose(Diagnostic::MinifiedJsNotEscapable { position });
+            };
+            escaped
+        });
+        // Like the main variant, the code is trimmed if it isn't minified.
+        match escaped {
+            Some(output) if output.len() < src.len() => proc.write_slice(output.as_slice()),
+            _ => write_trimmed(proc, src),
         };
+    } else if js {
+        write_trimmed(proc, src);
     } else {
         proc.write_range(src);
     };
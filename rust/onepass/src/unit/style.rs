@@ -2,40 +2,43 @@ use aho_corasick::{AhoCorasick, AhoCorasickBuilder};
 use css_minify::optimizations::{Level, Minifier};
 use lazy_static::lazy_static;
 
-use std::str::from_utf8_unchecked;
+use std::str::from_utf8;
 
+use crate::diagnostic::EmbeddedLanguage;
 use crate::err::ProcessingResult;
-use crate::proc::checkpoint::WriteCheckpoint;
 use crate::proc::MatchAction::*;
 use crate::proc::MatchMode::*;
 use crate::proc::Processor;
+use crate::unit::embedded::{minify_embedded, write_trimmed};
 use crate::Cfg;
 
 lazy_static! {
     static ref STYLE_END: AhoCorasick = AhoCorasickBuilder::new()
         .ascii_case_insensitive(true)
-        .build(&["</style"]);
+        .build(["</style"]);
 }
 
 #[inline(always)]
 pub fn process_style(proc: &mut Processor, cfg: &Cfg) -> ProcessingResult<()> {
-    let start = WriteCheckpoint::new(proc);
     proc.require_not_at_end()?;
     let src = proc.m(WhileNotSeq(&STYLE_END), Discard);
     // `process_tag` will require closing tag.
 
     if cfg.minify_css {
-        let result = Minifier::default()
-            .minify(unsafe { from_utf8_unchecked(&proc[src]) }, Level::Three)
-            .ok();
-        // TODO Collect error as warning.
-        if result.as_ref().filter(|r| r.len() < src.len()).is_some() {
-            proc.write_slice(result.unwrap().as_bytes());
-        } else {
-            proc.write_range(src);
+        // The source can contain any bytes, but the minifier requires valid UTF-8.
+        let result = minify_embedded(proc, EmbeddedLanguage::Css, src, |code| {
+            let code = from_utf8(code).map_err(|err| err.to_string())?;
+            Minifier::default()
+                .minify(code, Level::Three)
+                .map_err(|err| format!("{:?}", err))
+        });
+        // Like the main variant, the code is trimmed if it isn't minified.
+        match result {
+            Some(min) if min.len() < src.len() => proc.write_slice(min.as_bytes()),
+            _ => write_trimmed(proc, src),
         };
     } else {
-        proc.write_range(src);
+        write_trimmed(proc, src);
     };
 
     Ok(())
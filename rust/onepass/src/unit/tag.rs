@@ -1,18 +1,20 @@
 use crate::cfg::Cfg;
 use crate::common::gen::attrs::{AttributeMinification, ATTRS};
-use crate::common::gen::codepoints::{TAG_NAME_CHAR, WHITESPACE};
+use crate::common::gen::codepoints::{TAG_NAME_CHAR, WHITESPACE, WHITESPACE_OR_SLASH};
 use crate::common::spec::script::JAVASCRIPT_MIME_TYPES;
 use crate::common::spec::tag::ns::Namespace;
 use crate::common::spec::tag::omission::{can_omit_as_before, can_omit_as_last_node};
 use crate::common::spec::tag::void::VOID_TAGS;
+use crate::diagnostic::Diagnostic;
 use crate::err::{ErrorType, ProcessingResult};
 use crate::proc::checkpoint::{ReadCheckpoint, WriteCheckpoint};
 use crate::proc::range::ProcessorRange;
 use crate::proc::MatchAction::*;
 use crate::proc::MatchMode::*;
 use crate::proc::Processor;
-use crate::unit::attr::{process_attr, AttrType, ProcessedAttr};
+use crate::unit::attr::{process_attr, skip_attr, AttrType, ProcessedAttr};
 use crate::unit::content::process_content;
+use crate::unit::raw_text::{process_plaintext, process_raw_text, raw_text_len, RawTextLen};
 use crate::unit::script::process_script;
 use crate::unit::style::process_style;
 
@@ -21,6 +23,8 @@ enum TagType {
     ScriptJs,
     ScriptData,
     Style,
+    RawText(RawTextLen),
+    Plaintext,
     Other,
 }
 
@@ -34,7 +38,7 @@ impl MaybeClosingTag {
     }
 
     #[inline(always)]
-    pub fn write(&mut self, proc: &mut Processor) -> () {
+    pub fn write(&mut self, proc: &mut Processor) {
         proc.write_slice(b"</");
         proc.write_range(self.0.take().unwrap());
         proc.write(b'>');
@@ -67,11 +71,23 @@ impl MaybeClosingTag {
     }
 
     #[inline(always)]
-    pub fn replace(&mut self, tag: MaybeClosingTag) -> () {
+    pub fn replace(&mut self, tag: MaybeClosingTag) {
         self.0 = tag.0;
     }
 }
 
+// Discards the rest of a tag after its name without writing anything, for tags that are dropped when recovering from errors.
+// Attribute-like syntax is skipped like attributes, so that a `>` in a quoted value doesn't end the tag e.g. `</div x=">">`.
+pub fn skip_tag_rest(proc: &mut Processor) {
+    loop {
+        proc.m(WhileInLookup(WHITESPACE_OR_SLASH), Discard);
+        if proc.at_end() || proc.m(IsChar(b'>'), Discard).nonempty() {
+            break;
+        };
+        skip_attr(proc);
+    }
+}
+
 // TODO Comment param `prev_sibling_closing_tag`.
 pub fn process_tag(
     proc: &mut Processor,
@@ -82,24 +98,40 @@ pub fn process_tag(
     mut prev_sibling_closing_tag: MaybeClosingTag,
     source_tag_name: ProcessorRange,
 ) -> ProcessingResult<MaybeClosingTag> {
-    if prev_sibling_closing_tag
-        .exists_and(|prev_tag| !can_omit_as_before(&proc[prev_tag], &proc[source_tag_name]))
-    {
+    if prev_sibling_closing_tag.exists_and(|prev_tag| {
+        cfg.keep_closing_tags || !can_omit_as_before(&proc[prev_tag], &proc[source_tag_name])
+    }) {
         prev_sibling_closing_tag.write(proc);
     };
+    // The source tag name has just been read, and the `<` is immediately before it.
+    let opening_tag_position = proc.read_len() - source_tag_name.len() - 1;
+    // Mark opening tag start in case we want to omit it.
+    let opening_tag_checkpoint = WriteCheckpoint::new(proc);
     // Write initially skipped left chevron.
     proc.write(b'<');
     // Write previously skipped name and use written code as range (otherwise source code will eventually be overwritten).
     let tag_name = proc.write_range(source_tag_name);
+    // Like the main variant, an `<svg>` is itself in the svg namespace, so its attributes are minified as such.
+    let ns = if proc[tag_name].eq(b"svg") {
+        Namespace::Svg
+    } else {
+        ns
+    };
 
     let mut tag_type = match &proc[tag_name] {
         // Unless non-JS MIME `type` is provided, `script` tags contain JS.
         b"script" => TagType::ScriptJs,
         b"style" => TagType::Style,
+        b"plaintext" if ns == Namespace::Html => TagType::Plaintext,
+        b"noscript" if !cfg.parse_noscript_as_raw_text => TagType::Other,
+        name if ns == Namespace::Html => {
+            raw_text_len(name).map_or(TagType::Other, TagType::RawText)
+        }
         _ => TagType::Other,
     };
 
     let mut last_attr_type: Option<AttrType> = None;
+    let mut is_meta_viewport = false;
     let mut self_closing = false;
     let is_void_tag = VOID_TAGS.contains(&proc[tag_name]);
 
@@ -117,6 +149,10 @@ pub fn process_tag(
         if self_closing {
             break;
         }
+        // A `/` not followed by `>` is ignored, like in browsers and the main variant.
+        if proc.m(IsChar(b'/'), Discard).nonempty() {
+            continue;
+        };
 
         // Mark attribute start in case we want to erase it completely.
         let attr_checkpoint = WriteCheckpoint::new(proc);
@@ -130,11 +166,33 @@ pub fn process_tag(
             return Err(ErrorType::UnexpectedEnd);
         };
         match last_attr_type {
+            // The source may not have a space after a quoted value, in which case there's no room to add one.
+            Some(AttrType::Quoted)
+                if cfg.keep_spaces_between_attributes && proc.writable_len() > 0 =>
+            {
+                proc.write(b' ')
+            }
+            Some(AttrType::Quoted) if cfg.keep_spaces_between_attributes => {
+                let position = proc.read_len();
+                proc.diagnose(Diagnostic::SpaceBetweenAttributesOmitted { position });
+            }
             Some(AttrType::Unquoted) | Some(AttrType::NoValue) | None => proc.write(b' '),
             _ => {}
         };
 
-        let ProcessedAttr { name, typ, value } = process_attr(proc, ns, tag_name)?;
+        let ProcessedAttr { name, typ, value } =
+            process_attr(proc, cfg, ns, tag_name, is_meta_viewport)?;
+        // Like the main variant, all whitespace is removed from the values of a `<meta name=viewport>`, but only from
+        // attributes after `name`, as those before it have already been written.
+        if ns == Namespace::Html
+            && proc[tag_name] == *b"meta"
+            && proc[name] == *b"name"
+            && value
+                .filter(|v| proc[*v].eq_ignore_ascii_case(b"viewport"))
+                .is_some()
+        {
+            is_meta_viewport = true;
+        };
         match (tag_type, &proc[name]) {
             // NOTE: We don't support multiple `type` attributes, so can't go from ScriptData => ScriptJs.
             (TagType::ScriptJs, b"type") => {
@@ -167,7 +225,7 @@ pub fn process_tag(
                             default_value: Some(defval),
                             ..
                         }),
-                    ) => proc[val].eq(*defval),
+                    ) => proc[val].eq(defval.as_ref()),
                     _ => false,
                 };
             }
@@ -197,50 +255,80 @@ pub fn process_tag(
         return Ok(MaybeClosingTag(None));
     };
 
-    let child_ns = if proc[tag_name].eq(b"svg") {
-        Namespace::Svg
+    // `<html>` and `<head>` opening tags without attributes can be omitted. They've already been written, so remember their
+    // length and erase them once the element has been processed.
+    let omitted_opening_tag_len = if !cfg.keep_html_and_head_opening_tags
+        && last_attr_type.is_none()
+        && (proc[tag_name] == *b"html" || proc[tag_name] == *b"head")
+    {
+        Some(opening_tag_checkpoint.written_count(proc))
     } else {
-        ns
+        None
     };
 
+    // Open until its closing tag has been processed, so that errors in the closing tag have it in their element path.
+    proc.open_elements.push(tag_name);
     let mut closing_tag_omitted = false;
     match tag_type {
         TagType::ScriptData => process_script(proc, cfg, false)?,
         TagType::ScriptJs => process_script(proc, cfg, true)?,
         TagType::Style => process_style(proc, cfg)?,
+        TagType::RawText(len) => process_raw_text(proc, len),
+        TagType::Plaintext => {
+            process_plaintext(proc);
+            closing_tag_omitted = true;
+        }
         _ => {
-            closing_tag_omitted =
-                process_content(proc, cfg, child_ns, Some(tag_name), descendant_of_pre)?
-                    .closing_tag_omitted
+            closing_tag_omitted = process_content(proc, cfg, ns, Some(tag_name), descendant_of_pre)?
+                .closing_tag_omitted
         }
     };
 
     let can_omit_closing_tag = can_omit_as_last_node(proc.get_or_empty(parent), &proc[tag_name]);
-    if closing_tag_omitted || proc.at_end() && can_omit_closing_tag {
-        return Ok(MaybeClosingTag(None));
-    };
-
-    let closing_tag_checkpoint = ReadCheckpoint::new(proc);
-    proc.m(IsSeq(b"</"), Discard).require("closing tag")?;
-    let closing_tag = proc
-        .m(WhileInLookup(TAG_NAME_CHAR), Discard)
-        .require("closing tag name")?;
-    proc.make_lowercase(closing_tag);
+    let closing_tag = if closing_tag_omitted || proc.at_end() && can_omit_closing_tag {
+        None
+    } else if proc.at_end() && cfg.recover_from_errors {
+        proc.diagnose(Diagnostic::UnclosedElementClosed {
+            name: unsafe { String::from_utf8_unchecked(proc[tag_name].to_vec()) },
+            position: opening_tag_position,
+        });
+        None
+    } else {
+        let closing_tag_checkpoint = ReadCheckpoint::new(proc);
+        proc.m(IsSeq(b"</"), Discard).require("closing tag")?;
+        let closing_tag = proc
+            .m(WhileInLookup(TAG_NAME_CHAR), Discard)
+            .require("closing tag name")?;
+        proc.make_lowercase(closing_tag);
 
-    // We need to check closing tag matches as otherwise when we later write closing tag, it might be longer than source closing tag and cause source to be overwritten.
-    if proc[closing_tag] != proc[tag_name] {
-        if can_omit_closing_tag {
+        // We need to check closing tag matches as otherwise when we later write closing tag, it might be longer than source closing tag and cause source to be overwritten.
+        if proc[closing_tag] != proc[tag_name] {
+            if !can_omit_closing_tag {
+                return Err(ErrorType::ClosingTagMismatch {
+                    expected: unsafe { String::from_utf8_unchecked(proc[tag_name].to_vec()) },
+                    got: unsafe { String::from_utf8_unchecked(proc[closing_tag].to_vec()) },
+                });
+            };
             closing_tag_checkpoint.restore(proc);
-            Ok(MaybeClosingTag(None))
+            None
         } else {
-            Err(ErrorType::ClosingTagMismatch {
-                expected: unsafe { String::from_utf8_unchecked(proc[tag_name].to_vec()) },
-                got: unsafe { String::from_utf8_unchecked(proc[closing_tag].to_vec()) },
-            })
+            if cfg.recover_from_errors {
+                skip_tag_rest(proc);
+            } else {
+                proc.m(WhileInLookup(WHITESPACE), Discard);
+                proc.m(IsChar(b'>'), Discard).require("closing tag end")?;
+            };
+            Some(closing_tag)
         }
-    } else {
-        proc.m(WhileInLookup(WHITESPACE), Discard);
-        proc.m(IsChar(b'>'), Discard).require("closing tag end")?;
-        Ok(MaybeClosingTag(Some(tag_name)))
+    };
+    proc.open_elements.pop();
+
+    match omitted_opening_tag_len {
+        // `tag_name` is part of the erased output, so use the name from the (already consumed) source closing tag.
+        Some(len) => {
+            opening_tag_checkpoint.erase_written_prefix(proc, len);
+            Ok(MaybeClosingTag(closing_tag))
+        }
+        None => Ok(MaybeClosingTag(closing_tag.map(|_| tag_name))),
     }
 }
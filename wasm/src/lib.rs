@@ -11,29 +11,77 @@ static ALLOC: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
 
 macro_rules! get_prop {
     ($cfg:expr, $x:literal) => {
+        get_prop!($cfg, $x, false)
+    };
+    ($cfg:expr, $x:literal, $default:expr) => {
         Reflect::get($cfg, &JsValue::from_str($x))
             .ok()
             .and_then(|p| p.as_bool())
-            .unwrap_or(false)
+            .unwrap_or($default)
+    };
+}
+
+macro_rules! parse_prop {
+    ($cfg:expr, $x:literal, $default:expr) => {
+        match Reflect::get($cfg, &JsValue::from_str($x))
+            .ok()
+            .and_then(|p| p.as_string())
+        {
+            Some(v) => v.parse().map_err(|e: String| JsValue::from_str(&e))?,
+            None => $default,
+        }
     };
 }
 
 #[wasm_bindgen]
 pub fn minify(code: &[u8], cfg: &JsValue) -> Vec<u8> {
     let cfg = minify_html::Cfg {
+        always_quote_attribute_values: get_prop!(cfg, "always_quote_attribute_values"),
+        ascii_only: get_prop!(cfg, "ascii_only"),
+        conservative_entities: get_prop!(cfg, "conservative_entities"),
         do_not_minify_doctype: get_prop!(cfg, "do_not_minify_doctype"),
         ensure_spec_compliant_unquoted_attribute_values: get_prop!(
             cfg,
             "ensure_spec_compliant_unquoted_attribute_values"
         ),
+        ensure_no_parse_errors: get_prop!(cfg, "ensure_no_parse_errors"),
+        infer_whitespace_from_css: get_prop!(cfg, "infer_whitespace_from_css"),
         keep_closing_tags: get_prop!(cfg, "keep_closing_tags"),
         keep_html_and_head_opening_tags: get_prop!(cfg, "keep_html_and_head_opening_tags"),
         keep_spaces_between_attributes: get_prop!(cfg, "keep_spaces_between_attributes"),
         keep_comments: get_prop!(cfg, "keep_comments"),
+        keep_conditional_comments: get_prop!(cfg, "keep_conditional_comments"),
         minify_css: get_prop!(cfg, "minify_css"),
         minify_js: get_prop!(cfg, "minify_js"),
+        parse_noscript_as_raw_text: get_prop!(cfg, "parse_noscript_as_raw_text"),
+        preserve_line_breaks: get_prop!(cfg, "preserve_line_breaks"),
         remove_bangs: get_prop!(cfg, "remove_bangs"),
         remove_processing_instructions: get_prop!(cfg, "remove_processing_instructions"),
+        ..minify_html::Cfg::default()
     };
     minify_html::minify(code, &cfg)
 }
+
+#[wasm_bindgen]
+pub fn canonicalise(code: &[u8], cfg: &JsValue) -> Result<Vec<u8>, JsValue> {
+    let cfg = minify_html::CanonicaliseCfg {
+        sort_attributes: get_prop!(cfg, "sort_attributes", true),
+        attribute_quotes: parse_prop!(
+            cfg,
+            "attribute_quotes",
+            minify_html::AttributeQuotes::Source
+        ),
+        entities: parse_prop!(cfg, "entities", minify_html::EntityPolicy::Minimal),
+        normalise_whitespace: get_prop!(cfg, "normalise_whitespace"),
+        indent: Reflect::get(cfg, &JsValue::from_str("indent"))
+            .ok()
+            .and_then(|p| p.as_f64())
+            .filter(|&v| v >= 0.0)
+            .map(|v| v as usize),
+        ..minify_html::CanonicaliseCfg::default()
+    };
+    let mut out = Vec::new();
+    minify_html::canonicalise_with_cfg(&mut out, code, &cfg)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    Ok(out)
+}